@@ -0,0 +1,542 @@
+//! Command line client for scripting Harmony, which shares the session of the GUI.
+
+use std::{
+    future::Future,
+    io::{self, Read, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use crust::client::{
+    auth::{self, FieldKind, LOGIN_CHOICE, REGISTER_CHOICE},
+    content::ContentStore,
+    error::{ClientError, ClientResult},
+    message::{Content, Message},
+    Client, Session, Url,
+};
+use futures::future::join_all;
+use harmony_rust_sdk::{
+    api::{
+        auth::{auth_step::Step, next_step_request::form_fields::Field},
+        chat::event::*,
+    },
+    client::api::auth::AuthStepResponse,
+};
+use serde_json::{json, Value};
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "Usage: crust-cli <command> [arguments]
+
+Commands:
+    login <homeserver>                         Log in and save the session
+    register <homeserver>                      Create an account and save the session
+    guilds                                     List the guilds you are in
+    channels <guild id>                        List the channels of a guild
+    send <guild id> <channel id> [text]        Send a message, read from stdin if no text is given
+    history <guild id> <channel id> [count]    Print the last messages of a channel as JSON lines
+    tail                                       Print events as JSON lines as they arrive
+    upload <guild id> <channel id> <file>...   Upload files and send them in a message";
+
+/// Environment variable with the passphrase of the session, for when there is no keyring.
+const PASSPHRASE_VAR: &str = "CRUST_PASSPHRASE";
+
+/// How many messages `history` prints if no count is given.
+const DEFAULT_HISTORY_COUNT: usize = 50;
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(io::stderr)
+        .init();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let content_store = Arc::new(ContentStore::default());
+
+    let result = match content_store.create_req_dirs() {
+        Ok(()) => run(content_store, &args).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run(content_store: Arc<ContentStore>, args: &[String]) -> ClientResult<()> {
+    let command = args.first().map(String::as_str);
+    let args = args.get(1..).unwrap_or_default();
+
+    match (command, args) {
+        (Some("login"), [homeserver]) => login(content_store, homeserver, LOGIN_CHOICE).await,
+        (Some("register"), [homeserver]) => login(content_store, homeserver, REGISTER_CHOICE).await,
+        (Some("guilds"), []) => guilds(&mut logged_in(content_store).await?).await,
+        (Some("channels"), [guild_id]) => {
+            channels(&mut logged_in(content_store).await?, parse_id(guild_id)?).await
+        }
+        (Some("send"), [guild_id, channel_id, text @ ..]) => {
+            let text = if text.is_empty() {
+                let mut text = String::new();
+                io::stdin().read_to_string(&mut text)?;
+                text
+            } else {
+                text.join(" ")
+            };
+            let text = text.trim();
+            if text.is_empty() {
+                return Err(ClientError::Custom(
+                    "Can't send an empty message.".to_string(),
+                ));
+            }
+            send(
+                &logged_in(content_store).await?,
+                parse_id(guild_id)?,
+                parse_id(channel_id)?,
+                Content::Text(text.to_string()),
+            )
+            .await
+        }
+        (Some("history"), [guild_id, channel_id, count @ ..]) if count.len() <= 1 => {
+            let count = match count.first() {
+                Some(count) => count.parse().map_err(|_| {
+                    ClientError::Custom(format!("'{}' is not a message count", count))
+                })?,
+                None => DEFAULT_HISTORY_COUNT,
+            };
+            history(
+                &mut logged_in(content_store).await?,
+                parse_id(guild_id)?,
+                parse_id(channel_id)?,
+                count,
+            )
+            .await
+        }
+        (Some("tail"), []) => tail(&mut logged_in(content_store).await?).await,
+        (Some("upload"), [guild_id, channel_id, files @ ..]) if !files.is_empty() => {
+            let client = logged_in(content_store).await?;
+            let mut attachments = Vec::with_capacity(files.len());
+            for file in files {
+                attachments.push(client.requests().upload_file(Path::new(file)).await?);
+            }
+            send(
+                &client,
+                parse_id(guild_id)?,
+                parse_id(channel_id)?,
+                Content::Files(attachments),
+            )
+            .await
+        }
+        _ => Err(ClientError::Custom(USAGE.to_string())),
+    }
+}
+
+fn parse_id(id: &str) -> ClientResult<u64> {
+    id.parse()
+        .map_err(|_| ClientError::Custom(format!("'{}' is not an ID", id)))
+}
+
+/// Asks the user for a value on stderr, so that stdout only has the output of the command.
+fn prompt(name: &str) -> ClientResult<String> {
+    eprint!("{}: ", name);
+    io::stderr().flush()?;
+    let mut value = String::new();
+    if io::stdin().read_line(&mut value)? == 0 {
+        return Err(ClientError::MissingLoginInfo);
+    }
+    Ok(value.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+/// Asks for a value like [`prompt`], without showing what is typed.
+fn prompt_secret(name: &str) -> ClientResult<String> {
+    rpassword::prompt_password_stderr(&format!("{}: ", name)).map_err(|err| {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            ClientError::MissingLoginInfo
+        } else {
+            err.into()
+        }
+    })
+}
+
+async fn logged_in(content_store: Arc<ContentStore>) -> ClientResult<Client> {
+    if !content_store.session_file().exists() {
+        return Err(ClientError::Custom(
+            "Not logged in, use `crust-cli login <homeserver>` first.".to_string(),
+        ));
+    }
+    let session = with_passphrase(|passphrase| Session::load(&content_store, passphrase)).await?;
+    Client::from_session(session, content_store).await
+}
+
+/// Runs `f` with the passphrase from `CRUST_PASSPHRASE`, asking for one if it's needed and wasn't
+/// set.
+///
+/// Passphrases are only used for sessions that can't be kept in the system keyring.
+async fn with_passphrase<T, F, Fut>(f: F) -> ClientResult<T>
+where
+    F: Fn(Option<String>) -> Fut,
+    Fut: Future<Output = ClientResult<T>>,
+{
+    let passphrase = std::env::var(PASSPHRASE_VAR).ok();
+    let asked = passphrase.is_none();
+    match f(passphrase).await {
+        Err(ClientError::PassphraseRequired) if asked => {
+            f(Some(prompt_secret("passphrase")?)).await
+        }
+        Err(err @ ClientError::KeyringUnavailable(_)) if asked => {
+            eprintln!("{}", err);
+            loop {
+                let passphrase = prompt_secret("new passphrase")?;
+                if passphrase.is_empty() {
+                    eprintln!("The passphrase can't be empty");
+                } else if prompt_secret("new passphrase again")? != passphrase {
+                    eprintln!("The passphrases don't match");
+                } else {
+                    return f(Some(passphrase)).await;
+                }
+            }
+        }
+        result => result,
+    }
+}
+
+/// Logs in, or registers if `choice` is [`REGISTER_CHOICE`], and saves the session.
+///
+/// `choice` is made for the user the first time the homeserver offers it.
+async fn login(
+    content_store: Arc<ContentStore>,
+    homeserver: &str,
+    choice: &str,
+) -> ClientResult<()> {
+    let homeserver_url = homeserver
+        .parse::<Url>()
+        .map_err(|err| ClientError::UrlParse(homeserver.to_string(), err))?;
+    let client = Client::new(homeserver_url.clone(), None, content_store.clone()).await?;
+    let requests = client.requests();
+
+    let mut choice = Some(choice);
+    // The session the homeserver pushed after a waiting step, which the client doesn't have
+    let mut pushed_session = None;
+    let mut step = requests.begin_auth().await?;
+    while let Some(auth_step) = step {
+        let response = match auth_step.step {
+            Some(Step::Choice(options)) => match choice.take() {
+                Some(choice) if options.options.iter().any(|option| option == choice) => {
+                    AuthStepResponse::Choice(choice.to_string())
+                }
+                _ => {
+                    eprintln!("{}", auth::label(&options.title));
+                    eprintln!("Options: {}", options.options.join(", "));
+                    AuthStepResponse::Choice(prompt("choice")?)
+                }
+            },
+            Some(Step::Form(form)) => {
+                eprintln!("{}", auth::label(&form.title));
+                let mut fields = Vec::with_capacity(form.fields.len());
+                for field in form.fields {
+                    let kind = FieldKind::from_type(&field.r#type);
+                    fields.push(prompt_field(&auth::label(&field.name), kind)?);
+                }
+                AuthStepResponse::form(fields)
+            }
+            Some(Step::Waiting(waiting)) => {
+                eprintln!("{}", auth::label(&waiting.title));
+                eprintln!("{}", waiting.description);
+                // The homeserver pushes the next step once what it waits for is done
+                let next = requests.wait_for_auth_step().await?;
+                if let Some(Step::Session(session)) = next.step {
+                    pushed_session = Some(Session::new(&homeserver_url, session));
+                    break;
+                }
+                step = Some(next);
+                continue;
+            }
+            _ => {
+                return Err(ClientError::Custom(
+                    "The homeserver asked for a login step that isn't supported here.".to_string(),
+                ))
+            }
+        };
+        step = requests.next_auth_step(response).await?;
+    }
+
+    let session = pushed_session
+        .or_else(|| client.session())
+        .ok_or(ClientError::MissingLoginInfo)?;
+    with_passphrase(|passphrase| session.save(&content_store, passphrase)).await?;
+    eprintln!("Logged in as {}.", session.user_id);
+
+    Ok(())
+}
+
+/// Asks for the value of a form field until it's valid, twice for new passwords.
+fn prompt_field(label: &str, kind: FieldKind) -> ClientResult<Field> {
+    loop {
+        let read = if kind.is_secret() {
+            prompt_secret
+        } else {
+            prompt
+        };
+        let value = read(label)?;
+        let field = match kind.parse(label, &value) {
+            Ok(field) => field,
+            Err(err) => {
+                eprintln!("{}", err);
+                continue;
+            }
+        };
+        if kind == FieldKind::NewPassword && prompt_secret(&format!("{} again", label))? != value {
+            eprintln!("{} doesn't match what you typed again", label);
+            continue;
+        }
+        return Ok(field);
+    }
+}
+
+async fn guilds(client: &mut Client) -> ClientResult<()> {
+    let requests = client.requests().clone();
+    for event in requests.guild_list().await? {
+        client.process_event(event);
+    }
+
+    let guild_data = join_all(
+        client
+            .guilds
+            .keys()
+            .map(|guild_id| requests.guild_data(*guild_id)),
+    )
+    .await;
+    for event in guild_data {
+        client.process_event(event?);
+    }
+
+    let mut guilds = client.guilds.iter().collect::<Vec<_>>();
+    guilds.sort_unstable_by_key(|(guild_id, _)| **guild_id);
+    for (guild_id, guild) in guilds {
+        println!("{}\t{}", guild_id, guild.name);
+    }
+
+    Ok(())
+}
+
+async fn channels(client: &mut Client, guild_id: u64) -> ClientResult<()> {
+    let events = client.requests().guild_contents(guild_id).await?;
+    client.guilds.insert(guild_id, Default::default());
+    for event in events {
+        if let Event::CreatedChannel(_) = event {
+            client.process_event(event);
+        }
+    }
+
+    if let Some(guild) = client.guilds.get(&guild_id) {
+        for (channel_id, channel) in &guild.channels {
+            let kind = if channel.is_category {
+                "category"
+            } else {
+                "text"
+            };
+            println!("{}\t{}\t{}", channel_id, kind, channel.name);
+        }
+    }
+
+    Ok(())
+}
+
+async fn send(
+    client: &Client,
+    guild_id: u64,
+    channel_id: u64,
+    content: Content,
+) -> ClientResult<()> {
+    let message = Message {
+        content,
+        sender: client.user_id.unwrap_or_default(),
+        ..Default::default()
+    };
+    let message_id = client
+        .requests()
+        .send_message(guild_id, channel_id, &message)
+        .await?;
+    println!("{}", message_id);
+
+    Ok(())
+}
+
+async fn history(
+    client: &mut Client,
+    guild_id: u64,
+    channel_id: u64,
+    count: usize,
+) -> ClientResult<()> {
+    let requests = client.requests().clone();
+    let mut messages: Vec<Message> = Vec::with_capacity(count);
+    let mut before = None;
+    while messages.len() < count {
+        let (mut page, reached_top) = requests
+            .channel_messages(guild_id, channel_id, before)
+            .await?;
+        before = page.first().map(|message| message.id.id()).flatten();
+        page.append(&mut messages);
+        messages = page;
+        if reached_top || before.is_none() {
+            break;
+        }
+    }
+    let messages = &messages[messages.len().saturating_sub(count)..];
+
+    let mut authors = messages
+        .iter()
+        .map(|message| message.sender)
+        .collect::<Vec<_>>();
+    authors.sort_unstable();
+    authors.dedup();
+    if !authors.is_empty() {
+        for event in requests.profiles(authors).await? {
+            client.process_event(event);
+        }
+    }
+
+    for message in messages {
+        let author = message.overrides.as_ref().map_or_else(
+            || {
+                client
+                    .members
+                    .get(&message.sender)
+                    .map(|member| member.username.clone())
+            },
+            |overrides| Some(overrides.name.clone()),
+        );
+        println!(
+            "{}",
+            json!({
+                "guild_id": guild_id,
+                "channel_id": channel_id,
+                "message_id": message.id.id(),
+                "author_id": message.sender,
+                "author": author,
+                "timestamp": message.timestamp.timestamp(),
+                "edited_at": message.edited_at.map(|edited_at| edited_at.timestamp()),
+                "reply_to": message.reply_to,
+                "content": content_json(&message.content),
+            })
+        );
+    }
+
+    Ok(())
+}
+
+async fn tail(client: &mut Client) -> ClientResult<()> {
+    for event in client.requests().guild_list().await? {
+        client.process_event(event);
+    }
+
+    let mut socket = client.requests().subscribe(client.subscribe_to()).await?;
+    let stdout = io::stdout();
+    loop {
+        match socket.get_event().await {
+            Some(Ok(event)) => {
+                let mut stdout = stdout.lock();
+                writeln!(stdout, "{}", event_json(&event))?;
+                // Pipes are block buffered, but whoever reads this wants events as they happen
+                stdout.flush()?;
+            }
+            Some(Err(err)) => return Err(err.into()),
+            None => {}
+        }
+    }
+}
+
+fn content_json(content: &Content) -> Value {
+    match content {
+        Content::Text(text) => json!({ "type": "text", "text": text }),
+        Content::Files(attachments) => json!({
+            "type": "files",
+            "files": attachments
+                .iter()
+                .map(|attachment| {
+                    json!({
+                        "id": attachment.id.to_string(),
+                        "name": attachment.name,
+                        "kind": attachment.kind,
+                        "size": attachment.size,
+                        "caption": attachment.caption,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        }),
+        Content::Embeds(embed) => json!({
+            "type": "embed",
+            "title": embed.title,
+            "body": embed.body,
+        }),
+    }
+}
+
+fn event_json(event: &Event) -> Value {
+    match event {
+        Event::SentMessage(sent) => match &sent.message {
+            Some(message) => json!({
+                "type": "message_sent",
+                "guild_id": message.guild_id,
+                "channel_id": message.channel_id,
+                "message_id": message.message_id,
+                "author_id": message.author_id,
+                "reply_to": (message.in_reply_to != 0).then(|| message.in_reply_to),
+                "content": content_json(&Message::from(message.clone()).content),
+            }),
+            None => json!({ "type": "message_sent" }),
+        },
+        Event::EditedMessage(edited) => json!({
+            "type": "message_edited",
+            "guild_id": edited.guild_id,
+            "channel_id": edited.channel_id,
+            "message_id": edited.message_id,
+            "text": edited.content,
+        }),
+        Event::DeletedMessage(deleted) => json!({
+            "type": "message_deleted",
+            "guild_id": deleted.guild_id,
+            "channel_id": deleted.channel_id,
+            "message_id": deleted.message_id,
+        }),
+        Event::Typing(typing) => json!({
+            "type": "typing",
+            "guild_id": typing.guild_id,
+            "channel_id": typing.channel_id,
+            "user_id": typing.user_id,
+        }),
+        Event::CreatedChannel(created) => json!({
+            "type": "channel_created",
+            "guild_id": created.guild_id,
+            "channel_id": created.channel_id,
+            "name": created.name,
+        }),
+        Event::EditedChannel(edited) => json!({
+            "type": "channel_edited",
+            "guild_id": edited.guild_id,
+            "channel_id": edited.channel_id,
+            "name": edited.update_name.then(|| &edited.name),
+        }),
+        Event::DeletedChannel(deleted) => json!({
+            "type": "channel_deleted",
+            "guild_id": deleted.guild_id,
+            "channel_id": deleted.channel_id,
+        }),
+        Event::JoinedMember(joined) => json!({
+            "type": "member_joined",
+            "guild_id": joined.guild_id,
+            "user_id": joined.member_id,
+        }),
+        Event::LeftMember(left) => json!({
+            "type": "member_left",
+            "guild_id": left.guild_id,
+            "user_id": left.member_id,
+        }),
+        Event::ProfileUpdated(updated) => json!({
+            "type": "profile_updated",
+            "user_id": updated.user_id,
+            "username": updated.update_username.then(|| &updated.new_username),
+        }),
+        // Events scripts are unlikely to care about are still printed, just not in detail
+        other => json!({ "type": "other", "event": format!("{:?}", other) }),
+    }
+}
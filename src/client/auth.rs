@@ -0,0 +1,95 @@
+//! Checks what the user enters in the steps of logging in or registering.
+//!
+//! Harmony homeservers describe their own login flow as choices, forms and waiting steps, so
+//! both the UI and the command line client build their inputs from these.
+
+use harmony_rust_sdk::api::auth::next_step_request::form_fields::Field;
+
+/// The choice that starts logging in, which homeservers usually offer first.
+pub const LOGIN_CHOICE: &str = "login";
+/// The choice that starts creating an account.
+pub const REGISTER_CHOICE: &str = "register";
+
+/// What a form field asks for, which decides how it is typed and checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Text,
+    Email,
+    Number,
+    Password,
+    /// A password that is being chosen, which is typed twice to catch typos.
+    NewPassword,
+}
+
+impl FieldKind {
+    /// Parses the type of a form field, treating types we don't know as text.
+    pub fn from_type(r#type: &str) -> Self {
+        match r#type {
+            "email" => FieldKind::Email,
+            "number" => FieldKind::Number,
+            "password" => FieldKind::Password,
+            "new-password" => FieldKind::NewPassword,
+            _ => FieldKind::Text,
+        }
+    }
+
+    /// Whether the value should be hidden while it's typed.
+    pub fn is_secret(self) -> bool {
+        matches!(self, FieldKind::Password | FieldKind::NewPassword)
+    }
+
+    /// Checks the value of a field, converting it to what the homeserver expects.
+    ///
+    /// `label` is used to refer to the field in the error.
+    pub fn parse(self, label: &str, value: &str) -> Result<Field, String> {
+        if value.trim().is_empty() {
+            return Err(format!("{} can't be empty", label));
+        }
+
+        match self {
+            FieldKind::Text => Ok(Field::String(value.to_string())),
+            FieldKind::Email => {
+                let value = value.trim();
+                if is_email(value) {
+                    Ok(Field::String(value.to_string()))
+                } else {
+                    Err(format!(
+                        "{} must be an email address, like name@example.org",
+                        label
+                    ))
+                }
+            }
+            FieldKind::Number => value
+                .trim()
+                .parse()
+                .map(Field::Number)
+                .map_err(|_| format!("{} must be a whole number", label)),
+            // Passwords are sent as they are, spaces included
+            FieldKind::Password | FieldKind::NewPassword => {
+                Ok(Field::Bytes(value.as_bytes().to_vec()))
+            }
+        }
+    }
+}
+
+/// Turns the name of a field or choice, like `new-password`, into something to show.
+pub fn label(name: &str) -> String {
+    let name = name.replace(|c| c == '-' || c == '_', " ");
+    let mut chars = name.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+/// Only checks the shape of an address, the homeserver knows better if it's real.
+fn is_email(value: &str) -> bool {
+    match value.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.is_empty()
+                && !domain.contains('@')
+                && !value.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,98 @@
+use tokio::sync::broadcast;
+
+use super::message::MessageId;
+
+/// How many changes a receiver can fall behind before it starts missing them.
+const CHANGES_CAPACITY: usize = 1024;
+
+/// A change to the state of the [`Client`](super::Client), so that frontends can update only what changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateChange {
+    GuildAdded(u64),
+    /// The name or picture of a guild changed.
+    GuildUpdated(u64),
+    GuildRemoved(u64),
+    ChannelAdded {
+        guild_id: u64,
+        channel_id: u64,
+    },
+    /// The name or position of a channel changed.
+    ChannelUpdated {
+        guild_id: u64,
+        channel_id: u64,
+    },
+    ChannelRemoved {
+        guild_id: u64,
+        channel_id: u64,
+    },
+    /// A message was added to a channel, either sent by us or received, or put back after its
+    /// deletion was undone.
+    MessageInserted {
+        guild_id: u64,
+        channel_id: u64,
+        message_id: MessageId,
+    },
+    /// A message was edited, or one we sent was acknowledged and now has the given ID.
+    MessageUpdated {
+        guild_id: u64,
+        channel_id: u64,
+        message_id: MessageId,
+    },
+    MessageRemoved {
+        guild_id: u64,
+        channel_id: u64,
+        message_id: u64,
+    },
+    /// Older messages were put at the start of a channel.
+    HistoryLoaded {
+        guild_id: u64,
+        channel_id: u64,
+    },
+    PinsUpdated {
+        guild_id: u64,
+        channel_id: u64,
+    },
+    MemberJoined {
+        guild_id: u64,
+        user_id: u64,
+    },
+    MemberLeft {
+        guild_id: u64,
+        user_id: u64,
+    },
+    ProfileUpdated(u64),
+    /// The roles of a guild, or the roles of its members changed.
+    RolesUpdated(u64),
+    /// The permissions of the current user in a guild were invalidated or fetched.
+    PermissionsUpdated(u64),
+    TypingStarted {
+        guild_id: u64,
+        channel_id: u64,
+        user_id: u64,
+    },
+}
+
+/// Sends the changes of the client to everyone who subscribed to them.
+#[derive(Debug)]
+pub struct Changes {
+    sender: broadcast::Sender<StateChange>,
+}
+
+impl Default for Changes {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CHANGES_CAPACITY).0,
+        }
+    }
+}
+
+impl Changes {
+    pub fn subscribe(&self) -> broadcast::Receiver<StateChange> {
+        self.sender.subscribe()
+    }
+
+    pub fn send(&self, change: StateChange) {
+        // Nobody listening is fine, the state is still up to date
+        let _ = self.sender.send(change);
+    }
+}
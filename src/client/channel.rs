@@ -1,4 +1,4 @@
-use super::message::Messages;
+use super::message::{Attachment, Content, Message, MessageId, Messages};
 use indexmap::IndexMap;
 
 pub type Channels = IndexMap<u64, Channel>;
@@ -8,7 +8,55 @@ pub struct Channel {
     pub name: String,
     pub is_category: bool,
     pub messages: Messages,
-    pub looking_at_message: usize,
     pub loading_messages_history: bool,
     pub reached_top: bool,
+    /// How many messages were received since the user last read this channel.
+    pub unread: usize,
+    /// IDs of the pinned messages, oldest pin first. `None` if they weren't fetched yet.
+    pub pinned: Option<Vec<u64>>,
+}
+
+impl Channel {
+    /// Removes a message, keeping the count of unread messages to the ones that are left.
+    pub fn remove_message(&mut self, message_id: u64) -> Option<Message> {
+        let index = self
+            .messages
+            .iter()
+            .position(|message| message.id == MessageId::Ack(message_id))?;
+        if index >= self.messages.len().saturating_sub(self.unread) {
+            self.unread = self.unread.saturating_sub(1);
+        }
+        Some(self.messages.remove(index))
+    }
+
+    /// Puts a removed message back where its timestamp places it, counting it as unread if it's
+    /// among the unread messages.
+    pub fn restore_message(&mut self, message: Message) {
+        let index = self
+            .messages
+            .iter()
+            .position(|other| other.timestamp > message.timestamp)
+            .unwrap_or_else(|| self.messages.len());
+        if self.unread > 0 && index >= self.messages.len() - self.unread.min(self.messages.len()) {
+            self.unread += 1;
+        }
+        self.messages.insert(index, message);
+    }
+
+    /// Returns all image attachments in the loaded messages, oldest first, along with the message they belong to.
+    pub fn images(&self) -> Vec<(&Message, &Attachment)> {
+        self.messages
+            .iter()
+            .flat_map(|message| {
+                let attachments: &[Attachment] = match &message.content {
+                    Content::Files(attachments) => attachments,
+                    _ => &[],
+                };
+                attachments
+                    .iter()
+                    .filter(|attachment| attachment.is_image())
+                    .map(move |attachment| (message, attachment))
+            })
+            .collect()
+    }
 }
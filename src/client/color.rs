@@ -0,0 +1,28 @@
+/// A color as harmony sends it, which has no alpha.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const WHITE: Color = Color {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+
+    /// Converts a color in the `0xRRGGBB` format harmony uses.
+    pub fn from_harmony(color: i64) -> Self {
+        Self {
+            r: ((color >> 16) & 255) as u8,
+            g: ((color >> 8) & 255) as u8,
+            b: (color & 255) as u8,
+        }
+    }
+
+    pub fn to_harmony(self) -> i64 {
+        ((self.r as i64) << 16) + ((self.g as i64) << 8) + self.b as i64
+    }
+}
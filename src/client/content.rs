@@ -1,13 +1,16 @@
-use super::ClientError;
+use super::{
+    secret::{Keyring, SecretStore},
+    ClientError,
+};
 use harmony_rust_sdk::client::api::rest::FileId;
-use iced_native::image::Data;
-use indexmap::IndexMap;
-use std::path::{Path, PathBuf};
-
-pub use iced::image::Handle as ImageHandle;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 pub const SESSION_FILENAME: &str = "session";
 pub const LOG_FILENAME: &str = "log";
+pub const CONFIG_FILENAME: &str = "config.toml";
 pub const CONTENT_DIR_NAME: &str = "content";
 
 pub fn infer_type_from_bytes(data: &[u8]) -> String {
@@ -27,22 +30,27 @@ pub fn get_filename<P: AsRef<Path>>(path: P) -> String {
 pub struct ContentStore {
     session_file: PathBuf,
     log_file: PathBuf,
+    config_file: PathBuf,
     content_dir: PathBuf,
+    /// Where the session token is saved, see [`Session::save`](super::Session::save).
+    secret_store: Arc<dyn SecretStore>,
 }
 
 impl Default for ContentStore {
     fn default() -> Self {
-        let (session_file, log_file, content_dir) =
+        let (session_file, log_file, config_file, content_dir) =
             match directories_next::ProjectDirs::from("nodomain", "yusdacra", "crust") {
                 Some(app_dirs) => (
                     app_dirs.data_dir().join(SESSION_FILENAME),
                     app_dirs.data_dir().join(LOG_FILENAME),
+                    app_dirs.config_dir().join(CONFIG_FILENAME),
                     app_dirs.cache_dir().join(CONTENT_DIR_NAME),
                 ),
                 // Fallback to current working directory if no HOME is present
                 None => (
                     SESSION_FILENAME.into(),
                     LOG_FILENAME.into(),
+                    CONFIG_FILENAME.into(),
                     CONTENT_DIR_NAME.into(),
                 ),
             };
@@ -50,12 +58,31 @@ impl Default for ContentStore {
         Self {
             session_file,
             log_file,
+            config_file,
             content_dir,
+            secret_store: Arc::new(Keyring),
         }
     }
 }
 
 impl ContentStore {
+    /// Creates a store that keeps everything in one directory, instead of the usual places for each.
+    pub fn in_dir(dir: &Path) -> Self {
+        Self {
+            session_file: dir.join(SESSION_FILENAME),
+            log_file: dir.join(LOG_FILENAME),
+            config_file: dir.join(CONFIG_FILENAME),
+            content_dir: dir.join(CONTENT_DIR_NAME),
+            secret_store: Arc::new(Keyring),
+        }
+    }
+
+    /// Saves session tokens to another store instead of the system keyring.
+    pub fn with_secret_store(mut self, secret_store: Arc<dyn SecretStore>) -> Self {
+        self.secret_store = secret_store;
+        self
+    }
+
     pub fn content_path(&self, id: &FileId) -> PathBuf {
         let normalized_id = id
             .to_string()
@@ -81,6 +108,7 @@ impl ContentStore {
         create_dir_all(self.content_dir())?;
         create_dir_all(self.session_file().parent().unwrap_or(&Path::new(".")))?;
         create_dir_all(self.log_file().parent().unwrap_or(&Path::new(".")))?;
+        create_dir_all(self.config_file().parent().unwrap_or(&Path::new(".")))?;
 
         Ok(())
     }
@@ -93,96 +121,17 @@ impl ContentStore {
         self.session_file.as_path()
     }
 
+    pub fn secret_store(&self) -> &Arc<dyn SecretStore> {
+        &self.secret_store
+    }
+
     pub fn log_file(&self) -> &Path {
         self.log_file.as_path()
     }
-}
 
-fn get_image_size_from_handle(handle: &ImageHandle) -> Option<u64> {
-    // This one angers me a lot, iced pls read the file beforehand and cache it
-    match handle.data() {
-        Data::Bytes(raw) => Some(raw.len() as u64),
-        Data::Path(path) => std::fs::metadata(path).map_or(None, |meta| Some(meta.len())),
-        Data::Pixels {
-            pixels,
-            height: _,
-            width: _,
-        } => Some(pixels.len() as u64),
+    pub fn config_file(&self) -> &Path {
+        self.config_file.as_path()
     }
 }
 
 pub const MAX_THUMB_SIZE: u64 = 1000 * 500; // 500kb
-#[derive(Debug)]
-pub struct ThumbnailCache {
-    thumbnails: IndexMap<FileId, ImageHandle>,
-    max_size: u64,
-}
-
-impl Default for ThumbnailCache {
-    fn default() -> Self {
-        const MAX_CACHE_SIZE: u64 = 1000 * 1000 * 100; // 100Mb
-        Self::new(MAX_CACHE_SIZE)
-    }
-}
-
-impl ThumbnailCache {
-    pub fn new(max_size: u64) -> Self {
-        Self {
-            thumbnails: IndexMap::new(),
-            max_size,
-        }
-    }
-
-    pub fn put_thumbnail(&mut self, thumbnail_id: FileId, thumbnail: ImageHandle) {
-        let thumbnail_size = match get_image_size_from_handle(&thumbnail) {
-            Some(size) => size,
-            None => return,
-        };
-        let cache_size = self.len();
-
-        if cache_size + thumbnail_size > self.max_size {
-            let mut current_size = 0;
-            let mut remove_upto = 0;
-            for (index, size) in self
-                .thumbnails
-                .values()
-                .flat_map(|h| get_image_size_from_handle(h))
-                .enumerate()
-            {
-                if current_size >= thumbnail_size {
-                    remove_upto = index + 1;
-                    break;
-                }
-                current_size += size;
-            }
-            for index in 0..remove_upto {
-                self.thumbnails.shift_remove_index(index);
-            }
-        } else {
-            self.thumbnails.insert(thumbnail_id, thumbnail);
-        }
-    }
-
-    pub fn len(&self) -> u64 {
-        self.thumbnails
-            .values()
-            .flat_map(|h| get_image_size_from_handle(h))
-            .sum()
-    }
-
-    pub fn is_empty(&self) -> bool {
-        self.len() < 1
-    }
-
-    pub fn has_thumbnail(&self, thumbnail_id: &FileId) -> bool {
-        self.thumbnails.contains_key(thumbnail_id)
-    }
-
-    pub fn get_thumbnail(&self, thumbnail_id: &FileId) -> Option<&ImageHandle> {
-        self.thumbnails.get(thumbnail_id)
-    }
-
-    pub fn invalidate_thumbnail(&mut self, thumbnail_id: &FileId) {
-        self.thumbnails.remove(thumbnail_id);
-    }
-}
@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+use ahash::AHashMap;
+use chrono::NaiveDateTime;
+
+/// How long a typing indicator is shown after the last typing event.
+pub const TYPING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// State that only lives for a short time or is only derived from events, and never fetched.
+#[derive(Debug, Default)]
+pub struct EphemeralState {
+    /// Maps user IDs to the `(guild_id, channel_id)` they are typing in, and when it expires.
+    typing: AHashMap<u64, (u64, u64, Instant)>,
+    /// Maps user IDs to the last time we saw an event from them, in UTC.
+    last_seen: AHashMap<u64, NaiveDateTime>,
+}
+
+impl EphemeralState {
+    /// Marks a user as typing in a channel until [`TYPING_TIMEOUT`] passes.
+    pub fn set_typing(&mut self, user_id: u64, guild_id: u64, channel_id: u64) {
+        self.typing.insert(
+            user_id,
+            (guild_id, channel_id, Instant::now() + TYPING_TIMEOUT),
+        );
+    }
+
+    pub fn stop_typing(&mut self, user_id: u64) {
+        self.typing.remove(&user_id);
+    }
+
+    /// Returns the `(guild_id, channel_id)` a user is typing in, if any.
+    pub fn typing_in(&self, user_id: u64) -> Option<(u64, u64)> {
+        self.typing
+            .get(&user_id)
+            .map(|(guild_id, channel_id, _)| (*guild_id, *channel_id))
+    }
+
+    /// Returns the IDs of users who are typing in the given channel.
+    pub fn typing_users(&self, guild_id: u64, channel_id: u64) -> impl Iterator<Item = u64> + '_ {
+        self.typing.iter().filter_map(move |(user_id, (g, c, _))| {
+            (*g == guild_id && *c == channel_id).then(|| *user_id)
+        })
+    }
+
+    /// Records that we have seen an event from this user just now.
+    pub fn saw(&mut self, user_id: u64) {
+        self.last_seen
+            .insert(user_id, chrono::Utc::now().naive_utc());
+    }
+
+    pub fn last_seen(&self, user_id: u64) -> Option<NaiveDateTime> {
+        self.last_seen.get(&user_id).copied()
+    }
+
+    /// Removes everything that expired at or before `now`.
+    pub fn expire(&mut self, now: Instant) {
+        self.typing.retain(|_, (_, _, deadline)| *deadline > now);
+    }
+
+    /// Returns when the earliest ephemeral state expires, which is when [`EphemeralState::expire`]
+    /// should be called next.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.typing.values().map(|(_, _, deadline)| *deadline).min()
+    }
+}
@@ -2,6 +2,7 @@ use harmony_rust_sdk::{
     api::exports::hrpc::url::{ParseError, Url},
     client::error::{ClientError as InnerClientError, HmcParseError},
 };
+use serde::Deserialize;
 use std::fmt::{self, Display};
 
 pub type ClientResult<T> = Result<T, ClientError>;
@@ -20,8 +21,143 @@ pub enum ClientError {
     AlreadyLoggedIn,
     /// Not all required login information was provided.
     MissingLoginInfo,
+    /// There is no keyring to save the session in, so a passphrase is needed to encrypt it.
+    KeyringUnavailable(String),
+    /// The session is encrypted, and needs its passphrase to be read.
+    PassphraseRequired,
+    /// The passphrase doesn't decrypt the session.
+    WrongPassphrase,
     /// Custom error
     Custom(String),
+    /// An error that was turned into its message so it could be cloned, keeping its kind.
+    Described(ErrorKind, String),
+}
+
+/// What went wrong, which decides how the UI recovers from an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The session is invalid or expired.
+    Auth,
+    /// The homeserver couldn't be reached.
+    Network,
+    /// The homeserver wants us to slow down.
+    RateLimit,
+    /// The user isn't allowed to do this.
+    PermissionDenied,
+    /// The guild, channel, message or user doesn't exist.
+    NotFound,
+    /// The request was rejected because of what the user entered.
+    Validation,
+    /// A local file couldn't be read or written.
+    Io,
+    Other,
+}
+
+/// How the UI should react to an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// The error is most likely temporary, so the request can be sent again later.
+    ///
+    /// Requests made through the [`Scheduler`](super::scheduler::Scheduler) are sent again by it,
+    /// other ones are left for the user to try again.
+    Retry,
+    /// Go back to the login screen.
+    Relogin,
+    /// Show the error next to what caused it, and let the user decide.
+    ShowInline,
+}
+
+impl ErrorKind {
+    pub fn recovery(self) -> Recovery {
+        match self {
+            ErrorKind::Auth => Recovery::Relogin,
+            ErrorKind::Network | ErrorKind::RateLimit => Recovery::Retry,
+            _ => Recovery::ShowInline,
+        }
+    }
+
+    /// Finds the kind of an error returned by an hRPC endpoint.
+    ///
+    /// The identifier of the error decides, and the HTTP status is used for identifiers that
+    /// aren't known.
+    fn from_endpoint_error(status: u16, raw_error: &[u8]) -> Self {
+        let identifier = EndpointError::decode(raw_error).map(|err| err.identifier);
+
+        match identifier.as_deref() {
+            Some("h.bad-session") | Some("h.invalid-session") | Some("h.missing-session") => {
+                ErrorKind::Auth
+            }
+            Some("h.rate-limited") => ErrorKind::RateLimit,
+            Some("h.not-enough-permissions") | Some("h.blocked") | Some("h.user-banned") => {
+                ErrorKind::PermissionDenied
+            }
+            Some("h.no-such-guild")
+            | Some("h.no-such-channel")
+            | Some("h.no-such-message")
+            | Some("h.no-such-user")
+            | Some("h.no-such-role")
+            | Some("h.no-such-invite")
+            | Some("h.no-such-file")
+            | Some("h.unknown-endpoint") => ErrorKind::NotFound,
+            Some("h.bad-request")
+            | Some("h.bad-form")
+            | Some("h.bad-field")
+            | Some("h.bad-choice")
+            | Some("h.bad-step")
+            | Some("h.bad-auth-id")
+            | Some("h.bad-email")
+            | Some("h.bad-password")
+            | Some("h.bad-upload")
+            | Some("h.empty-content")
+            | Some("h.too-long") => ErrorKind::Validation,
+            _ => match status {
+                401 => ErrorKind::Auth,
+                429 => ErrorKind::RateLimit,
+                403 => ErrorKind::PermissionDenied,
+                404 => ErrorKind::NotFound,
+                400 => ErrorKind::Validation,
+                _ => ErrorKind::Other,
+            },
+        }
+    }
+}
+
+/// The body of an error returned by an hRPC endpoint.
+#[derive(Debug, Deserialize)]
+struct EndpointError {
+    identifier: String,
+    #[serde(default)]
+    message: String,
+}
+
+impl EndpointError {
+    fn decode(raw_error: &[u8]) -> Option<Self> {
+        serde_json::from_slice(raw_error).ok()
+    }
+}
+
+impl ClientError {
+    pub fn kind(&self) -> ErrorKind {
+        use harmony_rust_sdk::api::exports::hrpc::client::ClientError as HrpcError;
+
+        match self {
+            ClientError::IoError(_) => ErrorKind::Io,
+            ClientError::UrlParse(..) | ClientError::HmcParse(..) => ErrorKind::Validation,
+            ClientError::MissingLoginInfo => ErrorKind::Validation,
+            ClientError::PassphraseRequired | ClientError::WrongPassphrase => ErrorKind::Validation,
+            ClientError::KeyringUnavailable(_) => ErrorKind::Io,
+            ClientError::AlreadyLoggedIn | ClientError::Custom(_) => ErrorKind::Other,
+            ClientError::Described(kind, _) => *kind,
+            ClientError::Internal(InnerClientError::Internal(err)) => match err {
+                HrpcError::EndpointError {
+                    raw_error, status, ..
+                } => ErrorKind::from_endpoint_error(status.as_u16(), raw_error),
+                HrpcError::Reqwest(_) | HrpcError::SocketError(_) => ErrorKind::Network,
+                _ => ErrorKind::Other,
+            },
+            ClientError::Internal(_) => ErrorKind::Other,
+        }
+    }
 }
 
 impl Clone for ClientError {
@@ -31,8 +167,12 @@ impl Clone for ClientError {
         match self {
             AlreadyLoggedIn => AlreadyLoggedIn,
             MissingLoginInfo => MissingLoginInfo,
+            KeyringUnavailable(reason) => KeyringUnavailable(reason.clone()),
+            PassphraseRequired => PassphraseRequired,
+            WrongPassphrase => WrongPassphrase,
             Custom(err) => Custom(err.clone()),
-            _ => Custom(self.to_string()),
+            Described(kind, err) => Described(*kind, err.clone()),
+            _ => Described(self.kind(), self.to_string()),
         }
     }
 }
@@ -66,13 +206,18 @@ impl Display for ClientError {
                     },
                 ) = err
                 {
-                    write!(
-                        fmt,
-                        "API error: {}",
-                        std::str::from_utf8(raw_error)
-                            .unwrap_or("couldn't parse error")
-                            .replace('\n', " "),
-                    )
+                    match EndpointError::decode(raw_error) {
+                        Some(err) if !err.message.is_empty() => {
+                            write!(fmt, "API error: {} ({})", err.message, err.identifier)
+                        }
+                        _ => write!(
+                            fmt,
+                            "API error: {}",
+                            std::str::from_utf8(raw_error)
+                                .unwrap_or("couldn't parse error")
+                                .replace('\n', " "),
+                        ),
+                    }
                 } else {
                     write!(fmt, "{}", err)
                 }
@@ -82,7 +227,16 @@ impl Display for ClientError {
             ClientError::MissingLoginInfo => {
                 write!(fmt, "Missing required login information, can't login.")
             }
-            ClientError::Custom(msg) => write!(fmt, "{}", msg),
+            ClientError::KeyringUnavailable(reason) => write!(
+                fmt,
+                "The system keyring isn't available ({}), a passphrase is needed to protect the session.",
+                reason
+            ),
+            ClientError::PassphraseRequired => {
+                write!(fmt, "The session is encrypted, its passphrase is needed.")
+            }
+            ClientError::WrongPassphrase => write!(fmt, "Wrong passphrase."),
+            ClientError::Custom(msg) | ClientError::Described(_, msg) => write!(fmt, "{}", msg),
         }
     }
 }
@@ -1,7 +1,8 @@
 use ahash::{AHashMap, AHashSet};
 use harmony_rust_sdk::client::api::rest::FileId;
+use indexmap::IndexMap;
 
-use super::channel::Channels;
+use super::{channel::Channels, message::Message, role::Roles};
 
 pub type Guilds = AHashMap<u64, Guild>;
 
@@ -11,28 +12,61 @@ pub struct Guild {
     pub picture: Option<FileId>,
     pub channels: Channels,
     pub members: AHashSet<u64>,
+    pub roles: Roles,
+    /// Role IDs of members, only contains members whose roles were fetched.
+    pub user_roles: AHashMap<u64, Vec<u64>>,
 }
 
 impl Guild {
     pub fn update_channel_order(&mut self, previous_id: u64, next_id: u64, channel_id: u64) {
-        if let Some(chan_pos) = self.channels.keys().position(|k| k == &channel_id) {
-            let prev_pos = self.channels.keys().position(|id| *id == previous_id);
-            let next_pos = self.channels.keys().position(|id| *id == next_id);
-
-            if let Some(pos) = prev_pos {
-                if pos != chan_pos {
-                    self.channels.swap_indices(pos + 1, chan_pos);
-                }
-            } else if let Some(pos) = next_pos {
-                if pos != 0 {
-                    self.channels.swap_indices(pos - 1, chan_pos);
-                } else {
-                    let (k, v) = self.channels.pop().unwrap();
-                    self.channels.reverse();
-                    self.channels.insert(k, v);
-                    self.channels.reverse();
-                }
+        move_between(&mut self.channels, previous_id, next_id, channel_id);
+    }
+
+    /// Moves a role so that it's placed between `previous_id` and `next_id`.
+    pub fn update_role_order(&mut self, previous_id: u64, next_id: u64, role_id: u64) {
+        move_between(&mut self.roles, previous_id, next_id, role_id);
+    }
+
+    /// Adds a system notice to the end of every channel that has its messages loaded.
+    pub fn push_system_notice(&mut self, text: String) {
+        for channel in self.channels.values_mut() {
+            if !channel.is_category && !channel.messages.is_empty() {
+                channel.messages.push(Message::system_notice(text.clone()));
             }
         }
     }
+
+    /// Returns the IDs of the channels that would be before and after a channel, if it was moved
+    /// to `position`. An ID is `0` if there is no channel there.
+    pub fn channel_neighbours_at(&self, channel_id: u64, position: usize) -> Option<(u64, u64)> {
+        let mut ids = self.channels.keys().copied().collect::<Vec<_>>();
+        let from = ids.iter().position(|id| *id == channel_id)?;
+        ids.remove(from);
+        let position = position.min(ids.len());
+
+        let previous_id = position
+            .checked_sub(1)
+            .map_or(0, |pos| ids.get(pos).copied().unwrap_or(0));
+        let next_id = ids.get(position).copied().unwrap_or(0);
+
+        Some((previous_id, next_id))
+    }
+}
+
+/// Moves an entry so that it's placed right after `previous_id`, or right before `next_id` if
+/// `previous_id` isn't in the map. Moves it to the end if neither of them are in the map.
+fn move_between<V>(map: &mut IndexMap<u64, V>, previous_id: u64, next_id: u64, id: u64) {
+    let value = match map.shift_remove(&id) {
+        Some(value) => value,
+        None => return,
+    };
+
+    let position = map
+        .get_index_of(&previous_id)
+        .map(|pos| pos + 1)
+        .or_else(|| map.get_index_of(&next_id))
+        .unwrap_or_else(|| map.len());
+    let mut entries = map.drain(..).collect::<Vec<_>>();
+    entries.insert(position, (id, value));
+    map.extend(entries);
 }
@@ -0,0 +1,29 @@
+use harmony_rust_sdk::api::chat::get_guild_invites_response::Invite as HarmonyInvite;
+
+#[derive(Debug, Clone)]
+pub struct Invite {
+    pub id: String,
+    /// How many times this invite can be used, `-1` if it can be used any number of times.
+    pub possible_uses: i32,
+    pub use_count: i32,
+}
+
+impl From<HarmonyInvite> for Invite {
+    fn from(invite: HarmonyInvite) -> Self {
+        Self {
+            id: invite.invite_id,
+            possible_uses: invite.possible_uses,
+            use_count: invite.use_count,
+        }
+    }
+}
+
+impl Default for Invite {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            possible_uses: -1,
+            use_count: 0,
+        }
+    }
+}
@@ -1,5 +1,3 @@
-use std::time::Instant;
-
 use ahash::AHashMap;
 use harmony_rust_sdk::{api::harmonytypes::UserStatus, client::api::rest::FileId};
 
@@ -10,8 +8,8 @@ pub struct Member {
     pub avatar_url: Option<FileId>,
     pub username: String,
     pub display_user: bool,
-    pub typing_in_channel: Option<(u64, u64, Instant)>,
     pub status: UserStatus,
+    pub is_bot: bool,
 }
 
 impl Default for Member {
@@ -20,8 +18,8 @@ impl Default for Member {
             avatar_url: None,
             username: String::default(),
             display_user: true,
-            typing_in_channel: None,
             status: UserStatus::Offline,
+            is_bot: false,
         }
     }
 }
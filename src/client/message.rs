@@ -9,7 +9,7 @@ use harmony_rust_sdk::{
 use std::{str::FromStr, time::UNIX_EPOCH};
 use uuid::Uuid;
 
-use super::{content::MAX_THUMB_SIZE, post_heading, PostProcessEvent};
+use super::{color::Color, content::MAX_THUMB_SIZE, post_heading, PostProcessEvent};
 
 pub type Messages = Vec<Message>;
 
@@ -54,7 +54,7 @@ impl From<EmbedHeading> for harmonytypes::EmbedHeading {
 pub struct Embed {
     pub title: String,
     pub body: String,
-    pub color: iced::Color,
+    pub color: Color,
     pub footer: Option<EmbedHeading>,
     pub header: Option<EmbedHeading>,
     pub fields: Vec<EmbedField>,
@@ -64,12 +64,7 @@ impl From<Embed> for harmonytypes::Embed {
     fn from(e: Embed) -> harmonytypes::Embed {
         harmonytypes::Embed {
             body: e.body,
-            color: {
-                let mut c = (e.color.r * 255_f32) as i64;
-                c = (c << 8) + (e.color.g * 255_f32) as i64;
-                c = (c << 8) + (e.color.b * 255_f32) as i64;
-                c as i64
-            },
+            color: e.color.to_harmony(),
             fields: e.fields.into_iter().map(Into::into).collect(),
             title: e.title,
             footer: e.footer.map(Into::into),
@@ -84,6 +79,8 @@ pub struct Attachment {
     pub name: String,
     pub id: FileId,
     pub size: u32,
+    /// Text shown with the attachment, which can be edited after it's sent.
+    pub caption: String,
 }
 
 impl From<Attachment> for harmonytypes::Attachment {
@@ -93,7 +90,7 @@ impl From<Attachment> for harmonytypes::Attachment {
             name: a.name,
             size: a.size as i32,
             r#type: a.kind,
-            caption: Default::default(),
+            caption: a.caption,
         }
     }
 }
@@ -105,11 +102,16 @@ impl Attachment {
             kind: "application/octet-stream".into(),
             name: "unknown".to_string(),
             size: 0,
+            caption: String::new(),
         }
     }
 
+    pub fn is_image(&self) -> bool {
+        matches!(self.kind.split('/').next(), Some("image"))
+    }
+
     pub fn is_thumbnail(&self) -> bool {
-        matches!(self.kind.split('/').next(), Some("image")) && (self.size as u64) < MAX_THUMB_SIZE
+        self.is_image() && (self.size as u64) < MAX_THUMB_SIZE
     }
 
     pub fn from_harmony_attachment(attachment: harmonytypes::Attachment) -> Option<Self> {
@@ -118,6 +120,7 @@ impl Attachment {
             kind: attachment.r#type,
             name: attachment.name,
             size: attachment.size as u32,
+            caption: attachment.caption,
         })
     }
 }
@@ -141,10 +144,12 @@ impl From<Override> for harmonytypes::Override {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MessageId {
     Ack(u64),
     Unack(u64),
+    /// A message that only exists in this client, like system notices.
+    Local(u64),
 }
 
 impl MessageId {
@@ -219,6 +224,14 @@ impl Default for Content {
     }
 }
 
+/// A previous content of an edited message.
+#[derive(Debug, Clone)]
+pub struct Revision {
+    pub content: Content,
+    /// When this content was replaced by an edit, in UTC.
+    pub replaced_at: NaiveDateTime,
+}
+
 #[derive(Debug, Clone)]
 pub struct Message {
     pub id: MessageId,
@@ -227,9 +240,39 @@ pub struct Message {
     pub timestamp: NaiveDateTime,
     pub overrides: Option<Override>,
     pub being_edited: Option<String>,
+    /// The ID of the message this message replies to.
+    pub reply_to: Option<u64>,
+    /// When the message was last edited, in UTC.
+    pub edited_at: Option<NaiveDateTime>,
+    /// Previous contents of the message that were seen by this client, oldest first.
+    pub revisions: Vec<Revision>,
 }
 
 impl Message {
+    /// Creates a notice that is only shown locally, like a member being kicked.
+    pub fn system_notice(text: String) -> Self {
+        Self {
+            id: MessageId::Local(Uuid::new_v4().as_u128() as u64),
+            content: Content::Text(text),
+            overrides: Some(Override {
+                name: "System".to_string(),
+                avatar_url: None,
+                reason: Some(Reason::SystemMessage(Default::default())),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Replaces the content of the message with an edited one, keeping the old content as a revision.
+    pub fn edit(&mut self, content: Content, edited_at: NaiveDateTime) {
+        let previous = std::mem::replace(&mut self.content, content);
+        self.revisions.push(Revision {
+            content: previous,
+            replaced_at: edited_at,
+        });
+        self.edited_at = Some(edited_at);
+    }
+
     pub fn post_process(&self, post: &mut Vec<PostProcessEvent>) {
         match &self.content {
             Content::Files(attachments) => {
@@ -259,6 +302,9 @@ impl Default for Message {
             },
             overrides: None,
             being_edited: None,
+            reply_to: None,
+            edited_at: None,
+            revisions: Vec::new(),
         }
     }
 }
@@ -310,11 +356,7 @@ impl From<harmonytypes::Embed> for Embed {
                     body: f.body,
                 })
                 .collect(),
-            color: iced::Color::from_rgb8(
-                ((e.color >> 16) & 255) as u8,
-                ((e.color >> 8) & 255) as u8,
-                (e.color & 255) as u8,
-            ),
+            color: Color::from_harmony(e.color),
         }
     }
 }
@@ -338,6 +380,11 @@ impl From<HarmonyMessage> for Message {
             },
             overrides: message.overrides.map(From::from),
             being_edited: None,
+            reply_to: (message.in_reply_to != 0).then(|| message.in_reply_to),
+            edited_at: message
+                .edited_at
+                .map(|t| NaiveDateTime::from_timestamp(t.seconds, t.nanos as u32)),
+            revisions: Vec::new(),
         }
     }
 }
@@ -1,13 +1,25 @@
 #![allow(clippy::field_reassign_with_default)]
 
+pub mod auth;
+pub mod change;
 pub mod channel;
+pub mod color;
 pub mod content;
+pub mod ephemeral;
 pub mod error;
 pub mod guild;
+pub mod invite;
 pub mod member;
 pub mod message;
+pub mod permission;
+pub mod requests;
+pub mod role;
+pub mod scheduler;
+pub mod secret;
 
+use change::{Changes, StateChange};
 use channel::Channel;
+use chrono::NaiveDateTime;
 use guild::Guild;
 pub use harmony_rust_sdk::{
     api::exports::hrpc::url::Url,
@@ -15,36 +27,28 @@ pub use harmony_rust_sdk::{
 };
 use harmony_rust_sdk::{
     api::{
-        chat::{event::*, DeleteMessageRequest},
-        harmonytypes::{Message as HarmonyMessage, UserStatus},
-    },
-    client::api::{
-        chat::{
-            message::{
-                delete_message, send_message, update_message_text, SendMessage,
-                SendMessageSelfBuilder, UpdateMessageTextRequest,
-            },
-            EventSource,
-        },
-        rest::FileId,
+        chat::{event::*, LeaveReason},
+        harmonytypes::UserStatus,
     },
+    client::api::{chat::EventSource, rest::FileId},
 };
 
 use content::ContentStore;
+use ephemeral::EphemeralState;
 use error::{ClientError, ClientResult};
-use iced::Command;
 use member::{Member, Members};
-use message::{harmony_messages_to_ui_messages, Attachment, Content, Embed, MessageId};
+use message::{Attachment, Content, Embed, MessageId};
+use permission::{Node, PermissionCache};
+use requests::Requests;
+use role::Role;
+use scheduler::Scheduler;
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::{self, Debug, Formatter},
-    path::PathBuf,
     str::FromStr,
     sync::Arc,
-    time::{Duration, Instant},
 };
-
-use crate::ui::component::event_history::SHOWN_MSGS_LIMIT;
+use tokio::sync::broadcast;
 
 use self::{
     guild::Guilds,
@@ -68,6 +72,44 @@ impl Debug for Session {
     }
 }
 
+impl Session {
+    /// The session a homeserver gave when logging in to it.
+    pub fn new(homeserver: &Url, session: InnerSession) -> Self {
+        Self {
+            homeserver: homeserver.to_string(),
+            session_token: session.session_token,
+            user_id: session.user_id.to_string(),
+        }
+    }
+
+    /// Reads a session saved with [`Session::save`].
+    ///
+    /// `passphrase` is needed for sessions that were encrypted. Sessions saved in plain text by
+    /// older versions are saved again like [`Session::save`] does, which also needs it if there
+    /// is no keyring.
+    pub async fn load(
+        content_store: &ContentStore,
+        passphrase: Option<String>,
+    ) -> ClientResult<Self> {
+        secret::load(content_store, passphrase).await
+    }
+
+    /// Saves the token to the secret store of `content_store`, or encrypts the whole session with
+    /// `passphrase` if the store isn't available.
+    pub async fn save(
+        &self,
+        content_store: &ContentStore,
+        passphrase: Option<String>,
+    ) -> ClientResult<()> {
+        secret::save(self, content_store, passphrase).await
+    }
+
+    /// Removes the session file, and the token if it's in a secret store.
+    pub async fn remove(content_store: &ContentStore) -> ClientResult<()> {
+        secret::remove(content_store).await
+    }
+}
+
 impl From<Session> for InnerSession {
     fn from(session: Session) -> Self {
         InnerSession {
@@ -82,15 +124,21 @@ pub enum PostProcessEvent {
     FetchProfile(u64),
     FetchGuildData(u64),
     FetchThumbnail(Attachment),
-    GoToFirstMsgOnChannel(u64),
+    /// Fetches the permissions of a guild again, after they were invalidated.
+    FetchPermissions(u64),
 }
 
 pub struct Client {
     inner: InnerClient,
     pub guilds: Guilds,
     pub members: Members,
+    pub ephemeral: EphemeralState,
+    pub permissions: PermissionCache,
     pub user_id: Option<u64>,
     content_store: Arc<ContentStore>,
+    scheduler: Scheduler,
+    requests: Requests,
+    changes: Changes,
 }
 
 impl Debug for Client {
@@ -114,18 +162,46 @@ impl Client {
         session: Option<InnerSession>,
         content_store: Arc<ContentStore>,
     ) -> ClientResult<Self> {
+        let user_id = session.as_ref().map(|s| s.user_id);
+        let inner = InnerClient::new(homeserver_url, session).await?;
         Ok(Self {
             guilds: Guilds::new(),
             members: Members::new(),
-            user_id: session.as_ref().map(|s| s.user_id),
+            ephemeral: EphemeralState::default(),
+            permissions: PermissionCache::default(),
+            user_id,
+            requests: Requests::new(inner.clone(), content_store.clone()),
             content_store,
-            inner: InnerClient::new(homeserver_url, session).await?,
+            scheduler: Scheduler::default(),
+            changes: Changes::default(),
+            inner,
         })
     }
 
-    pub async fn logout(_inner: InnerClient, session_file: PathBuf) -> ClientResult<()> {
-        tokio::fs::remove_file(session_file).await?;
-        Ok(())
+    /// Creates a client that is logged in with a saved session.
+    pub async fn from_session(
+        session: Session,
+        content_store: Arc<ContentStore>,
+    ) -> ClientResult<Self> {
+        let homeserver_url = session
+            .homeserver
+            .parse::<Url>()
+            .map_err(|err| ClientError::UrlParse(session.homeserver.clone(), err))?;
+        Self::new(homeserver_url, Some(session.into()), content_store).await
+    }
+
+    /// The session to save once logging in is complete.
+    pub fn session(&self) -> Option<Session> {
+        match self.auth_status() {
+            AuthStatus::Complete(session) => {
+                Some(Session::new(self.inner.homeserver_url(), session))
+            }
+            _ => None,
+        }
+    }
+
+    pub async fn logout(content_store: Arc<ContentStore>) -> ClientResult<()> {
+        Session::remove(&content_store).await
     }
 
     #[inline(always)]
@@ -148,6 +224,21 @@ impl Client {
         &self.inner
     }
 
+    #[inline(always)]
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    #[inline(always)]
+    pub fn requests(&self) -> &Requests {
+        &self.requests
+    }
+
+    /// Returns a receiver of the changes made to the state from now on.
+    pub fn state_changes(&self) -> broadcast::Receiver<StateChange> {
+        self.changes.subscribe()
+    }
+
     #[inline(always)]
     pub fn get_guild(&mut self, guild_id: u64) -> Option<&mut Guild> {
         self.guilds.get_mut(&guild_id)
@@ -164,136 +255,148 @@ impl Client {
         self.members.get_mut(&user_id)
     }
 
-    pub fn send_msg_cmd(
+    /// Adds a message to the end of a channel, returning `false` if the channel isn't known.
+    pub fn push_message(&mut self, guild_id: u64, channel_id: u64, message: Message) -> bool {
+        let message_id = message.id;
+        match self.get_channel(guild_id, channel_id) {
+            Some(channel) => {
+                channel.messages.push(message);
+                self.changes.send(StateChange::MessageInserted {
+                    guild_id,
+                    channel_id,
+                    message_id,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes a message from a channel, like when it's being deleted, returning it.
+    pub fn remove_message(
         &mut self,
         guild_id: u64,
         channel_id: u64,
-        retry_after: Duration,
-        message: Message,
-    ) -> Option<Command<crate::ui::screen::Message>> {
-        use crate::ui::screen::Message;
+        message_id: u64,
+    ) -> Option<Message> {
+        let message = self
+            .get_channel(guild_id, channel_id)?
+            .remove_message(message_id)?;
+        self.changes.send(StateChange::MessageRemoved {
+            guild_id,
+            channel_id,
+            message_id,
+        });
+        Some(message)
+    }
 
+    /// Puts a message that was removed back in its channel, like when its deletion is undone.
+    pub fn restore_message(&mut self, guild_id: u64, channel_id: u64, message: Message) {
+        let message_id = message.id;
         if let Some(channel) = self.get_channel(guild_id, channel_id) {
-            if retry_after.as_secs() == 0 {
-                channel.messages.push(message.clone());
-            }
-
-            let inner = self.inner().clone();
-
-            Some(Command::perform(
-                async move {
-                    tokio::time::sleep(retry_after).await;
-
-                    let msg = SendMessage::new(guild_id, channel_id)
-                        .content(harmony_rust_sdk::api::harmonytypes::Content {
-                            content: Some(message.content.clone().into()),
-                        })
-                        .echo_id(message.id.transaction_id().unwrap())
-                        .overrides(message.overrides.clone().map(Into::into));
-
-                    let send_result = send_message(&inner, msg).await;
-
-                    match send_result {
-                        Ok(resp) => Message::MessageSent {
-                            message_id: resp.message_id,
-                            transaction_id: message.id.transaction_id().unwrap(),
-                            channel_id,
-                            guild_id,
-                        },
-                        Err(err) => {
-                            tracing::error!("error occured when sending message: {}", err);
-                            Message::SendMessage {
-                                message,
-                                retry_after: retry_after + Duration::from_secs(1),
-                                channel_id,
-                                guild_id,
-                            }
-                        }
-                    }
-                },
-                |retry| retry,
-            ))
-        } else {
-            None
+            channel.restore_message(message);
+            self.changes.send(StateChange::MessageInserted {
+                guild_id,
+                channel_id,
+                message_id,
+            });
         }
     }
 
-    pub fn delete_msg_cmd(
-        &self,
+    /// Gives a message we sent the ID the homeserver acknowledged it with.
+    pub fn ack_message(
+        &mut self,
         guild_id: u64,
         channel_id: u64,
+        transaction_id: u64,
         message_id: u64,
-    ) -> Command<crate::ui::screen::Message> {
-        use crate::ui::screen::Message;
-
-        let inner = self.inner().clone();
-
-        Command::perform(
-            async move {
-                delete_message(
-                    &inner,
-                    DeleteMessageRequest {
-                        guild_id,
-                        channel_id,
-                        message_id,
-                    },
-                )
-                .await
-            },
-            |result| {
-                result.map_or_else(
-                    |err| Message::Error(Box::new(err.into())),
-                    |_| Message::Nothing,
-                )
-            },
-        )
+    ) {
+        if let Some(msg) = self
+            .get_channel(guild_id, channel_id)
+            .map(|channel| {
+                channel
+                    .messages
+                    .iter_mut()
+                    .find(|msg| msg.id.transaction_id() == Some(transaction_id))
+            })
+            .flatten()
+        {
+            msg.id = MessageId::Ack(message_id);
+            self.changes.send(StateChange::MessageUpdated {
+                guild_id,
+                channel_id,
+                message_id: MessageId::Ack(message_id),
+            });
+        }
     }
 
-    pub fn edit_msg_cmd(
-        &self,
+    /// Finishes editing a message, applying content the homeserver won't send an event for.
+    pub fn finish_edit(
+        &mut self,
         guild_id: u64,
         channel_id: u64,
         message_id: u64,
-        new_content: String,
-    ) -> Command<crate::ui::screen::Message> {
-        use crate::ui::screen::Message;
+        new_content: Option<Content>,
+    ) {
+        if let Some(msg) = self
+            .get_channel(guild_id, channel_id)
+            .map(|c| {
+                c.messages
+                    .iter_mut()
+                    .find(|m| m.id.id() == Some(message_id))
+            })
+            .flatten()
+        {
+            msg.being_edited = None;
+            if let Some(content) = new_content {
+                msg.edit(content, chrono::Utc::now().naive_utc());
+            }
+            self.changes.send(StateChange::MessageUpdated {
+                guild_id,
+                channel_id,
+                message_id: MessageId::Ack(message_id),
+            });
+        }
+    }
 
-        let inner = self.inner().clone();
+    pub fn set_pins(&mut self, guild_id: u64, channel_id: u64, message_ids: Vec<u64>) {
+        if let Some(channel) = self.get_channel(guild_id, channel_id) {
+            channel.pinned = Some(message_ids);
+            self.changes.send(StateChange::PinsUpdated {
+                guild_id,
+                channel_id,
+            });
+        }
+    }
 
-        Command::perform(
-            async move {
-                let result = update_message_text(
-                    &inner,
-                    UpdateMessageTextRequest {
-                        guild_id,
-                        channel_id,
-                        message_id,
-                        new_content,
-                    },
-                )
-                .await;
-
-                result.map_or_else(
-                    |err| Message::MessageEdited {
-                        guild_id,
-                        channel_id,
-                        message_id,
-                        err: Some(Box::new(err.into())),
-                    },
-                    |_| Message::MessageEdited {
-                        guild_id,
-                        channel_id,
-                        message_id,
-                        err: None,
-                    },
-                )
-            },
-            |m| m,
+    pub fn insert_permissions(
+        &mut self,
+        guild_id: u64,
+        channel_id: Option<u64>,
+        permissions: Vec<(Node, bool)>,
+    ) {
+        for (node, ok) in permissions {
+            self.permissions.insert(guild_id, channel_id, node, ok);
+        }
+        self.changes.send(StateChange::PermissionsUpdated(guild_id));
+    }
+
+    /// Creates a link that points to a message on our homeserver.
+    pub fn message_link(&self, guild_id: u64, channel_id: u64, message_id: u64) -> String {
+        let homeserver = self.inner().homeserver_url();
+        let host = homeserver.host_str().unwrap_or_default();
+        let host = homeserver
+            .port()
+            .map_or_else(|| host.to_string(), |port| format!("{}:{}", host, port));
+        format!(
+            "harmony://{}/{}/{}/{}",
+            host, guild_id, channel_id, message_id
         )
     }
 
     pub fn process_event(&mut self, event: Event) -> Vec<PostProcessEvent> {
         let mut post = Vec::new();
+        let mut changes = Vec::new();
 
         match event {
             Event::SentMessage(message_sent) => {
@@ -304,6 +407,11 @@ impl Client {
                     let channel_id = message.channel_id;
                     let message_id = message.message_id;
 
+                    // A sent message means the author is done typing it
+                    self.ephemeral.stop_typing(message.author_id);
+                    self.ephemeral.saw(message.author_id);
+
+                    let own_user_id = self.user_id;
                     if let Some(channel) = self.get_channel(guild_id, channel_id) {
                         let message = Message::from(message);
 
@@ -327,20 +435,32 @@ impl Client {
                             .find(|omsg| omsg.id == MessageId::Unack(echo_id))
                         {
                             *msg = message;
+                            changes.push(StateChange::MessageUpdated {
+                                guild_id,
+                                channel_id,
+                                message_id: MessageId::Ack(message_id),
+                            });
                         } else if let Some(msg) = channel
                             .messages
                             .iter_mut()
                             .find(|omsg| omsg.id == MessageId::Ack(message_id))
                         {
                             *msg = message;
+                            changes.push(StateChange::MessageUpdated {
+                                guild_id,
+                                channel_id,
+                                message_id: MessageId::Ack(message_id),
+                            });
                         } else {
+                            if Some(message.sender) != own_user_id {
+                                channel.unread += 1;
+                            }
                             channel.messages.push(message);
-                        }
-
-                        let disp = channel.messages.len();
-                        if channel.looking_at_message >= disp.saturating_sub(SHOWN_MSGS_LIMIT) {
-                            channel.looking_at_message = disp.saturating_sub(1);
-                            post.push(PostProcessEvent::GoToFirstMsgOnChannel(channel_id));
+                            changes.push(StateChange::MessageInserted {
+                                guild_id,
+                                channel_id,
+                                message_id: MessageId::Ack(message_id),
+                            });
                         }
                     }
                 }
@@ -351,13 +471,53 @@ impl Client {
                 message_id,
             }) => {
                 if let Some(channel) = self.get_channel(guild_id, channel_id) {
-                    if let Some(pos) = channel
-                        .messages
-                        .iter()
-                        .position(|msg| msg.id == MessageId::Ack(message_id))
-                    {
-                        channel.messages.remove(pos);
+                    if channel.remove_message(message_id).is_some() {
+                        changes.push(StateChange::MessageRemoved {
+                            guild_id,
+                            channel_id,
+                            message_id,
+                        });
+                    }
+                    if let Some(pinned) = channel.pinned.as_mut() {
+                        pinned.retain(|id| *id != message_id);
+                    }
+                }
+            }
+            Event::PinnedMessage(MessagePinned {
+                guild_id,
+                channel_id,
+                message_id,
+            }) => {
+                // Pins that weren't fetched yet will be up to date when they are
+                if let Some(pinned) = self
+                    .get_channel(guild_id, channel_id)
+                    .map(|channel| channel.pinned.as_mut())
+                    .flatten()
+                {
+                    if !pinned.contains(&message_id) {
+                        pinned.push(message_id);
                     }
+                    changes.push(StateChange::PinsUpdated {
+                        guild_id,
+                        channel_id,
+                    });
+                }
+            }
+            Event::UnpinnedMessage(MessageUnpinned {
+                guild_id,
+                channel_id,
+                message_id,
+            }) => {
+                if let Some(pinned) = self
+                    .get_channel(guild_id, channel_id)
+                    .map(|channel| channel.pinned.as_mut())
+                    .flatten()
+                {
+                    pinned.retain(|id| *id != message_id);
+                    changes.push(StateChange::PinsUpdated {
+                        guild_id,
+                        channel_id,
+                    });
                 }
             }
             Event::EditedMessage(message_updated) => {
@@ -369,8 +529,20 @@ impl Client {
                         .messages
                         .iter_mut()
                         .find(|message| message.id == MessageId::Ack(message_updated.message_id))
+                        // The event only has text, which would replace the attachments or embeds
+                        // of other messages
+                        .filter(|message| matches!(message.content, Content::Text(_)))
                     {
-                        msg.content = Content::Text(message_updated.content);
+                        let edited_at = message_updated.edited_at.map_or_else(
+                            || chrono::Utc::now().naive_utc(),
+                            |t| NaiveDateTime::from_timestamp(t.seconds, t.nanos as u32),
+                        );
+                        msg.edit(Content::Text(message_updated.content), edited_at);
+                        changes.push(StateChange::MessageUpdated {
+                            guild_id,
+                            channel_id,
+                            message_id: msg.id,
+                        });
                     }
                 }
             }
@@ -381,6 +553,10 @@ impl Client {
                 if let Some(guild) = self.get_guild(guild_id) {
                     guild.channels.remove(&channel_id);
                 }
+                changes.push(StateChange::ChannelRemoved {
+                    guild_id,
+                    channel_id,
+                });
             }
             Event::EditedChannel(ChannelUpdated {
                 guild_id,
@@ -404,6 +580,10 @@ impl Client {
                         guild.update_channel_order(previous_id, next_id, channel_id);
                     }
                 }
+                changes.push(StateChange::ChannelUpdated {
+                    guild_id,
+                    channel_id,
+                });
             }
             Event::CreatedChannel(ChannelCreated {
                 guild_id,
@@ -421,22 +601,31 @@ impl Client {
                             is_category,
                             name,
                             loading_messages_history: false,
-                            looking_at_message: 0,
                             messages: Vec::new(),
                             reached_top: false,
+                            unread: 0,
+                            pinned: None,
                         },
                     );
                     guild.update_channel_order(previous_id, next_id, channel_id);
                 }
+                changes.push(StateChange::ChannelAdded {
+                    guild_id,
+                    channel_id,
+                });
             }
             Event::Typing(Typing {
                 guild_id,
                 channel_id,
                 user_id,
             }) => {
-                if let Some(member) = self.get_member(user_id) {
-                    member.typing_in_channel = Some((guild_id, channel_id, Instant::now()));
-                }
+                self.ephemeral.set_typing(user_id, guild_id, channel_id);
+                self.ephemeral.saw(user_id);
+                changes.push(StateChange::TypingStarted {
+                    guild_id,
+                    channel_id,
+                    user_id,
+                });
             }
             Event::JoinedMember(MemberJoined {
                 guild_id,
@@ -449,6 +638,11 @@ impl Client {
                 if let Some(guild) = self.get_guild(guild_id) {
                     guild.members.insert(member_id);
                 }
+                self.ephemeral.saw(member_id);
+                changes.push(StateChange::MemberJoined {
+                    guild_id,
+                    user_id: member_id,
+                });
 
                 if !self.members.contains_key(&member_id) {
                     post.push(PostProcessEvent::FetchProfile(member_id));
@@ -457,11 +651,27 @@ impl Client {
             Event::LeftMember(MemberLeft {
                 guild_id,
                 member_id,
-                leave_reason: _,
+                leave_reason,
             }) => {
+                let name = self
+                    .members
+                    .get(&member_id)
+                    .map_or_else(|| member_id.to_string(), |member| member.username.clone());
+                let notice = match LeaveReason::from_i32(leave_reason) {
+                    Some(LeaveReason::Banned) => format!("{} was banned", name),
+                    Some(LeaveReason::Kicked) => format!("{} was kicked", name),
+                    _ => format!("{} left", name),
+                };
+
                 if let Some(guild) = self.get_guild(guild_id) {
                     guild.members.remove(&member_id);
+                    guild.user_roles.remove(&member_id);
+                    guild.push_system_notice(notice);
                 }
+                changes.push(StateChange::MemberLeft {
+                    guild_id,
+                    user_id: member_id,
+                });
             }
             Event::ProfileUpdated(ProfileUpdated {
                 user_id,
@@ -471,13 +681,16 @@ impl Client {
                 update_avatar,
                 new_status,
                 update_status,
-                is_bot: _,
-                update_is_bot: _,
+                is_bot,
+                update_is_bot,
             }) => {
                 let member = self.members.entry(user_id).or_default();
                 if update_username {
                     member.username = new_username;
                 }
+                if update_is_bot {
+                    member.is_bot = is_bot;
+                }
                 if update_status {
                     member.status = UserStatus::from_i32(new_status).unwrap();
                 }
@@ -491,6 +704,7 @@ impl Client {
                         }));
                     }
                 };
+                changes.push(StateChange::ProfileUpdated(user_id));
             }
             Event::GuildAddedToList(GuildAddedToList {
                 guild_id,
@@ -498,15 +712,18 @@ impl Client {
             }) => {
                 self.guilds.insert(guild_id, Default::default());
                 post.push(PostProcessEvent::FetchGuildData(guild_id));
+                changes.push(StateChange::GuildAdded(guild_id));
             }
             Event::GuildRemovedFromList(GuildRemovedFromList {
                 guild_id,
                 homeserver: _,
             }) => {
                 self.guilds.remove(&guild_id);
+                changes.push(StateChange::GuildRemoved(guild_id));
             }
             Event::DeletedGuild(GuildDeleted { guild_id }) => {
                 self.guilds.remove(&guild_id);
+                changes.push(StateChange::GuildRemoved(guild_id));
             }
             Event::EditedGuild(GuildUpdated {
                 guild_id,
@@ -532,10 +749,128 @@ impl Client {
                         }));
                     }
                 }
+                changes.push(StateChange::GuildUpdated(guild_id));
+            }
+            Event::RoleCreated(RoleCreated {
+                guild_id,
+                role_id,
+                name,
+                color,
+                hoist,
+                pingable,
+            }) => {
+                if let Some(guild) = self.get_guild(guild_id) {
+                    guild.roles.insert(
+                        role_id,
+                        Role {
+                            name,
+                            color: color::Color::from_harmony(color.into()),
+                            hoist,
+                            pingable,
+                        },
+                    );
+                }
+                changes.push(StateChange::RolesUpdated(guild_id));
+            }
+            Event::RoleDeleted(RoleDeleted { guild_id, role_id }) => {
+                if let Some(guild) = self.get_guild(guild_id) {
+                    guild.roles.shift_remove(&role_id);
+                    for roles in guild.user_roles.values_mut() {
+                        roles.retain(|id| *id != role_id);
+                    }
+                }
+                changes.push(StateChange::RolesUpdated(guild_id));
+                self.permissions.invalidate_guild(guild_id);
+                post.push(PostProcessEvent::FetchPermissions(guild_id));
+                changes.push(StateChange::PermissionsUpdated(guild_id));
+            }
+            Event::RoleUpdated(RoleUpdated {
+                guild_id,
+                role_id,
+                new_name,
+                new_color,
+                new_hoist,
+                new_pingable,
+                update_name,
+                update_color,
+                update_hoist,
+                update_pingable,
+            }) => {
+                if let Some(role) = self
+                    .get_guild(guild_id)
+                    .map(|guild| guild.roles.get_mut(&role_id))
+                    .flatten()
+                {
+                    if update_name {
+                        role.name = new_name;
+                    }
+                    if update_color {
+                        role.color = color::Color::from_harmony(new_color.into());
+                    }
+                    if update_hoist {
+                        role.hoist = new_hoist;
+                    }
+                    if update_pingable {
+                        role.pingable = new_pingable;
+                    }
+                }
+                changes.push(StateChange::RolesUpdated(guild_id));
+            }
+            Event::RoleMoved(RoleMoved {
+                guild_id,
+                role_id,
+                before_id,
+                after_id,
+            }) => {
+                if let Some(guild) = self.get_guild(guild_id) {
+                    guild.update_role_order(after_id, before_id, role_id);
+                }
+                changes.push(StateChange::RolesUpdated(guild_id));
+                // Roles higher in the list override the ones below
+                self.permissions.invalidate_guild(guild_id);
+                post.push(PostProcessEvent::FetchPermissions(guild_id));
+                changes.push(StateChange::PermissionsUpdated(guild_id));
+            }
+            Event::RolePermissionsUpdated(RolePermissionsUpdated { guild_id, .. }) => {
+                self.permissions.invalidate_guild(guild_id);
+                post.push(PostProcessEvent::FetchPermissions(guild_id));
+                changes.push(StateChange::PermissionsUpdated(guild_id));
+            }
+            Event::UserRolesUpdated(UserRolesUpdated {
+                guild_id,
+                user_id,
+                new_role_ids,
+            }) => {
+                if let Some(guild) = self.get_guild(guild_id) {
+                    guild.user_roles.insert(user_id, new_role_ids);
+                }
+                if Some(user_id) == self.user_id {
+                    self.permissions.invalidate_guild(guild_id);
+                    post.push(PostProcessEvent::FetchPermissions(guild_id));
+                    changes.push(StateChange::PermissionsUpdated(guild_id));
+                }
+                changes.push(StateChange::RolesUpdated(guild_id));
+            }
+            Event::PermissionUpdated(PermissionUpdated {
+                guild_id,
+                channel_id,
+                query,
+                ok,
+            }) => {
+                if self
+                    .permissions
+                    .update_from_event(guild_id, channel_id, &query, ok)
+                {
+                    post.push(PostProcessEvent::FetchPermissions(guild_id));
+                }
+                changes.push(StateChange::PermissionsUpdated(guild_id));
             }
             x => todo!("implement {:?}", x),
         }
 
+        for change in changes {
+            self.changes.send(change);
+        }
         post
     }
 
@@ -543,11 +878,10 @@ impl Client {
         &mut self,
         guild_id: u64,
         channel_id: u64,
-        messages: Vec<HarmonyMessage>,
+        mut messages: Vec<Message>,
         reached_top: bool,
     ) -> Vec<PostProcessEvent> {
         let mut post = Vec::new();
-        let mut messages = harmony_messages_to_ui_messages(messages);
 
         for message in &messages {
             message.post_process(&mut post);
@@ -563,10 +897,16 @@ impl Client {
         }
 
         if let Some(channel) = self.get_channel(guild_id, channel_id) {
+            // Messages that arrived from the socket while the history was being fetched
+            messages.retain(|msg| channel.messages.iter().all(|other| other.id != msg.id));
             messages.append(&mut channel.messages);
             channel.messages = messages;
             channel.reached_top = reached_top;
         }
+        self.changes.send(StateChange::HistoryLoaded {
+            guild_id,
+            channel_id,
+        });
 
         post
     }
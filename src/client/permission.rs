@@ -0,0 +1,137 @@
+use ahash::AHashMap;
+
+/// A permission the UI checks before offering an action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Node {
+    SendMessages,
+    /// Delete messages of other users, everyone can delete their own.
+    DeleteMessages,
+    PinMessages,
+    UnpinMessages,
+    ManageChannels,
+    ManageGuild,
+    CreateInvites,
+    ManageUserRoles,
+    Kick,
+    Ban,
+}
+
+/// Nodes that apply to the whole guild.
+pub const GUILD_NODES: &[Node] = &[
+    Node::ManageChannels,
+    Node::ManageGuild,
+    Node::CreateInvites,
+    Node::ManageUserRoles,
+    Node::Kick,
+    Node::Ban,
+];
+
+/// Nodes that can be different in each channel.
+pub const CHANNEL_NODES: &[Node] = &[
+    Node::SendMessages,
+    Node::DeleteMessages,
+    Node::PinMessages,
+    Node::UnpinMessages,
+];
+
+impl Node {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Node::SendMessages => "messages.send",
+            Node::DeleteMessages => "messages.manage.delete",
+            Node::PinMessages => "messages.pins.add",
+            Node::UnpinMessages => "messages.pins.remove",
+            Node::ManageChannels => "channels.manage.create",
+            Node::ManageGuild => "guild.manage.change-information",
+            Node::CreateInvites => "invites.manage.create",
+            Node::ManageUserRoles => "roles.user.manage",
+            Node::Kick => "user.manage.kick",
+            Node::Ban => "user.manage.ban",
+        }
+    }
+
+    fn from_str(node: &str) -> Option<Self> {
+        GUILD_NODES
+            .iter()
+            .chain(CHANNEL_NODES)
+            .find(|known| known.as_str() == node)
+            .copied()
+    }
+
+    /// The nodes to fetch for a guild, or a channel of it.
+    pub fn for_scope(channel_id: Option<u64>) -> &'static [Node] {
+        if channel_id.is_some() {
+            CHANNEL_NODES
+        } else {
+            GUILD_NODES
+        }
+    }
+}
+
+/// What the current user is allowed to do, keyed by guild, channel (`None` for guild wide nodes) and node.
+#[derive(Debug, Default)]
+pub struct PermissionCache {
+    known: AHashMap<(u64, Option<u64>, Node), bool>,
+}
+
+impl PermissionCache {
+    /// Returns whether the current user has a permission, or `None` if it wasn't fetched yet.
+    pub fn get(&self, guild_id: u64, channel_id: Option<u64>, node: Node) -> Option<bool> {
+        let channel_id = channel_id.filter(|_| CHANNEL_NODES.contains(&node));
+        self.known.get(&(guild_id, channel_id, node)).copied()
+    }
+
+    /// Returns whether the current user has a permission, assuming they don't until it is fetched.
+    ///
+    /// This is what buttons should use to decide if they are shown.
+    pub fn has(&self, guild_id: u64, channel_id: Option<u64>, node: Node) -> bool {
+        self.get(guild_id, channel_id, node).unwrap_or(false)
+    }
+
+    /// Whether every node of a guild, or a channel of it, is known.
+    pub fn is_loaded(&self, guild_id: u64, channel_id: Option<u64>) -> bool {
+        Node::for_scope(channel_id)
+            .iter()
+            .all(|node| self.known.contains_key(&(guild_id, channel_id, *node)))
+    }
+
+    pub fn insert(&mut self, guild_id: u64, channel_id: Option<u64>, node: Node, ok: bool) {
+        let channel_id = channel_id.filter(|_| CHANNEL_NODES.contains(&node));
+        self.known.insert((guild_id, channel_id, node), ok);
+    }
+
+    /// Updates a permission from an event, ignoring nodes the UI doesn't use.
+    ///
+    /// A guild wide update of a node that can be different in each channel makes what is known
+    /// for the channels stale, since they may or may not override it. They are forgotten, and
+    /// `true` is returned to tell that they should be fetched again.
+    pub fn update_from_event(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        node: &str,
+        ok: bool,
+    ) -> bool {
+        let node = match Node::from_str(node) {
+            Some(node) => node,
+            None => return false,
+        };
+        let channel_id = (channel_id != 0).then(|| channel_id);
+        self.insert(guild_id, channel_id, node, ok);
+
+        let is_stale = channel_id.is_none() && CHANNEL_NODES.contains(&node);
+        if is_stale {
+            self.known
+                .retain(|(other_id, other_channel_id, other_node), _| {
+                    *other_id != guild_id || other_channel_id.is_none() || *other_node != node
+                });
+        }
+        is_stale
+    }
+
+    /// Forgets the permissions of a guild and its channels, after roles changed.
+    pub fn invalidate_guild(&mut self, guild_id: u64) {
+        self.known
+            .retain(|(other_id, _, _), _| *other_id != guild_id);
+    }
+}
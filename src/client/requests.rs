@@ -0,0 +1,907 @@
+use std::{path::Path, sync::Arc};
+
+use harmony_rust_sdk::{
+    api::{
+        chat::{
+            event::{
+                ChannelCreated, Event, GuildAddedToList, GuildUpdated, MemberJoined,
+                ProfileUpdated, RoleCreated, RoleDeleted, RoleMoved, RoleUpdated, UserRolesUpdated,
+            },
+            AddGuildRoleRequest, AddGuildToGuildListRequest, BanUserRequest, CreateInviteRequest,
+            DeleteChannelRequest, DeleteGuildRequest, DeleteGuildRoleRequest, DeleteInviteRequest,
+            DeleteMessageRequest, GetBannedUsersRequest, GetGuildInvitesRequest,
+            GetGuildListRequest, GetGuildRolesRequest, GetPermissionsRequest,
+            GetPinnedMessagesRequest, GetUserResponse, GetUserRolesRequest, InviteId,
+            KickUserRequest, LeaveGuildRequest, ManageUserRolesRequest, ModifyGuildRoleRequest,
+            MoveRoleRequest, PermissionList, PinMessageRequest, Place, QueryPermissionsRequest,
+            RemoveGuildFromGuildListRequest, SetPermissionsRequest, UnbanUserRequest,
+            UnpinMessageRequest, UpdateChannelInformationRequest, UpdateChannelOrderRequest,
+            UpdateGuildInformationRequest, UpdateMessageContentRequest,
+        },
+        harmonytypes::{self, UserStatus},
+    },
+    client::{
+        api::{
+            auth::{AuthStep, AuthStepResponse},
+            chat::{
+                channel::{
+                    create_channel, delete_channel, get_channel_messages, get_guild_channels,
+                    update_channel_information, update_channel_order, CreateChannel,
+                    CreateChannelSelfBuilder, GetChannelMessages, GetChannelMessagesSelfBuilder,
+                },
+                guild::{
+                    add_guild_to_guild_list, ban_user, create_guild, delete_guild,
+                    get_banned_users, get_guild, get_guild_list, get_guild_members, join_guild,
+                    kick_user, leave_guild, remove_guild_from_guild_list, unban_user,
+                    update_guild_information, CreateGuild,
+                },
+                invite::{create_invite, delete_invite, get_guild_invites},
+                message::{
+                    delete_message, get_pinned_messages, pin_message, send_message, unpin_message,
+                    update_message_content, update_message_text, SendMessage,
+                    SendMessageSelfBuilder, UpdateMessageTextRequest,
+                },
+                permissions::{
+                    add_guild_role, delete_guild_role, get_guild_roles, get_permissions,
+                    get_user_roles, manage_user_roles, modify_guild_role, move_role,
+                    query_has_permission, set_permissions,
+                },
+                profile::{get_user, get_user_bulk, profile_update, ProfileUpdate},
+                typing, EventSource, GuildId, Typing, UserId,
+            },
+            rest::{download_extract_file, upload_extract_id, FileId},
+        },
+        Client as InnerClient, EventsSocket,
+    },
+};
+
+use futures::{
+    future::join_all,
+    stream::{self, StreamExt, TryStreamExt},
+};
+
+use super::{
+    content::{self, ContentStore},
+    error::{ClientError, ClientResult},
+    invite::Invite,
+    message::{harmony_messages_to_ui_messages, Attachment, Content, Message},
+    permission::Node,
+    role::{PermissionRule, Role, RoleField},
+};
+
+/// How many requests for the roles of members are sent at the same time.
+const USER_ROLES_CONCURRENCY: usize = 8;
+/// How many pages of history are fetched at most to reach a message.
+const MAX_PAGES_TO_MESSAGE: usize = 20;
+
+/// Makes requests to the homeserver, without touching the state of the [`Client`](super::Client).
+///
+/// Requests that fetch state return the events that apply it, which are given to
+/// [`Client::process_event`](super::Client::process_event) like the ones from the socket.
+/// This is cheap to clone, so futures of it can outlive the client.
+#[derive(Clone)]
+pub struct Requests {
+    inner: InnerClient,
+    content_store: Arc<ContentStore>,
+}
+
+impl Requests {
+    pub(super) fn new(inner: InnerClient, content_store: Arc<ContentStore>) -> Self {
+        Self {
+            inner,
+            content_store,
+        }
+    }
+
+    /// Starts logging in or registering, returning the first step the homeserver asks for.
+    pub async fn begin_auth(&self) -> ClientResult<Option<AuthStep>> {
+        self.inner.begin_auth().await?;
+        self.next_auth_step(AuthStepResponse::Initial).await
+    }
+
+    /// Answers the current step of logging in, which results in the next step, or `None` once
+    /// logged in.
+    pub async fn next_auth_step(
+        &self,
+        response: AuthStepResponse,
+    ) -> ClientResult<Option<AuthStep>> {
+        Ok(self.inner.next_auth_step(response).await?)
+    }
+
+    /// Goes back to the previous step of logging in.
+    pub async fn prev_auth_step(&self) -> ClientResult<AuthStep> {
+        Ok(self.inner.prev_auth_step().await?)
+    }
+
+    /// Waits for the homeserver to push the step after a waiting step, like once an email is
+    /// verified.
+    ///
+    /// Unlike with [`Self::next_auth_step`], a session step isn't kept by the library, so logging
+    /// in is completed by creating a client with the session it has.
+    pub async fn wait_for_auth_step(&self) -> ClientResult<AuthStep> {
+        let mut socket = self.inner.auth_stream().await?;
+        loop {
+            if let Some(step) = socket.get_message().await {
+                return Ok(step?);
+            }
+        }
+    }
+
+    /// Fetches the guilds the current user is in.
+    pub async fn guild_list(&self) -> ClientResult<Vec<Event>> {
+        let guilds = get_guild_list(&self.inner, GetGuildListRequest {})
+            .await?
+            .guilds;
+        Ok(guilds
+            .into_iter()
+            .map(|guild| {
+                Event::GuildAddedToList(GuildAddedToList {
+                    guild_id: guild.guild_id,
+                    homeserver: guild.host,
+                })
+            })
+            .collect())
+    }
+
+    /// Fetches the name and picture of a guild.
+    pub async fn guild_data(&self, guild_id: u64) -> ClientResult<Event> {
+        let guild_data = get_guild(&self.inner, GuildId::new(guild_id)).await?;
+        Ok(Event::EditedGuild(GuildUpdated {
+            guild_id,
+            metadata: guild_data.metadata,
+            name: guild_data.guild_name,
+            picture: guild_data.guild_picture,
+            update_name: true,
+            update_picture: true,
+            update_metadata: true,
+        }))
+    }
+
+    /// Creates a guild and adds it to the guild list, returning its ID.
+    pub async fn create_guild(&self, name: String) -> ClientResult<u64> {
+        let guild_id = create_guild(&self.inner, CreateGuild::new(name))
+            .await?
+            .guild_id;
+        self.add_to_guild_list(guild_id).await?;
+        Ok(guild_id)
+    }
+
+    /// Joins a guild with an invite and adds it to the guild list, returning its ID.
+    pub async fn join_guild(&self, invite: InviteId) -> ClientResult<u64> {
+        let guild_id = join_guild(&self.inner, invite).await?.guild_id;
+        self.add_to_guild_list(guild_id).await?;
+        Ok(guild_id)
+    }
+
+    async fn add_to_guild_list(&self, guild_id: u64) -> ClientResult<()> {
+        add_guild_to_guild_list(
+            &self.inner,
+            AddGuildToGuildListRequest {
+                guild_id,
+                homeserver: self.inner.homeserver_url().to_string(),
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Leaves a guild and removes it from the guild list.
+    pub async fn leave_guild(&self, guild_id: u64) -> ClientResult<()> {
+        leave_guild(&self.inner, LeaveGuildRequest { guild_id }).await?;
+        remove_guild_from_guild_list(
+            &self.inner,
+            RemoveGuildFromGuildListRequest {
+                guild_id,
+                homeserver: self.inner.homeserver_url().to_string(),
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_guild(&self, guild_id: u64) -> ClientResult<()> {
+        delete_guild(&self.inner, DeleteGuildRequest { guild_id }).await?;
+        Ok(())
+    }
+
+    pub async fn rename_guild(&self, guild_id: u64, new_guild_name: String) -> ClientResult<()> {
+        update_guild_information(
+            &self.inner,
+            UpdateGuildInformationRequest {
+                guild_id,
+                new_guild_name,
+                update_guild_name: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_guild_picture(&self, guild_id: u64, picture: FileId) -> ClientResult<()> {
+        update_guild_information(
+            &self.inner,
+            UpdateGuildInformationRequest {
+                guild_id,
+                new_guild_picture: picture.to_string(),
+                update_guild_picture: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Fetches the IDs of the users banned from a guild.
+    pub async fn banned_users(&self, guild_id: u64) -> ClientResult<Vec<u64>> {
+        Ok(
+            get_banned_users(&self.inner, GetBannedUsersRequest { guild_id })
+                .await?
+                .banned_users,
+        )
+    }
+
+    pub async fn invites(&self, guild_id: u64) -> ClientResult<Vec<Invite>> {
+        Ok(
+            get_guild_invites(&self.inner, GetGuildInvitesRequest { guild_id })
+                .await?
+                .invites
+                .into_iter()
+                .map(Invite::from)
+                .collect(),
+        )
+    }
+
+    /// Creates an invite, returning its name.
+    ///
+    /// `possible_uses` is `-1` for an invite that can be used any number of times.
+    pub async fn create_invite(
+        &self,
+        guild_id: u64,
+        name: String,
+        possible_uses: i32,
+    ) -> ClientResult<String> {
+        Ok(create_invite(
+            &self.inner,
+            CreateInviteRequest {
+                guild_id,
+                name,
+                possible_uses,
+            },
+        )
+        .await?
+        .name)
+    }
+
+    pub async fn delete_invite(&self, guild_id: u64, invite_id: String) -> ClientResult<()> {
+        delete_invite(
+            &self.inner,
+            DeleteInviteRequest {
+                guild_id,
+                invite_id,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Fetches the channels and members of a guild.
+    pub async fn guild_contents(&self, guild_id: u64) -> ClientResult<Vec<Event>> {
+        let guildid = GuildId::new(guild_id);
+        let channels_list = get_guild_channels(&self.inner, guildid).await?.channels;
+        let mut events = Vec::with_capacity(channels_list.len());
+        for channel in channels_list {
+            events.push(Event::CreatedChannel(ChannelCreated {
+                guild_id,
+                channel_id: channel.channel_id,
+                is_category: channel.is_category,
+                name: channel.channel_name,
+                metadata: channel.metadata,
+                ..Default::default()
+            }));
+        }
+
+        let members = get_guild_members(&self.inner, guildid).await?.members;
+        events.reserve(members.len());
+        for member_id in members {
+            events.push(Event::JoinedMember(MemberJoined {
+                member_id,
+                guild_id,
+            }));
+        }
+
+        Ok(events)
+    }
+
+    pub async fn profile(&self, user_id: u64) -> ClientResult<Event> {
+        let profile = get_user(&self.inner, UserId::new(user_id)).await?;
+        Ok(profile_event(user_id, profile))
+    }
+
+    /// Fetches the profiles of many users with one request.
+    pub async fn profiles(&self, user_ids: Vec<u64>) -> ClientResult<Vec<Event>> {
+        let profiles = get_user_bulk(&self.inner, user_ids.clone()).await?;
+        Ok(profiles
+            .users
+            .into_iter()
+            .zip(user_ids.into_iter())
+            .map(|(profile, user_id)| profile_event(user_id, profile))
+            .collect())
+    }
+
+    /// Fetches the messages of a channel before a message, or the newest ones.
+    ///
+    /// Returns the messages oldest first, and whether there are no older messages.
+    pub async fn channel_messages(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        before: Option<u64>,
+    ) -> ClientResult<(Vec<Message>, bool)> {
+        let mut request = GetChannelMessages::new(guild_id, channel_id);
+        if let Some(before) = before {
+            request = request.before_message(before);
+        }
+        let response = get_channel_messages(&self.inner, request).await?;
+        Ok((
+            harmony_messages_to_ui_messages(response.messages),
+            response.reached_top,
+        ))
+    }
+
+    /// Fetches the history older than `before` until it has a message, so that the message can be
+    /// shown with the ones around it.
+    ///
+    /// Returns the messages oldest first, and whether the start of the channel was reached. The
+    /// message isn't in them if it's not in the channel or too far back.
+    pub async fn channel_messages_until(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        mut before: Option<u64>,
+        message_id: u64,
+    ) -> ClientResult<(Vec<Message>, bool)> {
+        let mut messages = Vec::new();
+        for _ in 0..MAX_PAGES_TO_MESSAGE {
+            let (mut page, reached_top) =
+                self.channel_messages(guild_id, channel_id, before).await?;
+            let found = page
+                .iter()
+                .any(|message| message.id.id() == Some(message_id));
+            before = page.first().map(|message| message.id.id()).flatten();
+            page.append(&mut messages);
+            messages = page;
+            if found || reached_top || before.is_none() {
+                return Ok((messages, reached_top));
+            }
+        }
+
+        Ok((messages, false))
+    }
+
+    /// Fetches the IDs of the pinned messages of a channel.
+    pub async fn pinned_messages(&self, guild_id: u64, channel_id: u64) -> ClientResult<Vec<u64>> {
+        let response = get_pinned_messages(
+            &self.inner,
+            GetPinnedMessagesRequest {
+                guild_id,
+                channel_id,
+            },
+        )
+        .await?;
+        Ok(response.pinned_message_ids)
+    }
+
+    /// Fetches the permissions the UI checks in a guild, or in a channel of it.
+    pub async fn permissions(
+        &self,
+        guild_id: u64,
+        channel_id: Option<u64>,
+    ) -> ClientResult<Vec<(Node, bool)>> {
+        let queries = Node::for_scope(channel_id).iter().map(|node| async move {
+            query_has_permission(
+                &self.inner,
+                QueryPermissionsRequest {
+                    guild_id,
+                    channel_id: channel_id.unwrap_or_default(),
+                    check_for: node.as_str().to_string(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map(|response| (*node, response.ok))
+        });
+        Ok(join_all(queries)
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Sends a message, returning the ID the homeserver gave it.
+    pub async fn send_message(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        message: &Message,
+    ) -> ClientResult<u64> {
+        let mut request = SendMessage::new(guild_id, channel_id)
+            .content(harmonytypes::Content {
+                content: Some(message.content.clone().into()),
+            })
+            .overrides(message.overrides.clone().map(Into::into))
+            .in_reply_to(message.reply_to.unwrap_or_default());
+        if let Some(transaction_id) = message.id.transaction_id() {
+            request = request.echo_id(transaction_id);
+        }
+
+        Ok(send_message(&self.inner, request).await?.message_id)
+    }
+
+    /// Replaces the content of a message.
+    pub async fn edit_message(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        message_id: u64,
+        new_content: Content,
+    ) -> ClientResult<()> {
+        match new_content {
+            Content::Text(new_content) => {
+                update_message_text(
+                    &self.inner,
+                    UpdateMessageTextRequest {
+                        guild_id,
+                        channel_id,
+                        message_id,
+                        new_content,
+                    },
+                )
+                .await?;
+            }
+            new_content => {
+                update_message_content(
+                    &self.inner,
+                    UpdateMessageContentRequest {
+                        guild_id,
+                        channel_id,
+                        message_id,
+                        new_content: Some(harmonytypes::Content {
+                            content: Some(new_content.into()),
+                        }),
+                    },
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn delete_message(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        message_id: u64,
+    ) -> ClientResult<()> {
+        delete_message(
+            &self.inner,
+            DeleteMessageRequest {
+                guild_id,
+                channel_id,
+                message_id,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn pin_message(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        message_id: u64,
+    ) -> ClientResult<()> {
+        pin_message(
+            &self.inner,
+            PinMessageRequest {
+                guild_id,
+                channel_id,
+                message_id,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn unpin_message(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        message_id: u64,
+    ) -> ClientResult<()> {
+        unpin_message(
+            &self.inner,
+            UnpinMessageRequest {
+                guild_id,
+                channel_id,
+                message_id,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Creates a channel at the top of a guild, returning its ID.
+    pub async fn create_channel(
+        &self,
+        guild_id: u64,
+        name: String,
+        is_category: bool,
+    ) -> ClientResult<u64> {
+        let request =
+            CreateChannel::new(guild_id, name, Place::Top { before: 0 }).is_category(is_category);
+        Ok(create_channel(&self.inner, request).await?.channel_id)
+    }
+
+    pub async fn rename_channel(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        name: String,
+    ) -> ClientResult<()> {
+        update_channel_information(
+            &self.inner,
+            UpdateChannelInformationRequest {
+                guild_id,
+                channel_id,
+                name,
+                update_name: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_channel(&self, guild_id: u64, channel_id: u64) -> ClientResult<()> {
+        delete_channel(
+            &self.inner,
+            DeleteChannelRequest {
+                guild_id,
+                channel_id,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Moves a channel so that it's between `previous_id` and `next_id`.
+    pub async fn move_channel(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        previous_id: u64,
+        next_id: u64,
+    ) -> ClientResult<()> {
+        update_channel_order(
+            &self.inner,
+            UpdateChannelOrderRequest {
+                guild_id,
+                channel_id,
+                previous_id,
+                next_id,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn kick_user(&self, guild_id: u64, user_id: u64) -> ClientResult<()> {
+        kick_user(&self.inner, KickUserRequest { guild_id, user_id }).await?;
+        Ok(())
+    }
+
+    pub async fn ban_user(&self, guild_id: u64, user_id: u64) -> ClientResult<()> {
+        ban_user(&self.inner, BanUserRequest { guild_id, user_id }).await?;
+        Ok(())
+    }
+
+    pub async fn unban_user(&self, guild_id: u64, user_id: u64) -> ClientResult<()> {
+        unban_user(&self.inner, UnbanUserRequest { guild_id, user_id }).await?;
+        Ok(())
+    }
+
+    /// Fetches the roles of a guild, highest first.
+    pub async fn guild_roles(&self, guild_id: u64) -> ClientResult<Vec<(u64, Role)>> {
+        let roles = get_guild_roles(&self.inner, GetGuildRolesRequest { guild_id })
+            .await?
+            .roles;
+        Ok(roles
+            .into_iter()
+            .map(|role| (role.role_id, Role::from(role)))
+            .collect())
+    }
+
+    /// Fetches the roles of members of a guild, a few members at a time.
+    pub async fn user_roles(&self, guild_id: u64, user_ids: Vec<u64>) -> ClientResult<Vec<Event>> {
+        stream::iter(user_ids)
+            .map(|user_id| async move {
+                let new_role_ids =
+                    get_user_roles(&self.inner, GetUserRolesRequest { guild_id, user_id })
+                        .await?
+                        .roles;
+                Ok::<_, ClientError>(Event::UserRolesUpdated(UserRolesUpdated {
+                    guild_id,
+                    user_id,
+                    new_role_ids,
+                }))
+            })
+            .buffer_unordered(USER_ROLES_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    /// Fetches the IDs of the roles of one member of a guild.
+    pub async fn member_roles(&self, guild_id: u64, user_id: u64) -> ClientResult<Vec<u64>> {
+        Ok(
+            get_user_roles(&self.inner, GetUserRolesRequest { guild_id, user_id })
+                .await?
+                .roles,
+        )
+    }
+
+    pub async fn create_role(&self, guild_id: u64, role: Role) -> ClientResult<Event> {
+        let role = role.into_harmony(0);
+        let role_id = add_guild_role(
+            &self.inner,
+            AddGuildRoleRequest {
+                guild_id,
+                role: Some(role.clone()),
+            },
+        )
+        .await?
+        .role_id;
+        Ok(Event::RoleCreated(RoleCreated {
+            guild_id,
+            role_id,
+            name: role.name,
+            color: role.color,
+            hoist: role.hoist,
+            pingable: role.pingable,
+        }))
+    }
+
+    /// Changes one property of a role to the one in `role`.
+    pub async fn modify_role(
+        &self,
+        guild_id: u64,
+        role_id: u64,
+        role: Role,
+        field: RoleField,
+    ) -> ClientResult<Event> {
+        let role = role.into_harmony(role_id);
+        modify_guild_role(
+            &self.inner,
+            ModifyGuildRoleRequest {
+                guild_id,
+                role: Some(role.clone()),
+                modify_name: field == RoleField::Name,
+                modify_color: field == RoleField::Color,
+                modify_hoist: field == RoleField::Hoist,
+                modify_pingable: field == RoleField::Pingable,
+            },
+        )
+        .await?;
+        Ok(Event::RoleUpdated(RoleUpdated {
+            guild_id,
+            role_id,
+            new_name: role.name,
+            new_color: role.color,
+            new_hoist: role.hoist,
+            new_pingable: role.pingable,
+            update_name: field == RoleField::Name,
+            update_color: field == RoleField::Color,
+            update_hoist: field == RoleField::Hoist,
+            update_pingable: field == RoleField::Pingable,
+        }))
+    }
+
+    /// Moves a role so that it's between `previous_id` and `next_id`.
+    pub async fn move_role(
+        &self,
+        guild_id: u64,
+        role_id: u64,
+        previous_id: u64,
+        next_id: u64,
+    ) -> ClientResult<Event> {
+        move_role(
+            &self.inner,
+            MoveRoleRequest {
+                guild_id,
+                role_id,
+                before_id: next_id,
+                after_id: previous_id,
+            },
+        )
+        .await?;
+        Ok(Event::RoleMoved(RoleMoved {
+            guild_id,
+            role_id,
+            before_id: next_id,
+            after_id: previous_id,
+        }))
+    }
+
+    pub async fn delete_role(&self, guild_id: u64, role_id: u64) -> ClientResult<Event> {
+        delete_guild_role(&self.inner, DeleteGuildRoleRequest { guild_id, role_id }).await?;
+        Ok(Event::RoleDeleted(RoleDeleted { guild_id, role_id }))
+    }
+
+    /// Gives roles to a member and takes others away.
+    pub async fn manage_user_roles(
+        &self,
+        guild_id: u64,
+        user_id: u64,
+        give_role_ids: Vec<u64>,
+        take_role_ids: Vec<u64>,
+    ) -> ClientResult<()> {
+        manage_user_roles(
+            &self.inner,
+            ManageUserRolesRequest {
+                guild_id,
+                user_id,
+                give_role_ids,
+                take_role_ids,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Fetches the permission rules of a role, in a channel or in the whole guild if `channel_id`
+    /// is `0`.
+    pub async fn role_permissions(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        role_id: u64,
+    ) -> ClientResult<Vec<PermissionRule>> {
+        Ok(get_permissions(
+            &self.inner,
+            GetPermissionsRequest {
+                guild_id,
+                channel_id,
+                role_id,
+            },
+        )
+        .await?
+        .perms
+        .map_or_else(Vec::new, |list| {
+            list.permissions.into_iter().map(Into::into).collect()
+        }))
+    }
+
+    /// Replaces the permission rules of a role, in a channel or in the whole guild if
+    /// `channel_id` is `0`.
+    pub async fn set_role_permissions(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        role_id: u64,
+        rules: Vec<PermissionRule>,
+    ) -> ClientResult<()> {
+        set_permissions(
+            &self.inner,
+            SetPermissionsRequest {
+                guild_id,
+                channel_id,
+                role_id,
+                perms: Some(PermissionList {
+                    permissions: rules.into_iter().map(Into::into).collect(),
+                }),
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Asks whether a user has a permission, in a channel or in the whole guild if `channel_id`
+    /// is `0`.
+    pub async fn has_permission(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        node: String,
+        user_id: u64,
+    ) -> ClientResult<bool> {
+        Ok(query_has_permission(
+            &self.inner,
+            QueryPermissionsRequest {
+                guild_id,
+                channel_id,
+                check_for: node,
+                r#as: user_id,
+            },
+        )
+        .await?
+        .ok)
+    }
+
+    pub async fn set_status(&self, status: UserStatus) -> ClientResult<()> {
+        profile_update(&self.inner, ProfileUpdate::default().new_status(status)).await?;
+        Ok(())
+    }
+
+    pub async fn set_username(&self, username: String) -> ClientResult<()> {
+        profile_update(&self.inner, ProfileUpdate::default().new_username(username)).await?;
+        Ok(())
+    }
+
+    pub async fn set_avatar(&self, avatar: FileId) -> ClientResult<()> {
+        profile_update(
+            &self.inner,
+            ProfileUpdate::default().new_avatar(Some(avatar)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Tells the others in a channel that the current user is typing.
+    pub async fn typing(&self, guild_id: u64, channel_id: u64) -> ClientResult<()> {
+        typing(&self.inner, Typing::new(guild_id, channel_id)).await?;
+        Ok(())
+    }
+
+    /// Downloads a file into the content store, returning its data.
+    pub async fn download_file(&self, id: FileId) -> ClientResult<Vec<u8>> {
+        let file = download_extract_file(&self.inner, id.clone()).await?;
+        let data = file.data().to_vec();
+        tokio::fs::write(self.content_store.content_path(&id), &data).await?;
+        Ok(data)
+    }
+
+    /// Uploads a file, returning an attachment that can be sent in a message.
+    ///
+    /// The file is also linked into the content store, so it doesn't have to be downloaded again.
+    pub async fn upload_file(&self, path: &Path) -> ClientResult<Attachment> {
+        let data = tokio::fs::read(path).await?;
+        let kind = content::infer_type_from_bytes(&data);
+        let name = content::get_filename(path);
+        let size = data.len() as u32;
+
+        let id =
+            FileId::Id(upload_extract_id(&self.inner, name.clone(), kind.clone(), data).await?);
+        if let Err(err) = tokio::fs::hard_link(path, self.content_store.content_path(&id)).await {
+            tracing::warn!("An IO error occured while hard linking a file you tried to upload (this may result in a duplication of the file): {}", err);
+        }
+
+        Ok(Attachment {
+            id,
+            kind,
+            name,
+            size,
+            caption: String::new(),
+        })
+    }
+
+    /// Opens a socket that receives the events of the given sources.
+    pub async fn subscribe(&self, sources: Vec<EventSource>) -> ClientResult<EventsSocket> {
+        self.inner
+            .subscribe_events(sources)
+            .await
+            .map_err(ClientError::from)
+    }
+}
+
+fn profile_event(user_id: u64, profile: GetUserResponse) -> Event {
+    Event::ProfileUpdated(ProfileUpdated {
+        user_id,
+        new_avatar: profile.user_avatar,
+        new_status: profile.user_status,
+        new_username: profile.user_name,
+        is_bot: profile.is_bot,
+        update_is_bot: true,
+        update_status: true,
+        update_avatar: true,
+        update_username: true,
+    })
+}
@@ -0,0 +1,113 @@
+use harmony_rust_sdk::api::chat::{self, permission::Mode};
+use indexmap::IndexMap;
+
+use super::color::Color;
+
+pub type Roles = IndexMap<u64, Role>;
+
+#[derive(Debug, Clone)]
+pub struct Role {
+    pub name: String,
+    pub color: Color,
+    pub hoist: bool,
+    pub pingable: bool,
+}
+
+impl Default for Role {
+    fn default() -> Self {
+        Self {
+            name: String::default(),
+            color: Color::WHITE,
+            hoist: false,
+            pingable: false,
+        }
+    }
+}
+
+impl From<chat::Role> for Role {
+    fn from(role: chat::Role) -> Self {
+        Self {
+            name: role.name,
+            color: Color::from_harmony(role.color.into()),
+            hoist: role.hoist,
+            pingable: role.pingable,
+        }
+    }
+}
+
+impl Role {
+    pub fn into_harmony(self, role_id: u64) -> chat::Role {
+        chat::Role {
+            role_id,
+            name: self.name,
+            color: self.color.to_harmony() as _,
+            hoist: self.hoist,
+            pingable: self.pingable,
+        }
+    }
+}
+
+/// Which property of a role is modified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoleField {
+    Name,
+    Color,
+    Hoist,
+    Pingable,
+}
+
+/// A single permission rule, allowing or denying the nodes matched by `matches`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionRule {
+    pub matches: String,
+    pub allow: bool,
+}
+
+impl From<chat::Permission> for PermissionRule {
+    fn from(perm: chat::Permission) -> Self {
+        Self {
+            allow: !matches!(Mode::from_i32(perm.mode), Some(Mode::Deny)),
+            matches: perm.matches,
+        }
+    }
+}
+
+impl From<PermissionRule> for chat::Permission {
+    fn from(rule: PermissionRule) -> Self {
+        chat::Permission {
+            matches: rule.matches,
+            mode: (if rule.allow { Mode::Allow } else { Mode::Deny }) as i32,
+        }
+    }
+}
+
+impl PermissionRule {
+    /// Checks if this rule applies to a permission node.
+    ///
+    /// A rule can either match a node exactly, or match everything under a node with a `.*` suffix.
+    pub fn applies_to(&self, node: &str) -> bool {
+        if self.matches == "*" || self.matches == node {
+            return true;
+        }
+
+        self.matches.strip_suffix('*').map_or(false, |prefix| {
+            prefix.ends_with('.') && node.starts_with(prefix)
+        })
+    }
+}
+
+/// Finds the rule that decides a permission node, going through the rule lists in order.
+///
+/// Callers should order the lists the way the server checks them: channel rules before guild rules,
+/// and higher roles before lower ones.
+pub fn deciding_rule<'a, S>(
+    node: &str,
+    rule_lists: &'a [(S, Vec<PermissionRule>)],
+) -> Option<(&'a S, &'a PermissionRule)> {
+    rule_lists.iter().find_map(|(source, rules)| {
+        rules
+            .iter()
+            .find(|rule| rule.applies_to(node))
+            .map(|rule| (source, rule))
+    })
+}
@@ -0,0 +1,456 @@
+use std::{
+    any::Any,
+    collections::hash_map::Entry,
+    fmt::{self, Debug, Formatter},
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use ahash::AHashMap;
+use futures::future::{AbortHandle, Abortable};
+use tokio::{
+    sync::{oneshot, Semaphore},
+    time::Instant,
+};
+
+use super::error::{ClientResult, Recovery};
+
+/// How many requests can run at the same time.
+const MAX_RUNNING: usize = 4;
+/// How many of the running requests can be background work, the rest is kept for what the user is looking at.
+const MAX_BACKGROUND: usize = 1;
+/// How many times a request that failed with a temporary error is sent before giving up.
+const MAX_ATTEMPTS: u32 = 5;
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Identifies a request, so that identical requests are only sent once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestKey {
+    /// The channels and members of a guild.
+    GuildContents(u64),
+    GuildData(u64),
+    Profile(u64),
+    /// Messages of a channel, before a message or the newest ones.
+    ChannelMessages {
+        guild_id: u64,
+        channel_id: u64,
+        before: Option<u64>,
+    },
+    /// The permissions the UI checks in a guild, or in a channel of it.
+    Permissions {
+        guild_id: u64,
+        channel_id: Option<u64>,
+    },
+    PinnedMessages {
+        guild_id: u64,
+        channel_id: u64,
+    },
+    /// Sending the message with this transaction ID.
+    SendMessage(u64),
+}
+
+impl RequestKey {
+    /// The guild (and channel) this request is for, if it's useless once the user navigates away.
+    fn scope(&self) -> Option<(u64, Option<u64>)> {
+        match *self {
+            RequestKey::GuildContents(guild_id) => Some((guild_id, None)),
+            RequestKey::Permissions {
+                guild_id,
+                channel_id,
+            } => Some((guild_id, channel_id)),
+            RequestKey::ChannelMessages {
+                guild_id,
+                channel_id,
+                before: None,
+            }
+            | RequestKey::PinnedMessages {
+                guild_id,
+                channel_id,
+            } => Some((guild_id, Some(channel_id))),
+            // Loading older messages sets a flag on the channel that only the response clears, and
+            // messages are sent even if the user navigates away
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Work the user doesn't wait for, like fetching profiles.
+    Background,
+    /// Work for what the user is looking at.
+    Visible,
+}
+
+#[derive(Debug, Default)]
+struct Backoff {
+    until: Option<Instant>,
+    delay: Duration,
+}
+
+/// The requests waiting for the result of an identical one.
+type Waiters<T> = Vec<oneshot::Sender<Option<ClientResult<T>>>>;
+
+/// A request that is running or waiting to run.
+struct InFlight {
+    id: u64,
+    abort: AbortHandle,
+    /// The [`Waiters`] of the request, which are all for the same result type as it.
+    waiters: Box<dyn Any + Send>,
+}
+
+impl Debug for InFlight {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InFlight")
+            .field("id", &self.id)
+            .field("abort", &self.abort)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// Requests that are running or waiting to run.
+    in_flight: Mutex<AHashMap<RequestKey, InFlight>>,
+    next_id: AtomicU64,
+    running: Semaphore,
+    background: Semaphore,
+    backoff: Mutex<Backoff>,
+}
+
+/// Schedules the requests made to the homeserver.
+///
+/// Identical requests are deduplicated, requests that failed with a temporary error (the homeserver
+/// being unreachable or rate limiting us) are retried after backing off, and background work can't
+/// use up all the connections.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    inner: Arc<Inner>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                in_flight: Mutex::new(AHashMap::new()),
+                next_id: AtomicU64::new(0),
+                running: Semaphore::new(MAX_RUNNING),
+                background: Semaphore::new(MAX_BACKGROUND),
+                backoff: Mutex::new(Backoff::default()),
+            }),
+        }
+    }
+}
+
+impl Scheduler {
+    /// Schedules a request, which is created by `make_request` each time it's sent.
+    ///
+    /// If an identical request is already in flight, this resolves to its result instead of
+    /// sending another one. Resolves to `None` if the request was cancelled.
+    pub fn run<T, Fut, F>(
+        &self,
+        key: RequestKey,
+        priority: Priority,
+        make_request: F,
+    ) -> impl Future<Output = Option<ClientResult<T>>> + Send + 'static
+    where
+        T: Clone + Send + 'static,
+        Fut: Future<Output = ClientResult<T>> + Send + 'static,
+        F: Fn() -> Fut + Send + 'static,
+    {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let (handle, registration) = AbortHandle::new_pair();
+        // Register right away, so that requests made before this one starts are deduplicated
+        let waiting = match self.inner.in_flight.lock().unwrap().entry(key) {
+            Entry::Occupied(mut entry) => {
+                let (sender, receiver) = oneshot::channel();
+                match entry.get_mut().waiters.downcast_mut::<Waiters<T>>() {
+                    Some(waiters) => {
+                        waiters.push(sender);
+                        Some(receiver)
+                    }
+                    None => {
+                        tracing::warn!("request {:?} has another result type, sending it", key);
+                        None
+                    }
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(InFlight {
+                    id,
+                    abort: handle,
+                    waiters: Box::new(Waiters::<T>::new()),
+                });
+                None
+            }
+        };
+        let registered = Registered {
+            inner: self.inner.clone(),
+            key,
+            id,
+        };
+
+        async move {
+            if let Some(receiver) = waiting {
+                tracing::debug!("request {:?} is already in flight", key);
+                // The sender is dropped if the request is cancelled
+                return receiver.await.ok().flatten();
+            }
+
+            let result = Abortable::new(
+                registered.inner.clone().send(priority, make_request),
+                registration,
+            )
+            .await
+            .ok();
+            if result.is_none() {
+                tracing::debug!("request {:?} was cancelled", key);
+            }
+
+            if let Some(waiters) = registered
+                .take()
+                .and_then(|in_flight| in_flight.waiters.downcast::<Waiters<T>>().ok())
+            {
+                for waiter in *waiters {
+                    let _ = waiter.send(result.clone());
+                }
+            }
+            result
+        }
+    }
+
+    /// Cancels the requests for guilds and channels other than the one the user is looking at.
+    pub fn cancel_stale(&self, guild_id: u64, channel_id: Option<u64>) {
+        self.inner
+            .in_flight
+            .lock()
+            .unwrap()
+            .retain(|key, in_flight| {
+                let is_stale = key.scope().map_or(false, |(key_guild_id, key_channel_id)| {
+                    key_guild_id != guild_id
+                        || (key_channel_id.is_some() && key_channel_id != channel_id)
+                });
+                if is_stale {
+                    in_flight.abort.abort();
+                }
+                !is_stale
+            });
+    }
+}
+
+/// Removes a request from the ones in flight once it's done, or if it's dropped before that.
+struct Registered {
+    inner: Arc<Inner>,
+    key: RequestKey,
+    id: u64,
+}
+
+impl Registered {
+    /// Removes the request, unless it was cancelled or never registered because it's a duplicate.
+    fn take(&self) -> Option<InFlight> {
+        let mut in_flight = self.inner.in_flight.lock().unwrap();
+        if in_flight
+            .get(&self.key)
+            .map_or(false, |other| other.id == self.id)
+        {
+            in_flight.remove(&self.key)
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for Registered {
+    fn drop(&mut self) {
+        self.take();
+    }
+}
+
+impl Inner {
+    async fn send<T, Fut, F>(
+        self: Arc<Self>,
+        priority: Priority,
+        make_request: F,
+    ) -> ClientResult<T>
+    where
+        Fut: Future<Output = ClientResult<T>>,
+        F: Fn() -> Fut,
+    {
+        let mut attempt = 1;
+        loop {
+            // Back off without holding a permit, so that the requests that can run do
+            if let Some(until) = self.backoff_until() {
+                tokio::time::sleep_until(until).await;
+            }
+
+            let _background_permit = match priority {
+                Priority::Background => Some(
+                    self.background
+                        .acquire()
+                        .await
+                        .expect("the semaphore is never closed"),
+                ),
+                Priority::Visible => None,
+            };
+            let _permit = self
+                .running
+                .acquire()
+                .await
+                .expect("the semaphore is never closed");
+            // Another request may have backed off while this one waited for a permit
+            if self.backoff_until().is_some() {
+                continue;
+            }
+
+            match make_request().await {
+                Err(err) if err.kind().recovery() == Recovery::Retry && attempt < MAX_ATTEMPTS => {
+                    let delay = self.back_off();
+                    tracing::warn!("request failed, retrying in {:?}: {}", delay, err);
+                    attempt += 1;
+                }
+                result => {
+                    if result.is_ok() {
+                        self.backoff.lock().unwrap().delay = Duration::default();
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    /// Returns until when requests have to wait before being sent, if they have to.
+    fn backoff_until(&self) -> Option<Instant> {
+        self.backoff
+            .lock()
+            .unwrap()
+            .until
+            .filter(|until| *until > Instant::now())
+    }
+
+    /// Makes every request wait before being sent, longer each time one fails with a temporary error.
+    fn back_off(&self) -> Duration {
+        let mut backoff = self.backoff.lock().unwrap();
+        backoff.delay = (backoff.delay * 2).max(MIN_BACKOFF).min(MAX_BACKOFF);
+        let until = Instant::now() + backoff.delay;
+        if backoff.until.map_or(true, |other| other < until) {
+            backoff.until = Some(until);
+        }
+        backoff.delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::error::{ClientError, ErrorKind};
+    use std::sync::atomic::AtomicU32;
+
+    const KEY: RequestKey = RequestKey::GuildContents(1);
+
+    fn rate_limited() -> ClientError {
+        ClientError::Described(ErrorKind::RateLimit, "rate limited".to_string())
+    }
+
+    /// Makes requests that count how many times they were sent, and fail the first `failures` times.
+    fn counted(
+        failures: u32,
+    ) -> (
+        Arc<AtomicU32>,
+        impl Fn() -> futures::future::BoxFuture<'static, ClientResult<u32>> + Send + 'static,
+    ) {
+        let sent = Arc::new(AtomicU32::new(0));
+        let counter = sent.clone();
+        let make_request = move || {
+            let counter = counter.clone();
+            let fut: futures::future::BoxFuture<'static, ClientResult<u32>> =
+                Box::pin(async move {
+                    let attempt = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                    // Stay in flight long enough for other requests to be made
+                    tokio::task::yield_now().await;
+                    if attempt <= failures {
+                        Err(rate_limited())
+                    } else {
+                        Ok(attempt)
+                    }
+                });
+            fut
+        };
+        (sent, make_request)
+    }
+
+    #[tokio::test]
+    async fn duplicates_share_the_result() {
+        let scheduler = Scheduler::default();
+        let (sent, make_request) = counted(0);
+        let (_, make_duplicate) = counted(0);
+
+        let first = scheduler.run(KEY, Priority::Visible, make_request);
+        let duplicate = scheduler.run(KEY, Priority::Background, make_duplicate);
+        let (first, duplicate) = futures::future::join(first, duplicate).await;
+
+        assert!(matches!(first, Some(Ok(1))));
+        assert!(matches!(duplicate, Some(Ok(1))));
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+        assert!(scheduler.inner.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn stale_requests_are_cancelled() {
+        let scheduler = Scheduler::default();
+        let (sent, make_request) = counted(0);
+        let (_, make_duplicate) = counted(0);
+        let (_, make_other) = counted(0);
+
+        let first = scheduler.run(KEY, Priority::Visible, make_request);
+        let duplicate = scheduler.run(KEY, Priority::Visible, make_duplicate);
+        // Requests that aren't for a guild aren't stale
+        let other = scheduler.run(RequestKey::Profile(1), Priority::Visible, make_other);
+        scheduler.cancel_stale(2, None);
+        let (first, duplicate, other) = futures::future::join3(first, duplicate, other).await;
+
+        assert!(first.is_none());
+        assert!(duplicate.is_none());
+        assert!(matches!(other, Some(Ok(1))));
+        assert_eq!(sent.load(Ordering::SeqCst), 0);
+
+        // Cancelled requests can be made again
+        let (sent, make_request) = counted(0);
+        let again = scheduler.run(KEY, Priority::Visible, make_request).await;
+        assert!(matches!(again, Some(Ok(1))));
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn temporary_errors_are_retried_after_backing_off() {
+        tokio::time::pause();
+        let scheduler = Scheduler::default();
+        let (sent, make_request) = counted(2);
+        let start = Instant::now();
+
+        let request = tokio::spawn(scheduler.run(KEY, Priority::Visible, make_request));
+        tokio::time::sleep(MIN_BACKOFF / 2).await;
+        // Backing off doesn't keep other requests from running
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+        assert_eq!(scheduler.inner.running.available_permits(), MAX_RUNNING);
+
+        let result = request.await.unwrap();
+        assert!(matches!(result, Some(Ok(3))));
+        assert!(start.elapsed() >= MIN_BACKOFF * 3);
+    }
+
+    #[tokio::test]
+    async fn retries_give_up() {
+        tokio::time::pause();
+        let scheduler = Scheduler::default();
+        let (sent, make_request) = counted(u32::MAX);
+
+        let result = scheduler.run(KEY, Priority::Visible, make_request).await;
+        assert!(matches!(result, Some(Err(err)) if err.kind() == ErrorKind::RateLimit));
+        assert_eq!(sent.load(Ordering::SeqCst), MAX_ATTEMPTS);
+    }
+}
@@ -0,0 +1,245 @@
+//! Keeps the session token out of plain files.
+//!
+//! The token goes to the system keyring when there is one (the Secret Service on Linux), and the
+//! session file only says which entry has it. Without a keyring, the whole session is saved
+//! encrypted with a passphrase the user chooses.
+
+use super::{
+    content::ContentStore,
+    error::{ClientError, ClientResult},
+    Session,
+};
+use age::secrecy::Secret;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Debug,
+    fs::{self, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+/// The service that keyring entries are saved under.
+pub const KEYRING_SERVICE: &str = "crust";
+
+/// Files encrypted by `age` start with this.
+const ENCRYPTED_MAGIC: &[u8] = b"age-encryption.org/";
+
+/// Somewhere to save secrets, like the system keyring.
+///
+/// Methods may block, so they are called on a thread where that is fine.
+pub trait SecretStore: Debug + Send + Sync {
+    /// Returns the secret saved with a key, if there is one.
+    fn get(&self, key: &str) -> ClientResult<Option<String>>;
+    fn set(&self, key: &str, secret: &str) -> ClientResult<()>;
+    /// Deletes the secret saved with a key, doing nothing if there isn't one.
+    fn delete(&self, key: &str) -> ClientResult<()>;
+}
+
+/// The keyring of the system, which fails with [`ClientError::KeyringUnavailable`] if there
+/// isn't one.
+#[derive(Debug, Default)]
+pub struct Keyring;
+
+impl Keyring {
+    fn entry(key: &str) -> keyring::Keyring<'_> {
+        keyring::Keyring::new(KEYRING_SERVICE, key)
+    }
+}
+
+fn keyring_error(err: keyring::KeyringError) -> ClientError {
+    ClientError::KeyringUnavailable(err.to_string())
+}
+
+impl SecretStore for Keyring {
+    fn get(&self, key: &str) -> ClientResult<Option<String>> {
+        match Self::entry(key).get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::KeyringError::NoPasswordFound) => Ok(None),
+            Err(err) => Err(keyring_error(err)),
+        }
+    }
+
+    fn set(&self, key: &str, secret: &str) -> ClientResult<()> {
+        Self::entry(key).set_password(secret).map_err(keyring_error)
+    }
+
+    fn delete(&self, key: &str) -> ClientResult<()> {
+        match Self::entry(key).delete_password() {
+            Ok(()) | Err(keyring::KeyringError::NoPasswordFound) => Ok(()),
+            Err(err) => Err(keyring_error(err)),
+        }
+    }
+}
+
+/// What the session file has when the token is in the keyring.
+#[derive(Deserialize, Serialize)]
+struct KeyringSession {
+    user_id: String,
+    homeserver: String,
+}
+
+impl KeyringSession {
+    /// The key of the keyring entry, so that sessions of different accounts don't overwrite
+    /// each other.
+    fn key(&self) -> String {
+        format!("{}@{}", self.user_id, self.homeserver)
+    }
+}
+
+/// The ways a session file can be saved.
+enum SessionFile {
+    Keyring(KeyringSession),
+    Encrypted(Vec<u8>),
+    /// Saved in plain text by older versions, which is replaced when it's read.
+    Plain(Session),
+}
+
+impl SessionFile {
+    fn parse(data: Vec<u8>) -> ClientResult<Self> {
+        if data.starts_with(ENCRYPTED_MAGIC) {
+            return Ok(SessionFile::Encrypted(data));
+        }
+        // Only plain sessions have a token
+        toml::de::from_slice(&data)
+            .map(SessionFile::Plain)
+            .or_else(|_| toml::de::from_slice(&data).map(SessionFile::Keyring))
+            .map_err(|_| ClientError::MissingLoginInfo)
+    }
+}
+
+/// Runs something that blocks, like talking to the keyring over D-Bus or deriving a key.
+async fn blocking<T, F>(f: F) -> ClientResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> ClientResult<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|err| ClientError::Custom(err.to_string()))?
+}
+
+pub(super) async fn load(
+    content_store: &ContentStore,
+    passphrase: Option<String>,
+) -> ClientResult<Session> {
+    let data = tokio::fs::read(content_store.session_file()).await?;
+    match SessionFile::parse(data)? {
+        SessionFile::Keyring(entry) => {
+            let secrets = content_store.secret_store().clone();
+            let key = entry.key();
+            let session_token = blocking(move || secrets.get(&key))
+                .await?
+                // The entry was deleted from somewhere else, like the keyring manager of the system
+                .ok_or(ClientError::MissingLoginInfo)?;
+            Ok(Session {
+                session_token,
+                user_id: entry.user_id,
+                homeserver: entry.homeserver,
+            })
+        }
+        SessionFile::Encrypted(data) => {
+            let passphrase = passphrase.ok_or(ClientError::PassphraseRequired)?;
+            let data = blocking(move || decrypt(&data, passphrase)).await?;
+            toml::de::from_slice(&data).map_err(|_| ClientError::MissingLoginInfo)
+        }
+        SessionFile::Plain(session) => {
+            tracing::info!("moving the session out of the plain text session file");
+            save(&session, content_store, passphrase).await?;
+            Ok(session)
+        }
+    }
+}
+
+pub(super) async fn save(
+    session: &Session,
+    content_store: &ContentStore,
+    passphrase: Option<String>,
+) -> ClientResult<()> {
+    let entry = KeyringSession {
+        user_id: session.user_id.clone(),
+        homeserver: session.homeserver.clone(),
+    };
+    let secrets = content_store.secret_store().clone();
+    let (key, token) = (entry.key(), session.session_token.clone());
+
+    let data = match (
+        blocking(move || secrets.set(&key, &token)).await,
+        passphrase,
+    ) {
+        // These should never ever fail in our case, if they do something is very very very wrong
+        (Ok(()), _) => toml::ser::to_vec(&entry).unwrap(),
+        (Err(ClientError::KeyringUnavailable(reason)), Some(passphrase)) => {
+            tracing::warn!(
+                "saving the session encrypted, as there is no keyring: {}",
+                reason
+            );
+            let data = toml::ser::to_vec(session).unwrap();
+            blocking(move || encrypt(&data, passphrase)).await?
+        }
+        (Err(err), _) => return Err(err),
+    };
+    let path = content_store.session_file().to_path_buf();
+    blocking(move || write_private(&path, &data)).await
+}
+
+/// Writes a file only the user can read, replacing the old one at once so that a crash can't leave
+/// half of it.
+fn write_private(path: &Path, data: &[u8]) -> ClientResult<()> {
+    let temp = path.with_extension("tmp");
+    // The mode is only set when creating the file, so one left by a crash isn't reused
+    match fs::remove_file(&temp) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+        _ => {}
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&temp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+pub(super) async fn remove(content_store: &ContentStore) -> ClientResult<()> {
+    let data = tokio::fs::read(content_store.session_file()).await?;
+    if let Ok(SessionFile::Keyring(entry)) = SessionFile::parse(data) {
+        let secrets = content_store.secret_store().clone();
+        let key = entry.key();
+        // The file is what says there is a session, so it's removed even if the keyring fails
+        if let Err(err) = blocking(move || secrets.delete(&key)).await {
+            tracing::warn!("couldn't delete the session from the keyring: {}", err);
+        }
+    }
+    tokio::fs::remove_file(content_store.session_file()).await?;
+    Ok(())
+}
+
+fn encrypt(data: &[u8], passphrase: String) -> ClientResult<Vec<u8>> {
+    let encryptor = age::Encryptor::with_user_passphrase(Secret::new(passphrase));
+    let mut encrypted = Vec::new();
+    let mut writer = encryptor
+        .wrap_output(&mut encrypted)
+        .map_err(|err| ClientError::Custom(err.to_string()))?;
+    writer.write_all(data)?;
+    writer.finish()?;
+    Ok(encrypted)
+}
+
+fn decrypt(data: &[u8], passphrase: String) -> ClientResult<Vec<u8>> {
+    let decryptor = match age::Decryptor::new(data) {
+        Ok(age::Decryptor::Passphrase(decryptor)) => decryptor,
+        _ => return Err(ClientError::MissingLoginInfo),
+    };
+    let mut reader = decryptor
+        .decrypt(&Secret::new(passphrase), None)
+        .map_err(|_| ClientError::WrongPassphrase)?;
+    let mut decrypted = Vec::new();
+    reader.read_to_end(&mut decrypted)?;
+    Ok(decrypted)
+}
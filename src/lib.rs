@@ -0,0 +1,8 @@
+//! Rust client for the Harmony protocol.
+//!
+//! [`client`] talks to the homeserver and keeps its state, while [`ui`] is the `iced` frontend
+//! built on top of it. The `crust` binary runs the frontend, and `crust-cli` uses the client
+//! directly for scripting.
+
+pub mod client;
+pub mod ui;
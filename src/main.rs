@@ -1,14 +1,13 @@
 #![windows_subsystem = "windows"]
 
-use client::content::ContentStore;
-use ui::{screen::ScreenManager, style::DEF_SIZE};
+use crust::{
+    client::content::ContentStore,
+    ui::{screen::ScreenManager, style::DEF_SIZE},
+};
 
 use iced::{Application, Settings};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
-pub mod client;
-pub mod ui;
-
 fn main() {
     // Create the content store
     let content_store = ContentStore::default();
@@ -35,7 +34,7 @@ fn main() {
         .init();
 
     let mut settings = Settings::with_flags(content_store);
-    settings.window.size = (1280, 720);
+    settings.window.size = (1280, 720); // [tag:initial_window_size]
     settings.antialiasing = false;
     settings.default_font = Some(include_bytes!("NotoSans-Regular.ttf"));
     settings.default_text_size = DEF_SIZE;
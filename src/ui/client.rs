@@ -0,0 +1,331 @@
+//! Adapts the [`Client`] to iced, turning its requests into commands and its timers into subscriptions.
+
+use std::{
+    hash::{Hash, Hasher},
+    time::Instant,
+};
+
+use harmony_rust_sdk::api::harmonytypes::UserStatus;
+use iced::{Command, Subscription};
+use iced_futures::{futures::stream, BoxStream};
+
+use crate::{
+    client::{
+        error::ClientResult,
+        message::{Content, Message as IcyMessage},
+        scheduler::{Priority, RequestKey},
+        Client,
+    },
+    ui::screen::{main::Message as MainMessage, Message},
+};
+
+/// Commands for the requests the UI makes, which report their result as UI messages.
+pub trait ClientExt {
+    /// Sends a message, showing it right away.
+    ///
+    /// Sending is scheduled, so it's retried a few times if it fails for a reason that can go away.
+    fn send_msg_cmd(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        message: IcyMessage,
+    ) -> Option<Command<Message>>;
+    /// Deletes a message, reporting the result to the main screen so it can restore the message on failure.
+    fn delete_msg_cmd(&self, guild_id: u64, channel_id: u64, message_id: u64) -> Command<Message>;
+    /// Replaces the content of a message.
+    ///
+    /// Text edits are applied when the server sends the edit event. Other edits are applied
+    /// once the request succeeds, since the edit event only carries text.
+    fn edit_msg_cmd(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        message_id: u64,
+        new_content: Content,
+    ) -> Command<Message>;
+    fn pin_msg_cmd(&self, guild_id: u64, channel_id: u64, message_id: u64) -> Command<Message>;
+    fn unpin_msg_cmd(&self, guild_id: u64, channel_id: u64, message_id: u64) -> Command<Message>;
+    /// Fetches the pinned messages of a channel, which are then kept up to date from events.
+    fn fetch_pins_cmd(&self, guild_id: u64, channel_id: u64) -> Command<Message>;
+    /// Fetches the permissions the UI checks in a guild, or in a channel of it, unless they are cached.
+    fn fetch_permissions_cmd(&self, guild_id: u64, channel_id: Option<u64>) -> Command<Message>;
+    fn kick_user_cmd(&self, guild_id: u64, user_id: u64) -> Command<Message>;
+    fn ban_user_cmd(&self, guild_id: u64, user_id: u64) -> Command<Message>;
+    fn set_status_cmd(&self, status: UserStatus) -> Command<Message>;
+    /// Moves a channel so that it's between `previous_id` and `next_id`.
+    fn move_channel_cmd(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        previous_id: u64,
+        next_id: u64,
+    ) -> Command<Message>;
+    /// Returns a subscription that fires once the earliest ephemeral state expires.
+    fn expiry_subscription(&self) -> Subscription<Instant>;
+}
+
+impl ClientExt for Client {
+    fn send_msg_cmd(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        message: IcyMessage,
+    ) -> Option<Command<Message>> {
+        let transaction_id = message.id.transaction_id()?;
+        if !self.push_message(guild_id, channel_id, message.clone()) {
+            return None;
+        }
+
+        let requests = self.requests().clone();
+        let request = move || {
+            let requests = requests.clone();
+            let message = message.clone();
+            async move { requests.send_message(guild_id, channel_id, &message).await }
+        };
+
+        Some(Command::perform(
+            self.scheduler().run(
+                RequestKey::SendMessage(transaction_id),
+                Priority::Visible,
+                request,
+            ),
+            move |result| match result {
+                Some(Ok(message_id)) => Message::MessageSent {
+                    message_id,
+                    transaction_id,
+                    channel_id,
+                    guild_id,
+                },
+                Some(Err(err)) => {
+                    tracing::error!("error occured when sending message: {}", err);
+                    Message::Error(Box::new(err))
+                }
+                None => Message::Nothing,
+            },
+        ))
+    }
+
+    fn delete_msg_cmd(&self, guild_id: u64, channel_id: u64, message_id: u64) -> Command<Message> {
+        let requests = self.requests().clone();
+
+        Command::perform(
+            async move {
+                requests
+                    .delete_message(guild_id, channel_id, message_id)
+                    .await
+            },
+            move |result| {
+                Message::MainScreen(MainMessage::DeleteFinished {
+                    guild_id,
+                    channel_id,
+                    message_id,
+                    err: result.err().map(Box::new),
+                })
+            },
+        )
+    }
+
+    fn edit_msg_cmd(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        message_id: u64,
+        new_content: Content,
+    ) -> Command<Message> {
+        let requests = self.requests().clone();
+
+        Command::perform(
+            async move {
+                let applied_content = match &new_content {
+                    Content::Text(_) => None,
+                    new_content => Some(new_content.clone()),
+                };
+                let result = requests
+                    .edit_message(guild_id, channel_id, message_id, new_content)
+                    .await;
+
+                result.map_or_else(
+                    |err| Message::MessageEdited {
+                        guild_id,
+                        channel_id,
+                        message_id,
+                        new_content: None,
+                        err: Some(Box::new(err)),
+                    },
+                    |_| Message::MessageEdited {
+                        guild_id,
+                        channel_id,
+                        message_id,
+                        new_content: applied_content,
+                        err: None,
+                    },
+                )
+            },
+            |m| m,
+        )
+    }
+
+    fn pin_msg_cmd(&self, guild_id: u64, channel_id: u64, message_id: u64) -> Command<Message> {
+        let requests = self.requests().clone();
+
+        Command::perform(
+            async move { requests.pin_message(guild_id, channel_id, message_id).await },
+            nothing_or_error,
+        )
+    }
+
+    fn unpin_msg_cmd(&self, guild_id: u64, channel_id: u64, message_id: u64) -> Command<Message> {
+        let requests = self.requests().clone();
+
+        Command::perform(
+            async move {
+                requests
+                    .unpin_message(guild_id, channel_id, message_id)
+                    .await
+            },
+            nothing_or_error,
+        )
+    }
+
+    fn fetch_pins_cmd(&self, guild_id: u64, channel_id: u64) -> Command<Message> {
+        let requests = self.requests().clone();
+        let key = RequestKey::PinnedMessages {
+            guild_id,
+            channel_id,
+        };
+
+        Command::perform(
+            self.scheduler().run(key, Priority::Visible, move || {
+                let requests = requests.clone();
+                async move { requests.pinned_messages(guild_id, channel_id).await }
+            }),
+            move |result| match result {
+                Some(result) => result.map_or_else(
+                    |err| Message::Error(Box::new(err)),
+                    |message_ids| {
+                        Message::MainScreen(MainMessage::PinsLoaded {
+                            guild_id,
+                            channel_id,
+                            message_ids,
+                        })
+                    },
+                ),
+                None => Message::Nothing,
+            },
+        )
+    }
+
+    fn fetch_permissions_cmd(&self, guild_id: u64, channel_id: Option<u64>) -> Command<Message> {
+        if self.permissions.is_loaded(guild_id, channel_id) {
+            return Command::none();
+        }
+
+        let requests = self.requests().clone();
+        let request = move || {
+            let requests = requests.clone();
+            async move { requests.permissions(guild_id, channel_id).await }
+        };
+
+        Command::perform(
+            self.scheduler().run(
+                RequestKey::Permissions {
+                    guild_id,
+                    channel_id,
+                },
+                Priority::Visible,
+                request,
+            ),
+            move |result| match result {
+                Some(result) => result.map_or_else(
+                    |err| Message::Error(Box::new(err)),
+                    |permissions| Message::PermissionsLoaded {
+                        guild_id,
+                        channel_id,
+                        permissions,
+                    },
+                ),
+                None => Message::Nothing,
+            },
+        )
+    }
+
+    fn kick_user_cmd(&self, guild_id: u64, user_id: u64) -> Command<Message> {
+        let requests = self.requests().clone();
+
+        Command::perform(
+            async move { requests.kick_user(guild_id, user_id).await },
+            nothing_or_error,
+        )
+    }
+
+    fn ban_user_cmd(&self, guild_id: u64, user_id: u64) -> Command<Message> {
+        let requests = self.requests().clone();
+
+        Command::perform(
+            async move { requests.ban_user(guild_id, user_id).await },
+            nothing_or_error,
+        )
+    }
+
+    fn set_status_cmd(&self, status: UserStatus) -> Command<Message> {
+        let requests = self.requests().clone();
+
+        Command::perform(
+            async move { requests.set_status(status).await },
+            nothing_or_error,
+        )
+    }
+
+    fn move_channel_cmd(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        previous_id: u64,
+        next_id: u64,
+    ) -> Command<Message> {
+        let requests = self.requests().clone();
+
+        Command::perform(
+            async move {
+                requests
+                    .move_channel(guild_id, channel_id, previous_id, next_id)
+                    .await
+            },
+            nothing_or_error,
+        )
+    }
+
+    fn expiry_subscription(&self) -> Subscription<Instant> {
+        self.ephemeral
+            .next_deadline()
+            .map_or_else(Subscription::none, |deadline| {
+                Subscription::from_recipe(Deadline(deadline))
+            })
+    }
+}
+
+fn nothing_or_error(result: ClientResult<()>) -> Message {
+    result.map_or_else(|err| Message::Error(Box::new(err)), |_| Message::Nothing)
+}
+
+/// Fires once at a deadline.
+///
+/// Since the subscription is identified by its deadline, it's replaced whenever the deadline changes.
+struct Deadline(Instant);
+
+impl<H: Hasher, E> iced_futures::subscription::Recipe<H, E> for Deadline {
+    type Output = Instant;
+
+    fn hash(&self, state: &mut H) {
+        std::any::TypeId::of::<Self>().hash(state);
+        self.0.hash(state);
+    }
+
+    fn stream(self: Box<Self>, _input: BoxStream<E>) -> BoxStream<Self::Output> {
+        let deadline = self.0;
+        Box::pin(stream::once(async move {
+            tokio::time::sleep_until(deadline.into()).await;
+            deadline
+        }))
+    }
+}
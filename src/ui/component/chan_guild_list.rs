@@ -1,22 +1,64 @@
 use crate::{
-    client::{channel::Channels, content::ThumbnailCache, guild::Guilds},
-    label,
+    client::{channel::Channels, guild::Guilds},
+    label, space,
     ui::{
-        component::*,
-        style::{Theme, DEF_SIZE, PADDING, SPACING},
+        component::{drag, *},
+        style::{Theme, ALT_COLOR, DEF_SIZE, PADDING, SPACING},
+        thumbnail::ThumbnailCache,
     },
 };
 
+use ahash::AHashSet;
 use iced::{tooltip::Position, Tooltip};
+use iced_aw::{Icon, ICON_FONT};
+
+/// Button states of an entry in the channel list.
+#[derive(Debug, Default)]
+pub struct ChannelButtonsState {
+    select: button::State,
+    menu: button::State,
+    drag: drag::State,
+}
+
+/// Something done to a channel from the channel list, other than selecting it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelListAction {
+    /// Collapses the category if it's expanded, expands it otherwise.
+    ToggleCollapsed(u64),
+    OpenMenu(u64),
+    /// Starts dragging the channel to move it.
+    StartDrag(u64),
+    /// The dragged channel was moved over this channel.
+    DragOver(u64),
+    /// Drops the dragged channel, moving it to where this channel is.
+    Drop(u64),
+    /// The dragged channel was dropped somewhere that isn't a channel.
+    CancelDrag,
+}
+
+/// A channel that is being dragged in the channel list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelDrag {
+    pub channel_id: u64,
+    /// The channel it was last dragged over, which is where it goes if it's dropped.
+    pub target_id: Option<u64>,
+}
 
 /// Builds a room list.
+///
+/// Channels following a category are grouped under it, and are hidden if the category is in
+/// `collapsed`. The menu and drag handle of channels are only shown if `can_manage` is `true`.
 #[allow(clippy::clippy::too_many_arguments)]
 pub fn build_channel_list<'a, Message: Clone + 'a>(
     channels: &Channels,
     current_channel_id: Option<u64>,
+    collapsed: &AHashSet<u64>,
+    dragged: Option<ChannelDrag>,
     state: &'a mut scrollable::State,
-    buttons_state: &'a mut [button::State],
+    buttons_state: &'a mut [ChannelButtonsState],
+    can_manage: bool,
     on_button_press: fn(u64) -> Message,
+    on_action: fn(ChannelListAction) -> Message,
     theme: Theme,
 ) -> Element<'a, Message> {
     let mut channel_list = Scrollable::new(state)
@@ -26,20 +68,91 @@ pub fn build_channel_list<'a, Message: Clone + 'a>(
         .spacing(SPACING)
         .padding(PADDING / 4);
 
-    for ((channel_id, channel), button_state) in channels.iter().zip(buttons_state.iter_mut()) {
-        let channel_name_prefix = if channel.is_category { "+" } else { "#" };
-        let channel_name_formatted = format!("{}{}", channel_name_prefix, channel.name);
-        let content = label!(channel_name_formatted).size(DEF_SIZE - 2);
+    let icon = |icon: Icon| label!(icon).font(ICON_FONT).size(DEF_SIZE - 4);
+    let mut in_category = false;
+    let mut category_collapsed = false;
 
-        let mut but = Button::new(button_state, content)
-            .width(length!(+))
-            .style(theme.secondary());
+    for ((channel_id, channel), buttons_state) in channels.iter().zip(buttons_state.iter_mut()) {
+        let channel_id = *channel_id;
+        let ChannelButtonsState { select, menu, drag } = buttons_state;
+        let is_dragged = dragged.map_or(false, |d| d.channel_id == channel_id);
+
+        if channel.is_category {
+            in_category = true;
+            category_collapsed = collapsed.contains(&channel_id);
+        } else if category_collapsed && dragged.is_none() {
+            continue;
+        }
 
-        if current_channel_id != Some(*channel_id) {
-            but = but.on_press(on_button_press(*channel_id));
+        let select_but = if channel.is_category {
+            let arrow = if category_collapsed {
+                Icon::ChevronRight
+            } else {
+                Icon::ChevronDown
+            };
+            Button::new(
+                select,
+                Row::with_children(vec![
+                    icon(arrow).into(),
+                    label!(channel.name.to_uppercase())
+                        .size(DEF_SIZE - 4)
+                        .into(),
+                ])
+                .align_items(align!(|))
+                .spacing(SPACING),
+            )
+            .on_press(on_action(ChannelListAction::ToggleCollapsed(channel_id)))
+        } else {
+            let name = if channel.unread > 0 {
+                label!("#{} ({})", channel.name, channel.unread)
+            } else {
+                label!("#{}", channel.name)
+            };
+            let name = if is_dragged {
+                name.color(ALT_COLOR)
+            } else {
+                name
+            };
+            let mut but = Button::new(select, name.size(DEF_SIZE - 2));
+            if current_channel_id != Some(channel_id) {
+                but = but.on_press(on_button_press(channel_id));
+            }
+            but
         }
+        .width(length!(+))
+        .style(theme.secondary());
+
+        // Not a button, so that pressing on it starts a drag
+        let handle = match dragged {
+            Some(d) if !is_dragged && d.target_id == Some(channel_id) => icon(Icon::ArrowLeft),
+            _ => icon(Icon::GripVertical),
+        };
+        let menu_but = Button::new(menu, icon(Icon::ThreeDots))
+            .on_press(on_action(ChannelListAction::OpenMenu(channel_id)))
+            .style(theme.secondary());
+
+        let indent = if in_category && !channel.is_category {
+            PADDING / 2
+        } else {
+            0
+        };
 
-        channel_list = channel_list.push(but);
+        let mut row_widgets = vec![space!(w = indent).into(), select_but.into()];
+        if can_manage {
+            row_widgets.push(menu_but.into());
+            row_widgets.push(handle.into());
+        }
+        let mut area =
+            drag::DragArea::new(drag, Row::with_children(row_widgets).align_items(align!(|)));
+        if can_manage {
+            area = match dragged {
+                Some(_) => area
+                    .on_hover(on_action(ChannelListAction::DragOver(channel_id)))
+                    .on_drop(on_action(ChannelListAction::Drop(channel_id))),
+                None => area.on_drag(on_action(ChannelListAction::StartDrag(channel_id))),
+            };
+        }
+        channel_list = channel_list.push(area);
     }
 
     channel_list.into()
@@ -0,0 +1,156 @@
+//! Dragging things around with the mouse, like the entries of a list.
+
+use iced_native::{
+    event, layout, mouse, overlay, Clipboard, Element, Event, Hasher, Layout, Length, Point,
+    Rectangle, Widget,
+};
+
+/// Whether the cursor was over a [`DragArea`] the last time it moved.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct State {
+    hovered: bool,
+}
+
+/// Makes its content something that can be dragged, or that dragged things can be dropped on.
+///
+/// Pressing the left mouse button on a part of the content that doesn't handle it itself (so not
+/// on a button) starts a drag. Keeping what is being dragged is up to whoever builds the areas,
+/// who sets the hover and drop messages only while there is a drag.
+pub struct DragArea<'a, Message, Renderer> {
+    state: &'a mut State,
+    content: Element<'a, Message, Renderer>,
+    on_drag: Option<Message>,
+    on_hover: Option<Message>,
+    on_drop: Option<Message>,
+}
+
+impl<'a, Message, Renderer> DragArea<'a, Message, Renderer> {
+    pub fn new(state: &'a mut State, content: impl Into<Element<'a, Message, Renderer>>) -> Self {
+        Self {
+            state,
+            content: content.into(),
+            on_drag: None,
+            on_hover: None,
+            on_drop: None,
+        }
+    }
+
+    /// Sets the message sent when a drag is started on this.
+    pub fn on_drag(mut self, msg: Message) -> Self {
+        self.on_drag = Some(msg);
+        self
+    }
+
+    /// Sets the message sent when the cursor enters this while dragging.
+    pub fn on_hover(mut self, msg: Message) -> Self {
+        self.on_hover = Some(msg);
+        self
+    }
+
+    /// Sets the message sent when the dragged thing is dropped on this.
+    pub fn on_drop(mut self, msg: Message) -> Self {
+        self.on_drop = Some(msg);
+        self
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for DragArea<'a, Message, Renderer>
+where
+    Message: Clone,
+    Renderer: iced_native::Renderer,
+{
+    fn width(&self) -> Length {
+        self.content.width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        self.content.layout(renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        defaults: &Renderer::Defaults,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) -> Renderer::Output {
+        self.content
+            .draw(renderer, defaults, layout, cursor_position, viewport)
+    }
+
+    fn hash_layout(&self, state: &mut Hasher) {
+        use std::hash::Hash;
+
+        struct Marker;
+        std::any::TypeId::of::<Marker>().hash(state);
+        self.content.hash_layout(state);
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        messages: &mut Vec<Message>,
+    ) -> event::Status {
+        let status = self.content.on_event(
+            event.clone(),
+            layout,
+            cursor_position,
+            renderer,
+            clipboard,
+            messages,
+        );
+        let is_over = layout.bounds().contains(cursor_position);
+
+        let msg = match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                if is_over && status == event::Status::Ignored =>
+            {
+                self.on_drag.clone()
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) if is_over => {
+                self.on_drop.clone()
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                let entered = is_over && !self.state.hovered;
+                self.state.hovered = is_over;
+                // The cursor moving isn't captured, every area needs to know about it
+                if let Some(msg) = self.on_hover.clone().filter(|_| entered) {
+                    messages.push(msg);
+                }
+                None
+            }
+            _ => None,
+        };
+
+        match msg {
+            Some(msg) => {
+                messages.push(msg);
+                event::Status::Captured
+            }
+            None => status,
+        }
+    }
+
+    fn overlay(&mut self, layout: Layout<'_>) -> Option<overlay::Element<'_, Message, Renderer>> {
+        self.content.overlay(layout)
+    }
+}
+
+impl<'a, Message, Renderer> From<DragArea<'a, Message, Renderer>> for Element<'a, Message, Renderer>
+where
+    Message: Clone + 'a,
+    Renderer: iced_native::Renderer + 'a,
+{
+    fn from(area: DragArea<'a, Message, Renderer>) -> Self {
+        Element::new(area)
+    }
+}
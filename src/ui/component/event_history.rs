@@ -1,9 +1,13 @@
+use std::ops::Range;
+
 use crate::{
     client::{
         channel::Channel,
-        content::{ContentStore, ThumbnailCache},
+        content::ContentStore,
         member::Members,
-        message::{Attachment, Content as IcyContent, EmbedHeading},
+        message::{
+            Attachment, Content as IcyContent, EmbedHeading, Message as IcyMessage, MessageId,
+        },
     },
     color, label, space,
     ui::{
@@ -13,13 +17,442 @@ use crate::{
             Theme, ALT_COLOR, AVATAR_WIDTH, DATE_SEPERATOR_SIZE, DEF_SIZE, ERROR_COLOR,
             MESSAGE_SENDER_SIZE, MESSAGE_SIZE, MESSAGE_TIMESTAMP_SIZE, PADDING, SPACING,
         },
+        thumbnail::ThumbnailCache,
     },
 };
-use chrono::{Datelike, Timelike};
-use harmony_rust_sdk::api::harmonytypes::r#override::Reason;
+use ahash::{AHashMap, AHashSet};
+use chrono::Timelike;
+use harmony_rust_sdk::{api::harmonytypes::r#override::Reason, client::api::rest::FileId};
+use iced::{tooltip::Position, Tooltip};
+use iced_native::image::Data;
 
-pub const SHOWN_MSGS_LIMIT: usize = 32;
 const MSG_LR_PADDING: u16 = SPACING * 2;
+/// How far outside the visible area messages are still built, so scrolling doesn't show gaps.
+const OVERSCAN: f32 = 600.0;
+/// Rough width of a character relative to the text size, used to estimate how text wraps.
+const CHAR_WIDTH_RATIO: f32 = 0.55;
+/// Rough height of a line of text relative to the text size.
+const LINE_HEIGHT_RATIO: f32 = 1.3;
+/// Width taken next to the text of a message in the history, like its timestamp and padding.
+const MESSAGE_SIDE_WIDTH: f32 = (PADDING * 5) as f32;
+const THUMBNAIL_WIDTH: u16 = 320;
+/// Messages that are further apart than this start a new group.
+const GROUP_TIMEOUT_MINUTES: i64 = 5;
+/// How many characters of a message are shown when it's summarized, like above a reply.
+const PREVIEW_CHARS: usize = 60;
+const REPLY_SIZE: u16 = MESSAGE_SIZE - 4;
+
+/// Widget state for one built message row, kept with the message it was built for.
+#[derive(Debug, Default)]
+pub struct MessageButtonsState {
+    content_open: button::State,
+    embed: (button::State, button::State),
+    edit: button::State,
+    sender: button::State,
+}
+
+/// Scroll position of the message history of a channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryView {
+    /// Distance from the top of the first loaded message to the top of the viewport.
+    pub offset: f32,
+    /// Whether the view sticks to the newest message.
+    pub at_bottom: bool,
+}
+
+impl Default for HistoryView {
+    fn default() -> Self {
+        Self {
+            offset: 0.0,
+            at_bottom: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LayoutKey {
+    guild_id: u64,
+    channel_id: u64,
+    width: u32,
+}
+
+/// Measured height of a message row.
+#[derive(Debug, Clone, Copy)]
+struct Row {
+    id: MessageId,
+    /// The message before this one when it was measured, which decides whether the sender and
+    /// a date separator are shown above it.
+    previous: Option<MessageId>,
+    height: f32,
+}
+
+/// Measured heights of the loaded messages of a channel.
+///
+/// Heights are estimated from the message contents instead of being laid out, which lets us
+/// build only the messages that are in view no matter how many are loaded. Measurements are
+/// kept until they're invalidated, so only the messages that changed are measured again.
+#[derive(Debug, Default)]
+pub struct HistoryLayout {
+    key: Option<LayoutKey>,
+    rows: Vec<Row>,
+    /// `tops[i]` is where message `i` starts; the last element is the total height.
+    tops: Vec<f32>,
+    /// Whether the messages changed since they were measured.
+    changed: bool,
+    /// Messages whose contents changed since they were measured.
+    stale: AHashSet<MessageId>,
+    /// Thumbnails that were loaded since the messages were measured.
+    loaded_thumbnails: AHashSet<FileId>,
+}
+
+impl HistoryLayout {
+    /// Measures the messages of a channel for a history of some width, unless the current
+    /// measurements are still valid for them.
+    ///
+    /// Only messages that are new or were invalidated since they were measured are measured
+    /// again. Returns `true` if the layout changed.
+    pub fn update(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        channel: &Channel,
+        thumbnail_cache: &ThumbnailCache,
+        width: f32,
+    ) -> bool {
+        let key = LayoutKey {
+            guild_id,
+            channel_id,
+            width: width as u32,
+        };
+        if self.key == Some(key) && !self.changed {
+            return false;
+        }
+
+        let known_rows = if self.key == Some(key) {
+            self.rows
+                .iter()
+                .map(|row| (row.id, *row))
+                .collect::<AHashMap<_, _>>()
+        } else {
+            AHashMap::new()
+        };
+        let text_width = (width - MESSAGE_SIDE_WIDTH).max(1.0);
+
+        self.rows.clear();
+        self.rows.reserve(channel.messages.len());
+        self.tops.clear();
+        self.tops.reserve(channel.messages.len() + 1);
+        let mut top = 0.0;
+        let mut previous: Option<&IcyMessage> = None;
+        for message in &channel.messages {
+            let previous_id = previous.map(|previous| previous.id);
+            let height = match known_rows.get(&message.id) {
+                Some(row)
+                    if row.previous == previous_id
+                        && !self.stale.contains(&message.id)
+                        && !self.shows_loaded_thumbnail(message) =>
+                {
+                    row.height
+                }
+                _ => row_height(previous, message, thumbnail_cache, text_width),
+            };
+            self.rows.push(Row {
+                id: message.id,
+                previous: previous_id,
+                height,
+            });
+            self.tops.push(top);
+            top += height;
+            previous = Some(message);
+        }
+        self.tops.push(top);
+        self.key = Some(key);
+        self.changed = false;
+        self.stale.clear();
+        self.loaded_thumbnails.clear();
+
+        true
+    }
+
+    /// Marks the messages of a channel as changed, like when some were added or removed.
+    pub fn invalidate_channel(&mut self, guild_id: u64, channel_id: u64) {
+        if self.is_for(guild_id, channel_id) {
+            self.changed = true;
+        }
+    }
+
+    /// Marks the messages of every channel in a guild as changed.
+    pub fn invalidate_guild(&mut self, guild_id: u64) {
+        if self.key.map_or(false, |key| key.guild_id == guild_id) {
+            self.changed = true;
+        }
+    }
+
+    /// Marks a message as changed, so it's measured again.
+    pub fn invalidate_message(&mut self, guild_id: u64, channel_id: u64, message_id: MessageId) {
+        if self.is_for(guild_id, channel_id) {
+            self.changed = true;
+            self.stale.insert(message_id);
+        }
+    }
+
+    /// Marks the messages showing a thumbnail as changed, now that it's loaded.
+    pub fn thumbnail_loaded(&mut self, id: FileId) {
+        if self.key.is_some() {
+            self.changed = true;
+            self.loaded_thumbnails.insert(id);
+        }
+    }
+
+    /// Drops every measurement, for when we can't know what changed.
+    pub fn invalidate_all(&mut self) {
+        self.key = None;
+    }
+
+    fn shows_loaded_thumbnail(&self, message: &IcyMessage) -> bool {
+        match &message.content {
+            IcyContent::Files(attachments) if !self.loaded_thumbnails.is_empty() => attachments
+                .iter()
+                .any(|attachment| self.loaded_thumbnails.contains(&attachment.id)),
+            _ => false,
+        }
+    }
+
+    pub fn is_for(&self, guild_id: u64, channel_id: u64) -> bool {
+        self.key.map_or(false, |key| {
+            key.guild_id == guild_id && key.channel_id == channel_id
+        })
+    }
+
+    /// Returns where a message starts, by its index in the channel.
+    pub fn top_of(&self, index: usize) -> Option<f32> {
+        // The last element is the total height, not a message
+        self.tops[..self.tops.len().saturating_sub(1)]
+            .get(index)
+            .copied()
+    }
+
+    pub fn total_height(&self) -> f32 {
+        self.tops.last().copied().unwrap_or(0.0)
+    }
+
+    /// Returns the offset the view should be at, clamped so it doesn't go past the last message.
+    pub fn offset_of(&self, view: HistoryView, viewport_height: f32) -> f32 {
+        let max_offset = (self.total_height() - viewport_height).max(0.0);
+        if view.at_bottom {
+            max_offset
+        } else {
+            view.offset.min(max_offset)
+        }
+    }
+
+    /// Returns the range of messages that are visible, or nearly visible, at an offset.
+    fn visible_range(&self, offset: f32, viewport_height: f32) -> Range<usize> {
+        let message_tops = &self.tops[..self.tops.len().saturating_sub(1)];
+        let start = message_tops
+            .partition_point(|top| *top <= offset - OVERSCAN)
+            .saturating_sub(1);
+        let end = message_tops.partition_point(|top| *top < offset + viewport_height + OVERSCAN);
+
+        start..end.max(start)
+    }
+}
+
+fn sender_of(message: &IcyMessage) -> (u64, Option<&str>) {
+    (
+        message.sender,
+        message
+            .overrides
+            .as_ref()
+            .map(|overrides| overrides.name.as_str()),
+    )
+}
+
+/// Whether a message starts a new group, which shows the sender again.
+fn starts_group(previous: Option<&IcyMessage>, message: &IcyMessage) -> bool {
+    previous.map_or(true, |previous| {
+        sender_of(previous) != sender_of(message)
+            || message.timestamp.signed_duration_since(previous.timestamp)
+                > chrono::Duration::minutes(GROUP_TIMEOUT_MINUTES)
+    })
+}
+
+fn day_changed(previous: Option<&IcyMessage>, message: &IcyMessage) -> bool {
+    previous.map_or(false, |previous| {
+        previous.timestamp.date() != message.timestamp.date()
+    })
+}
+
+/// Returns the height of the thumbnail shown for a file, if it's loaded.
+fn thumbnail_height(thumbnail_cache: &ThumbnailCache, id: &FileId) -> Option<f32> {
+    thumbnail_cache.get_thumbnail(id).map(|handle| {
+        // Only decoded thumbnails know their size, assume 4:3 for the others
+        match handle.data() {
+            Data::Pixels { width, height, .. } if *width > 0 => {
+                THUMBNAIL_WIDTH as f32 * *height as f32 / *width as f32
+            }
+            _ => THUMBNAIL_WIDTH as f32 * 0.75,
+        }
+    })
+}
+
+fn line_height(size: u16) -> f32 {
+    size as f32 * LINE_HEIGHT_RATIO
+}
+
+fn text_height(text: &str, size: u16, width: f32) -> f32 {
+    let chars_per_line = (width / (size as f32 * CHAR_WIDTH_RATIO)).max(1.0);
+    let lines = text
+        .lines()
+        .map(|line| {
+            (line.chars().count() as f32 / chars_per_line)
+                .ceil()
+                .max(1.0)
+        })
+        .sum::<f32>()
+        .max(1.0);
+
+    lines * line_height(size)
+}
+
+/// Estimates the height of a message row, including the separators and sender shown before it.
+fn row_height(
+    previous: Option<&IcyMessage>,
+    message: &IcyMessage,
+    thumbnail_cache: &ThumbnailCache,
+    width: f32,
+) -> f32 {
+    let mut height = 0.0;
+
+    let day_changed = day_changed(previous, message);
+    if day_changed {
+        height += line_height(DATE_SEPERATOR_SIZE) + (SPACING * 2) as f32;
+    }
+    if starts_group(previous, message) {
+        height += (PADDING + SPACING * 2) as f32 + (AVATAR_WIDTH + SPACING) as f32;
+    } else if day_changed {
+        height += (PADDING + SPACING * 2) as f32;
+    } else {
+        height += SPACING as f32;
+    }
+
+    let mut parts = Vec::with_capacity(4);
+    if message.reply_to.is_some() {
+        parts.push(line_height(REPLY_SIZE));
+    }
+    // The edited marker is next to the text, or on its own line if there is no text
+    if message.edited_at.is_some() && !matches!(message.content, IcyContent::Text(_)) {
+        parts.push(line_height(MESSAGE_TIMESTAMP_SIZE));
+    }
+    if let Some(text) = message.being_edited.as_deref().or_else(|| {
+        if let IcyContent::Text(text) = &message.content {
+            Some(text)
+        } else {
+            None
+        }
+    }) {
+        parts.push(text_height(text, MESSAGE_SIZE, width));
+    }
+    match &message.content {
+        IcyContent::Embeds(embed) => {
+            let heading = |h: &Option<EmbedHeading>| {
+                h.as_ref().map_or(0.0, |_| {
+                    line_height(DEF_SIZE + 2) + line_height(DEF_SIZE - 6) + (SPACING / 2) as f32
+                })
+            };
+            let inner_width = width - PADDING as f32;
+            let fields = embed
+                .fields
+                .iter()
+                .map(|field| {
+                    text_height(&field.title, DEF_SIZE - 1, inner_width)
+                        + text_height(&field.subtitle, DEF_SIZE - 3, inner_width)
+                        + text_height(&field.body, DEF_SIZE - 3, inner_width)
+                        + (PADDING / 2 + SPACING / 2) as f32
+                })
+                .sum::<f32>();
+            parts.push(
+                heading(&embed.header)
+                    + text_height(&embed.title, DEF_SIZE + 2, inner_width)
+                    + text_height(&embed.body, DEF_SIZE - 2, inner_width)
+                    + fields
+                    + heading(&embed.footer)
+                    + (PADDING + SPACING / 2 * (embed.fields.len() as u16 + 2)) as f32,
+            );
+        }
+        IcyContent::Files(attachments) => {
+            if let Some(attachment) = attachments.first() {
+                let button_height = match thumbnail_height(thumbnail_cache, &attachment.id) {
+                    Some(image_height) => line_height(DEF_SIZE - 4) + SPACING as f32 + image_height,
+                    None => line_height(DEF_SIZE),
+                };
+                parts.push(button_height + PADDING as f32);
+                if !attachment.caption.is_empty() {
+                    parts.push(text_height(&attachment.caption, MESSAGE_SIZE, width));
+                }
+            }
+        }
+        IcyContent::Text(_) => {}
+    }
+
+    let body_height = parts.iter().sum::<f32>()
+        + (MSG_LR_PADDING as usize * parts.len().saturating_sub(1)) as f32
+        + 2.0;
+
+    height + body_height.max(line_height(MESSAGE_TIMESTAMP_SIZE))
+}
+
+/// Creates a one line summary of a message, with its sender and the start of its content.
+pub fn message_summary(message: &IcyMessage, members: &Members) -> String {
+    let name = message.overrides.as_ref().map_or_else(
+        || {
+            members
+                .get(&message.sender)
+                .map_or_else(|| "unknown".to_string(), |member| member.username.clone())
+        },
+        |overrides| overrides.name.clone(),
+    );
+    let preview = match &message.content {
+        IcyContent::Text(text) => {
+            let first_line = text.lines().next().unwrap_or_default();
+            let mut preview = first_line.chars().take(PREVIEW_CHARS).collect::<String>();
+            if preview.len() < text.len() {
+                preview.push('…');
+            }
+            preview
+        }
+        IcyContent::Files(_) => "[file]".to_string(),
+        IcyContent::Embeds(_) => "[embed]".to_string(),
+    };
+
+    format!("{}: {}", name, preview)
+}
+
+/// Creates the line shown above a reply, naming who is replied to and the start of their message.
+pub fn reply_preview(channel: &Channel, members: &Members, reply_to: u64) -> String {
+    channel
+        .messages
+        .iter()
+        .rev()
+        .find(|message| message.id.id() == Some(reply_to))
+        .map_or_else(
+            || "↪ Replying to an older message".to_string(),
+            |replied| format!("↪ Replying to {}", message_summary(replied, members)),
+        )
+}
+
+/// Creates empty space of any height, since a single `Space` can't be taller than `u16::MAX`.
+fn spacer<'a>(height: f32) -> Element<'a, Message> {
+    let mut height = height.max(0.0) as u32;
+    let mut spaces = Vec::with_capacity(1);
+    while height > 0 {
+        let chunk = height.min(u16::MAX as u32);
+        spaces.push(space!(h = chunk as u16).into());
+        height -= chunk;
+    }
+
+    Column::with_children(spaces).into()
+}
 
 #[allow(clippy::mutable_key_type)]
 #[allow(clippy::clippy::too_many_arguments)]
@@ -29,11 +462,11 @@ pub fn build_event_history<'a>(
     channel: &Channel,
     members: &Members,
     current_user_id: u64,
-    looking_at_message: usize,
+    layout: &HistoryLayout,
+    view: HistoryView,
+    viewport_height: f32,
     scrollable_state: &'a mut scrollable::State,
-    content_open_buttons: &'a mut [button::State; SHOWN_MSGS_LIMIT],
-    embed_buttons: &'a mut [(button::State, button::State); SHOWN_MSGS_LIMIT],
-    edit_buts_sate: &'a mut [button::State; SHOWN_MSGS_LIMIT],
+    buttons_state: &'a mut AHashMap<MessageId, MessageButtonsState>,
     mode: Mode,
     theme: Theme,
 ) -> Element<'a, Message> {
@@ -52,30 +485,48 @@ pub fn build_event_history<'a>(
         .spacing(SPACING * 2)
         .padding(PADDING);
 
-    let displayable_events = &channel.messages;
-    let timeline_range_end = looking_at_message
-        .saturating_add(SHOWN_MSGS_LIMIT)
-        .min(displayable_events.len());
-    let timeline_range_start = timeline_range_end.saturating_sub(SHOWN_MSGS_LIMIT);
-    let displayable_events = &displayable_events[timeline_range_start..timeline_range_end];
-
-    let mut last_timestamp = if let Some(ev) = displayable_events.first() {
-        ev.timestamp
-    } else {
-        return event_history.into();
+    let offset = layout.offset_of(view, viewport_height);
+    let visible = layout.visible_range(offset, viewport_height);
+    let displayable_events = match channel.messages.get(visible.clone()) {
+        Some(events) if !events.is_empty() => events,
+        _ => return event_history.into(),
     };
-    let mut last_sender_id = None;
-    let mut last_sender_name = None;
+    // States stay with their message as others scroll in and out of view
+    let visible_ids = displayable_events
+        .iter()
+        .map(|message| message.id)
+        .collect::<AHashSet<_>>();
+    buttons_state.retain(|id, _| visible_ids.contains(id));
+    for id in visible_ids {
+        buttons_state.entry(id).or_default();
+    }
+    let mut buttons_state = buttons_state
+        .iter_mut()
+        .map(|(id, state)| (*id, state))
+        .collect::<AHashMap<_, _>>();
+
+    // Keep the space taken by the messages that aren't built, so the scrollbar stays put
+    event_history = event_history.push(spacer(layout.tops[visible.start]));
+
+    let mut previous = visible
+        .start
+        .checked_sub(1)
+        .map(|index| &channel.messages[index]);
     let mut message_group = vec![];
 
-    for (((message, media_open_button_state), (h_embed_but, f_embed_but)), edit_but_state) in
-        displayable_events
-            .iter()
-            .zip(content_open_buttons.iter_mut())
-            .zip(embed_buttons.iter_mut())
-            .zip(edit_buts_sate.iter_mut())
-    {
-        let id_to_use = if !message.id.is_ack() {
+    for message in displayable_events {
+        let buttons = match buttons_state.remove(&message.id) {
+            Some(buttons) => buttons,
+            None => continue,
+        };
+        let MessageButtonsState {
+            content_open: media_open_button_state,
+            embed: (h_embed_but, f_embed_but),
+            edit: edit_but_state,
+            sender: sender_but_state,
+        } = buttons;
+
+        let id_to_use = if message.id.transaction_id().is_some() {
             current_user_id
         } else {
             message.sender
@@ -114,7 +565,7 @@ pub fn build_event_history<'a>(
                 .map(|m| m.avatar_url.as_ref())
                 .flatten()
         };
-        let sender_body_creator = |sender_display_name: &str| {
+        let sender_body_creator = |sender_display_name: &str, state: &'a mut button::State| {
             let mut widgets = Vec::with_capacity(2);
 
             if let Some(handle) = sender_avatar_url
@@ -126,12 +577,18 @@ pub fn build_event_history<'a>(
                 widgets.push(Image::new(handle).width(length!(= AVATAR_WIDTH)).into());
             }
 
-            widgets.push(
+            let mut sender_but = Button::new(
+                state,
                 label!("[{}]", sender_display_name)
                     .color(sender_color)
-                    .size(MESSAGE_SENDER_SIZE)
-                    .into(),
-            );
+                    .size(MESSAGE_SENDER_SIZE),
+            )
+            .padding(0)
+            .style(theme.secondary());
+            if members.contains_key(&id_to_use) {
+                sender_but = sender_but.on_press(Message::SelectedMember(id_to_use));
+            }
+            widgets.push(sender_but.into());
 
             if let Some(reason) = &override_reason {
                 widgets.push(
@@ -149,21 +606,16 @@ pub fn build_event_history<'a>(
                 .padding(0)
         };
 
-        let is_sender_different = last_sender_id.as_ref() != Some(&id_to_use)
-            || last_sender_name.as_ref() != Some(&sender_display_name);
-        if is_sender_different {
-            if !message_group.is_empty() {
-                event_history = event_history.push(
-                    Container::new(
-                        column(message_group.drain(..).collect()).align_items(align!(|<)),
-                    )
+        let is_new_group = starts_group(previous, message);
+        let is_day_changed = day_changed(previous, message);
+        if (is_new_group || is_day_changed) && !message_group.is_empty() {
+            event_history = event_history.push(
+                Container::new(column(message_group.drain(..).collect()).align_items(align!(|<)))
                     .style(theme.round()),
-                );
-            }
-            message_group.push(sender_body_creator(&sender_display_name).into());
+            );
         }
 
-        if message.timestamp.day() != last_timestamp.day() {
+        if is_day_changed {
             let date_time_seperator = fill_container(
                 label!(message.timestamp.format("[%d %B %Y]").to_string())
                     .size(DATE_SEPERATOR_SIZE)
@@ -174,19 +626,20 @@ pub fn build_event_history<'a>(
             event_history = event_history.push(date_time_seperator);
         }
 
-        if !is_sender_different
-            && !message_group.is_empty()
-            && last_timestamp.signed_duration_since(message.timestamp)
-                > chrono::Duration::minutes(5)
-        {
-            event_history = event_history.push(
-                Container::new(column(message_group.drain(..).collect()).align_items(align!(|<)))
-                    .style(theme.round()),
-            );
-            message_group.push(sender_body_creator(&sender_display_name).into());
+        if is_new_group {
+            message_group.push(sender_body_creator(&sender_display_name, sender_but_state).into());
         }
 
-        let mut message_body_widgets = Vec::with_capacity(2);
+        let mut message_body_widgets = Vec::with_capacity(3);
+
+        if let Some(reply_to) = message.reply_to {
+            message_body_widgets.push(
+                label!(reply_preview(channel, members, reply_to))
+                    .size(REPLY_SIZE)
+                    .color(ALT_COLOR)
+                    .into(),
+            );
+        }
 
         let msg_text = message.being_edited.as_deref().or_else(|| {
             if let IcyContent::Text(text) = &message.content {
@@ -196,6 +649,18 @@ pub fn build_event_history<'a>(
             }
         });
 
+        let mut edited_marker = message.edited_at.map(|edited_at| {
+            Tooltip::new(
+                label!("(edited)")
+                    .size(MESSAGE_TIMESTAMP_SIZE)
+                    .color(ALT_COLOR),
+                edited_at.format("Edited %d %B %Y, %H:%M").to_string(),
+                Position::Top,
+            )
+            .gap(4)
+            .style(theme.secondary())
+        });
+
         if let Some(text) = msg_text {
             let mut message_text = label!(text).size(MESSAGE_SIZE);
 
@@ -205,7 +670,15 @@ pub fn build_event_history<'a>(
                 message_text = message_text.color(ERROR_COLOR);
             }
 
-            message_body_widgets.push(message_text.into());
+            match edited_marker.take() {
+                Some(marker) => message_body_widgets.push(
+                    row(vec![message_text.into(), marker.into()])
+                        .align_items(align!(|<))
+                        .padding(0)
+                        .into(),
+                ),
+                None => message_body_widgets.push(message_text.into()),
+            }
         }
 
         if let IcyContent::Embeds(embeds) = &message.content {
@@ -293,7 +766,12 @@ pub fn build_event_history<'a>(
                         .spacing(SPACING / 2)
                         .align_items(Align::Start),
                 )
-                .style(theme.round().secondary().with_border_color(embeds.color))
+                .style(
+                    theme
+                        .round()
+                        .secondary()
+                        .with_border_color(embeds.color.into()),
+                )
                 .into(),
             );
         }
@@ -316,7 +794,7 @@ pub fn build_event_history<'a>(
                         .into()
                 }
 
-                let is_thumbnail = matches!(attachment.kind.split('/').next(), Some("image"));
+                let is_thumbnail = attachment.is_image();
                 let does_content_exist = content_store.content_exists(&attachment.id);
 
                 if let Some(thumbnail_image) = thumbnail_cache
@@ -368,18 +846,31 @@ pub fn build_event_history<'a>(
                         theme,
                     ));
                 }
+
+                if !attachment.caption.is_empty() {
+                    let mut caption = label!(&attachment.caption).size(MESSAGE_SIZE);
+                    if mode == message.id.id().map_or(Mode::Normal, Mode::EditingMessage) {
+                        caption = caption.color(ERROR_COLOR);
+                    }
+                    message_body_widgets.push(caption.into());
+                }
             }
         }
 
+        if let Some(marker) = edited_marker {
+            message_body_widgets.push(marker.into());
+        }
+
         let msg_body = column(message_body_widgets)
             .align_items(align!(|<))
             .padding(0)
             .spacing(MSG_LR_PADDING);
         let mut message_row = Vec::with_capacity(2);
 
-        let maybe_timestamp = if is_sender_different
-            || last_timestamp.minute() != message.timestamp.minute()
-        {
+        let maybe_timestamp = if is_new_group
+            || previous.map_or(true, |previous| {
+                previous.timestamp.minute() != message.timestamp.minute()
+            }) {
             let message_timestamp = message.timestamp.format("%H:%M").to_string();
 
             let timestamp_label = label!(message_timestamp)
@@ -399,8 +890,10 @@ pub fn build_event_history<'a>(
         let mut but = Button::new(edit_but_state, msg_body)
             .padding(1)
             .style(theme.embed());
-        if msg_text.is_some() && current_user_id == message.sender && Mode::EditMessage == mode {
-            if let Some(id) = message.id.id() {
+        if let Some(id) = message.id.id() {
+            if Mode::EditMessage != mode {
+                but = but.on_press(Message::OpenMessageMenu(id));
+            } else if current_user_id == message.sender {
                 but = but.on_press(Message::ChangeMode(Mode::EditingMessage(id)));
             }
         }
@@ -408,9 +901,7 @@ pub fn build_event_history<'a>(
 
         message_group.push(row(message_row).align_items(align!(|<)).padding(0).into());
 
-        last_sender_id = Some(id_to_use);
-        last_sender_name = Some(sender_display_name);
-        last_timestamp = message.timestamp;
+        previous = Some(message);
     }
     if !message_group.is_empty() {
         event_history = event_history.push(
@@ -418,5 +909,7 @@ pub fn build_event_history<'a>(
                 .style(theme.round()),
         );
     }
+    event_history = event_history.push(spacer(layout.total_height() - layout.tops[visible.end]));
+
     event_history.into()
 }
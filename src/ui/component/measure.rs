@@ -0,0 +1,108 @@
+//! Finding out how big a widget was laid out to be.
+
+use iced_native::{
+    event, layout, overlay, Clipboard, Element, Event, Hasher, Layout, Length, Point, Rectangle,
+    Size, Widget,
+};
+
+/// Tells how big its content is, whenever that changes.
+///
+/// The size is only known once the content has been laid out, so it's sent with the first event
+/// that reaches the content after it changed, like the cursor moving.
+pub struct Measured<'a, Message, Renderer> {
+    content: Element<'a, Message, Renderer>,
+    /// The size the content was last known to have.
+    size: Option<Size>,
+    on_resize: fn(Size) -> Message,
+}
+
+impl<'a, Message, Renderer> Measured<'a, Message, Renderer> {
+    pub fn new(
+        content: impl Into<Element<'a, Message, Renderer>>,
+        size: Option<Size>,
+        on_resize: fn(Size) -> Message,
+    ) -> Self {
+        Self {
+            content: content.into(),
+            size,
+            on_resize,
+        }
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for Measured<'a, Message, Renderer>
+where
+    Renderer: iced_native::Renderer,
+{
+    fn width(&self) -> Length {
+        self.content.width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        self.content.layout(renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        defaults: &Renderer::Defaults,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) -> Renderer::Output {
+        self.content
+            .draw(renderer, defaults, layout, cursor_position, viewport)
+    }
+
+    fn hash_layout(&self, state: &mut Hasher) {
+        use std::hash::Hash;
+
+        struct Marker;
+        std::any::TypeId::of::<Marker>().hash(state);
+        self.content.hash_layout(state);
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        messages: &mut Vec<Message>,
+    ) -> event::Status {
+        let bounds = layout.bounds();
+        let size = Size::new(bounds.width, bounds.height);
+        if self.size != Some(size) {
+            self.size = Some(size);
+            messages.push((self.on_resize)(size));
+        }
+
+        self.content.on_event(
+            event,
+            layout,
+            cursor_position,
+            renderer,
+            clipboard,
+            messages,
+        )
+    }
+
+    fn overlay(&mut self, layout: Layout<'_>) -> Option<overlay::Element<'_, Message, Renderer>> {
+        self.content.overlay(layout)
+    }
+}
+
+impl<'a, Message, Renderer> From<Measured<'a, Message, Renderer>> for Element<'a, Message, Renderer>
+where
+    Message: 'a,
+    Renderer: iced_native::Renderer + 'a,
+{
+    fn from(measured: Measured<'a, Message, Renderer>) -> Self {
+        Element::new(measured)
+    }
+}
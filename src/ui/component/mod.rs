@@ -1,14 +1,21 @@
 pub mod chan_guild_list;
+pub mod drag;
 pub mod event_history;
+pub mod measure;
+pub mod pinned_list;
 
 use crate::length;
 pub use crate::{align, color, label};
-pub use chan_guild_list::build_channel_list;
+pub use chan_guild_list::{
+    build_channel_list, ChannelButtonsState, ChannelDrag, ChannelListAction,
+};
 pub use event_history::build_event_history;
 pub use iced::{
     button, pick_list, scrollable, text_input, Align, Button, Color, Column, Command, Container,
-    Element, Image, Length, PickList, Row, Scrollable, Space, Subscription, Text, TextInput,
+    Element, Image, Length, PickList, Row, Scrollable, Size, Space, Subscription, Text, TextInput,
 };
+pub use measure::Measured;
+pub use pinned_list::build_pinned_list;
 
 use super::style::{PADDING, SPACING};
 
@@ -0,0 +1,64 @@
+use crate::{
+    client::{channel::Channel, member::Members},
+    label,
+    ui::{
+        component::{event_history::message_summary, *},
+        style::{Theme, ALT_COLOR, DEF_SIZE, PADDING, SPACING},
+    },
+};
+
+/// Builds the list of pinned messages of a channel, newest pin first.
+///
+/// Pressing a pinned message calls `on_jump` with its ID, even if it's older than the loaded
+/// history.
+pub fn build_pinned_list<'a, Message: Clone + 'a>(
+    channel: &Channel,
+    members: &Members,
+    state: &'a mut scrollable::State,
+    buttons_state: &'a mut Vec<button::State>,
+    on_jump: fn(u64) -> Message,
+    theme: Theme,
+) -> Element<'a, Message> {
+    let mut pinned_list = Scrollable::new(state)
+        .style(theme)
+        .align_items(align!(|<))
+        .height(length!(+))
+        .spacing(SPACING)
+        .padding(PADDING / 2)
+        .push(label!("Pinned messages").size(DEF_SIZE + 2));
+
+    let pinned = match &channel.pinned {
+        Some(pinned) if pinned.is_empty() => {
+            return pinned_list
+                .push(label!("Nothing is pinned here").color(ALT_COLOR))
+                .into()
+        }
+        Some(pinned) => pinned,
+        None => {
+            return pinned_list
+                .push(label!("Loading...").color(ALT_COLOR))
+                .into()
+        }
+    };
+    buttons_state.resize_with(pinned.len(), Default::default);
+
+    for (message_id, button_state) in pinned.iter().rev().zip(buttons_state.iter_mut()) {
+        let message = channel
+            .messages
+            .iter()
+            .find(|message| message.id.id() == Some(*message_id));
+        let content = match message {
+            Some(message) => label!(message_summary(message, members)),
+            // Jumping to it loads it, with the history up to it
+            None => label!("Older message {}", message_id).color(ALT_COLOR),
+        };
+        let button = Button::new(button_state, content.size(DEF_SIZE - 2))
+            .on_press(on_jump(*message_id))
+            .width(length!(+))
+            .style(theme.secondary());
+
+        pinned_list = pinned_list.push(button);
+    }
+
+    pinned_list.into()
+}
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path, time::Duration};
+
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 60 * 5;
+
+/// User configuration, read from the config file on startup.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// Seconds without input after which the user is marked as idle. `0` disables it.
+    pub idle_timeout_secs: u64,
+    /// Key bindings that override the defaults, mapping action names to bindings like `"Ctrl+K"`.
+    pub keybindings: HashMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            idle_timeout_secs: DEFAULT_IDLE_TIMEOUT_SECS,
+            keybindings: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config from the given path, falling back to the defaults if it's missing or invalid.
+    pub fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(raw) => toml::from_str(&raw).unwrap_or_else(|err| {
+                tracing::error!("could not parse config file, using defaults: {}", err);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        (self.idle_timeout_secs > 0).then(|| Duration::from_secs(self.idle_timeout_secs))
+    }
+}
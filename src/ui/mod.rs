@@ -1,3 +1,6 @@
+pub mod client;
 pub mod component;
+pub mod config;
 pub mod screen;
 pub mod style;
+pub mod thumbnail;
@@ -1,7 +1,4 @@
-use harmony_rust_sdk::{
-    api::chat::InviteId,
-    client::api::chat::{guild::AddGuildToGuildListRequest, *},
-};
+use harmony_rust_sdk::api::chat::InviteId;
 
 use super::{Message as TopLevelMessage, Screen as TopLevelScreen};
 
@@ -171,24 +168,10 @@ impl GuildDiscovery {
                 self.joined_guild = None;
                 self.joining_guild = Some(guild_name.clone());
                 self.error_text.clear();
-                let inner = client.inner().clone();
+                let requests = client.requests().clone();
 
                 return Command::perform(
-                    async move {
-                        let guild_id =
-                            guild::create_guild(&inner, guild::CreateGuild::new(guild_name))
-                                .await?
-                                .guild_id;
-                        guild::add_guild_to_guild_list(
-                            &inner,
-                            AddGuildToGuildListRequest {
-                                guild_id,
-                                homeserver: inner.homeserver_url().to_string(),
-                            },
-                        )
-                        .await?;
-                        Ok(guild_id)
-                    },
+                    async move { requests.create_guild(guild_name).await },
                     |result| {
                         result.map_or_else(
                             |e| TopLevelMessage::Error(Box::new(e)),
@@ -203,21 +186,10 @@ impl GuildDiscovery {
                 self.joined_guild = None;
                 self.joining_guild = Some(invite.to_string());
                 self.error_text.clear();
-                let inner = client.inner().clone();
+                let requests = client.requests().clone();
 
                 return Command::perform(
-                    async move {
-                        let guild_id = guild::join_guild(&inner, invite).await?.guild_id;
-                        guild::add_guild_to_guild_list(
-                            &inner,
-                            AddGuildToGuildListRequest {
-                                guild_id,
-                                homeserver: inner.homeserver_url().to_string(),
-                            },
-                        )
-                        .await?;
-                        Ok(guild_id)
-                    },
+                    async move { requests.join_guild(invite).await },
                     |result| {
                         result.map_or_else(
                             |e| TopLevelMessage::Error(Box::new(e)),
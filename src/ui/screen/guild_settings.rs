@@ -1,34 +1,162 @@
+mod bans;
 mod general;
+mod invites;
+mod roles;
 
 use iced::{Command, Element};
+use iced_aw::{TabLabel, Tabs};
 
+use super::{Message as TopLevelMessage, Screen as TopLevelScreen};
 use crate::{
-    client::{error::ClientError, Client},
-    ui::{component::*, style::*},
+    client::{error::ClientError, invite::Invite, Client},
+    label, label_button, length, space,
+    ui::{component::*, style::*, thumbnail::ThumbnailCache},
 };
 
-#[derive(Debug)]
-pub enum Message {}
+use bans::Bans;
+use general::General;
+use invites::Invites;
+use roles::Roles;
+
+const ROLES_TAB: usize = 1;
+pub const INVITES_TAB: usize = 2;
+pub const BANS_TAB: usize = 3;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    TabSelected(usize),
+    General(general::Message),
+    Roles(roles::Message),
+    Invites(invites::Message),
+    Bans(bans::Message),
+    GoBack,
+}
 
 #[derive(Debug)]
 pub struct GuildSettings {
     guild_id: u64,
+    current_tab: usize,
+    general_tab: General,
+    roles_tab: Roles,
+    invites_tab: Invites,
+    bans_tab: Bans,
+    back_but_state: button::State,
 }
 
 impl GuildSettings {
     pub fn new(guild_id: u64) -> Self {
-        Self { guild_id }
+        Self {
+            guild_id,
+            current_tab: 0,
+            general_tab: General::new(guild_id),
+            roles_tab: Roles::new(guild_id),
+            invites_tab: Invites::new(guild_id),
+            bans_tab: Bans::new(guild_id),
+            back_but_state: Default::default(),
+        }
     }
 
-    pub fn view(&mut self, theme: Theme, client: &Client) -> Element<Message> {
-        label!("asd").into()
+    /// Creates the settings screen opened on the invites tab, with the already fetched invites.
+    pub fn with_invites(guild_id: u64, invites: Vec<Invite>) -> Self {
+        let mut settings = Self::new(guild_id);
+        settings.current_tab = INVITES_TAB;
+        settings.invites_tab.set_invites(invites);
+        settings
     }
 
-    pub fn update(&mut self, msg: Message, client: &Client) -> Command<super::Message> {
-        Command::none()
+    /// Creates the settings screen opened on the bans tab, with the already fetched bans.
+    pub fn with_bans(guild_id: u64, banned_users: Vec<u64>) -> Self {
+        let mut settings = Self::new(guild_id);
+        settings.current_tab = BANS_TAB;
+        settings.bans_tab.set_banned_users(banned_users);
+        settings
     }
 
-    pub fn on_error(&mut self, error: ClientError) -> Command<super::Message> {
+    pub fn view<'a>(
+        &'a mut self,
+        theme: Theme,
+        client: &'a Client,
+        thumbnail_cache: &'a ThumbnailCache,
+    ) -> Element<'a, Message> {
+        let guild_name = client
+            .guilds
+            .get(&self.guild_id)
+            .map_or("Unknown guild", |guild| guild.name.as_str());
+
+        let tabs = Tabs::new(self.current_tab, Message::TabSelected)
+            .push(
+                TabLabel::Text("General".to_string()),
+                self.general_tab
+                    .view(theme, client, thumbnail_cache)
+                    .map(Message::General),
+            )
+            .push(
+                TabLabel::Text("Roles".to_string()),
+                self.roles_tab.view(theme, client).map(Message::Roles),
+            )
+            .push(
+                TabLabel::Text("Invites".to_string()),
+                self.invites_tab.view(theme).map(Message::Invites),
+            )
+            .push(
+                TabLabel::Text("Bans".to_string()),
+                self.bans_tab.view(theme, client).map(Message::Bans),
+            )
+            .tab_bar_style(theme)
+            .width(length!(+))
+            .height(length!(+));
+
+        let header = row(vec![
+            label_button!(&mut self.back_but_state, "Back")
+                .on_press(Message::GoBack)
+                .style(theme)
+                .width(length!(= 80))
+                .into(),
+            space!(w = PADDING).into(),
+            label!("{} settings", guild_name).size(DEF_SIZE + 6).into(),
+        ]);
+
+        fill_container(
+            Column::with_children(vec![header.into(), tabs.into()])
+                .width(length!(+))
+                .height(length!(+)),
+        )
+        .style(theme)
+        .into()
+    }
+
+    pub fn update(
+        &mut self,
+        msg: Message,
+        client: &mut Client,
+        clip: &mut dyn iced_native::Clipboard,
+    ) -> Command<TopLevelMessage> {
+        match msg {
+            Message::TabSelected(tab) => {
+                self.current_tab = tab;
+                match tab {
+                    ROLES_TAB => return self.roles_tab.load(client),
+                    INVITES_TAB => return self.invites_tab.load(client),
+                    BANS_TAB => return self.bans_tab.load(client),
+                    _ => {}
+                }
+            }
+            Message::General(msg) => return self.general_tab.update(msg, client),
+            Message::Roles(msg) => return self.roles_tab.update(msg, client),
+            Message::Invites(msg) => return self.invites_tab.update(msg, client, clip),
+            Message::Bans(msg) => return self.bans_tab.update(msg, client),
+            Message::GoBack => return TopLevelScreen::pop_screen_cmd(),
+        }
+
         Command::none()
     }
+
+    pub fn on_error(&mut self, error: ClientError) -> Command<TopLevelMessage> {
+        match self.current_tab {
+            ROLES_TAB => self.roles_tab.on_error(error),
+            INVITES_TAB => self.invites_tab.on_error(error),
+            BANS_TAB => self.bans_tab.on_error(error),
+            _ => self.general_tab.on_error(error),
+        }
+    }
 }
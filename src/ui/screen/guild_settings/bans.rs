@@ -0,0 +1,156 @@
+use ahash::AHashSet;
+use iced::{Command, Element};
+
+use super::Message as ParentMessage;
+use crate::{
+    client::{error::ClientError, Client},
+    label, label_button, length,
+    ui::{component::*, screen::Message as TopLevelMessage, style::*},
+};
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Loaded(Vec<u64>),
+    Unban(u64),
+    Unbanned(u64),
+    UnbanFailed { user_id: u64, err: ClientError },
+}
+
+#[derive(Debug, Default)]
+pub struct Bans {
+    guild_id: u64,
+    banned_users: Vec<(u64, button::State)>,
+    /// Users who are still banned until the server confirms their unban.
+    unbanning: AHashSet<u64>,
+    loading: bool,
+    ban_list_state: scrollable::State,
+    status_text: String,
+    error_text: String,
+}
+
+impl Bans {
+    pub fn new(guild_id: u64) -> Self {
+        Self {
+            guild_id,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_banned_users(&mut self, banned_users: Vec<u64>) {
+        self.loading = false;
+        self.banned_users = banned_users
+            .into_iter()
+            .map(|user_id| (user_id, Default::default()))
+            .collect();
+    }
+
+    pub fn load(&mut self, client: &Client) -> Command<TopLevelMessage> {
+        self.loading = true;
+        let requests = client.requests().clone();
+        let guild_id = self.guild_id;
+
+        Command::perform(
+            async move { requests.banned_users(guild_id).await },
+            |result| {
+                result.map_or_else(
+                    |err| TopLevelMessage::Error(Box::new(err)),
+                    |banned| {
+                        TopLevelMessage::GuildSettings(ParentMessage::Bans(Message::Loaded(banned)))
+                    },
+                )
+            },
+        )
+    }
+
+    pub fn view<'a>(&'a mut self, theme: Theme, client: &'a Client) -> Element<'a, Message> {
+        let mut ban_list = Scrollable::new(&mut self.ban_list_state)
+            .style(theme)
+            .align_items(align!(|<))
+            .width(length!(+))
+            .height(length!(+))
+            .spacing(SPACING)
+            .padding(PADDING / 2);
+        if self.loading {
+            ban_list = ban_list.push(label!("Loading bans...").color(ALT_COLOR));
+        } else if self.banned_users.is_empty() {
+            ban_list = ban_list.push(label!("Nobody is banned from this guild.").color(ALT_COLOR));
+        }
+        for (user_id, unban_but_state) in &mut self.banned_users {
+            let name = client.members.get(user_id).map_or_else(
+                || "Unknown user".to_string(),
+                |member| member.username.clone(),
+            );
+            let unban_but = if self.unbanning.contains(user_id) {
+                label_button!(unban_but_state, "Unbanning...")
+            } else {
+                label_button!(unban_but_state, "Unban").on_press(Message::Unban(*user_id))
+            };
+            ban_list = ban_list.push(row(vec![
+                label!(name).width(length!(= 250)).into(),
+                label!(user_id.to_string())
+                    .color(ALT_COLOR)
+                    .width(length!(= 200))
+                    .into(),
+                unban_but.style(theme).into(),
+            ]));
+        }
+
+        let mut widgets = Vec::with_capacity(2);
+        if !self.error_text.is_empty() {
+            widgets.push(label!(&self.error_text).color(ERROR_COLOR).into());
+        } else if !self.status_text.is_empty() {
+            widgets.push(label!(&self.status_text).color(SUCCESS_COLOR).into());
+        }
+        widgets.push(ban_list.into());
+
+        column(widgets)
+            .align_items(align!(|<))
+            .width(length!(+))
+            .height(length!(+))
+            .into()
+    }
+
+    pub fn update(&mut self, msg: Message, client: &Client) -> Command<TopLevelMessage> {
+        match msg {
+            Message::Loaded(banned_users) => self.set_banned_users(banned_users),
+            Message::Unban(user_id) => {
+                self.unbanning.insert(user_id);
+                let requests = client.requests().clone();
+                let guild_id = self.guild_id;
+
+                return Command::perform(
+                    async move { requests.unban_user(guild_id, user_id).await },
+                    move |result| {
+                        let msg = match result {
+                            Ok(()) => Message::Unbanned(user_id),
+                            Err(err) => Message::UnbanFailed { user_id, err },
+                        };
+                        TopLevelMessage::GuildSettings(ParentMessage::Bans(msg))
+                    },
+                );
+            }
+            Message::Unbanned(user_id) => {
+                self.unbanning.remove(&user_id);
+                self.banned_users.retain(|(id, _)| *id != user_id);
+                self.error_text.clear();
+                self.status_text = format!("Unbanned {}.", user_id);
+            }
+            // The user stays in the list, so unbanning them can be tried again
+            Message::UnbanFailed { user_id, err } => {
+                self.unbanning.remove(&user_id);
+                self.status_text.clear();
+                self.error_text = format!("Couldn't unban {}: {}", user_id, err);
+            }
+        }
+
+        Command::none()
+    }
+
+    pub fn on_error(&mut self, error: ClientError) -> Command<TopLevelMessage> {
+        self.loading = false;
+        self.status_text.clear();
+        self.error_text = error.to_string();
+
+        Command::none()
+    }
+}
@@ -1,29 +1,287 @@
 use iced::{Command, Element};
 
+use super::{super::select_upload_files, Message as ParentMessage};
 use crate::{
     client::{error::ClientError, Client},
-    ui::{component::*, style::*},
+    label, label_button, length, space,
+    ui::{component::*, screen::Message as TopLevelMessage, style::*, thumbnail::ThumbnailCache},
 };
 
-#[derive(Debug)]
-pub enum Message {}
+/// A destructive action that needs to be confirmed before it's done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirm {
+    Delete,
+    Leave,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    NameChanged(String),
+    Rename,
+    UploadPicture,
+    AskConfirm(Confirm),
+    Confirmed(bool),
+    Done(String),
+}
 
 #[derive(Debug)]
 pub struct General {
     guild_id: u64,
     name_edit_state: text_input::State,
+    name_field: String,
+    rename_but_state: button::State,
+    picture_but_state: button::State,
+    delete_but_state: button::State,
+    leave_but_state: button::State,
+    confirm_yes_but_state: button::State,
+    confirm_no_but_state: button::State,
+    confirming: Option<Confirm>,
+    waiting: bool,
+    status_text: String,
+    error_text: String,
 }
 
 impl General {
-    pub fn view(&mut self, theme: Theme, client: &Client) -> Element<Message> {
-        label!("asd").into()
+    pub fn new(guild_id: u64) -> Self {
+        Self {
+            guild_id,
+            name_edit_state: Default::default(),
+            name_field: Default::default(),
+            rename_but_state: Default::default(),
+            picture_but_state: Default::default(),
+            delete_but_state: Default::default(),
+            leave_but_state: Default::default(),
+            confirm_yes_but_state: Default::default(),
+            confirm_no_but_state: Default::default(),
+            confirming: None,
+            waiting: false,
+            status_text: Default::default(),
+            error_text: Default::default(),
+        }
     }
 
-    pub fn update(&mut self, msg: Message, client: &Client) -> Command<super::Message> {
+    pub fn view<'a>(
+        &'a mut self,
+        theme: Theme,
+        client: &'a Client,
+        thumbnail_cache: &'a ThumbnailCache,
+    ) -> Element<'a, Message> {
+        let guild = match client.guilds.get(&self.guild_id) {
+            Some(guild) => guild,
+            None => return fill_container(label!("This guild is not available anymore.")).into(),
+        };
+
+        if let Some(confirm) = self.confirming {
+            let text = match confirm {
+                Confirm::Delete => format!("Do you want to delete {}?", guild.name),
+                Confirm::Leave => format!("Do you want to leave {}?", guild.name),
+            };
+            let make_button = |state, confirm| {
+                let text = if confirm { "Yes" } else { "No" };
+
+                label_button!(state, text)
+                    .style(theme)
+                    .on_press(Message::Confirmed(confirm))
+                    .width(length!(= 80))
+            };
+
+            return fill_container(
+                column(vec![
+                    label!(text).size(DEF_SIZE + 2).into(),
+                    label!("This can't be undone.")
+                        .color(ERROR_COLOR)
+                        .size(DEF_SIZE + 2)
+                        .into(),
+                    row(vec![
+                        make_button(&mut self.confirm_yes_but_state, true).into(),
+                        space!(w = 200).into(),
+                        make_button(&mut self.confirm_no_but_state, false).into(),
+                    ])
+                    .into(),
+                ])
+                .spacing(12),
+            )
+            .into();
+        }
+
+        let picture: Element<Message> = if let Some(handle) = guild
+            .picture
+            .as_ref()
+            .map(|id| thumbnail_cache.get_thumbnail(id))
+            .flatten()
+        {
+            Image::new(handle.clone())
+                .height(length!(+))
+                .width(length!(+))
+                .into()
+        } else {
+            let initial = guild.name.chars().next().unwrap_or('u');
+            label!(initial.to_ascii_uppercase())
+                .size(DEF_SIZE + 30)
+                .into()
+        };
+
+        let mut picture_but = Button::new(&mut self.picture_but_state, fill_container(picture))
+            .width(length!(= 96))
+            .height(length!(= 96))
+            .style(theme);
+        let mut name_field = TextInput::new(
+            &mut self.name_edit_state,
+            "Enter a new guild name...",
+            &self.name_field,
+            Message::NameChanged,
+        )
+        .padding(PADDING / 2)
+        .width(length!(= 300))
+        .style(theme);
+        let mut rename_but = label_button!(&mut self.rename_but_state, "Rename").style(theme);
+        let mut delete_but = label_button!(&mut self.delete_but_state, "Delete guild").style(theme);
+        let mut leave_but = label_button!(&mut self.leave_but_state, "Leave guild").style(theme);
+
+        if !self.waiting {
+            picture_but = picture_but.on_press(Message::UploadPicture);
+            delete_but = delete_but.on_press(Message::AskConfirm(Confirm::Delete));
+            leave_but = leave_but.on_press(Message::AskConfirm(Confirm::Leave));
+            if !self.name_field.trim().is_empty() {
+                name_field = name_field.on_submit(Message::Rename);
+                rename_but = rename_but.on_press(Message::Rename);
+            }
+        }
+
+        let mut widgets = Vec::with_capacity(6);
+        if !self.error_text.is_empty() {
+            widgets.push(label!(&self.error_text).color(ERROR_COLOR).into());
+        } else if !self.status_text.is_empty() {
+            widgets.push(label!(&self.status_text).color(SUCCESS_COLOR).into());
+        }
+        widgets.push(
+            row(vec![
+                picture_but.into(),
+                label!(&guild.name).size(DEF_SIZE + 12).into(),
+            ])
+            .into(),
+        );
+        widgets.push(
+            row(vec![
+                name_field.into(),
+                rename_but.width(length!(= 100)).into(),
+            ])
+            .into(),
+        );
+        widgets.push(
+            row(vec![
+                leave_but.width(length!(= 140)).into(),
+                delete_but.width(length!(= 140)).into(),
+            ])
+            .into(),
+        );
+
+        fill_container(column(widgets).align_items(align!(|<))).into()
+    }
+
+    pub fn update(&mut self, msg: Message, client: &Client) -> Command<TopLevelMessage> {
+        fn done_msg(text: &str) -> TopLevelMessage {
+            TopLevelMessage::GuildSettings(ParentMessage::General(Message::Done(text.to_string())))
+        }
+
+        let guild_id = self.guild_id;
+
+        match msg {
+            Message::NameChanged(name) => self.name_field = name,
+            Message::Rename => {
+                let requests = client.requests().clone();
+                let new_guild_name = self.name_field.trim().to_string();
+                self.start_waiting();
+                return Command::perform(
+                    async move { requests.rename_guild(guild_id, new_guild_name).await },
+                    |result| {
+                        result.map_or_else(
+                            |err| TopLevelMessage::Error(Box::new(err)),
+                            |_| done_msg("Renamed the guild."),
+                        )
+                    },
+                );
+            }
+            Message::UploadPicture => {
+                let requests = client.requests().clone();
+                self.start_waiting();
+                return Command::perform(
+                    async move {
+                        let id = select_upload_files(&requests)
+                            .await?
+                            .into_iter()
+                            .next()
+                            .ok_or_else(|| ClientError::Custom("No file uploaded".to_string()))?
+                            .id;
+                        requests.set_guild_picture(guild_id, id).await
+                    },
+                    |result| {
+                        result.map_or_else(
+                            |err| TopLevelMessage::Error(Box::new(err)),
+                            |_| done_msg("Changed the guild picture."),
+                        )
+                    },
+                );
+            }
+            Message::AskConfirm(confirm) => {
+                self.error_text.clear();
+                self.confirming = Some(confirm);
+            }
+            Message::Confirmed(confirmed) => {
+                let confirm = self.confirming.take();
+                if !confirmed {
+                    return Command::none();
+                }
+
+                let requests = client.requests().clone();
+                self.start_waiting();
+                match confirm {
+                    Some(Confirm::Delete) => {
+                        return Command::perform(
+                            async move { requests.delete_guild(guild_id).await },
+                            |result| {
+                                result.map_or_else(
+                                    |err| TopLevelMessage::Error(Box::new(err)),
+                                    |_| TopLevelMessage::PopScreen,
+                                )
+                            },
+                        );
+                    }
+                    Some(Confirm::Leave) => {
+                        return Command::perform(
+                            async move { requests.leave_guild(guild_id).await },
+                            |result| {
+                                result.map_or_else(
+                                    |err| TopLevelMessage::Error(Box::new(err)),
+                                    |_| TopLevelMessage::PopScreen,
+                                )
+                            },
+                        );
+                    }
+                    None => self.waiting = false,
+                }
+            }
+            Message::Done(text) => {
+                self.waiting = false;
+                self.name_field.clear();
+                self.status_text = text;
+            }
+        }
+
         Command::none()
     }
 
-    pub fn on_error(&mut self, error: ClientError) -> Command<super::Message> {
+    pub fn on_error(&mut self, error: ClientError) -> Command<TopLevelMessage> {
+        self.waiting = false;
+        self.status_text.clear();
+        self.error_text = error.to_string();
+
         Command::none()
     }
+
+    fn start_waiting(&mut self) {
+        self.waiting = true;
+        self.status_text.clear();
+        self.error_text.clear();
+    }
 }
@@ -0,0 +1,256 @@
+use iced::{Command, Element};
+
+use super::Message as ParentMessage;
+use crate::{
+    client::{
+        error::{ClientError, ClientResult},
+        invite::Invite,
+        Client,
+    },
+    label, label_button, length, space,
+    ui::{component::*, screen::Message as TopLevelMessage, style::*},
+};
+
+impl Invite {
+    fn uses_text(&self) -> String {
+        if self.possible_uses < 0 {
+            format!("Used {} times", self.use_count)
+        } else {
+            format!("Used {} / {} times", self.use_count, self.possible_uses)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Loaded(Vec<Invite>),
+    NameChanged(String),
+    UsesChanged(String),
+    Create,
+    Created(String),
+    Copy(String),
+    Revoke(String),
+    Revoked(String),
+}
+
+#[derive(Debug, Default)]
+struct InviteEntry {
+    invite: Invite,
+    copy_but_state: button::State,
+    revoke_but_state: button::State,
+}
+
+#[derive(Debug, Default)]
+pub struct Invites {
+    guild_id: u64,
+    invites: Vec<InviteEntry>,
+    loading: bool,
+    invite_list_state: scrollable::State,
+    name_edit_state: text_input::State,
+    name_field: String,
+    uses_edit_state: text_input::State,
+    uses_field: String,
+    create_but_state: button::State,
+    status_text: String,
+    error_text: String,
+}
+
+impl Invites {
+    pub fn new(guild_id: u64) -> Self {
+        Self {
+            guild_id,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_invites(&mut self, invites: Vec<Invite>) {
+        self.loading = false;
+        self.invites = invites
+            .into_iter()
+            .map(|invite| InviteEntry {
+                invite,
+                ..Default::default()
+            })
+            .collect();
+    }
+
+    pub fn load(&mut self, client: &Client) -> Command<TopLevelMessage> {
+        self.loading = true;
+        let requests = client.requests().clone();
+        let guild_id = self.guild_id;
+
+        Command::perform(async move { requests.invites(guild_id).await }, |result| {
+            map_result(result.map(Message::Loaded))
+        })
+    }
+
+    pub fn view(&mut self, theme: Theme) -> Element<Message> {
+        let mut name_field = TextInput::new(
+            &mut self.name_edit_state,
+            "Invite name (optional)",
+            &self.name_field,
+            Message::NameChanged,
+        )
+        .padding(PADDING / 2)
+        .width(length!(= 250))
+        .style(theme);
+        let mut uses_field = TextInput::new(
+            &mut self.uses_edit_state,
+            "Max uses (empty for unlimited)",
+            &self.uses_field,
+            Message::UsesChanged,
+        )
+        .padding(PADDING / 2)
+        .width(length!(= 250))
+        .style(theme);
+        let mut create_but = label_button!(&mut self.create_but_state, "Create").style(theme);
+
+        if parse_uses(&self.uses_field).is_some() {
+            name_field = name_field.on_submit(Message::Create);
+            uses_field = uses_field.on_submit(Message::Create);
+            create_but = create_but.on_press(Message::Create);
+        }
+
+        let mut invite_list = Scrollable::new(&mut self.invite_list_state)
+            .style(theme)
+            .align_items(align!(|<))
+            .width(length!(+))
+            .height(length!(+))
+            .spacing(SPACING)
+            .padding(PADDING / 2);
+        if self.loading {
+            invite_list = invite_list.push(label!("Loading invites...").color(ALT_COLOR));
+        } else if self.invites.is_empty() {
+            invite_list = invite_list.push(label!("This guild has no invites.").color(ALT_COLOR));
+        }
+        for entry in &mut self.invites {
+            invite_list = invite_list.push(row(vec![
+                label!(&entry.invite.id).width(length!(= 250)).into(),
+                label!(entry.invite.uses_text())
+                    .color(ALT_COLOR)
+                    .width(length!(= 200))
+                    .into(),
+                label_button!(&mut entry.copy_but_state, "Copy")
+                    .on_press(Message::Copy(entry.invite.id.clone()))
+                    .style(theme)
+                    .into(),
+                label_button!(&mut entry.revoke_but_state, "Revoke")
+                    .on_press(Message::Revoke(entry.invite.id.clone()))
+                    .style(theme)
+                    .into(),
+            ]));
+        }
+
+        let mut widgets = Vec::with_capacity(4);
+        if !self.error_text.is_empty() {
+            widgets.push(label!(&self.error_text).color(ERROR_COLOR).into());
+        } else if !self.status_text.is_empty() {
+            widgets.push(label!(&self.status_text).color(SUCCESS_COLOR).into());
+        }
+        widgets.push(
+            row(vec![
+                name_field.into(),
+                uses_field.into(),
+                create_but.width(length!(= 100)).into(),
+            ])
+            .into(),
+        );
+        widgets.push(space!(h = PADDING / 2).into());
+        widgets.push(invite_list.into());
+
+        column(widgets)
+            .align_items(align!(|<))
+            .width(length!(+))
+            .height(length!(+))
+            .into()
+    }
+
+    pub fn update(
+        &mut self,
+        msg: Message,
+        client: &Client,
+        clip: &mut dyn iced_native::Clipboard,
+    ) -> Command<TopLevelMessage> {
+        let guild_id = self.guild_id;
+
+        match msg {
+            Message::Loaded(invites) => self.set_invites(invites),
+            Message::NameChanged(name) => self.name_field = name,
+            Message::UsesChanged(uses) => self.uses_field = uses,
+            Message::Create => {
+                let name = match self.name_field.trim() {
+                    "" => uuid::Uuid::new_v4().to_simple().to_string()[..8].to_string(),
+                    name => name.to_string(),
+                };
+                let possible_uses = match parse_uses(&self.uses_field) {
+                    Some(uses) => uses,
+                    None => {
+                        self.error_text = "Max uses must be a positive number.".to_string();
+                        return Command::none();
+                    }
+                };
+                let requests = client.requests().clone();
+                self.name_field.clear();
+                self.uses_field.clear();
+                self.error_text.clear();
+
+                return Command::perform(
+                    async move { requests.create_invite(guild_id, name, possible_uses).await },
+                    |result| map_result(result.map(Message::Created)),
+                );
+            }
+            Message::Created(id) => {
+                clip.write(id.clone());
+                self.status_text = format!("Created invite {} and copied it to clipboard.", id);
+                return self.load(client);
+            }
+            Message::Copy(id) => {
+                self.status_text = format!("Copied invite {} to clipboard.", id);
+                clip.write(id);
+            }
+            Message::Revoke(invite_id) => {
+                let requests = client.requests().clone();
+                self.error_text.clear();
+
+                return Command::perform(
+                    async move {
+                        requests.delete_invite(guild_id, invite_id.clone()).await?;
+                        Ok(invite_id)
+                    },
+                    |result| map_result(result.map(Message::Revoked)),
+                );
+            }
+            Message::Revoked(id) => {
+                self.invites.retain(|entry| entry.invite.id != id);
+                self.status_text = format!("Revoked invite {}.", id);
+            }
+        }
+
+        Command::none()
+    }
+
+    pub fn on_error(&mut self, error: ClientError) -> Command<TopLevelMessage> {
+        self.loading = false;
+        self.status_text.clear();
+        self.error_text = error.to_string();
+
+        Command::none()
+    }
+}
+
+/// Parses the max uses of a new invite, where an empty field means unlimited uses (`-1`).
+///
+/// Returns `None` if the field isn't a positive number that fits in the request.
+fn parse_uses(field: &str) -> Option<i32> {
+    match field.trim() {
+        "" => Some(-1),
+        uses => uses.parse::<i32>().ok().filter(|uses| *uses > 0),
+    }
+}
+
+fn map_result(result: ClientResult<Message>) -> TopLevelMessage {
+    result.map_or_else(
+        |err| TopLevelMessage::Error(Box::new(err)),
+        |msg| TopLevelMessage::GuildSettings(ParentMessage::Invites(msg)),
+    )
+}
@@ -0,0 +1,919 @@
+use std::fmt::{self, Display, Formatter};
+
+use harmony_rust_sdk::api::chat::event::Event;
+use iced::{Checkbox, Command, Element};
+use iced_aw::{color_picker, ColorPicker};
+
+use super::Message as ParentMessage;
+use crate::{
+    client::{
+        error::{ClientError, ClientResult},
+        requests::Requests,
+        role::{deciding_rule, PermissionRule, Role, RoleField},
+        Client,
+    },
+    label, label_button, length,
+    ui::{component::*, screen::Message as TopLevelMessage, style::*},
+};
+
+/// Where a set of permission rules applies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PermScope {
+    Guild,
+    Channel { id: u64, name: String },
+}
+
+impl PermScope {
+    fn channel_id(&self) -> u64 {
+        match self {
+            PermScope::Guild => 0,
+            PermScope::Channel { id, .. } => *id,
+        }
+    }
+}
+
+impl Default for PermScope {
+    fn default() -> Self {
+        PermScope::Guild
+    }
+}
+
+impl Display for PermScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PermScope::Guild => write!(f, "Whole guild"),
+            PermScope::Channel { name, .. } => write!(f, "#{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberChoice {
+    id: u64,
+    name: String,
+}
+
+impl Display for MemberChoice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Loaded {
+        roles: Vec<(u64, Role)>,
+        /// Events that set the roles of each member.
+        user_roles: Vec<Event>,
+    },
+    SelectRole(u64),
+    NewRoleNameChanged(String),
+    CreateRole,
+    RoleNameChanged(String),
+    RenameRole,
+    ShowColorPicker,
+    ColorCancelled,
+    ColorSubmitted(iced::Color),
+    HoistToggled(bool),
+    PingableToggled(bool),
+    MoveRole {
+        up: bool,
+    },
+    DeleteRole,
+    ScopeSelected(PermScope),
+    PermissionsLoaded {
+        role_id: u64,
+        scope: PermScope,
+        rules: Vec<PermissionRule>,
+    },
+    RuleChanged(usize, String),
+    RuleModeToggled(usize),
+    RemoveRule(usize),
+    NewRuleChanged(String),
+    AddRule,
+    SavePermissions,
+    ToggleMemberRole {
+        user_id: u64,
+        give: bool,
+    },
+    /// Sent when giving or taking a role failed, to undo the change shown before the server answered.
+    MemberRoleFailed {
+        user_id: u64,
+        role_id: u64,
+        give: bool,
+        err: ClientError,
+    },
+    TesterMemberSelected(MemberChoice),
+    TesterScopeSelected(PermScope),
+    TesterNodeChanged(String),
+    RunTest,
+    TestResult {
+        allowed: bool,
+        explanation: String,
+    },
+    /// Sent when a request succeeded, with the events that apply its changes.
+    Applied {
+        events: Vec<Event>,
+        status: String,
+    },
+    Done(String),
+}
+
+#[derive(Debug, Default)]
+struct RuleEntry {
+    rule: PermissionRule,
+    input_state: text_input::State,
+    mode_but_state: button::State,
+    remove_but_state: button::State,
+}
+
+#[derive(Debug, Default)]
+pub struct Roles {
+    guild_id: u64,
+    selected_role: Option<u64>,
+
+    role_list_state: scrollable::State,
+    role_buts_state: Vec<button::State>,
+    new_role_state: text_input::State,
+    new_role_field: String,
+    create_role_but_state: button::State,
+
+    editor_state: scrollable::State,
+    role_name_state: text_input::State,
+    role_name_field: String,
+    rename_but_state: button::State,
+    color_but_state: button::State,
+    color_picker_state: color_picker::State,
+    move_up_but_state: button::State,
+    move_down_but_state: button::State,
+    delete_but_state: button::State,
+    confirm_delete: bool,
+
+    scope_state: pick_list::State<PermScope>,
+    scope: PermScope,
+    rules: Vec<RuleEntry>,
+    loading_rules: bool,
+    new_rule_state: text_input::State,
+    new_rule_field: String,
+    add_rule_but_state: button::State,
+    save_rules_but_state: button::State,
+
+    tester_member_state: pick_list::State<MemberChoice>,
+    tester_member: Option<MemberChoice>,
+    tester_scope_state: pick_list::State<PermScope>,
+    tester_scope: PermScope,
+    tester_node_state: text_input::State,
+    tester_node: String,
+    test_but_state: button::State,
+    test_result: Option<(bool, String)>,
+
+    status_text: String,
+    error_text: String,
+}
+
+impl Roles {
+    pub fn new(guild_id: u64) -> Self {
+        Self {
+            guild_id,
+            ..Default::default()
+        }
+    }
+
+    /// Fetches the roles of the guild and the roles of every member.
+    pub fn load(&mut self, client: &Client) -> Command<TopLevelMessage> {
+        let guild_id = self.guild_id;
+        let members = client
+            .guilds
+            .get(&guild_id)
+            .map(|guild| guild.members.iter().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let requests = client.requests().clone();
+
+        Command::perform(
+            async move {
+                let (roles, user_roles) = futures::try_join!(
+                    requests.guild_roles(guild_id),
+                    requests.user_roles(guild_id, members),
+                )?;
+                Ok(Message::Loaded { roles, user_roles })
+            },
+            map_result,
+        )
+    }
+
+    pub fn view<'a>(&'a mut self, theme: Theme, client: &'a Client) -> Element<'a, Message> {
+        let guild = match client.guilds.get(&self.guild_id) {
+            Some(guild) => guild,
+            None => return fill_container(label!("This guild is not available anymore.")).into(),
+        };
+
+        // Role list
+        self.role_buts_state
+            .resize_with(guild.roles.len(), Default::default);
+        let mut role_list = Scrollable::new(&mut self.role_list_state)
+            .style(theme)
+            .align_items(align!(|<))
+            .height(length!(+))
+            .spacing(SPACING)
+            .padding(PADDING / 4);
+        for ((role_id, role), state) in guild.roles.iter().zip(self.role_buts_state.iter_mut()) {
+            let mut but = Button::new(state, label!(&role.name).color(role.color))
+                .width(length!(+))
+                .style(theme.secondary());
+            if self.selected_role != Some(*role_id) {
+                but = but.on_press(Message::SelectRole(*role_id));
+            }
+            role_list = role_list.push(but);
+        }
+
+        let mut new_role_input = TextInput::new(
+            &mut self.new_role_state,
+            "New role name...",
+            &self.new_role_field,
+            Message::NewRoleNameChanged,
+        )
+        .padding(PADDING / 2)
+        .style(theme);
+        let mut create_role_but =
+            label_button!(&mut self.create_role_but_state, "Create").style(theme);
+        if !self.new_role_field.trim().is_empty() {
+            new_role_input = new_role_input.on_submit(Message::CreateRole);
+            create_role_but = create_role_but.on_press(Message::CreateRole);
+        }
+
+        let mut left_widgets = Vec::with_capacity(4);
+        if !self.error_text.is_empty() {
+            left_widgets.push(label!(&self.error_text).color(ERROR_COLOR).into());
+        } else if !self.status_text.is_empty() {
+            left_widgets.push(label!(&self.status_text).color(SUCCESS_COLOR).into());
+        }
+        left_widgets.push(role_list.into());
+        left_widgets.push(new_role_input.into());
+        left_widgets.push(create_role_but.width(length!(+)).into());
+        let left = Column::with_children(left_widgets)
+            .spacing(SPACING)
+            .padding(PADDING / 2)
+            .width(length!(= 220))
+            .height(length!(+));
+
+        let (role_id, role) = match self
+            .selected_role
+            .map(|id| Some((id, guild.roles.get(&id)?)))
+            .flatten()
+        {
+            Some(selected) => selected,
+            None => {
+                return Row::with_children(vec![
+                    left.into(),
+                    fill_container(label!("Select a role to edit it").color(ALT_COLOR)).into(),
+                ])
+                .into()
+            }
+        };
+        let role_position = guild.roles.get_index_of(&role_id).unwrap_or(0);
+
+        // Role properties
+        let mut name_input = TextInput::new(
+            &mut self.role_name_state,
+            "Enter a new role name...",
+            &self.role_name_field,
+            Message::RoleNameChanged,
+        )
+        .padding(PADDING / 2)
+        .width(length!(= 250))
+        .style(theme);
+        let mut rename_but = label_button!(&mut self.rename_but_state, "Rename").style(theme);
+        if !self.role_name_field.trim().is_empty() {
+            name_input = name_input.on_submit(Message::RenameRole);
+            rename_but = rename_but.on_press(Message::RenameRole);
+        }
+
+        let color_but = Button::new(&mut self.color_but_state, label!("Color").color(role.color))
+            .on_press(Message::ShowColorPicker)
+            .style(theme);
+        let color_picker = ColorPicker::new(
+            &mut self.color_picker_state,
+            color_but,
+            Message::ColorCancelled,
+            Message::ColorSubmitted,
+        );
+
+        let mut move_up_but = label_button!(&mut self.move_up_but_state, "Move up").style(theme);
+        if role_position > 0 {
+            move_up_but = move_up_but.on_press(Message::MoveRole { up: true });
+        }
+        let mut move_down_but =
+            label_button!(&mut self.move_down_but_state, "Move down").style(theme);
+        if role_position + 1 < guild.roles.len() {
+            move_down_but = move_down_but.on_press(Message::MoveRole { up: false });
+        }
+        let delete_text = if self.confirm_delete {
+            "Click again to delete"
+        } else {
+            "Delete role"
+        };
+        let delete_but = label_button!(&mut self.delete_but_state, delete_text)
+            .on_press(Message::DeleteRole)
+            .style(theme);
+
+        let mut editor = Scrollable::new(&mut self.editor_state)
+            .style(theme)
+            .align_items(align!(|<))
+            .width(length!(+))
+            .height(length!(+))
+            .spacing(SPACING * 2)
+            .padding(PADDING / 2)
+            .push(label!(&role.name).color(role.color).size(DEF_SIZE + 8))
+            .push(row(vec![
+                name_input.into(),
+                rename_but.width(length!(= 100)).into(),
+                color_picker.into(),
+            ]))
+            .push(row(vec![
+                Checkbox::new(role.hoist, "Show separately", Message::HoistToggled)
+                    .style(theme)
+                    .into(),
+                Checkbox::new(role.pingable, "Mentionable", Message::PingableToggled)
+                    .style(theme)
+                    .into(),
+            ]))
+            .push(row(vec![
+                move_up_but.width(length!(= 120)).into(),
+                move_down_but.width(length!(= 120)).into(),
+                delete_but.width(length!(= 200)).into(),
+            ]));
+
+        // Permission rules
+        let mut scopes = vec![PermScope::Guild];
+        scopes.extend(guild.channels.iter().filter(|(_, c)| !c.is_category).map(
+            |(id, channel)| PermScope::Channel {
+                id: *id,
+                name: channel.name.clone(),
+            },
+        ));
+
+        editor = editor.push(label!("Permissions").size(DEF_SIZE + 4)).push(
+            PickList::new(
+                &mut self.scope_state,
+                scopes.clone(),
+                Some(self.scope.clone()),
+                Message::ScopeSelected,
+            )
+            .style(theme),
+        );
+
+        if self.loading_rules {
+            editor = editor.push(label!("Loading permissions...").color(ALT_COLOR));
+        } else {
+            for (index, entry) in self.rules.iter_mut().enumerate() {
+                let mode_text = if entry.rule.allow { "Allow" } else { "Deny" };
+                editor = editor.push(row(vec![
+                    TextInput::new(
+                        &mut entry.input_state,
+                        "permission.node",
+                        &entry.rule.matches,
+                        move |new| Message::RuleChanged(index, new),
+                    )
+                    .padding(PADDING / 4)
+                    .width(length!(= 300))
+                    .style(theme)
+                    .into(),
+                    label_button!(&mut entry.mode_but_state, mode_text)
+                        .on_press(Message::RuleModeToggled(index))
+                        .width(length!(= 80))
+                        .style(theme)
+                        .into(),
+                    label_button!(&mut entry.remove_but_state, "Remove")
+                        .on_press(Message::RemoveRule(index))
+                        .width(length!(= 100))
+                        .style(theme)
+                        .into(),
+                ]));
+            }
+
+            let mut new_rule_input = TextInput::new(
+                &mut self.new_rule_state,
+                "New rule, like messages.send or messages.*",
+                &self.new_rule_field,
+                Message::NewRuleChanged,
+            )
+            .padding(PADDING / 4)
+            .width(length!(= 300))
+            .style(theme);
+            let mut add_rule_but = label_button!(&mut self.add_rule_but_state, "Add").style(theme);
+            if !self.new_rule_field.trim().is_empty() {
+                new_rule_input = new_rule_input.on_submit(Message::AddRule);
+                add_rule_but = add_rule_but.on_press(Message::AddRule);
+            }
+            editor = editor
+                .push(row(vec![
+                    new_rule_input.into(),
+                    add_rule_but.width(length!(= 80)).into(),
+                ]))
+                .push(
+                    label_button!(&mut self.save_rules_but_state, "Save permissions")
+                        .on_press(Message::SavePermissions)
+                        .width(length!(= 200))
+                        .style(theme),
+                );
+        }
+
+        // Members with this role
+        let mut members = guild
+            .members
+            .iter()
+            .flat_map(|id| Some((*id, client.members.get(id)?)))
+            .collect::<Vec<_>>();
+        members.sort_by(|(_, a), (_, b)| a.username.cmp(&b.username));
+
+        editor = editor.push(label!("Members").size(DEF_SIZE + 4));
+        for (user_id, member) in &members {
+            let has_role = guild
+                .user_roles
+                .get(user_id)
+                .map_or(false, |roles| roles.contains(&role_id));
+            let user_id = *user_id;
+            editor = editor.push(
+                Checkbox::new(has_role, &member.username, move |give| {
+                    Message::ToggleMemberRole { user_id, give }
+                })
+                .style(theme),
+            );
+        }
+
+        // Permission tester
+        let member_choices = members
+            .iter()
+            .map(|(id, member)| MemberChoice {
+                id: *id,
+                name: member.username.clone(),
+            })
+            .collect::<Vec<_>>();
+        let mut tester_node_input = TextInput::new(
+            &mut self.tester_node_state,
+            "Permission to check, like messages.send",
+            &self.tester_node,
+            Message::TesterNodeChanged,
+        )
+        .padding(PADDING / 4)
+        .width(length!(= 300))
+        .style(theme);
+        let mut test_but = label_button!(&mut self.test_but_state, "Check").style(theme);
+        if self.tester_member.is_some() && !self.tester_node.trim().is_empty() {
+            tester_node_input = tester_node_input.on_submit(Message::RunTest);
+            test_but = test_but.on_press(Message::RunTest);
+        }
+
+        editor = editor
+            .push(label!("Permission tester").size(DEF_SIZE + 4))
+            .push(row(vec![
+                PickList::new(
+                    &mut self.tester_member_state,
+                    member_choices,
+                    self.tester_member.clone(),
+                    Message::TesterMemberSelected,
+                )
+                .style(theme)
+                .into(),
+                PickList::new(
+                    &mut self.tester_scope_state,
+                    scopes,
+                    Some(self.tester_scope.clone()),
+                    Message::TesterScopeSelected,
+                )
+                .style(theme)
+                .into(),
+            ]))
+            .push(row(vec![
+                tester_node_input.into(),
+                test_but.width(length!(= 80)).into(),
+            ]));
+        if let Some((allowed, explanation)) = &self.test_result {
+            let (text, color) = if *allowed {
+                ("Allowed", SUCCESS_COLOR)
+            } else {
+                ("Not allowed", ERROR_COLOR)
+            };
+            editor = editor
+                .push(label!(text).color(color))
+                .push(label!(explanation).color(ALT_COLOR));
+        }
+
+        Row::with_children(vec![left.into(), editor.into()])
+            .width(length!(+))
+            .height(length!(+))
+            .into()
+    }
+
+    pub fn update(&mut self, msg: Message, client: &mut Client) -> Command<TopLevelMessage> {
+        let guild_id = self.guild_id;
+        let requests = client.requests().clone();
+
+        match msg {
+            Message::Loaded { roles, user_roles } => {
+                if let Some(guild) = client.get_guild(guild_id) {
+                    guild.roles = roles.into_iter().collect();
+                    if let Some(id) = self.selected_role {
+                        if !guild.roles.contains_key(&id) {
+                            self.selected_role = None;
+                        }
+                    }
+                }
+                return apply_events(user_roles);
+            }
+            Message::SelectRole(role_id) => {
+                self.selected_role = Some(role_id);
+                self.confirm_delete = false;
+                self.role_name_field.clear();
+                return self.load_rules(client);
+            }
+            Message::NewRoleNameChanged(name) => self.new_role_field = name,
+            Message::CreateRole => {
+                let role = Role {
+                    name: self
+                        .new_role_field
+                        .drain(..)
+                        .collect::<String>()
+                        .trim()
+                        .to_string(),
+                    ..Default::default()
+                };
+                return Command::perform(
+                    async move { requests.create_role(guild_id, role).await },
+                    applied("Created the role."),
+                );
+            }
+            Message::RoleNameChanged(name) => self.role_name_field = name,
+            Message::RenameRole => {
+                let name = self.role_name_field.drain(..).collect::<String>();
+                return self.modify_role(client, |role| {
+                    role.name = name.trim().to_string();
+                    RoleField::Name
+                });
+            }
+            Message::ShowColorPicker => self.color_picker_state.show(true),
+            Message::ColorCancelled => self.color_picker_state.show(false),
+            Message::ColorSubmitted(color) => {
+                self.color_picker_state.show(false);
+                return self.modify_role(client, |role| {
+                    role.color = color.into();
+                    RoleField::Color
+                });
+            }
+            Message::HoistToggled(hoist) => {
+                return self.modify_role(client, |role| {
+                    role.hoist = hoist;
+                    RoleField::Hoist
+                })
+            }
+            Message::PingableToggled(pingable) => {
+                return self.modify_role(client, |role| {
+                    role.pingable = pingable;
+                    RoleField::Pingable
+                })
+            }
+            Message::MoveRole { up } => {
+                if let Some((role_id, guild)) = self.selected_role.zip(client.guilds.get(&guild_id))
+                {
+                    let ids = guild.roles.keys().copied().collect::<Vec<_>>();
+                    if let Some(pos) = ids.iter().position(|id| *id == role_id) {
+                        // The new neighbours of the role after the move
+                        let (after, before) = if up {
+                            (
+                                pos.checked_sub(2).map(|p| ids[p]),
+                                pos.checked_sub(1).map(|p| ids[p]),
+                            )
+                        } else {
+                            (ids.get(pos + 1).copied(), ids.get(pos + 2).copied())
+                        };
+                        return Command::perform(
+                            async move {
+                                requests
+                                    .move_role(
+                                        guild_id,
+                                        role_id,
+                                        after.unwrap_or_default(),
+                                        before.unwrap_or_default(),
+                                    )
+                                    .await
+                            },
+                            applied("Moved the role."),
+                        );
+                    }
+                }
+            }
+            Message::DeleteRole => {
+                if !self.confirm_delete {
+                    self.confirm_delete = true;
+                } else if let Some(role_id) = self.selected_role.take() {
+                    self.confirm_delete = false;
+                    return Command::perform(
+                        async move { requests.delete_role(guild_id, role_id).await },
+                        applied("Deleted the role."),
+                    );
+                }
+            }
+            Message::ScopeSelected(scope) => {
+                self.scope = scope;
+                return self.load_rules(client);
+            }
+            Message::PermissionsLoaded {
+                role_id,
+                scope,
+                rules,
+            } => {
+                if self.selected_role == Some(role_id) && self.scope == scope {
+                    self.loading_rules = false;
+                    self.rules = rules
+                        .into_iter()
+                        .map(|rule| RuleEntry {
+                            rule,
+                            ..Default::default()
+                        })
+                        .collect();
+                }
+            }
+            Message::RuleChanged(index, matches) => {
+                if let Some(entry) = self.rules.get_mut(index) {
+                    entry.rule.matches = matches;
+                }
+            }
+            Message::RuleModeToggled(index) => {
+                if let Some(entry) = self.rules.get_mut(index) {
+                    entry.rule.allow = !entry.rule.allow;
+                }
+            }
+            Message::RemoveRule(index) => {
+                if index < self.rules.len() {
+                    self.rules.remove(index);
+                }
+            }
+            Message::NewRuleChanged(rule) => self.new_rule_field = rule,
+            Message::AddRule => {
+                let matches = self.new_rule_field.drain(..).collect::<String>();
+                self.rules.push(RuleEntry {
+                    rule: PermissionRule {
+                        matches: matches.trim().to_string(),
+                        allow: true,
+                    },
+                    ..Default::default()
+                });
+            }
+            Message::SavePermissions => {
+                if let Some(role_id) = self.selected_role {
+                    let channel_id = self.scope.channel_id();
+                    let rules = self
+                        .rules
+                        .iter()
+                        .filter(|entry| !entry.rule.matches.trim().is_empty())
+                        .map(|entry| entry.rule.clone())
+                        .collect();
+                    return Command::perform(
+                        async move {
+                            requests
+                                .set_role_permissions(guild_id, channel_id, role_id, rules)
+                                .await
+                        },
+                        done_or_error("Saved the permissions."),
+                    );
+                }
+            }
+            Message::ToggleMemberRole { user_id, give } => {
+                if let Some(role_id) = self.selected_role {
+                    if let Some(roles) = client
+                        .get_guild(guild_id)
+                        .map(|guild| guild.user_roles.entry(user_id).or_default())
+                    {
+                        set_role(roles, role_id, give);
+                    }
+                    let (give_role_ids, take_role_ids) = if give {
+                        (vec![role_id], Vec::new())
+                    } else {
+                        (Vec::new(), vec![role_id])
+                    };
+                    return Command::perform(
+                        async move {
+                            requests
+                                .manage_user_roles(guild_id, user_id, give_role_ids, take_role_ids)
+                                .await
+                        },
+                        move |result| {
+                            map_result(Ok(result.map_or_else(
+                                |err| Message::MemberRoleFailed {
+                                    user_id,
+                                    role_id,
+                                    give,
+                                    err,
+                                },
+                                |_| Message::Done("Updated member roles.".to_string()),
+                            )))
+                        },
+                    );
+                }
+            }
+            Message::MemberRoleFailed {
+                user_id,
+                role_id,
+                give,
+                err,
+            } => {
+                if let Some(roles) = client
+                    .get_guild(guild_id)
+                    .map(|guild| guild.user_roles.entry(user_id).or_default())
+                {
+                    set_role(roles, role_id, !give);
+                }
+                self.status_text.clear();
+                self.error_text = format!("Couldn't update the roles of {}: {}", user_id, err);
+            }
+            Message::TesterMemberSelected(member) => self.tester_member = Some(member),
+            Message::TesterScopeSelected(scope) => self.tester_scope = scope,
+            Message::TesterNodeChanged(node) => self.tester_node = node,
+            Message::RunTest => {
+                if let (Some(member), Some(guild)) =
+                    (self.tester_member.clone(), client.guilds.get(&guild_id))
+                {
+                    let roles = guild
+                        .roles
+                        .iter()
+                        .map(|(id, role)| (*id, role.name.clone()))
+                        .collect::<Vec<_>>();
+                    let scope = self.tester_scope.clone();
+                    let node = self.tester_node.trim().to_string();
+                    self.test_result = None;
+                    return Command::perform(
+                        async move {
+                            test_permission(&requests, guild_id, roles, member, scope, node).await
+                        },
+                        map_result,
+                    );
+                }
+            }
+            Message::TestResult {
+                allowed,
+                explanation,
+            } => {
+                self.test_result = Some((allowed, explanation));
+            }
+            Message::Applied { events, status } => {
+                self.error_text.clear();
+                self.status_text = status;
+                return apply_events(events);
+            }
+            Message::Done(text) => {
+                self.error_text.clear();
+                self.status_text = text;
+            }
+        }
+
+        Command::none()
+    }
+
+    pub fn on_error(&mut self, error: ClientError) -> Command<TopLevelMessage> {
+        self.loading_rules = false;
+        self.status_text.clear();
+        self.error_text = error.to_string();
+
+        Command::none()
+    }
+
+    fn load_rules(&mut self, client: &Client) -> Command<TopLevelMessage> {
+        let role_id = match self.selected_role {
+            Some(id) => id,
+            None => return Command::none(),
+        };
+        let guild_id = self.guild_id;
+        let scope = self.scope.clone();
+        let requests = client.requests().clone();
+        self.loading_rules = true;
+        self.rules.clear();
+
+        Command::perform(
+            async move {
+                let rules = requests
+                    .role_permissions(guild_id, scope.channel_id(), role_id)
+                    .await?;
+                Ok(Message::PermissionsLoaded {
+                    role_id,
+                    scope,
+                    rules,
+                })
+            },
+            map_result,
+        )
+    }
+
+    fn modify_role(
+        &mut self,
+        client: &Client,
+        modify: impl FnOnce(&mut Role) -> RoleField,
+    ) -> Command<TopLevelMessage> {
+        let guild_id = self.guild_id;
+        let (role_id, mut role) = match self
+            .selected_role
+            .map(|id| Some((id, client.guilds.get(&guild_id)?.roles.get(&id)?.clone())))
+            .flatten()
+        {
+            Some(role) => role,
+            None => return Command::none(),
+        };
+        let field = modify(&mut role);
+        let requests = client.requests().clone();
+
+        Command::perform(
+            async move { requests.modify_role(guild_id, role_id, role, field).await },
+            applied("Updated the role."),
+        )
+    }
+}
+
+/// Gives a role to a member in the local state, or takes it away.
+fn set_role(roles: &mut Vec<u64>, role_id: u64, give: bool) {
+    if !give {
+        roles.retain(|id| *id != role_id);
+    } else if !roles.contains(&role_id) {
+        roles.push(role_id);
+    }
+}
+
+fn map_result(result: ClientResult<Message>) -> TopLevelMessage {
+    result.map_or_else(
+        |err| TopLevelMessage::Error(Box::new(err)),
+        |msg| TopLevelMessage::GuildSettings(ParentMessage::Roles(msg)),
+    )
+}
+
+/// Returns a result mapper that applies the event of a request if it was successful.
+fn applied(text: &'static str) -> impl Fn(ClientResult<Event>) -> TopLevelMessage + 'static {
+    move |result| {
+        map_result(result.map(|event| Message::Applied {
+            events: vec![event],
+            status: text.to_string(),
+        }))
+    }
+}
+
+/// Gives events to the client like the ones from the socket, so that everything showing them is updated.
+fn apply_events(events: Vec<Event>) -> Command<TopLevelMessage> {
+    Command::perform(async move { events }, TopLevelMessage::EventsReceived)
+}
+
+fn done_or_error(text: &'static str) -> impl Fn(ClientResult<()>) -> TopLevelMessage + 'static {
+    move |result| map_result(result.map(|_| Message::Done(text.to_string())))
+}
+
+/// Asks the server if a member has a permission, and works out which rule decided it from the
+/// rules of the member's roles.
+async fn test_permission(
+    requests: &Requests,
+    guild_id: u64,
+    roles: Vec<(u64, String)>,
+    member: MemberChoice,
+    scope: PermScope,
+    node: String,
+) -> ClientResult<Message> {
+    let user_roles = requests.member_roles(guild_id, member.id).await?;
+    let member_roles = roles
+        .into_iter()
+        .filter(|(id, _)| user_roles.contains(id))
+        .collect::<Vec<_>>();
+
+    let mut rule_lists = Vec::with_capacity(member_roles.len() * 2);
+    let mut scopes = Vec::with_capacity(2);
+    if let PermScope::Channel { .. } = scope {
+        scopes.push(scope.clone());
+    }
+    scopes.push(PermScope::Guild);
+    for scope in scopes {
+        for (role_id, role_name) in &member_roles {
+            let rules = requests
+                .role_permissions(guild_id, scope.channel_id(), *role_id)
+                .await?;
+            rule_lists.push(((role_name.clone(), scope.clone()), rules));
+        }
+    }
+
+    let allowed = requests
+        .has_permission(guild_id, scope.channel_id(), node.clone(), member.id)
+        .await?;
+
+    let explanation = match deciding_rule(&node, &rule_lists) {
+        Some(((role_name, rule_scope), rule)) => format!(
+            "{} is {} by the rule \"{}\" of the role {} ({}).",
+            node,
+            if rule.allow { "allowed" } else { "denied" },
+            rule.matches,
+            role_name,
+            rule_scope,
+        ),
+        None => format!(
+            "None of the roles of {} have a rule for {}, so the server default applies.",
+            member.name, node
+        ),
+    };
+
+    Ok(Message::TestResult {
+        allowed,
+        explanation,
+    })
+}
@@ -1,10 +1,15 @@
 use super::Message as TopLevelMessage;
 use crate::{
-    client::{content::ContentStore, error::ClientError, Client, Session},
+    client::{
+        auth::{self, FieldKind, LOGIN_CHOICE, REGISTER_CHOICE},
+        content::ContentStore,
+        error::{ClientError, ErrorKind},
+        Client, Session,
+    },
     label, label_button, length, space,
     ui::{
         component::*,
-        style::{Theme, ERROR_COLOR, PADDING},
+        style::{Theme, ALT_COLOR, ERROR_COLOR, PADDING},
     },
 };
 use harmony_rust_sdk::{
@@ -12,45 +17,118 @@ use harmony_rust_sdk::{
         auth::{auth_step::Step, next_step_request::form_fields::Field},
         exports::hrpc::url::Url,
     },
-    client::{
-        api::auth::{AuthStep, AuthStepResponse},
-        AuthStatus,
-    },
+    client::api::auth::{AuthStep, AuthStepResponse},
 };
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
+
+const DEFAULT_HOMESERVER: &str = "https://chat.harmonyapp.io:2289";
+const KEYRING_UNAVAILABLE: &str = "There is no system keyring to keep your session in, choose a \
+    passphrase to encrypt it with instead. It will be asked for when Crust starts.";
 
-#[allow(dead_code)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum AuthType {
     Form,
     Choice,
+    /// The homeserver waits for something that happens elsewhere, like verifying an email.
     Waiting,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum AuthPart {
     Homeserver,
+    /// Checking that the saved session can still be used.
+    Session,
+    /// Choosing a passphrase to save the session with, as there is no keyring.
+    Protect,
     Step(AuthType),
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    FieldChanged(String, String),
+    /// Sent when the user types in the field at an index.
+    FieldChanged(usize, String),
+    /// Sent when the user types a new password again, in the field at an index.
+    ConfirmationChanged(usize, String),
     ProceedWithChoice(String),
     Proceed,
+    /// Connects to the homeserver to create an account, instead of logging in.
+    Register,
     GoBack,
     AuthStep(Option<AuthStep>),
+    /// Sent when the homeserver pushes the step after the waiting step with this number.
+    StepPushed(u64, AuthStep),
+    /// Checks the saved session again.
+    RestoreSession,
+    /// Sent when the saved session couldn't be checked, or isn't valid anymore.
+    RestoreFailed(ClientError),
+    /// Sent when the session of the client that logged in couldn't be saved.
+    SaveFailed(ClientError),
+    /// Continues without saving the session, so that the user has to log in again next time.
+    SkipSaving,
+}
+
+#[derive(Debug)]
+struct FormField {
+    name: String,
+    kind: FieldKind,
+    value: String,
+    state: text_input::State,
+    /// The value typed a second time, for new passwords.
+    confirmation: Option<(text_input::State, String)>,
+    error: Option<String>,
+}
+
+impl FormField {
+    fn new(name: String, kind: FieldKind) -> Self {
+        Self {
+            confirmation: (kind == FieldKind::NewPassword).then(Default::default),
+            name,
+            kind,
+            value: String::new(),
+            state: Default::default(),
+            error: None,
+        }
+    }
+
+    /// Checks the value, keeping the error to show it under the field.
+    fn validate(&mut self) -> Option<Field> {
+        let label = auth::label(&self.name);
+        let result =
+            self.kind
+                .parse(&label, &self.value)
+                .and_then(|field| match &self.confirmation {
+                    Some((_, confirmation)) if confirmation != &self.value => {
+                        Err(format!("{} doesn't match what you typed again", label))
+                    }
+                    _ => Ok(field),
+                });
+        self.error = result.as_ref().err().cloned();
+        result.ok()
+    }
 }
 
 #[derive(Debug)]
 pub struct LoginScreen {
-    fields: Vec<(String, (text_input::State, String, String))>,
-    choices: HashMap<String, button::State>,
+    fields: Vec<FormField>,
+    /// Options of a choice step, in the order the homeserver sent them.
+    choices: Vec<(String, button::State)>,
     proceed: button::State,
+    register: button::State,
     back: button::State,
+    retry: button::State,
+    skip: button::State,
 
     current_step: AuthPart,
+    /// Title of the current step, and what a waiting step waits for.
+    title: String,
+    description: String,
     can_go_back: bool,
+    /// Counts the steps, so that steps pushed after a waiting step we left are ignored.
+    step_number: u64,
+    /// The choice to make for the user when the homeserver first asks, picked on the first screen.
+    intent: Option<&'static str>,
+    /// The last homeserver the user entered, which is kept when starting over.
+    homeserver: String,
     /// The error formatted as a string to be displayed to the user.
     current_error: String,
     content_store: Arc<ContentStore>,
@@ -64,9 +142,17 @@ impl LoginScreen {
             fields: Default::default(),
             choices: Default::default(),
             proceed: Default::default(),
+            register: Default::default(),
             back: Default::default(),
+            retry: Default::default(),
+            skip: Default::default(),
             current_step: AuthPart::Homeserver,
+            title: Default::default(),
+            description: Default::default(),
             can_go_back: false,
+            step_number: 0,
+            intent: None,
+            homeserver: DEFAULT_HOMESERVER.to_string(),
             current_error: Default::default(),
             waiting: false,
         };
@@ -78,26 +164,128 @@ impl LoginScreen {
         self.waiting = false;
         self.can_go_back = false;
         self.current_step = AuthPart::Homeserver;
+        self.title.clear();
+        self.description.clear();
+        self.intent = None;
         self.fields.clear();
         self.choices.clear();
-        self.fields.push((
-            "homeserver".to_string(),
-            (
-                Default::default(),
-                "https://chat.harmonyapp.io:2289".to_string(),
-                "text".to_string(),
-            ),
-        ));
+        let mut homeserver = FormField::new("homeserver".to_string(), FieldKind::Text);
+        homeserver.value = self.homeserver.clone();
+        self.fields.push(homeserver);
+    }
+
+    pub fn error_text(&self) -> Option<&str> {
+        (!self.current_error.is_empty()).then(|| self.current_error.as_str())
+    }
+
+    /// Returns the names of the fields of the current step, with what's wrong with their values.
+    pub fn field_errors(&self) -> Vec<(&str, Option<&str>)> {
+        self.fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.error.as_deref()))
+            .collect()
+    }
+
+    /// Returns the title of the current step, if it has one.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Logs in with the saved session, once the homeserver confirmed that it's still valid.
+    pub fn restore_session(&mut self) -> Command<TopLevelMessage> {
+        if self.current_step != AuthPart::Session {
+            self.fields.clear();
+            self.title.clear();
+            self.description.clear();
+            self.current_step = AuthPart::Session;
+        }
+        let passphrase = match self.passphrase() {
+            Some(passphrase) => passphrase,
+            None => return Command::none(),
+        };
+
+        self.waiting = true;
+        self.current_error.clear();
+        let content_store = self.content_store.clone();
+        Command::perform(
+            async move {
+                let session = Session::load(&content_store, passphrase).await?;
+                let client = Client::from_session(session, content_store).await?;
+                let user_id = client.user_id.ok_or(ClientError::MissingLoginInfo)?;
+                // Any request that needs a session tells us if it's still valid
+                let requests = client.requests().clone();
+                requests.profile(user_id).await?;
+                Ok(client)
+            },
+            |result| {
+                result.map_or_else(
+                    |err| TopLevelMessage::LoginScreen(Message::RestoreFailed(err)),
+                    |client| TopLevelMessage::LoginComplete(Some(client)),
+                )
+            },
+        )
+    }
+
+    /// Saves the session of the client that logged in, with the passphrase if one was asked for.
+    fn save_session(&mut self, client: Option<&Client>) -> Command<TopLevelMessage> {
+        let passphrase = match self.passphrase() {
+            Some(passphrase) => passphrase,
+            None => return Command::none(),
+        };
+
+        self.waiting = true;
+        // If this unwrap fails, then something is very wrong, so we abort here.
+        // (How can there be no client, but we get authenticated?)
+        // We *can* recover from here but it's not worth the effort
+        let session = client.unwrap().session();
+        let content_store = self.content_store.clone();
+        Command::perform(
+            async move {
+                match session {
+                    Some(session) => session.save(&content_store, passphrase).await,
+                    None => Ok(()),
+                }
+            },
+            |result| {
+                result.map_or_else(
+                    |err| TopLevelMessage::LoginScreen(Message::SaveFailed(err)),
+                    |()| TopLevelMessage::LoginComplete(None),
+                )
+            },
+        )
+    }
+
+    /// Asks for the passphrase of the session, replacing the fields.
+    fn ask_passphrase(&mut self, kind: FieldKind, title: &str, description: &str) {
+        self.fields = vec![FormField::new("passphrase".to_string(), kind)];
+        self.title = title.to_string();
+        self.description = description.to_string();
+        self.can_go_back = false;
+    }
+
+    /// Checks the passphrase, if the current part asks for one.
+    ///
+    /// Returns `None` if it isn't valid, and `Some(None)` if there is no passphrase to check.
+    fn passphrase(&mut self) -> Option<Option<String>> {
+        match (self.current_step, self.fields.first_mut()) {
+            (AuthPart::Session, Some(field)) | (AuthPart::Protect, Some(field)) => {
+                field.validate().map(|_| Some(field.value.clone()))
+            }
+            _ => Some(None),
+        }
     }
 
     pub fn view(&mut self, theme: Theme) -> Element<Message> {
         if self.waiting {
-            return fill_container(label!("Please wait...").size(30))
-                .style(theme)
-                .into();
+            let text = if let AuthPart::Session = self.current_step {
+                "Checking your session..."
+            } else {
+                "Please wait..."
+            };
+            return fill_container(label!(text).size(30)).style(theme).into();
         }
 
-        let mut widgets = Vec::with_capacity(self.fields.len() + self.choices.len() + 1);
+        let mut widgets = Vec::with_capacity(self.fields.len() * 2 + self.choices.len() + 4);
         if !self.current_error.is_empty() {
             let error_text = label!(self
                 .current_error
@@ -110,44 +298,116 @@ impl LoginScreen {
             widgets.push(error_text.into());
         }
 
-        if !self.fields.is_empty() {
-            for (name, (state, value, r#type)) in self.fields.iter_mut() {
-                let namee = name.clone();
-                let mut input = TextInput::new(state, name, value, move |new| {
-                    Message::FieldChanged(namee.clone(), new)
-                })
+        if !self.title.is_empty() {
+            widgets.push(label!(auth::label(&self.title)).size(24).into());
+        }
+        if !self.description.is_empty() {
+            widgets.push(label!(&self.description).color(ALT_COLOR).into());
+        }
+
+        for (index, field) in self.fields.iter_mut().enumerate() {
+            let label = auth::label(&field.name);
+            let mut input = TextInput::new(&mut field.state, &label, &field.value, move |new| {
+                Message::FieldChanged(index, new)
+            })
+            .padding(PADDING / 2)
+            .on_submit(Message::Proceed)
+            .style(theme);
+            if field.kind.is_secret() {
+                input = input.password();
+            }
+            widgets.push(input.into());
+
+            if let Some((state, confirmation)) = &mut field.confirmation {
+                let input = TextInput::new(
+                    state,
+                    &format!("{} again", label),
+                    confirmation.as_str(),
+                    move |new| Message::ConfirmationChanged(index, new),
+                )
                 .padding(PADDING / 2)
+                .on_submit(Message::Proceed)
+                .password()
                 .style(theme);
-                input = match r#type.as_str() {
-                    "password" | "new-password" => input.password(),
-                    _ => input,
-                };
                 widgets.push(input.into());
             }
-        }
 
-        if !self.choices.is_empty() {
-            let mut sorted_choices = self.choices.iter_mut().collect::<Vec<_>>();
-            sorted_choices.sort_unstable_by_key(|(name, _)| name.as_str());
-            for (name, state) in &mut self.choices {
-                widgets.push(
-                    Button::new(state, label!(name))
-                        .on_press(Message::ProceedWithChoice(name.clone()))
-                        .style(theme)
-                        .into(),
-                );
+            if let Some(error) = &field.error {
+                widgets.push(label!(error).color(ERROR_COLOR).size(16).into());
             }
         }
 
-        if let AuthPart::Step(AuthType::Form) | AuthPart::Homeserver = self.current_step {
+        for (name, state) in &mut self.choices {
             widgets.push(
-                label_button!(&mut self.proceed, "Proceed")
-                    .on_press(Message::Proceed)
+                Button::new(state, label!(auth::label(name)))
+                    .on_press(Message::ProceedWithChoice(name.clone()))
                     .style(theme)
                     .into(),
             );
         }
 
+        match self.current_step {
+            AuthPart::Homeserver => {
+                widgets.push(
+                    label_button!(&mut self.proceed, "Login")
+                        .on_press(Message::Proceed)
+                        .style(theme)
+                        .into(),
+                );
+                widgets.push(
+                    label_button!(&mut self.register, "Create an account")
+                        .on_press(Message::Register)
+                        .style(theme)
+                        .into(),
+                );
+            }
+            AuthPart::Session => {
+                let retry = if self.fields.is_empty() {
+                    "Try again"
+                } else {
+                    "Continue"
+                };
+                widgets.push(
+                    label_button!(&mut self.retry, retry)
+                        .on_press(Message::RestoreSession)
+                        .style(theme)
+                        .into(),
+                );
+                widgets.push(
+                    label_button!(&mut self.back, "Log in again")
+                        .on_press(Message::GoBack)
+                        .style(theme)
+                        .into(),
+                );
+            }
+            AuthPart::Protect => {
+                widgets.push(
+                    label_button!(&mut self.proceed, "Save")
+                        .on_press(Message::Proceed)
+                        .style(theme)
+                        .into(),
+                );
+                widgets.push(
+                    label_button!(&mut self.skip, "Don't remember me")
+                        .on_press(Message::SkipSaving)
+                        .style(theme)
+                        .into(),
+                );
+            }
+            AuthPart::Step(AuthType::Form) => {
+                widgets.push(
+                    label_button!(&mut self.proceed, "Proceed")
+                        .on_press(Message::Proceed)
+                        .style(theme)
+                        .into(),
+                );
+            }
+            AuthPart::Step(AuthType::Waiting) => {
+                widgets.push(label!("Waiting for the homeserver...").into());
+            }
+            AuthPart::Step(AuthType::Choice) => {}
+        }
+
         if self.can_go_back {
             widgets.push(
                 label_button!(&mut self.back, "Back")
@@ -181,35 +441,36 @@ impl LoginScreen {
             response: AuthStepResponse,
         ) -> Command<TopLevelMessage> {
             screen.waiting = true;
-            let inner = client.inner().clone();
-            Command::perform(
-                async move { inner.next_auth_step(response).await },
-                |result| {
-                    result.map_or_else(
-                        |err| TopLevelMessage::Error(Box::new(err.into())),
-                        |step| TopLevelMessage::LoginScreen(Message::AuthStep(step)),
-                    )
-                },
-            )
+            next_step_cmd(client, response)
         }
 
         match msg {
-            Message::FieldChanged(field, value) => {
-                if let Some(index) = self.fields.iter().position(|(key, _)| key == &field) {
-                    if let Some((_, (_, val, _))) = self.fields.get_mut(index) {
-                        *val = value;
+            Message::FieldChanged(index, value) => {
+                if let Some(field) = self.fields.get_mut(index) {
+                    field.value = value;
+                    field.error = None;
+                }
+            }
+            Message::ConfirmationChanged(index, value) => {
+                if let Some(field) = self.fields.get_mut(index) {
+                    if let Some((_, confirmation)) = &mut field.confirmation {
+                        *confirmation = value;
+                        field.error = None;
                     }
                 }
             }
             Message::GoBack => {
-                if let Some(client) = client {
+                if let AuthPart::Session = self.current_step {
+                    self.current_error.clear();
+                    self.reset_to_first_step();
+                } else if let Some(client) = client {
                     self.waiting = true;
-                    let inner = client.inner().clone();
+                    let requests = client.requests().clone();
                     return Command::perform(
-                        async move { inner.prev_auth_step().await },
+                        async move { requests.prev_auth_step().await },
                         |result| {
                             result.map_or_else(
-                                |err| TopLevelMessage::Error(Box::new(err.into())),
+                                |err| TopLevelMessage::Error(Box::new(err)),
                                 |step| TopLevelMessage::LoginScreen(Message::AuthStep(Some(step))),
                             )
                         },
@@ -222,52 +483,104 @@ impl LoginScreen {
                     return respond(self, client, response);
                 }
             }
-            Message::Proceed => {
-                if let (Some(client), AuthPart::Step(step)) = (client, self.current_step) {
-                    let response = match step {
-                        AuthType::Choice => unreachable!("choice is not handled here"),
-                        AuthType::Form => AuthStepResponse::form(
-                            self.fields
-                                .iter()
-                                .map(|(_, (_, value, r#type))| match r#type.as_str() {
-                                    "number" => Field::Number(value.parse().unwrap()),
-                                    "password" => Field::Bytes(value.as_bytes().to_vec()),
-                                    "new-password" => Field::Bytes(value.as_bytes().to_vec()),
-                                    _ => Field::String(value.clone()),
-                                })
-                                .collect(),
-                        ),
-                        _ => todo!("Implement waiting"),
-                    };
-                    return respond(self, client, response);
-                } else if let AuthPart::Homeserver = &self.current_step {
-                    if let Some(index) = self.fields.iter().position(|(key, _)| key == "homeserver")
-                    {
-                        if let Some(homeserver) = self
-                            .fields
-                            .get(index)
-                            .map(|(_, (_, homeserver, _))| homeserver.clone())
-                        {
-                            return match homeserver.parse::<Url>() {
-                                Ok(uri) => {
-                                    let content_store = content_store.clone();
-                                    self.waiting = true;
-                                    Command::perform(
-                                        Client::new(uri, None, content_store),
-                                        |result| {
-                                            result.map_or_else(
-                                                |err| TopLevelMessage::Error(Box::new(err)),
-                                                TopLevelMessage::ClientCreated,
-                                            )
-                                        },
-                                    )
-                                }
-                                Err(err) => {
-                                    self.on_error(ClientError::UrlParse(homeserver.clone(), err))
-                                }
-                            };
+            Message::Proceed => match (client, self.current_step) {
+                (Some(client), AuthPart::Step(AuthType::Form)) => {
+                    // Check every field, so that all of the mistakes are shown at once
+                    let fields = self
+                        .fields
+                        .iter_mut()
+                        .map(FormField::validate)
+                        .collect::<Vec<_>>();
+                    if let Some(fields) = fields.into_iter().collect::<Option<Vec<_>>>() {
+                        return respond(self, client, AuthStepResponse::form(fields));
+                    }
+                }
+                (_, AuthPart::Homeserver) => return self.connect(LOGIN_CHOICE, content_store),
+                (_, AuthPart::Session) => return self.restore_session(),
+                (_, AuthPart::Protect) => return self.save_session(client),
+                _ => {}
+            },
+            Message::Register => {
+                if let AuthPart::Homeserver = self.current_step {
+                    return self.connect(REGISTER_CHOICE, content_store);
+                }
+            }
+            Message::StepPushed(step_number, step) => {
+                if let (Some(client), AuthPart::Step(AuthType::Waiting), true) =
+                    (client, self.current_step, step_number == self.step_number)
+                {
+                    if let Some(Step::Session(session)) = &step.step {
+                        // The library doesn't keep pushed sessions, so log in with a new client
+                        self.waiting = true;
+                        let session =
+                            Session::new(client.inner().homeserver_url(), session.clone());
+                        let content_store = content_store.clone();
+                        return Command::perform(
+                            Client::from_session(session, content_store),
+                            |result| {
+                                result.map_or_else(
+                                    |err| TopLevelMessage::Error(Box::new(err)),
+                                    TopLevelMessage::ClientCreated,
+                                )
+                            },
+                        );
+                    }
+                    return self.update(Some(client), Message::AuthStep(Some(step)), content_store);
+                }
+            }
+            Message::RestoreSession => return self.restore_session(),
+            Message::RestoreFailed(err) => {
+                self.waiting = false;
+                tracing::error!("couldn't restore the session: {}", err);
+                match err {
+                    ClientError::PassphraseRequired | ClientError::WrongPassphrase => {
+                        self.ask_passphrase(
+                            FieldKind::Password,
+                            "unlock-session",
+                            "Your session is encrypted, enter its passphrase to unlock it.",
+                        );
+                        if let ClientError::WrongPassphrase = err {
+                            self.current_error = err.to_string();
                         }
                     }
+                    // The plain text session of an older version has to be encrypted instead
+                    ClientError::KeyringUnavailable(_) => self.ask_passphrase(
+                        FieldKind::NewPassword,
+                        "protect-session",
+                        KEYRING_UNAVAILABLE,
+                    ),
+                    // A session that can't be used has to be replaced by logging in again
+                    err if matches!(err.kind(), ErrorKind::Auth | ErrorKind::Validation) => {
+                        self.current_error =
+                            "Your session is no longer valid, please log in again.".to_string();
+                        self.reset_to_first_step();
+                        let content_store = content_store.clone();
+                        return Command::perform(
+                            async move { Session::remove(&content_store).await },
+                            |_| TopLevelMessage::Nothing,
+                        );
+                    }
+                    err => self.current_error = format!("Couldn't check your session: {}", err),
+                }
+            }
+            Message::SaveFailed(err) => {
+                self.waiting = false;
+                tracing::error!("couldn't save the session: {}", err);
+                self.current_step = AuthPart::Protect;
+                self.current_error = match err {
+                    ClientError::KeyringUnavailable(_) => String::new(),
+                    err => format!("Couldn't save your session: {}", err),
+                };
+                self.ask_passphrase(
+                    FieldKind::NewPassword,
+                    "protect-session",
+                    KEYRING_UNAVAILABLE,
+                );
+            }
+            Message::SkipSaving => {
+                if let AuthPart::Protect = self.current_step {
+                    self.waiting = true;
+                    return Command::perform(async {}, |()| TopLevelMessage::LoginComplete(None));
                 }
             }
             Message::AuthStep(step) => match step {
@@ -276,64 +589,129 @@ impl LoginScreen {
                     self.waiting = false;
                     self.fields.clear();
                     self.choices.clear();
+                    self.title.clear();
+                    self.description.clear();
                     self.can_go_back = step.can_go_back;
+                    self.step_number += 1;
 
-                    if let Some(step) = step.step {
-                        match step {
-                            Step::Choice(choice) => {
-                                for option in choice.options {
-                                    self.choices.insert(option, Default::default());
+                    match step.step {
+                        Some(Step::Choice(choice)) => {
+                            // Pick what the user already chose on the first screen, if it's offered
+                            if let Some(intent) = self.intent.take() {
+                                if let (Some(client), true) =
+                                    (client, choice.options.iter().any(|option| option == intent))
+                                {
+                                    return respond(
+                                        self,
+                                        client,
+                                        AuthStepResponse::Choice(intent.to_string()),
+                                    );
                                 }
-                                self.current_step = AuthPart::Step(AuthType::Choice);
                             }
-                            Step::Form(form) => {
-                                for field in form.fields {
-                                    self.fields.push((
-                                        field.name,
-                                        (Default::default(), Default::default(), field.r#type),
-                                    ));
-                                }
-                                self.current_step = AuthPart::Step(AuthType::Form);
+                            self.title = choice.title;
+                            self.choices = choice
+                                .options
+                                .into_iter()
+                                .map(|option| (option, Default::default()))
+                                .collect();
+                            self.current_step = AuthPart::Step(AuthType::Choice);
+                        }
+                        Some(Step::Form(form)) => {
+                            self.title = form.title;
+                            self.fields = form
+                                .fields
+                                .into_iter()
+                                .map(|field| {
+                                    FormField::new(field.name, FieldKind::from_type(&field.r#type))
+                                })
+                                .collect();
+                            self.current_step = AuthPart::Step(AuthType::Form);
+                        }
+                        Some(Step::Waiting(waiting)) => {
+                            self.title = waiting.title;
+                            self.description = waiting.description;
+                            self.current_step = AuthPart::Step(AuthType::Waiting);
+                            if let Some(client) = client {
+                                return wait_for_step_cmd(client, self.step_number);
                             }
-                            _ => todo!("Implement waiting"),
                         }
+                        // The library keeps the session instead of returning it as a step
+                        Some(Step::Session(_)) | None => {}
                     }
                 }
-                None => {
-                    self.waiting = true;
-                    // If these unwraps fail, then something is very wrong, so we abort here.
-                    // (How can there be no client, but we get authenticated?)
-                    // We *can* recover from here but it's not worth the effort
-                    let auth_status = client.unwrap().auth_status();
-                    let homeserver = client.unwrap().inner().homeserver_url().to_string();
-                    let session_file = content_store.session_file().to_path_buf();
-                    return Command::perform(
-                        async move {
-                            if let AuthStatus::Complete(session) = auth_status {
-                                let session = Session {
-                                    homeserver,
-                                    session_token: session.session_token,
-                                    user_id: session.user_id.to_string(),
-                                };
-
-                                // This should never ever fail in our case, if it does something is very very very wrong
-                                let ser = toml::ser::to_vec(&session).unwrap();
-                                tokio::fs::write(session_file, ser).await?;
-                            }
-                            Ok(TopLevelMessage::LoginComplete(None))
-                        },
-                        |result| result.unwrap_or_else(|err| TopLevelMessage::Error(Box::new(err))),
-                    );
-                }
+                None => return self.save_session(client),
             },
         }
         Command::none()
     }
 
+    /// Connects to the homeserver that was entered, to log in or register with `intent`.
+    fn connect(
+        &mut self,
+        intent: &'static str,
+        content_store: &Arc<ContentStore>,
+    ) -> Command<TopLevelMessage> {
+        let homeserver = match self.fields.first() {
+            Some(field) => field.value.trim().to_string(),
+            None => return Command::none(),
+        };
+        self.homeserver = homeserver.clone();
+
+        match homeserver.parse::<Url>() {
+            Ok(uri) => {
+                let content_store = content_store.clone();
+                self.waiting = true;
+                self.intent = Some(intent);
+                Command::perform(Client::new(uri, None, content_store), |result| {
+                    result.map_or_else(
+                        |err| TopLevelMessage::Error(Box::new(err)),
+                        TopLevelMessage::ClientCreated,
+                    )
+                })
+            }
+            Err(err) => self.on_error(ClientError::UrlParse(homeserver, err)),
+        }
+    }
+
     pub fn on_error(&mut self, error: ClientError) -> Command<TopLevelMessage> {
         self.current_error = error.to_string();
-        self.reset_to_first_step();
+        self.waiting = false;
+
+        // Mistakes in a form can be fixed without starting over, the homeserver keeps the step
+        match (self.current_step, error.kind()) {
+            (AuthPart::Step(_), ErrorKind::Validation)
+            | (AuthPart::Step(_), ErrorKind::RateLimit) => {}
+            _ => self.reset_to_first_step(),
+        }
 
         Command::none()
     }
 }
+
+/// Waits for the homeserver to push the step after the waiting step with `step_number`.
+fn wait_for_step_cmd(client: &Client, step_number: u64) -> Command<TopLevelMessage> {
+    let requests = client.requests().clone();
+    Command::perform(
+        async move { requests.wait_for_auth_step().await },
+        move |result| {
+            result.map_or_else(
+                |err| TopLevelMessage::Error(Box::new(err)),
+                |step| TopLevelMessage::LoginScreen(Message::StepPushed(step_number, step)),
+            )
+        },
+    )
+}
+
+/// Answers the current step, which results in the next step, or `None` once logged in.
+fn next_step_cmd(client: &Client, response: AuthStepResponse) -> Command<TopLevelMessage> {
+    let requests = client.requests().clone();
+    Command::perform(
+        async move { requests.next_auth_step(response).await },
+        |result| {
+            result.map_or_else(
+                |err| TopLevelMessage::Error(Box::new(err)),
+                |step| TopLevelMessage::LoginScreen(Message::AuthStep(step)),
+            )
+        },
+    )
+}
@@ -5,57 +5,68 @@ use std::{
 };
 
 use super::{Message as TopLevelMessage, Screen as TopLevelScreen};
-use ahash::AHashMap;
-use channel::{get_channel_messages, GetChannelMessages};
-use chat::Typing;
-use harmony_rust_sdk::{
-    api::{
-        chat::event::{ChannelCreated, Event, MemberJoined, MessageSent},
-        harmonytypes::UserStatus,
-    },
-    client::api::{
-        chat::{
-            self,
-            channel::{self, get_guild_channels, GetChannelMessagesSelfBuilder},
-            guild::get_guild_members,
-            permissions::{self, QueryPermissions, QueryPermissionsSelfBuilder},
-            GuildId,
-        },
-        rest::download_extract_file,
-    },
-};
+use ahash::{AHashMap, AHashSet};
+use harmony_rust_sdk::{api::harmonytypes::UserStatus, client::api::rest::FileId};
 use iced_aw::{modal, Modal};
 use indexmap::IndexMap;
 
+use actions::{Action, KeyBinding, Keybindings};
+use bulk_delete::BulkDeleteModal;
 use chan_guild_list::build_guild_list;
+use channel_menu::ChannelMenuModal;
+use command_palette::CommandPaletteModal;
 use create_channel::ChannelCreationModal;
+use delete_message::DeleteMessageModal;
+use embed_editor::EmbedEditorModal;
 use help::HelpModal;
-use image_viewer::ImageViewerModal;
+use image_viewer::{ImageViewerModal, ViewedImage};
 use logout::LogoutModal;
+use member_card::MemberCardModal;
+use message_menu::MessageMenuModal;
 use profile_edit::ProfileEditModal;
+use search::SearchModal;
 
 use crate::{
     client::{
-        content::{ImageHandle, ThumbnailCache},
+        change::StateChange,
         error::ClientError,
-        message::{Attachment, Content as IcyContent, Message as IcyMessage},
+        message::{Attachment, Content as IcyContent, Message as IcyMessage, MessageId},
+        permission::Node,
+        scheduler::{Priority, RequestKey},
         Client,
     },
     label, label_button, length, space,
     ui::{
-        component::{event_history::SHOWN_MSGS_LIMIT, *},
+        client::ClientExt,
+        component::{
+            event_history::{
+                message_summary, reply_preview, HistoryLayout, HistoryView, MessageButtonsState,
+            },
+            *,
+        },
+        config::Config,
         style::{Theme, ALT_COLOR, AVATAR_WIDTH, ERROR_COLOR, MESSAGE_SIZE, PADDING, SPACING},
+        thumbnail::{ImageHandle, ThumbnailCache},
     },
 };
 
 use self::quick_switcher::QuickSwitcherModal;
 
+pub mod actions;
+pub mod bulk_delete;
+pub mod channel_menu;
+pub mod command_palette;
 pub mod create_channel;
+pub mod delete_message;
+pub mod embed_editor;
 pub mod help;
 pub mod image_viewer;
 pub mod logout;
+pub mod member_card;
+pub mod message_menu;
 pub mod profile_edit;
 pub mod quick_switcher;
+pub mod search;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Mode {
@@ -70,11 +81,64 @@ impl Default for Mode {
     }
 }
 
+/// The dialogs that can be shown over the main screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialog {
+    Help,
+    ProfileEdit,
+    QuickSwitcher,
+    Search,
+    CommandPalette,
+    Logout,
+    CreateChannel,
+    MemberCard,
+    ChannelMenu,
+    BulkDelete,
+    MessageMenu,
+    DeleteMessage,
+    EmbedEditor,
+    ImageViewer,
+}
+
+/// A message that was removed from its channel, and will be deleted once the undo window is over.
+#[derive(Debug)]
+struct PendingDelete {
+    guild_id: u64,
+    channel_id: u64,
+    message: IcyMessage,
+    /// Whether the server was asked to delete the message, after which it can't be undone.
+    sent: bool,
+}
+
+/// Statuses the user can pick from the user menu.
+const STATUS_OPTIONS: [(&str, UserStatus); 4] = [
+    ("Set status: Online", UserStatus::OnlineUnspecified),
+    ("Set status: Idle", UserStatus::Idle),
+    ("Set status: Do not disturb", UserStatus::DoNotDisturb),
+    ("Set status: Invisible", UserStatus::Offline),
+];
+/// How often to check if the user became idle.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// How long a deleted message can be restored before it is deleted on the server.
+const DELETE_UNDO_WINDOW: Duration = Duration::from_secs(5);
+/// Width taken by the guild, channel and member lists, used until the history is measured.
+const HISTORY_SIDE_WIDTH: f32 = 64.0 + 200.0 + 200.0;
+/// Height taken by the typing indicator and the composer below the message history, used until
+/// the history is measured.
+const HISTORY_BOTTOM_HEIGHT: f32 = 100.0;
+
 #[derive(Debug, Clone)]
 pub enum Message {
     EditLastMessage,
     QuickSwitch,
     ChangeMode(Mode),
+    /// Sent when the user releases a key, `captured` is whether a widget used it.
+    KeyReleased {
+        binding: KeyBinding,
+        captured: bool,
+    },
+    RunAction(Action),
+    CancelReply,
     ClearError,
     /// Sent when the user wants to send a message.
     SendMessageComposer {
@@ -96,7 +160,7 @@ pub enum Message {
     OpenImageView {
         handle: ImageHandle,
         path: PathBuf,
-        name: String,
+        attachment: Attachment,
     },
     OpenUrl(String),
     /// Sent when the user selects a different guild.
@@ -108,29 +172,79 @@ pub enum Message {
         prev_scroll_perc: f32,
         scroll_perc: f32,
     },
+    /// Sent when older messages were loaded into a channel.
+    HistoryLoaded {
+        guild_id: u64,
+        channel_id: u64,
+    },
     /// Sent when the user selects an option from the bottom menu.
     SelectedMenuOption(String),
     SelectedChannelMenuOption(String),
     SelectedMember(u64),
+    /// Sent when the user clicks on a message to see what they can do with it.
+    OpenMessageMenu(u64),
+    /// Sent when the user wants to delete a message, to ask them for confirmation.
+    DeleteMessage(u64),
+    /// Sent when the undo window of a deleted message is over.
+    CommitDelete(u64),
+    /// Restores the last message deleted in the current channel, if it's still in the undo window.
+    UndoDelete,
+    /// Sent when the server answered a message deletion.
+    DeleteFinished {
+        guild_id: u64,
+        channel_id: u64,
+        message_id: u64,
+        err: Option<Box<ClientError>>,
+    },
+    DismissDeleteError,
+    /// Sent when the user wants to scroll the message history to a message.
+    JumpToMessage(u64),
+    /// Sent when the pinned messages of a channel were fetched.
+    PinsLoaded {
+        guild_id: u64,
+        channel_id: u64,
+        message_ids: Vec<u64>,
+    },
     LogoutChoice(bool),
-    ChannelViewPerm(u64, bool),
     ChannelCreationMessage(create_channel::Message),
+    ChannelMenuMessage(channel_menu::Message),
+    ChannelListAction(ChannelListAction),
     ImageViewMessage(image_viewer::Message),
     QuickSwitchMsg(quick_switcher::Message),
+    SearchMsg(search::Message),
+    CommandPaletteMsg(command_palette::Message),
     ProfileEditMsg(profile_edit::Message),
+    MemberCardMsg(member_card::Message),
+    MessageMenuMsg(message_menu::Message),
+    DeleteMessageMsg(delete_message::Message),
+    EmbedEditorMsg(embed_editor::Message),
+    BulkDeleteMsg(bulk_delete::Message),
     HelpModal(help::Message),
+    /// Sent when the message history is laid out with a different size.
+    HistoryResized(Size),
+    /// Sent when the user presses a key, clicks or scrolls.
+    UserActivity,
+    /// Sent periodically to check if the user should be marked as idle.
+    CheckIdle,
 }
 
 #[derive(Debug, Default)]
 pub struct MainScreen {
     // Event history area state
     event_history_state: scrollable::State,
-    content_open_buts_state: [button::State; SHOWN_MSGS_LIMIT],
-    edit_buts_sate: [button::State; SHOWN_MSGS_LIMIT],
+    history_buts_state: AHashMap<MessageId, MessageButtonsState>,
+    history_layout: HistoryLayout,
+    /// Size of the message history, as it was last laid out.
+    history_size: Option<Size>,
+    /// Scroll positions of the channels, keyed by `(guild_id, channel_id)`.
+    history_views: AHashMap<(u64, u64), HistoryView>,
+    window_size: (u32, u32),
     send_file_but_state: button::State,
     composer_state: text_input::State,
     scroll_to_bottom_but_state: button::State,
-    embed_buttons_state: [(button::State, button::State); SHOWN_MSGS_LIMIT],
+    cancel_reply_but_state: button::State,
+    undo_delete_but_state: button::State,
+    dismiss_delete_error_but_state: button::State,
 
     // Room area state
     channel_menu_state: pick_list::State<String>,
@@ -138,15 +252,28 @@ pub struct MainScreen {
     guilds_list_state: scrollable::State,
     guilds_buts_state: Vec<button::State>,
     channels_list_state: scrollable::State,
-    channels_buts_state: Vec<button::State>,
+    channels_buts_state: Vec<ChannelButtonsState>,
     members_buts_state: Vec<button::State>,
     members_list_state: scrollable::State,
+    hide_members_list: bool,
+    pinned_list_state: scrollable::State,
+    pinned_buts_state: Vec<button::State>,
+    /// Whether the pinned messages are shown instead of the members list.
+    show_pinned: bool,
 
     logout_modal: modal::State<LogoutModal>,
     create_channel_modal: modal::State<ChannelCreationModal>,
+    channel_menu_modal: modal::State<ChannelMenuModal>,
     pub image_viewer_modal: modal::State<ImageViewerModal>,
     quick_switcher_modal: modal::State<QuickSwitcherModal>,
+    search_modal: modal::State<SearchModal>,
+    command_palette_modal: modal::State<CommandPaletteModal>,
     profile_edit_modal: modal::State<ProfileEditModal>,
+    member_card_modal: modal::State<MemberCardModal>,
+    message_menu_modal: modal::State<MessageMenuModal>,
+    delete_message_modal: modal::State<DeleteMessageModal>,
+    embed_editor_modal: modal::State<EmbedEditorModal>,
+    bulk_delete_modal: modal::State<BulkDeleteModal>,
     help_modal: modal::State<HelpModal>,
 
     // Join room screen state
@@ -154,15 +281,346 @@ pub struct MainScreen {
     guild_last_channels: IndexMap<u64, u64>,
     current_guild_id: Option<u64>,
     current_channel_id: Option<u64>,
+    /// Categories whose channels are hidden in the channel list.
+    collapsed_categories: AHashSet<u64>,
+    /// The `(guild_id, channel_id, message_id)` of a message to jump to once older messages are
+    /// loaded up to it.
+    pending_jump: Option<(u64, u64, u64)>,
+    /// The channel the user marked as unread, which new messages don't mark as read until they
+    /// leave it.
+    kept_unread: Option<(u64, u64)>,
+    /// The channel that is being dragged in the channel list to move it.
+    dragged_channel: Option<ChannelDrag>,
     /// The message the user is currently typing.
     message: String,
+    /// The message the user is replying to with the message they are typing.
+    replying_to: Option<u64>,
+    /// Messages that were deleted, oldest first, until the server confirms their deletion.
+    pending_deletes: Vec<PendingDelete>,
+    /// Why the server refused to delete a message.
+    delete_error: Option<String>,
     error_text: String,
     error_close_but_state: button::State,
     mode: Mode,
-    has_permission_to_send_msg: AHashMap<u64, bool>,
+    /// The attachment whose caption is edited, when editing a message with files.
+    editing_attachment: usize,
+    keybindings: Keybindings,
+
+    // Idle detection state
+    idle_timeout: Option<Duration>,
+    last_activity: Option<Instant>,
+    /// The status to restore when the user is back, if they were automatically marked as idle.
+    status_before_idle: Option<UserStatus>,
 }
 
 impl MainScreen {
+    pub fn new(config: &Config, window_size: (u32, u32)) -> Self {
+        Self {
+            idle_timeout: config.idle_timeout(),
+            keybindings: Keybindings::new(&config.keybindings),
+            window_size,
+            ..Default::default()
+        }
+    }
+
+    pub fn window_resized(&mut self, width: u32, height: u32) {
+        self.window_size = (width, height);
+    }
+
+    /// Returns the `(width, height)` of the message history, estimated from the window size if it
+    /// wasn't laid out yet.
+    fn history_viewport(&self) -> (f32, f32) {
+        match self.history_size {
+            Some(size) => (size.width, size.height),
+            None => {
+                let (width, height) = self.window_size;
+                (
+                    (width as f32 - HISTORY_SIDE_WIDTH).max(100.0),
+                    (height as f32 - HISTORY_BOTTOM_HEIGHT).max(100.0),
+                )
+            }
+        }
+    }
+
+    /// Scrolls the message history to an offset, relative to the current layout.
+    fn snap_history_to(&mut self, offset: f32) {
+        let (_, viewport_height) = self.history_viewport();
+        let max_offset = (self.history_layout.total_height() - viewport_height).max(0.0);
+        if max_offset > 0.0 {
+            self.event_history_state.snap_to(offset / max_offset);
+        }
+    }
+
+    fn scroll_history_to_bottom(&mut self, guild_id: u64, channel_id: u64) {
+        self.history_views
+            .insert((guild_id, channel_id), HistoryView::default());
+        self.event_history_state.scroll_to_bottom();
+    }
+
+    /// Returns the guild and channel the user is looking at.
+    pub fn current_channel(&self) -> Option<(u64, u64)> {
+        self.current_guild_id.zip(self.current_channel_id)
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Returns the message the user is typing.
+    pub fn composer_text(&self) -> &str {
+        &self.message
+    }
+
+    pub fn replying_to(&self) -> Option<u64> {
+        self.replying_to
+    }
+
+    /// Returns the error shown in the banner at the top, if there is one.
+    pub fn error_text(&self) -> Option<&str> {
+        (!self.error_text.is_empty()).then(|| self.error_text.as_str())
+    }
+
+    pub fn quick_switcher_results(&self) -> &[quick_switcher::SearchResult] {
+        &self.quick_switcher_modal.inner().results
+    }
+
+    pub fn search_results(&self) -> &[search::SearchResult] {
+        &self.search_modal.inner().results
+    }
+
+    /// Lets the embed editor know an edit of a message is done, closing it if it saved that edit.
+    ///
+    /// Returns `false` if the editor wasn't waiting for the edit, so the error wasn't shown in it.
+    pub fn embed_edit_finished(&mut self, message_id: u64, err: Option<&ClientError>) -> bool {
+        if !self.embed_editor_modal.is_shown()
+            || !self
+                .embed_editor_modal
+                .inner_mut()
+                .edit_finished(message_id, err)
+        {
+            return false;
+        }
+        if err.is_none() {
+            self.embed_editor_modal.show(false);
+        }
+
+        true
+    }
+
+    /// Returns where the user is in the message history of a channel.
+    pub fn history_view(&self, guild_id: u64, channel_id: u64) -> HistoryView {
+        self.history_views
+            .get(&(guild_id, channel_id))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn history_layout(&self) -> &HistoryLayout {
+        &self.history_layout
+    }
+
+    /// Measures the messages showing a thumbnail again, now that it's loaded.
+    pub fn on_thumbnail_loaded(&mut self, id: FileId) {
+        self.history_layout.thumbnail_loaded(id);
+    }
+
+    /// Measures every message again, since changes to the state of the client were missed.
+    pub fn on_changes_missed(&mut self) {
+        self.history_layout.invalidate_all();
+    }
+
+    /// Returns the dialogs that are shown, from the bottom one to the top one.
+    pub fn shown_dialogs(&self) -> Vec<Dialog> {
+        vec![
+            (Dialog::Help, self.help_modal.is_shown()),
+            (Dialog::ProfileEdit, self.profile_edit_modal.is_shown()),
+            (Dialog::QuickSwitcher, self.quick_switcher_modal.is_shown()),
+            (Dialog::Search, self.search_modal.is_shown()),
+            (
+                Dialog::CommandPalette,
+                self.command_palette_modal.is_shown(),
+            ),
+            (Dialog::Logout, self.logout_modal.is_shown()),
+            (Dialog::CreateChannel, self.create_channel_modal.is_shown()),
+            (Dialog::MemberCard, self.member_card_modal.is_shown()),
+            (Dialog::ChannelMenu, self.channel_menu_modal.is_shown()),
+            (Dialog::BulkDelete, self.bulk_delete_modal.is_shown()),
+            (Dialog::MessageMenu, self.message_menu_modal.is_shown()),
+            (Dialog::DeleteMessage, self.delete_message_modal.is_shown()),
+            (Dialog::EmbedEditor, self.embed_editor_modal.is_shown()),
+            (Dialog::ImageViewer, self.image_viewer_modal.is_shown()),
+        ]
+        .into_iter()
+        .filter_map(|(dialog, shown)| shown.then(|| dialog))
+        .collect()
+    }
+
+    /// Marks the current channel as read if the user is looking at its newest messages.
+    pub fn mark_current_channel_read(&self, client: &mut Client) {
+        if let Some((guild_id, channel_id)) = self
+            .current_guild_id
+            .zip(self.current_channel_id)
+            .filter(|current| self.kept_unread != Some(*current))
+        {
+            let at_bottom = self
+                .history_views
+                .get(&(guild_id, channel_id))
+                .map_or(true, |view| view.at_bottom);
+            if let (true, Some(channel)) = (at_bottom, client.get_channel(guild_id, channel_id)) {
+                channel.unread = 0;
+            }
+        }
+    }
+
+    /// Updates what is shown from a change to the state of the client.
+    ///
+    /// `shown` is whether this screen is the one the user sees, and not one under another screen.
+    pub fn on_state_change(
+        &mut self,
+        change: StateChange,
+        shown: bool,
+        client: &mut Client,
+        thumbnail_cache: &ThumbnailCache,
+        clip: &mut dyn iced_native::Clipboard,
+    ) -> Command<TopLevelMessage> {
+        match change {
+            StateChange::MessageInserted {
+                guild_id,
+                channel_id,
+                ..
+            }
+            | StateChange::MessageRemoved {
+                guild_id,
+                channel_id,
+                ..
+            }
+            | StateChange::HistoryLoaded {
+                guild_id,
+                channel_id,
+            } => self.history_layout.invalidate_channel(guild_id, channel_id),
+            StateChange::MessageUpdated {
+                guild_id,
+                channel_id,
+                message_id,
+            } => self
+                .history_layout
+                .invalidate_message(guild_id, channel_id, message_id),
+            // Members leaving are noted in every channel of the guild
+            StateChange::MemberLeft { guild_id, .. } => {
+                self.history_layout.invalidate_guild(guild_id)
+            }
+            _ => {}
+        }
+
+        let current = self.current_channel();
+        match change {
+            // Messages that arrive in the channel the user is looking at are already read
+            StateChange::MessageInserted {
+                guild_id,
+                channel_id,
+                message_id: MessageId::Ack(_),
+            } if shown && current == Some((guild_id, channel_id)) => {
+                self.mark_current_channel_read(client);
+            }
+            StateChange::HistoryLoaded {
+                guild_id,
+                channel_id,
+            } => {
+                return self.update(
+                    Message::HistoryLoaded {
+                        guild_id,
+                        channel_id,
+                    },
+                    client,
+                    thumbnail_cache,
+                    clip,
+                );
+            }
+            StateChange::MessageRemoved {
+                guild_id,
+                channel_id,
+                message_id,
+            } if current == Some((guild_id, channel_id)) => {
+                if self.replying_to == Some(message_id) {
+                    self.replying_to = None;
+                }
+                if self.mode == Mode::EditingMessage(message_id) {
+                    return self.update(
+                        Message::ChangeMode(Mode::Normal),
+                        client,
+                        thumbnail_cache,
+                        clip,
+                    );
+                }
+            }
+            StateChange::ChannelRemoved {
+                guild_id,
+                channel_id,
+            } if current == Some((guild_id, channel_id)) => {
+                self.current_channel_id = None;
+            }
+            StateChange::GuildRemoved(guild_id) if self.current_guild_id == Some(guild_id) => {
+                self.current_guild_id = None;
+                self.current_channel_id = None;
+            }
+            _ => {}
+        }
+
+        Command::none()
+    }
+
+    /// Fetches the pinned messages of the current channel if they are shown and weren't fetched yet.
+    fn fetch_pins_if_needed(&self, client: &mut Client) -> Command<TopLevelMessage> {
+        match self.current_guild_id.zip(self.current_channel_id) {
+            Some((guild_id, channel_id))
+                if self.show_pinned
+                    && client
+                        .get_channel(guild_id, channel_id)
+                        .map_or(false, |channel| channel.pinned.is_none()) =>
+            {
+                client.fetch_pins_cmd(guild_id, channel_id)
+            }
+            _ => Command::none(),
+        }
+    }
+
+    /// Returns the next (or previous) channel with unread messages, after the current channel.
+    fn unread_channel(&self, client: &Client, forward: bool) -> Option<(u64, u64)> {
+        let channels = client
+            .guilds
+            .iter()
+            .flat_map(|(guild_id, guild)| {
+                guild
+                    .channels
+                    .iter()
+                    .filter(|(_, channel)| !channel.is_category)
+                    .map(move |(channel_id, channel)| (*guild_id, *channel_id, channel.unread))
+            })
+            .collect::<Vec<_>>();
+        if channels.is_empty() {
+            return None;
+        }
+
+        let len = channels.len();
+        let current = self.current_guild_id.zip(self.current_channel_id);
+        // Start so that the first step lands on the first (or last) channel if none is selected
+        let origin = channels
+            .iter()
+            .position(|(guild_id, channel_id, _)| Some((*guild_id, *channel_id)) == current)
+            .unwrap_or(if forward { len - 1 } else { 0 });
+
+        (1..=len)
+            .map(|step| {
+                if forward {
+                    channels[(origin + step) % len]
+                } else {
+                    channels[(origin + len - step) % len]
+                }
+            })
+            .find(|(_, _, unread)| *unread > 0)
+            .map(|(guild_id, channel_id, _)| (guild_id, channel_id))
+    }
+
     pub fn view<'a>(
         &'a mut self,
         theme: Theme,
@@ -205,17 +663,22 @@ impl MainScreen {
             .get(&current_user_id)
             .map_or_else(|| String::from("unknown"), |member| member.username.clone());
 
+        let mut menu_entries = vec![
+            current_username.clone(),
+            "Join / Create a Guild".to_string(),
+            "Edit Profile".to_string(),
+        ];
+        menu_entries.extend(STATUS_OPTIONS.iter().map(|(name, _)| name.to_string()));
+        menu_entries.extend(vec![
+            "Help".to_string(),
+            "Logout".to_string(),
+            "Exit".to_string(),
+        ]);
+
         // TODO: show user avatar next to name
         let menu = PickList::new(
             &mut self.menu_state,
-            vec![
-                current_username.clone(),
-                "Join / Create a Guild".to_string(),
-                "Edit Profile".to_string(),
-                "Help".to_string(),
-                "Logout".to_string(),
-                "Exit".to_string(),
-            ],
+            menu_entries,
             Some(current_username),
             Message::SelectedMenuOption,
         )
@@ -301,14 +764,27 @@ impl MainScreen {
                 );
             }
 
-            let mut channel_menu_entries = vec![
-                guild.name.clone(),
-                "New Channel".to_string(),
-                "Edit Guild".to_string(),
-                "Copy Guild ID".to_string(), // [tag:copy_guild_id_menu_entry]
-            ];
-            if self.current_channel_id.is_some() {
+            let has = |channel_id, node| client.permissions.has(guild_id, channel_id, node);
+            let mut channel_menu_entries = vec![guild.name.clone()];
+            if has(None, Node::ManageChannels) {
+                channel_menu_entries.push("New Channel".to_string());
+            }
+            if has(None, Node::ManageGuild) {
+                channel_menu_entries.push("Edit Guild".to_string());
+            }
+            channel_menu_entries.push("Copy Guild ID".to_string()); // [tag:copy_guild_id_menu_entry]
+            if has(None, Node::CreateInvites) {
+                channel_menu_entries.push("Create invite".to_string());
+            }
+            if has(None, Node::Ban) {
+                channel_menu_entries.push("Bans".to_string());
+            }
+            if let Some(channel_id) = self.current_channel_id {
                 channel_menu_entries.push("Copy Channel ID".to_string()); // [tag:copy_channel_id_menu_entry]
+                if has(Some(channel_id), Node::DeleteMessages) {
+                    channel_menu_entries.push("Delete messages".to_string());
+                }
+                channel_menu_entries.push("Pinned messages".to_string());
             }
 
             let channel_menu = PickList::new(
@@ -332,9 +808,13 @@ impl MainScreen {
                 build_channel_list(
                     &guild.channels,
                     self.current_channel_id,
+                    &self.collapsed_categories,
+                    self.dragged_channel,
                     &mut self.channels_list_state,
                     &mut self.channels_buts_state,
+                    client.permissions.has(guild_id, None, Node::ManageChannels),
                     Message::ChannelChanged,
+                    Message::ChannelListAction,
                     theme,
                 )
             };
@@ -355,18 +835,30 @@ impl MainScreen {
                 .map(|id| Some((guild.channels.get(id)?, *id)))
                 .flatten()
             {
-                let message_count = channel.messages.len();
+                let (history_width, history_height) = self.history_viewport();
+                self.history_layout.update(
+                    guild_id,
+                    channel_id,
+                    channel,
+                    thumbnail_cache,
+                    history_width,
+                );
+                let history_view = self
+                    .history_views
+                    .get(&(guild_id, channel_id))
+                    .copied()
+                    .unwrap_or_default();
                 let message_history_list = build_event_history(
                     client.content_store(),
                     thumbnail_cache,
                     channel,
                     &client.members,
                     current_user_id,
-                    channel.looking_at_message,
+                    &self.history_layout,
+                    history_view,
+                    history_height,
                     &mut self.event_history_state,
-                    &mut self.content_open_buts_state,
-                    &mut self.embed_buttons_state,
-                    &mut self.edit_buts_sate,
+                    &mut self.history_buts_state,
                     self.mode,
                     theme,
                 );
@@ -380,13 +872,8 @@ impl MainScreen {
                             return None;
                         }
 
-                        if member.typing_in_channel.map(|(g, c, _)| (g, c))
-                            == Some((guild_id, channel_id))
-                        {
-                            Some(member.username.as_str())
-                        } else {
-                            None
-                        }
+                        (client.ephemeral.typing_in(**id) == Some((guild_id, channel_id)))
+                            .then(|| member.username.as_str())
                     })
                     .collect::<Vec<_>>();
 
@@ -429,45 +916,46 @@ impl MainScreen {
                     channel_id,
                 });
 
-                let message_composer = if self
-                    .has_permission_to_send_msg
-                    .get(&channel_id)
-                    .copied()
-                    .unwrap_or(false)
-                {
-                    match self.mode {
-                        Mode::Normal | Mode::EditingMessage(_) => TextInput::new(
-                            &mut self.composer_state,
-                            "Enter your message here...",
-                            self.message.as_str(),
-                            Message::ComposerMessageChanged,
-                        )
-                        .padding((PADDING / 4) * 3)
-                        .size(MESSAGE_SIZE)
-                        .style(theme.secondary())
-                        .on_submit(Message::SendMessageComposer {
-                            guild_id,
-                            channel_id,
-                        })
-                        .width(length!(+))
-                        .into(),
-                        Mode::EditMessage => fill_container(label!("Select a message to edit..."))
+                let message_composer =
+                    if client
+                        .permissions
+                        .has(guild_id, Some(channel_id), Node::SendMessages)
+                    {
+                        match self.mode {
+                            Mode::Normal | Mode::EditingMessage(_) => TextInput::new(
+                                &mut self.composer_state,
+                                "Enter your message here...",
+                                self.message.as_str(),
+                                Message::ComposerMessageChanged,
+                            )
                             .padding((PADDING / 4) * 3)
-                            .height(length!(-))
+                            .size(MESSAGE_SIZE)
                             .style(theme.secondary())
+                            .on_submit(Message::SendMessageComposer {
+                                guild_id,
+                                channel_id,
+                            })
+                            .width(length!(+))
                             .into(),
-                    }
-                } else {
-                    fill_container(label!("You don't have permission to send a message here"))
-                        .padding((PADDING / 4) * 3)
-                        .height(length!(-))
-                        .style(theme.secondary())
-                        .into()
-                };
+                            Mode::EditMessage => {
+                                fill_container(label!("Select a message to edit..."))
+                                    .padding((PADDING / 4) * 3)
+                                    .height(length!(-))
+                                    .style(theme.secondary())
+                                    .into()
+                            }
+                        }
+                    } else {
+                        fill_container(label!("You don't have permission to send a message here"))
+                            .padding((PADDING / 4) * 3)
+                            .height(length!(-))
+                            .style(theme.secondary())
+                            .into()
+                    };
 
                 let mut bottom_area_widgets = vec![send_file_button.into(), message_composer];
 
-                if channel.looking_at_message < message_count.saturating_sub(SHOWN_MSGS_LIMIT) {
+                if !history_view.at_bottom {
                     bottom_area_widgets.push(
                         Button::new(
                             &mut self.scroll_to_bottom_but_state,
@@ -481,9 +969,81 @@ impl MainScreen {
                     );
                 }
 
-                let message_area = Column::with_children(vec![
+                let message_history_list = Measured::new(
                     message_history_list,
-                    typing_users.into(),
+                    self.history_size,
+                    Message::HistoryResized,
+                );
+                let mut message_area_widgets =
+                    vec![message_history_list.into(), typing_users.into()];
+                if let Some(reply_to) = self.replying_to {
+                    message_area_widgets.push(
+                        Container::new(
+                            Row::with_children(vec![
+                                label!(reply_preview(channel, &client.members, reply_to))
+                                    .size(MESSAGE_SIZE - 4)
+                                    .color(ALT_COLOR)
+                                    .into(),
+                                space!(w+).into(),
+                                label_button!(&mut self.cancel_reply_but_state, "Cancel")
+                                    .on_press(Message::CancelReply)
+                                    .style(theme.secondary())
+                                    .into(),
+                            ])
+                            .align_items(align!(|)),
+                        )
+                        .width(length!(+))
+                        .padding(PADDING / 4)
+                        .into(),
+                    );
+                }
+                if self.pending_deletes.iter().any(|pending| {
+                    !pending.sent
+                        && pending.guild_id == guild_id
+                        && pending.channel_id == channel_id
+                }) {
+                    message_area_widgets.push(
+                        Container::new(
+                            Row::with_children(vec![
+                                label!("Message deleted")
+                                    .size(MESSAGE_SIZE - 4)
+                                    .color(ALT_COLOR)
+                                    .into(),
+                                space!(w+).into(),
+                                label_button!(&mut self.undo_delete_but_state, "Undo")
+                                    .on_press(Message::UndoDelete)
+                                    .style(theme.secondary())
+                                    .into(),
+                            ])
+                            .align_items(align!(|)),
+                        )
+                        .width(length!(+))
+                        .padding(PADDING / 4)
+                        .into(),
+                    );
+                }
+                if let Some(error) = &self.delete_error {
+                    message_area_widgets.push(
+                        Container::new(
+                            Row::with_children(vec![
+                                label!("Couldn't delete the message: {}", error)
+                                    .size(MESSAGE_SIZE - 4)
+                                    .color(ERROR_COLOR)
+                                    .into(),
+                                space!(w+).into(),
+                                label_button!(&mut self.dismiss_delete_error_but_state, "Dismiss")
+                                    .on_press(Message::DismissDeleteError)
+                                    .style(theme.secondary())
+                                    .into(),
+                            ])
+                            .align_items(align!(|)),
+                        )
+                        .width(length!(+))
+                        .padding(PADDING / 4)
+                        .into(),
+                    );
+                }
+                message_area_widgets.push(
                     Container::new(
                         Row::with_children(bottom_area_widgets)
                             .spacing(SPACING * 2)
@@ -492,7 +1052,8 @@ impl MainScreen {
                     .width(length!(+))
                     .padding(PADDING / 2)
                     .into(),
-                ]);
+                );
+                let message_area = Column::with_children(message_area_widgets);
 
                 screen_widgets.push(fill_container(message_area).style(theme.secondary()).into());
             } else {
@@ -502,9 +1063,26 @@ impl MainScreen {
 
                 screen_widgets.push(no_selected_channel_warning.into());
             }
+            let mut right_column = vec![menu.into()];
+            let current_channel = self
+                .current_channel_id
+                .map(|id| guild.channels.get(&id))
+                .flatten();
+            if let (true, Some(channel)) = (self.show_pinned, current_channel) {
+                right_column.push(build_pinned_list(
+                    channel,
+                    &client.members,
+                    &mut self.pinned_list_state,
+                    &mut self.pinned_buts_state,
+                    Message::JumpToMessage,
+                    theme,
+                ));
+            } else if !self.hide_members_list {
+                right_column.push(members_list.into());
+            }
             screen_widgets.push(
                 Container::new(
-                    Column::with_children(vec![menu.into(), members_list.into()])
+                    Column::with_children(right_column)
                         .width(length!(+))
                         .height(length!(+)),
                 )
@@ -570,9 +1148,11 @@ impl MainScreen {
             .into()
         };
 
+        let keybindings = &self.keybindings;
+
         // Show HelpModal
         let content = Modal::new(&mut self.help_modal, content, move |state| {
-            state.view(theme).map(Message::HelpModal)
+            state.view(theme, keybindings).map(Message::HelpModal)
         })
         .style(theme)
         .backdrop(Message::HelpModal(true))
@@ -596,6 +1176,24 @@ impl MainScreen {
         .backdrop(Message::QuickSwitch)
         .on_esc(Message::QuickSwitch);
 
+        // Show SearchModal
+        let content = Modal::new(&mut self.search_modal, content, move |state| {
+            state.view(theme).map(Message::SearchMsg)
+        })
+        .style(theme)
+        .backdrop(Message::RunAction(Action::Search))
+        .on_esc(Message::RunAction(Action::Search));
+
+        // Show CommandPaletteModal
+        let content = Modal::new(&mut self.command_palette_modal, content, move |state| {
+            state
+                .view(theme, keybindings)
+                .map(Message::CommandPaletteMsg)
+        })
+        .style(theme)
+        .backdrop(Message::RunAction(Action::CommandPalette))
+        .on_esc(Message::RunAction(Action::CommandPalette));
+
         // Show LogoutModal
         let content = Modal::new(&mut self.logout_modal, content, move |state| {
             state.view(theme).map(Message::LogoutChoice)
@@ -616,7 +1214,60 @@ impl MainScreen {
             .on_esc(Message::ChannelCreationMessage(
                 create_channel::Message::GoBack,
             ));
+            let guild_id = self.current_guild_id.unwrap();
+            // Show MemberCardModal, if a guild is selected
+            let content = Modal::new(&mut self.member_card_modal, content, move |state| {
+                state
+                    .view(theme, client, guild_id, thumbnail_cache)
+                    .map(Message::MemberCardMsg)
+            })
+            .style(theme)
+            .backdrop(Message::MemberCardMsg(member_card::Message::Back))
+            .on_esc(Message::MemberCardMsg(member_card::Message::Back));
+            // Show ChannelMenuModal, if a guild is selected
+            let content = Modal::new(&mut self.channel_menu_modal, content, move |state| {
+                state
+                    .view(theme, client, guild_id)
+                    .map(Message::ChannelMenuMessage)
+            })
+            .style(theme)
+            .backdrop(Message::ChannelMenuMessage(channel_menu::Message::GoBack))
+            .on_esc(Message::ChannelMenuMessage(channel_menu::Message::GoBack));
             if self.current_channel_id.is_some() {
+                // Show BulkDeleteModal, if a guild and a channel are selected
+                let content = Modal::new(&mut self.bulk_delete_modal, content, move |state| {
+                    state.view(theme).map(Message::BulkDeleteMsg)
+                })
+                .style(theme)
+                .backdrop(Message::BulkDeleteMsg(bulk_delete::Message::Confirm(false)))
+                .on_esc(Message::BulkDeleteMsg(bulk_delete::Message::Confirm(false)));
+                // Show MessageMenuModal, if a guild and a channel are selected
+                let content = Modal::new(&mut self.message_menu_modal, content, move |state| {
+                    state
+                        .view(theme, &client.permissions)
+                        .map(Message::MessageMenuMsg)
+                })
+                .style(theme)
+                .backdrop(Message::MessageMenuMsg(message_menu::Message::Back))
+                .on_esc(Message::MessageMenuMsg(message_menu::Message::Back));
+                // Show DeleteMessageModal, if a guild and a channel are selected
+                let content = Modal::new(&mut self.delete_message_modal, content, move |state| {
+                    state.view(theme).map(Message::DeleteMessageMsg)
+                })
+                .style(theme)
+                .backdrop(Message::DeleteMessageMsg(delete_message::Message::Confirm(
+                    false,
+                )))
+                .on_esc(Message::DeleteMessageMsg(
+                    delete_message::Message::Confirm(false),
+                ));
+                // Show EmbedEditorModal, if a guild and a channel are selected
+                let content = Modal::new(&mut self.embed_editor_modal, content, move |state| {
+                    state.view(theme).map(Message::EmbedEditorMsg)
+                })
+                .style(theme)
+                .backdrop(Message::EmbedEditorMsg(embed_editor::Message::Back))
+                .on_esc(Message::EmbedEditorMsg(embed_editor::Message::Back));
                 // Show Image view, if a guild and a channel are selected
                 Modal::new(&mut self.image_viewer_modal, content, move |state| {
                     state.view(theme).map(Message::ImageViewMessage)
@@ -640,27 +1291,9 @@ impl MainScreen {
         msg: Message,
         client: &mut Client,
         thumbnail_cache: &ThumbnailCache,
-        clip: &mut iced::Clipboard,
+        clip: &mut dyn iced_native::Clipboard,
     ) -> Command<TopLevelMessage> {
-        fn scroll_to_bottom(client: &mut Client, guild_id: u64, channel_id: u64) {
-            if let Some((disp, looking_at_message)) = client
-                .guilds
-                .get_mut(&guild_id)
-                .map(|guild| guild.channels.get_mut(&channel_id))
-                .flatten()
-                .map(|channel| (channel.messages.len(), &mut channel.looking_at_message))
-            {
-                *looking_at_message = disp.saturating_sub(1);
-            }
-        }
-
         match msg {
-            Message::ChannelViewPerm(channel_id, ok) => {
-                self.has_permission_to_send_msg
-                    .entry(channel_id)
-                    .and_modify(|o| *o = ok)
-                    .or_insert(ok);
-            }
             Message::QuickSwitch => {
                 self.quick_switcher_modal
                     .show(!self.quick_switcher_modal.is_shown());
@@ -796,37 +1429,97 @@ impl MainScreen {
                     self.quick_switcher_modal.inner_mut().search_value = new_term;
                 }
             },
-            Message::EditLastMessage => {
-                let current_user_id = client.user_id.expect("literally how?");
-                if let (Some(guild_id), Some(channel_id)) =
-                    (self.current_guild_id, self.current_channel_id)
-                {
-                    if let Some(mid) = client
-                        .get_channel(guild_id, channel_id)
-                        .map(|c| {
-                            c.messages.iter().rev().find_map(|m| {
-                                if m.sender == current_user_id && m.id.id().is_some() {
-                                    m.id.id()
-                                } else {
-                                    None
-                                }
-                            })
-                        })
-                        .flatten()
-                    {
-                        self.mode = Mode::EditMessage;
-                        return self.update(
-                            Message::ChangeMode(Mode::EditingMessage(mid)),
+            Message::SearchMsg(msg) => match msg {
+                search::Message::SearchChanged(term) => {
+                    self.search_modal.inner_mut().search(term, client);
+                }
+                search::Message::Open(result) => {
+                    self.search_modal.show(false);
+                    let mut cmds = Vec::with_capacity(3);
+                    if self.current_guild_id != Some(result.guild_id) {
+                        cmds.push(self.update(
+                            Message::GuildChanged(result.guild_id),
                             client,
                             thumbnail_cache,
                             clip,
-                        );
+                        ));
                     }
-                }
-            }
-            Message::ChangeMode(mode) => {
-                if let (Mode::EditMessage, Mode::EditingMessage(mid)) = (self.mode, mode) {
-                    if let (Some(gid), Some(cid)) = (self.current_guild_id, self.current_channel_id)
+                    if self.current_channel_id != Some(result.channel_id) {
+                        cmds.push(self.update(
+                            Message::ChannelChanged(result.channel_id),
+                            client,
+                            thumbnail_cache,
+                            clip,
+                        ));
+                    }
+                    cmds.push(self.update(
+                        Message::JumpToMessage(result.message_id),
+                        client,
+                        thumbnail_cache,
+                        clip,
+                    ));
+                    return Command::batch(cmds);
+                }
+            },
+            Message::CommandPaletteMsg(msg) => match msg {
+                command_palette::Message::SearchChanged(term) => {
+                    self.command_palette_modal.inner_mut().search(term);
+                }
+                command_palette::Message::Run(action) => {
+                    self.command_palette_modal.show(false);
+                    return self.update(Message::RunAction(action), client, thumbnail_cache, clip);
+                }
+            },
+            Message::KeyReleased { binding, captured } => {
+                if let Some(action) = self.keybindings.action_for(&binding) {
+                    // Don't treat keys used by a widget, like typing in a text input, as shortcuts
+                    if !(captured && action.is_image_viewer()) {
+                        return self.update(
+                            Message::RunAction(action),
+                            client,
+                            thumbnail_cache,
+                            clip,
+                        );
+                    }
+                }
+            }
+            Message::RunAction(action) => {
+                return self.run_action(action, client, thumbnail_cache, clip)
+            }
+            Message::CancelReply => {
+                self.replying_to = None;
+            }
+            Message::EditLastMessage => {
+                let current_user_id = client.user_id.expect("literally how?");
+                if let (Some(guild_id), Some(channel_id)) =
+                    (self.current_guild_id, self.current_channel_id)
+                {
+                    if let Some(mid) = client
+                        .get_channel(guild_id, channel_id)
+                        .map(|c| {
+                            c.messages.iter().rev().find_map(|m| {
+                                if m.sender == current_user_id && m.id.id().is_some() {
+                                    m.id.id()
+                                } else {
+                                    None
+                                }
+                            })
+                        })
+                        .flatten()
+                    {
+                        self.mode = Mode::EditMessage;
+                        return self.update(
+                            Message::ChangeMode(Mode::EditingMessage(mid)),
+                            client,
+                            thumbnail_cache,
+                            clip,
+                        );
+                    }
+                }
+            }
+            Message::ChangeMode(mode) => {
+                if let (Mode::EditMessage, Mode::EditingMessage(mid)) = (self.mode, mode) {
+                    if let (Some(gid), Some(cid)) = (self.current_guild_id, self.current_channel_id)
                     {
                         self.composer_state.focus();
                         if let Some(msg) = client
@@ -834,8 +1527,21 @@ impl MainScreen {
                             .map(|c| c.messages.iter_mut().rev().find(|m| m.id.id() == Some(mid)))
                             .flatten()
                         {
-                            if let IcyContent::Text(text) = &msg.content {
-                                self.message = text.clone();
+                            match &msg.content {
+                                IcyContent::Text(text) => self.message = text.clone(),
+                                IcyContent::Files(attachments) => {
+                                    self.message = attachments
+                                        .get(self.editing_attachment)
+                                        .map_or_else(String::new, |a| a.caption.clone());
+                                }
+                                // Embeds have their own editor instead of the composer
+                                IcyContent::Embeds(embed) => {
+                                    self.embed_editor_modal.inner_mut().open(mid, embed);
+                                    self.embed_editor_modal.show(true);
+                                    self.composer_state.unfocus();
+                                    self.mode = Mode::Normal;
+                                    return Command::none();
+                                }
                             }
                         }
                     } else {
@@ -847,6 +1553,9 @@ impl MainScreen {
                     self.composer_state.unfocus();
                     self.message.clear();
                 }
+                if !matches!(mode, Mode::EditingMessage(_)) {
+                    self.editing_attachment = 0;
+                }
                 self.mode = mode;
             }
             Message::ClearError => {
@@ -855,9 +1564,48 @@ impl MainScreen {
             Message::OpenUrl(url) => {
                 open::that_in_background(url);
             }
-            Message::OpenImageView { handle, path, name } => {
+            Message::OpenImageView {
+                handle,
+                path,
+                attachment,
+            } => {
+                let mut image = ViewedImage {
+                    handle,
+                    path,
+                    attachment,
+                    sender: None,
+                    timestamp: None,
+                    gallery_position: None,
+                };
+                if let Some(channel) = self
+                    .current_guild_id
+                    .zip(self.current_channel_id)
+                    .map(|(guild_id, channel_id)| {
+                        client.guilds.get(&guild_id)?.channels.get(&channel_id)
+                    })
+                    .flatten()
+                {
+                    let images = channel.images();
+                    if let Some(index) = images
+                        .iter()
+                        .position(|(_, attachment)| attachment.id == image.attachment.id)
+                    {
+                        let message = images[index].0;
+                        image.sender = Some(message.overrides.as_ref().map_or_else(
+                            || {
+                                client
+                                    .members
+                                    .get(&message.sender)
+                                    .map_or_else(|| String::from("unknown"), |m| m.username.clone())
+                            },
+                            |overrides| overrides.name.clone(),
+                        ));
+                        image.timestamp = Some(message.timestamp);
+                        image.gallery_position = Some((index, images.len()));
+                    }
+                }
+                self.image_viewer_modal.inner_mut().open(image);
                 self.image_viewer_modal.show(true);
-                self.image_viewer_modal.inner_mut().image_handle = Some((handle, (path, name)));
                 return self.update(
                     Message::ChangeMode(Mode::Normal),
                     client,
@@ -872,9 +1620,52 @@ impl MainScreen {
                     self.profile_edit_modal.show(false);
                 }
 
-                return cmd;
+                return dialog_cmd(Dialog::ProfileEdit, cmd);
             }
             Message::ImageViewMessage(msg) => {
+                if !self.image_viewer_modal.is_shown() {
+                    return Command::none();
+                }
+
+                if let image_viewer::Message::Previous | image_viewer::Message::Next = msg {
+                    let neighbour = self
+                        .current_guild_id
+                        .zip(self.current_channel_id)
+                        .map(|(guild_id, channel_id)| {
+                            client.guilds.get(&guild_id)?.channels.get(&channel_id)
+                        })
+                        .flatten()
+                        .zip(self.image_viewer_modal.inner().current_attachment())
+                        .map(|(channel, current)| {
+                            let images = channel.images();
+                            let index = images
+                                .iter()
+                                .position(|(_, attachment)| attachment.id == current.id)?;
+                            let index = if let image_viewer::Message::Next = msg {
+                                index + 1
+                            } else {
+                                index.checked_sub(1)?
+                            };
+                            images
+                                .get(index)
+                                .map(|(_, attachment)| (*attachment).clone())
+                        })
+                        .flatten();
+
+                    if let Some(attachment) = neighbour {
+                        return self.update(
+                            Message::OpenContent {
+                                attachment,
+                                is_thumbnail: true,
+                            },
+                            client,
+                            thumbnail_cache,
+                            clip,
+                        );
+                    }
+                    return Command::none();
+                }
+
                 let (cmd, go_back) = self.image_viewer_modal.inner_mut().update(msg);
 
                 if go_back {
@@ -894,8 +1685,76 @@ impl MainScreen {
                     self.create_channel_modal.show(false);
                 }
 
-                return cmd;
+                return dialog_cmd(Dialog::CreateChannel, cmd);
+            }
+            Message::ChannelMenuMessage(msg) => {
+                let (cmd, go_back) = self.channel_menu_modal.inner_mut().update(
+                    msg,
+                    self.current_guild_id.unwrap(),
+                    &client,
+                );
+
+                if go_back {
+                    self.channel_menu_modal.show(false);
+                }
+
+                return dialog_cmd(Dialog::ChannelMenu, cmd);
             }
+            Message::ChannelListAction(action) => match action {
+                ChannelListAction::ToggleCollapsed(category_id) => {
+                    if !self.collapsed_categories.remove(&category_id) {
+                        self.collapsed_categories.insert(category_id);
+                    }
+                }
+                ChannelListAction::OpenMenu(channel_id) => {
+                    self.dragged_channel = None;
+                    self.channel_menu_modal.inner_mut().open(channel_id);
+                    self.channel_menu_modal.show(true);
+                    return self.update(
+                        Message::ChangeMode(Mode::Normal),
+                        client,
+                        thumbnail_cache,
+                        clip,
+                    );
+                }
+                ChannelListAction::StartDrag(channel_id) => {
+                    self.dragged_channel = Some(ChannelDrag {
+                        channel_id,
+                        target_id: None,
+                    });
+                }
+                ChannelListAction::DragOver(target_id) => {
+                    if let Some(dragged) = &mut self.dragged_channel {
+                        dragged.target_id = Some(target_id);
+                    }
+                }
+                ChannelListAction::CancelDrag => self.dragged_channel = None,
+                ChannelListAction::Drop(target_id) => {
+                    if let Some((guild_id, channel_id)) = self.current_guild_id.zip(
+                        self.dragged_channel
+                            .take()
+                            .map(|dragged| dragged.channel_id)
+                            .filter(|channel_id| *channel_id != target_id),
+                    ) {
+                        if let Some((previous_id, next_id)) = client
+                            .guilds
+                            .get(&guild_id)
+                            .map(|guild| {
+                                let position = guild.channels.get_index_of(&target_id)?;
+                                guild.channel_neighbours_at(channel_id, position)
+                            })
+                            .flatten()
+                        {
+                            return client.move_channel_cmd(
+                                guild_id,
+                                channel_id,
+                                previous_id,
+                                next_id,
+                            );
+                        }
+                    }
+                }
+            },
             Message::HelpModal(msg) => {
                 if msg {
                     self.help_modal.show(false);
@@ -903,7 +1762,8 @@ impl MainScreen {
             }
             Message::LogoutChoice(confirm) => {
                 self.logout_modal.show(false);
-                return self.logout_modal.inner_mut().update(confirm, client);
+                let cmd = self.logout_modal.inner_mut().update(confirm, client);
+                return dialog_cmd(Dialog::Logout, cmd);
             }
             Message::MessageHistoryScrolled {
                 prev_scroll_perc,
@@ -912,78 +1772,419 @@ impl MainScreen {
                 if let (Some(guild_id), Some(channel_id)) =
                     (self.current_guild_id, self.current_channel_id)
                 {
-                    if scroll_perc < 0.01 && scroll_perc <= prev_scroll_perc {
-                        if let Some((
-                            oldest_msg_id,
-                            disp,
-                            reached_top,
-                            loading_messages_history,
-                            looking_at_message,
-                        )) = client
+                    if !self.history_layout.is_for(guild_id, channel_id) {
+                        return Command::none();
+                    }
+
+                    let (_, viewport_height) = self.history_viewport();
+                    let max_offset =
+                        (self.history_layout.total_height() - viewport_height).max(0.0);
+                    let offset = scroll_perc * max_offset;
+                    self.history_views.insert(
+                        (guild_id, channel_id),
+                        HistoryView {
+                            offset,
+                            at_bottom: scroll_perc > 0.99,
+                        },
+                    );
+
+                    // Load older messages before the user reaches the top
+                    if offset < viewport_height && scroll_perc <= prev_scroll_perc {
+                        return load_older_messages(client, guild_id, channel_id, None);
+                    }
+                }
+            }
+            Message::HistoryLoaded {
+                guild_id,
+                channel_id,
+            } => {
+                if self.history_layout.is_for(guild_id, channel_id) {
+                    let (width, _) = self.history_viewport();
+                    let old_height = self.history_layout.total_height();
+                    if let Some(channel) = client
+                        .guilds
+                        .get(&guild_id)
+                        .map(|guild| guild.channels.get(&channel_id))
+                        .flatten()
+                    {
+                        self.history_layout.update(
+                            guild_id,
+                            channel_id,
+                            channel,
+                            thumbnail_cache,
+                            width,
+                        );
+                    }
+
+                    let view = self
+                        .history_views
+                        .entry((guild_id, channel_id))
+                        .or_default();
+                    if !view.at_bottom {
+                        // Keep showing the same messages, now that older ones were put above them
+                        view.offset += self.history_layout.total_height() - old_height;
+                        let offset = view.offset;
+                        self.snap_history_to(offset);
+                    }
+                }
+
+                match self.pending_jump {
+                    Some((jump_guild_id, jump_channel_id, message_id))
+                        if (jump_guild_id, jump_channel_id) == (guild_id, channel_id) =>
+                    {
+                        self.pending_jump = None;
+                        let loaded = client.get_channel(guild_id, channel_id).map_or(false, |c| {
+                            c.messages.iter().any(|m| m.id.id() == Some(message_id))
+                        });
+                        if loaded {
+                            return self.update(
+                                Message::JumpToMessage(message_id),
+                                client,
+                                thumbnail_cache,
+                                clip,
+                            );
+                        }
+                        tracing::warn!("couldn't load message {} to jump to it", message_id);
+                    }
+                    _ => {}
+                }
+            }
+            Message::SelectedMember(user_id) => {
+                if let Some(guild_id) = self.current_guild_id {
+                    self.member_card_modal.inner_mut().open(user_id);
+                    self.member_card_modal.show(true);
+                    let load = self.member_card_modal.inner().load(guild_id, client);
+                    let permissions = client.fetch_permissions_cmd(guild_id, None);
+                    let cmd = self.update(
+                        Message::ChangeMode(Mode::Normal),
+                        client,
+                        thumbnail_cache,
+                        clip,
+                    );
+                    return Command::batch(vec![load, permissions, cmd]);
+                }
+            }
+            Message::BulkDeleteMsg(msg) => {
+                if let Some((guild_id, channel_id)) =
+                    self.current_guild_id.zip(self.current_channel_id)
+                {
+                    let (cmd, go_back) = self
+                        .bulk_delete_modal
+                        .inner_mut()
+                        .update(msg, guild_id, channel_id, client);
+
+                    if go_back {
+                        self.bulk_delete_modal.show(false);
+                    }
+
+                    return dialog_cmd(Dialog::BulkDelete, cmd);
+                }
+            }
+            Message::OpenMessageMenu(message_id) => {
+                if let (Some((guild_id, channel_id)), Some(user_id)) = (
+                    self.current_guild_id.zip(self.current_channel_id),
+                    client.user_id,
+                ) {
+                    if let Some(message) = client
+                        .get_channel(guild_id, channel_id)
+                        .map(|channel| {
+                            channel
+                                .messages
+                                .iter()
+                                .rev()
+                                .find(|message| message.id.id() == Some(message_id))
+                        })
+                        .flatten()
+                    {
+                        let is_pinned = client
                             .get_channel(guild_id, channel_id)
-                            .map(|channel| {
-                                Some((
-                                    channel.messages.first().map(|m| m.id.id()).flatten(),
-                                    channel.messages.len(),
-                                    channel.reached_top,
-                                    &mut channel.loading_messages_history,
-                                    &mut channel.looking_at_message,
-                                ))
-                            })
+                            .map(|channel| channel.pinned.as_ref())
                             .flatten()
-                        {
-                            if *looking_at_message == disp.saturating_sub(1) {
-                                *looking_at_message = disp.saturating_sub(SHOWN_MSGS_LIMIT + 1);
-                            } else {
-                                *looking_at_message = looking_at_message.saturating_sub(1);
-                            }
+                            .map_or(false, |pinned| pinned.contains(&message_id));
+                        self.message_menu_modal
+                            .inner_mut()
+                            .open(guild_id, channel_id, message, user_id, is_pinned);
+                        self.message_menu_modal.show(true);
+                        let load = client.fetch_permissions_cmd(guild_id, Some(channel_id));
+                        let cmd = self.update(
+                            Message::ChangeMode(Mode::Normal),
+                            client,
+                            thumbnail_cache,
+                            clip,
+                        );
+                        return Command::batch(vec![load, cmd]);
+                    }
+                }
+            }
+            Message::JumpToMessage(message_id) => {
+                if let Some((guild_id, channel_id)) =
+                    self.current_guild_id.zip(self.current_channel_id)
+                {
+                    let loaded = client.get_channel(guild_id, channel_id).map(|channel| {
+                        channel
+                            .messages
+                            .iter()
+                            .any(|message| message.id.id() == Some(message_id))
+                    });
+                    // Older messages are loaded up to it first, then it's jumped to
+                    if loaded == Some(false) {
+                        self.pending_jump = Some((guild_id, channel_id, message_id));
+                        return load_older_messages(client, guild_id, channel_id, Some(message_id));
+                    }
 
-                            if !reached_top && *looking_at_message < 2 && !*loading_messages_history
+                    let (width, _) = self.history_viewport();
+                    if let Some(index) = client
+                        .guilds
+                        .get(&guild_id)
+                        .map(|guild| guild.channels.get(&channel_id))
+                        .flatten()
+                        .map(|channel| {
+                            self.history_layout.update(
+                                guild_id,
+                                channel_id,
+                                channel,
+                                thumbnail_cache,
+                                width,
+                            );
+                            channel
+                                .messages
+                                .iter()
+                                .position(|message| message.id.id() == Some(message_id))
+                        })
+                        .flatten()
+                    {
+                        if let Some(top) = self.history_layout.top_of(index) {
+                            // Leave some of the previous messages in view for context
+                            let offset = (top - PADDING as f32 * 2.0).max(0.0);
+                            self.history_views.insert(
+                                (guild_id, channel_id),
+                                HistoryView {
+                                    offset,
+                                    at_bottom: false,
+                                },
+                            );
+                            self.snap_history_to(offset);
+                        }
+                    }
+                }
+            }
+            Message::PinsLoaded {
+                guild_id,
+                channel_id,
+                message_ids,
+            } => {
+                client.set_pins(guild_id, channel_id, message_ids);
+            }
+            Message::MessageMenuMsg(msg) => {
+                let (guild_id, channel_id) =
+                    match self.current_guild_id.zip(self.current_channel_id) {
+                        Some(ids) if self.message_menu_modal.is_shown() => ids,
+                        _ => return Command::none(),
+                    };
+                let message_id = self.message_menu_modal.inner().message_id;
+
+                let cmd = match msg {
+                    message_menu::Message::Reply => {
+                        self.replying_to = Some(message_id);
+                        self.composer_state.focus();
+                        Command::none()
+                    }
+                    message_menu::Message::Edit => {
+                        self.editing_attachment = 0;
+                        self.mode = Mode::EditMessage;
+                        self.update(
+                            Message::ChangeMode(Mode::EditingMessage(message_id)),
+                            client,
+                            thumbnail_cache,
+                            clip,
+                        )
+                    }
+                    message_menu::Message::EditCaption(index) => {
+                        self.editing_attachment = index;
+                        self.mode = Mode::EditMessage;
+                        self.update(
+                            Message::ChangeMode(Mode::EditingMessage(message_id)),
+                            client,
+                            thumbnail_cache,
+                            clip,
+                        )
+                    }
+                    message_menu::Message::Delete => self.update(
+                        Message::DeleteMessage(message_id),
+                        client,
+                        thumbnail_cache,
+                        clip,
+                    ),
+                    message_menu::Message::MarkUnread => {
+                        if let Some(channel) = client.get_channel(guild_id, channel_id) {
+                            if let Some(index) = channel
+                                .messages
+                                .iter()
+                                .position(|message| message.id.id() == Some(message_id))
                             {
-                                *loading_messages_history = true;
-                                let inner = client.inner().clone();
-                                return Command::perform(
-                                    async move {
-                                        channel::get_channel_messages(
-                                            &inner,
-                                            GetChannelMessages::new(guild_id, channel_id)
-                                                .before_message(oldest_msg_id.unwrap_or_default()),
-                                        )
-                                        .await
-                                        .map_or_else(
-                                            |err| TopLevelMessage::Error(Box::new(err.into())),
-                                            |response| {
-                                                TopLevelMessage::GetEventsBackwardsResponse {
-                                                    messages: response.messages,
-                                                    reached_top: response.reached_top,
-                                                    guild_id,
-                                                    channel_id,
-                                                }
-                                            },
-                                        )
-                                    },
-                                    |result| result,
-                                );
+                                channel.unread = channel.messages.len() - index;
+                                self.kept_unread = Some((guild_id, channel_id));
                             }
                         }
-                    } else if scroll_perc > 0.99 && scroll_perc >= prev_scroll_perc {
-                        if let Some((disp, looking_at_message)) =
-                            client.get_channel(guild_id, channel_id).map(|channel| {
-                                (channel.messages.len(), &mut channel.looking_at_message)
-                            })
-                        {
-                            if *looking_at_message > disp.saturating_sub(SHOWN_MSGS_LIMIT) {
-                                *looking_at_message = disp.saturating_sub(1);
-                            } else {
-                                *looking_at_message =
-                                    looking_at_message.saturating_add(1).min(disp);
-                            }
+                        Command::none()
+                    }
+                    _ => Command::none(),
+                };
+
+                let (cmd2, go_back) = self
+                    .message_menu_modal
+                    .inner_mut()
+                    .update(msg, guild_id, channel_id, client, clip);
+
+                if go_back {
+                    self.message_menu_modal.show(false);
+                }
+
+                return Command::batch(vec![cmd, dialog_cmd(Dialog::MessageMenu, cmd2)]);
+            }
+            Message::DeleteMessage(message_id) => {
+                let summary = self
+                    .current_guild_id
+                    .zip(self.current_channel_id)
+                    .and_then(|(guild_id, channel_id)| {
+                        client.guilds.get(&guild_id)?.channels.get(&channel_id)
+                    })
+                    .and_then(|channel| {
+                        channel
+                            .messages
+                            .iter()
+                            .find(|message| message.id.id() == Some(message_id))
+                    })
+                    .map(|message| message_summary(message, &client.members));
+                if let Some(summary) = summary {
+                    self.delete_message_modal
+                        .inner_mut()
+                        .open(message_id, summary);
+                    self.delete_message_modal.show(true);
+                }
+            }
+            Message::DeleteMessageMsg(delete_message::Message::Confirm(confirm)) => {
+                let (guild_id, channel_id) =
+                    match self.current_guild_id.zip(self.current_channel_id) {
+                        Some(ids) if self.delete_message_modal.is_shown() => ids,
+                        _ => return Command::none(),
+                    };
+                self.delete_message_modal.show(false);
+                if !confirm {
+                    return Command::none();
+                }
+
+                let message_id = self.delete_message_modal.inner().message_id;
+                // Remove the message right away, it is put back if the deletion is undone or fails
+                if let Some(message) = client.remove_message(guild_id, channel_id, message_id) {
+                    self.delete_error = None;
+                    self.pending_deletes.push(PendingDelete {
+                        guild_id,
+                        channel_id,
+                        message,
+                        sent: false,
+                    });
+
+                    return Command::perform(tokio::time::sleep(DELETE_UNDO_WINDOW), move |_| {
+                        TopLevelMessage::MainScreen(Message::CommitDelete(message_id))
+                    });
+                }
+            }
+            Message::CommitDelete(message_id) => {
+                if let Some(pending) = self
+                    .pending_deletes
+                    .iter_mut()
+                    .find(|pending| !pending.sent && pending.message.id.id() == Some(message_id))
+                {
+                    pending.sent = true;
+                    return client.delete_msg_cmd(pending.guild_id, pending.channel_id, message_id);
+                }
+            }
+            Message::UndoDelete => {
+                if let Some((guild_id, channel_id)) =
+                    self.current_guild_id.zip(self.current_channel_id)
+                {
+                    if let Some(index) = self.pending_deletes.iter().rposition(|pending| {
+                        !pending.sent
+                            && pending.guild_id == guild_id
+                            && pending.channel_id == channel_id
+                    }) {
+                        restore_deleted(client, self.pending_deletes.remove(index));
+                    }
+                }
+            }
+            Message::DeleteFinished {
+                guild_id,
+                channel_id,
+                message_id,
+                err,
+            } => {
+                let pending = self
+                    .pending_deletes
+                    .iter()
+                    .position(|pending| {
+                        pending.guild_id == guild_id
+                            && pending.channel_id == channel_id
+                            && pending.message.id.id() == Some(message_id)
+                    })
+                    .map(|index| self.pending_deletes.remove(index));
+                if let Some(err) = err {
+                    if let Some(pending) = pending {
+                        restore_deleted(client, pending);
+                    }
+                    self.delete_error = Some(err.to_string());
+                }
+            }
+            Message::DismissDeleteError => {
+                self.delete_error = None;
+            }
+            Message::EmbedEditorMsg(msg) => {
+                let (guild_id, channel_id) =
+                    match self.current_guild_id.zip(self.current_channel_id) {
+                        Some(ids) if self.embed_editor_modal.is_shown() => ids,
+                        _ => return Command::none(),
+                    };
+
+                let (cmd, go_back) = self
+                    .embed_editor_modal
+                    .inner_mut()
+                    .update(msg, guild_id, channel_id, client);
+
+                if go_back {
+                    self.embed_editor_modal.show(false);
+                }
+
+                return dialog_cmd(Dialog::EmbedEditor, cmd);
+            }
+            Message::MemberCardMsg(msg) => {
+                if !self.member_card_modal.is_shown() {
+                    return Command::none();
+                }
+
+                if let member_card::Message::Mention = msg {
+                    let user_id = self.member_card_modal.inner().user_id;
+                    if let Some(member) = client.members.get(&user_id) {
+                        if !self.message.is_empty() && !self.message.ends_with(' ') {
+                            self.message.push(' ');
                         }
+                        self.message.push('@');
+                        self.message.push_str(&member.username);
+                        self.message.push(' ');
                     }
                 }
-            }
-            Message::SelectedMember(user_id) => {
-                tracing::trace!("member: {}", user_id);
+
+                let (cmd, go_back) = self.member_card_modal.inner_mut().update(
+                    msg,
+                    self.current_guild_id.unwrap(),
+                    client,
+                    clip,
+                );
+
+                if go_back {
+                    self.member_card_modal.show(false);
+                }
+
+                return dialog_cmd(Dialog::MemberCard, cmd);
             }
             Message::SelectedChannelMenuOption(option) => match option.as_str() {
                 "New Channel" => {
@@ -998,41 +2199,71 @@ impl MainScreen {
                 }
                 "Edit Guild" => {
                     let guild_id = self.current_guild_id.unwrap();
-                    let client_inner = client.inner().clone();
+                    return TopLevelScreen::push_screen_cmd(TopLevelScreen::GuildSettings(
+                        super::GuildSettings::new(guild_id),
+                    ));
+                }
+                "Copy Guild ID" => {
+                    clip.write(
+                        self.current_guild_id
+                            .expect("this menu is only shown if a guild is selected") // [ref:copy_guild_id_menu_entry]
+                            .to_string(),
+                    );
+                }
+                "Create invite" => {
+                    let guild_id = self.current_guild_id.unwrap();
+                    let requests = client.requests().clone();
                     return Command::perform(
-                        async move {
-                            return permissions::query_has_permission(
-                                &client_inner,
-                                permissions::QueryPermissions::new(
-                                    guild_id,
-                                    "guild.manage.change-information".to_string(),
-                                ),
+                        async move { requests.invites(guild_id).await },
+                        move |result| {
+                            result.map_or_else(
+                                |err| TopLevelMessage::Error(Box::new(err)),
+                                |invites| {
+                                    TopLevelMessage::PushScreen(Box::new(
+                                        TopLevelScreen::GuildSettings(
+                                            super::GuildSettings::with_invites(guild_id, invites),
+                                        ),
+                                    ))
+                                },
                             )
-                            .await;
                         },
-                        move |result| match result {
-                            Ok(x) => {
-                                if x.ok {
+                    );
+                }
+                "Bans" => {
+                    let guild_id = self.current_guild_id.unwrap();
+                    let requests = client.requests().clone();
+                    return Command::perform(
+                        async move { requests.banned_users(guild_id).await },
+                        move |result| {
+                            result.map_or_else(
+                                |err| TopLevelMessage::Error(Box::new(err)),
+                                |banned_users| {
                                     TopLevelMessage::PushScreen(Box::new(
-                                        TopLevelScreen::GuildSettings(super::GuildSettings::new(
-                                            guild_id,
-                                        )),
+                                        TopLevelScreen::GuildSettings(
+                                            super::GuildSettings::with_bans(guild_id, banned_users),
+                                        ),
                                     ))
-                                } else {
-                                    TopLevelMessage::Error(Box::new(ClientError::Custom(
-                                        "Not permitted to edit guild information".to_string(),
-                                    )))
-                                }
-                            }
-                            Err(x) => TopLevelMessage::Error(Box::new(x.into())),
+                                },
+                            )
                         },
                     );
                 }
-                "Copy Guild ID" => {
-                    clip.write(
-                        self.current_guild_id
-                            .expect("this menu is only shown if a guild is selected") // [ref:copy_guild_id_menu_entry]
-                            .to_string(),
+                "Delete messages" => {
+                    self.bulk_delete_modal.inner_mut().reset();
+                    self.bulk_delete_modal.show(true);
+                    return self.update(
+                        Message::ChangeMode(Mode::Normal),
+                        client,
+                        thumbnail_cache,
+                        clip,
+                    );
+                }
+                "Pinned messages" => {
+                    return self.run_action(
+                        Action::TogglePinnedMessages,
+                        client,
+                        thumbnail_cache,
+                        clip,
                     );
                 }
                 "Copy Channel ID" => {
@@ -1072,41 +2303,75 @@ impl MainScreen {
                     );
                 }
                 "Help" => {
-                    self.help_modal.show(true);
-                    return self.update(
-                        Message::ChangeMode(Mode::Normal),
-                        client,
-                        thumbnail_cache,
-                        clip,
-                    );
+                    return self.run_action(Action::Help, client, thumbnail_cache, clip);
                 }
                 "Exit" => {
                     return Command::perform(async { TopLevelMessage::Exit }, |msg| msg);
                 }
-                _ => {}
+                option => {
+                    if let Some((_, status)) =
+                        STATUS_OPTIONS.iter().find(|(name, _)| *name == option)
+                    {
+                        // An explicitly picked status shouldn't be overwritten when the user is back
+                        self.status_before_idle = None;
+                        return set_own_status(client, *status);
+                    }
+                }
             },
+            Message::HistoryResized(size) => self.history_size = Some(size),
+            Message::UserActivity => {
+                self.last_activity = Some(Instant::now());
+                if let Some(status) = self.status_before_idle.take() {
+                    return set_own_status(client, status);
+                }
+            }
+            Message::CheckIdle => {
+                let inactive_for = self
+                    .last_activity
+                    .get_or_insert_with(Instant::now)
+                    .elapsed();
+                let current_status = client
+                    .user_id
+                    .map(|id| client.members.get(&id))
+                    .flatten()
+                    .map(|member| member.status);
+
+                if let (Some(idle_timeout), None, Some(status)) =
+                    (self.idle_timeout, self.status_before_idle, current_status)
+                {
+                    // Only go idle if the user is visibly online, don't touch do not disturb etc.
+                    if inactive_for >= idle_timeout
+                        && matches!(
+                            status,
+                            UserStatus::OnlineUnspecified | UserStatus::Streaming
+                        )
+                    {
+                        self.status_before_idle = Some(status);
+                        return set_own_status(client, UserStatus::Idle);
+                    }
+                }
+            }
             Message::ComposerMessageChanged(new_msg) => {
                 self.message = new_msg;
 
-                if let (Some(guild_id), Some(channel_id), Some(typing)) = (
+                if let (Some(guild_id), Some(channel_id), Some(user_id)) = (
                     self.current_guild_id,
                     self.current_channel_id,
-                    client
-                        .user_id
-                        .map(|id| client.get_member(id))
-                        .flatten()
-                        .map(|member| &mut member.typing_in_channel),
+                    client.user_id,
                 ) {
-                    if Some((guild_id, channel_id)) != typing.map(|(g, c, _)| (g, c))
-                        || typing.map_or(false, |(_, _, since)| since.elapsed().as_secs() >= 5)
-                    {
-                        *typing = Some((guild_id, channel_id, Instant::now()));
-                        let inner = client.inner().clone();
+                    if self.message.is_empty() {
+                        // Harmony has no request to stop typing, so others will see our typing
+                        // indicator until it expires; this lets us send a new one right away though
+                        client.ephemeral.stop_typing(user_id);
+                    } else if client.ephemeral.typing_in(user_id) != Some((guild_id, channel_id)) {
+                        // Our own typing state expires after the typing timeout, which throttles this
+                        client.ephemeral.set_typing(user_id, guild_id, channel_id);
+                        let requests = client.requests().clone();
                         return Command::perform(
-                            async move { chat::typing(&inner, Typing::new(guild_id, channel_id)).await },
+                            async move { requests.typing(guild_id, channel_id).await },
                             |result| {
                                 result.map_or_else(
-                                    |err| TopLevelMessage::Error(Box::new(err.into())),
+                                    |err| TopLevelMessage::Error(Box::new(err)),
                                     |_| TopLevelMessage::Nothing,
                                 )
                             },
@@ -1119,8 +2384,7 @@ impl MainScreen {
                     (self.current_guild_id, self.current_channel_id)
                 {
                     if sent_channel_id == channel_id {
-                        scroll_to_bottom(client, guild_id, channel_id);
-                        self.event_history_state.scroll_to_bottom();
+                        self.scroll_history_to_bottom(guild_id, channel_id);
                     }
                 }
             }
@@ -1150,7 +2414,7 @@ impl MainScreen {
                                 TopLevelMessage::MainScreen(Message::OpenImageView {
                                     handle: maybe_thumb.unwrap(),
                                     path: content_path,
-                                    name: attachment.name,
+                                    attachment,
                                 })
                             } else {
                                 open::that_in_background(content_path);
@@ -1160,15 +2424,11 @@ impl MainScreen {
                         |result| result.unwrap_or_else(|err| TopLevelMessage::Error(Box::new(err))),
                     )
                 } else {
-                    let inner = client.inner().clone();
+                    let requests = client.requests().clone();
                     Command::perform(
                         async move {
-                            let downloaded_file =
-                                download_extract_file(&inner, attachment.id.clone()).await?;
-                            tokio::fs::write(&content_path, downloaded_file.data()).await?;
-                            let bgra = image::load_from_memory(downloaded_file.data())
-                                .unwrap()
-                                .into_bgra8();
+                            let data = requests.download_file(attachment.id.clone()).await?;
+                            let bgra = image::load_from_memory(&data).unwrap().into_bgra8();
 
                             Ok(if is_thumbnail && maybe_thumb.is_none() {
                                 TopLevelMessage::DownloadedThumbnail {
@@ -1184,7 +2444,7 @@ impl MainScreen {
                                 TopLevelMessage::MainScreen(Message::OpenImageView {
                                     handle: maybe_thumb.unwrap(),
                                     path: content_path,
-                                    name: attachment.name,
+                                    attachment,
                                 })
                             } else {
                                 open::that_in_background(content_path);
@@ -1199,74 +2459,96 @@ impl MainScreen {
                 guild_id,
                 channel_id,
             } => {
-                if !self.message.trim().is_empty() {
-                    if let Mode::EditingMessage(message_id) = self.mode {
-                        let new_content: String =
-                            self.message.drain(..).collect::<String>().trim().into();
-                        if let Some(msg) = client
-                            .get_channel(guild_id, channel_id)
-                            .map(|c| {
-                                c.messages
-                                    .iter_mut()
-                                    .find(|m| m.id.id() == Some(message_id))
-                            })
-                            .flatten()
-                        {
-                            msg.being_edited = Some(new_content.clone());
+                if let Some(user_id) = client.user_id {
+                    client.ephemeral.stop_typing(user_id);
+                }
+                if let Mode::EditingMessage(message_id) = self.mode {
+                    let new_text: String = self.message.drain(..).collect::<String>().trim().into();
+                    let cleared = new_text.is_empty();
+                    let editing_attachment = std::mem::take(&mut self.editing_attachment);
+                    self.mode = Mode::Normal;
+                    let new_content = client
+                        .get_channel(guild_id, channel_id)
+                        .map(|c| {
+                            c.messages
+                                .iter_mut()
+                                .find(|m| m.id.id() == Some(message_id))
+                        })
+                        .flatten()
+                        .and_then(|msg| match &msg.content {
+                            // Captions can be cleared, unlike the text of a message
+                            IcyContent::Files(attachments) => {
+                                let mut attachments = attachments.clone();
+                                if let Some(attachment) = attachments.get_mut(editing_attachment) {
+                                    attachment.caption = new_text;
+                                }
+                                Some(IcyContent::Files(attachments))
+                            }
+                            IcyContent::Text(_) if !cleared => {
+                                msg.being_edited = Some(new_text.clone());
+                                Some(IcyContent::Text(new_text))
+                            }
+                            _ => None,
+                        });
+                    // The message shows the edit being sent
+                    self.history_layout.invalidate_message(
+                        guild_id,
+                        channel_id,
+                        MessageId::Ack(message_id),
+                    );
+                    match new_content {
+                        Some(new_content) => {
+                            return client.edit_msg_cmd(
+                                guild_id,
+                                channel_id,
+                                message_id,
+                                new_content,
+                            );
                         }
-                        self.mode = Mode::Normal;
-                        return client.edit_msg_cmd(guild_id, channel_id, message_id, new_content);
-                    } else if let Mode::Normal = self.mode {
+                        // Clearing the text doesn't delete the message right away, as it's easy to do by mistake
+                        None if cleared => {
+                            return self.update(
+                                Message::DeleteMessage(message_id),
+                                client,
+                                thumbnail_cache,
+                                clip,
+                            );
+                        }
+                        None => {}
+                    }
+                } else if !self.message.trim().is_empty() {
+                    if let Mode::Normal = self.mode {
                         let message = IcyMessage {
                             content: IcyContent::Text(
                                 self.message.drain(..).collect::<String>().trim().into(),
                             ),
                             sender: client.user_id.unwrap(),
+                            reply_to: self.replying_to.take(),
                             ..Default::default()
                         };
-                        if let Some(cmd) = client.send_msg_cmd(
-                            guild_id,
-                            channel_id,
-                            Duration::from_secs(0),
-                            message,
-                        ) {
-                            scroll_to_bottom(client, guild_id, channel_id);
-                            self.event_history_state.scroll_to_bottom();
+                        if let Some(cmd) = client.send_msg_cmd(guild_id, channel_id, message) {
+                            self.scroll_history_to_bottom(guild_id, channel_id);
                             return cmd;
                         }
                     }
-                } else if let Mode::EditingMessage(mid) = self.mode {
-                    self.mode = Mode::Normal;
-                    return client.delete_msg_cmd(guild_id, channel_id, mid);
                 }
             }
             Message::SendFiles {
                 guild_id,
                 channel_id,
             } => {
-                let inner = client.inner().clone();
-                let content_store = client.content_store_arc();
+                let requests = client.requests().clone();
                 let sender = client.user_id.unwrap();
 
                 return Command::perform(
                     async move {
-                        let ids = super::select_upload_files(&inner, content_store).await?;
+                        let attachments = super::select_upload_files(&requests).await?;
                         Ok(TopLevelMessage::SendMessage {
                             message: IcyMessage {
-                                content: IcyContent::Files(
-                                    ids.into_iter()
-                                        .map(|(id, kind, name, size)| Attachment {
-                                            id,
-                                            kind,
-                                            name,
-                                            size: size as u32,
-                                        })
-                                        .collect(),
-                                ),
+                                content: IcyContent::Files(attachments),
                                 sender,
                                 ..Default::default()
                             },
-                            retry_after: Duration::from_secs(0),
                             guild_id,
                             channel_id,
                         })
@@ -1285,47 +2567,33 @@ impl MainScreen {
             }
             Message::GuildChanged(guild_id) => {
                 self.mode = Mode::Normal;
+                self.kept_unread = None;
+                self.dragged_channel = None;
                 self.message.clear();
+                self.replying_to = None;
                 self.current_guild_id = Some(guild_id);
+                client.scheduler().cancel_stale(guild_id, None);
+                let scheduler = client.scheduler().clone();
+                let permissions_cmd = client.fetch_permissions_cmd(guild_id, None);
                 if let Some(guild) = client.get_guild(guild_id) {
                     if guild.channels.is_empty() {
-                        let inner = client.inner().clone();
-
-                        return Command::perform(
-                            async move {
-                                let guildid = GuildId::new(guild_id);
-                                let channels_list =
-                                    get_guild_channels(&inner, guildid).await?.channels;
-                                let mut events = Vec::with_capacity(channels_list.len());
-                                for channel in channels_list {
-                                    events.push(Event::CreatedChannel(ChannelCreated {
-                                        guild_id,
-                                        channel_id: channel.channel_id,
-                                        is_category: channel.is_category,
-                                        name: channel.channel_name,
-                                        metadata: channel.metadata,
-                                        ..Default::default()
-                                    }));
-                                }
-
-                                let members = get_guild_members(&inner, guildid).await?.members;
-                                events.reserve(members.len());
-                                for member_id in members {
-                                    events.push(Event::JoinedMember(MemberJoined {
-                                        member_id,
-                                        guild_id,
-                                    }));
-                                }
+                        let requests = client.requests().clone();
+                        let request = move || {
+                            let requests = requests.clone();
+                            async move { requests.guild_contents(guild_id).await }
+                        };
 
-                                Ok(events)
-                            },
-                            |result| {
-                                result.map_or_else(
-                                    |err| TopLevelMessage::Error(Box::new(err)),
-                                    TopLevelMessage::EventsReceived,
-                                )
-                            },
-                        );
+                        return Command::batch(vec![
+                            permissions_cmd,
+                            Command::perform(
+                                scheduler.run(
+                                    RequestKey::GuildContents(guild_id),
+                                    Priority::Visible,
+                                    request,
+                                ),
+                                super::scheduled_events_received,
+                            ),
+                        ]);
                     } else {
                         self.current_channel_id = self
                             .guild_last_channels
@@ -1334,75 +2602,83 @@ impl MainScreen {
                             .or_else(|| Some(*guild.channels.first().unwrap().0));
                     }
                 }
+                return Command::batch(vec![
+                    permissions_cmd,
+                    client.fetch_permissions_cmd(guild_id, self.current_channel_id),
+                ]);
             }
             Message::ChannelChanged(channel_id) => {
                 self.mode = Mode::Normal;
+                self.kept_unread = None;
+                self.pending_jump = None;
                 self.message.clear();
+                self.replying_to = None;
                 self.current_channel_id = Some(channel_id);
                 self.guild_last_channels
                     .insert(self.current_guild_id.unwrap(), channel_id);
-                if let Some((disp, disp_at)) = self
-                    .current_guild_id
-                    .map(|guild_id| client.get_channel(guild_id, channel_id))
+                let guild_id = self.current_guild_id.unwrap();
+                if let Some(disp) = client
+                    .guilds
+                    .get_mut(&guild_id)
+                    .map(|guild| guild.channels.get_mut(&channel_id))
                     .flatten()
-                    .map(|channel| (channel.messages.len(), &mut channel.looking_at_message))
+                    .map(|channel| {
+                        channel.unread = 0;
+                        let (width, _) = self.history_viewport();
+                        self.history_layout.update(
+                            guild_id,
+                            channel_id,
+                            channel,
+                            thumbnail_cache,
+                            width,
+                        );
+                        channel.messages.len()
+                    })
                 {
-                    if *disp_at >= disp.saturating_sub(SHOWN_MSGS_LIMIT) {
-                        *disp_at = disp.saturating_sub(1);
+                    // Go back to where the user left this channel
+                    let view = self
+                        .history_views
+                        .get(&(guild_id, channel_id))
+                        .copied()
+                        .unwrap_or_default();
+                    if view.at_bottom {
                         self.event_history_state.scroll_to_bottom();
+                    } else {
+                        self.snap_history_to(view.offset);
                     }
-                    let mut cmds = Vec::with_capacity(2);
-                    let inner = client.inner().clone();
-                    let guild_id = self.current_guild_id.unwrap();
-                    cmds.push(Command::perform(
-                        async move {
-                            let perm = permissions::query_has_permission(
-                                &inner,
-                                QueryPermissions::new(guild_id, "messages.send".to_string())
-                                    .channel_id(channel_id),
-                            )
-                            .await?;
-                            Ok(perm.ok)
-                        },
-                        move |res| {
-                            res.map_or_else(
-                                |err| TopLevelMessage::Error(Box::new(err)),
-                                |ok| {
-                                    TopLevelMessage::MainScreen(Message::ChannelViewPerm(
-                                        channel_id, ok,
-                                    ))
-                                },
-                            )
-                        },
-                    ));
+                    client.scheduler().cancel_stale(guild_id, Some(channel_id));
+                    let mut cmds = Vec::with_capacity(3);
+                    cmds.push(client.fetch_permissions_cmd(guild_id, Some(channel_id)));
+                    cmds.push(self.fetch_pins_if_needed(client));
                     if disp == 0 {
-                        let inner = client.inner().clone();
-                        let guild_id = self.current_guild_id.unwrap();
+                        let requests = client.requests().clone();
+                        let request = move || {
+                            let requests = requests.clone();
+                            async move { requests.channel_messages(guild_id, channel_id, None).await }
+                        };
                         cmds.push(Command::perform(
-                            async move {
-                                let messages = get_channel_messages(
-                                    &inner,
-                                    GetChannelMessages::new(guild_id, channel_id),
-                                )
-                                .await?
-                                .messages;
-                                let events = messages
-                                    .into_iter()
-                                    .map(|msg| {
-                                        Event::SentMessage(Box::new(MessageSent {
-                                            message: Some(msg),
-                                            ..Default::default()
-                                        }))
-                                    })
-                                    .rev()
-                                    .collect();
-                                Ok(events)
-                            },
-                            |result| {
-                                result.map_or_else(
+                            client.scheduler().run(
+                                RequestKey::ChannelMessages {
+                                    guild_id,
+                                    channel_id,
+                                    before: None,
+                                },
+                                Priority::Visible,
+                                request,
+                            ),
+                            move |result| match result {
+                                Some(result) => result.map_or_else(
                                     |err| TopLevelMessage::Error(Box::new(err)),
-                                    TopLevelMessage::EventsReceived,
-                                )
+                                    |(messages, reached_top)| {
+                                        TopLevelMessage::GetEventsBackwardsResponse {
+                                            messages,
+                                            reached_top,
+                                            guild_id,
+                                            channel_id,
+                                        }
+                                    },
+                                ),
+                                None => TopLevelMessage::Nothing,
                             },
                         ));
                     }
@@ -1414,52 +2690,313 @@ impl MainScreen {
         Command::none()
     }
 
-    pub fn subscription(&self) -> Subscription<TopLevelMessage> {
-        use iced_native::{
-            keyboard::{self, KeyCode},
-            window, Event,
+    fn run_action(
+        &mut self,
+        action: Action,
+        client: &mut Client,
+        thumbnail_cache: &ThumbnailCache,
+        clip: &mut dyn iced_native::Clipboard,
+    ) -> Command<TopLevelMessage> {
+        use image_viewer::{Message as ImageViewerMessage, Zoom};
+
+        let current = self.current_guild_id.zip(self.current_channel_id);
+        let msg = match action {
+            Action::CommandPalette => {
+                let show = !self.command_palette_modal.is_shown();
+                if show {
+                    self.command_palette_modal.inner_mut().reset();
+                }
+                self.command_palette_modal.show(show);
+                Message::ChangeMode(Mode::Normal)
+            }
+            Action::Search => {
+                let show = !self.search_modal.is_shown();
+                if show {
+                    self.search_modal.inner_mut().reset();
+                }
+                self.search_modal.show(show);
+                Message::ChangeMode(Mode::Normal)
+            }
+            Action::SwitchChannel => {
+                if self.quick_switcher_modal.is_shown() {
+                    self.quick_switcher_modal.show(false);
+                    return Command::none();
+                }
+                self.quick_switcher_modal.show(true);
+                let cmd = self.update(
+                    Message::ChangeMode(Mode::Normal),
+                    client,
+                    thumbnail_cache,
+                    clip,
+                );
+                let cmd2 = self.update(
+                    Message::QuickSwitchMsg(quick_switcher::Message::SearchTermChanged(
+                        "#".to_string(),
+                    )),
+                    client,
+                    thumbnail_cache,
+                    clip,
+                );
+                return Command::batch(vec![cmd, cmd2]);
+            }
+            Action::NextUnread | Action::PreviousUnread => {
+                let forward = matches!(action, Action::NextUnread);
+                if let Some((guild_id, channel_id)) = self.unread_channel(client, forward) {
+                    let cmd = self.update(
+                        Message::GuildChanged(guild_id),
+                        client,
+                        thumbnail_cache,
+                        clip,
+                    );
+                    let cmd2 = self.update(
+                        Message::ChannelChanged(channel_id),
+                        client,
+                        thumbnail_cache,
+                        clip,
+                    );
+                    return Command::batch(vec![cmd, cmd2]);
+                }
+                return Command::none();
+            }
+            Action::MarkRead => {
+                self.kept_unread = None;
+                if let Some(channel) = current
+                    .map(|(guild_id, channel_id)| client.get_channel(guild_id, channel_id))
+                    .flatten()
+                {
+                    channel.unread = 0;
+                }
+                return Command::none();
+            }
+            Action::EditLastMessage => Message::EditLastMessage,
+            Action::SelectMessageToEdit => Message::ChangeMode(Mode::EditMessage),
+            Action::ReplyToLastMessage => {
+                if let Some(message_id) = current
+                    .map(|(guild_id, channel_id)| client.get_channel(guild_id, channel_id))
+                    .flatten()
+                    .map(|channel| channel.messages.iter().rev().find_map(|m| m.id.id()))
+                    .flatten()
+                {
+                    self.replying_to = Some(message_id);
+                    self.composer_state.focus();
+                }
+                return Command::none();
+            }
+            Action::UploadFiles => match current {
+                Some((guild_id, channel_id)) => Message::SendFiles {
+                    guild_id,
+                    channel_id,
+                },
+                None => return Command::none(),
+            },
+            Action::ToggleMembersList => {
+                self.hide_members_list = !self.hide_members_list;
+                self.show_pinned = false;
+                return Command::none();
+            }
+            Action::TogglePinnedMessages => {
+                self.show_pinned = !self.show_pinned;
+                return self.fetch_pins_if_needed(client);
+            }
+            Action::ScrollToBottom => {
+                if let Some((guild_id, channel_id)) = current {
+                    self.scroll_history_to_bottom(guild_id, channel_id);
+                }
+                return Command::none();
+            }
+            Action::Help => {
+                self.help_modal.show(true);
+                Message::ChangeMode(Mode::Normal)
+            }
+            Action::Back => {
+                self.replying_to = None;
+                Message::ChangeMode(Mode::Normal)
+            }
+            Action::ImagePrevious => Message::ImageViewMessage(ImageViewerMessage::Previous),
+            Action::ImageNext => Message::ImageViewMessage(ImageViewerMessage::Next),
+            Action::ImageRotate => Message::ImageViewMessage(ImageViewerMessage::Rotate),
+            Action::ImageZoomFit => {
+                Message::ImageViewMessage(ImageViewerMessage::SetZoom(Zoom::Fit))
+            }
+            Action::ImageZoomOriginal => {
+                Message::ImageViewMessage(ImageViewerMessage::SetZoom(Zoom::Original))
+            }
+            Action::ImageZoomFill => {
+                Message::ImageViewMessage(ImageViewerMessage::SetZoom(Zoom::Fill))
+            }
         };
 
-        fn filter_events(
-            ev: Event,
-            _status: iced_native::event::Status,
-        ) -> Option<TopLevelMessage> {
+        self.update(msg, client, thumbnail_cache, clip)
+    }
+
+    pub fn subscription(&self) -> Subscription<TopLevelMessage> {
+        use iced_native::{keyboard, mouse, window, Event};
+
+        fn filter_events(ev: Event, status: iced_native::event::Status) -> Option<TopLevelMessage> {
             match ev {
+                // Key bindings are looked up in `update`, since this can't capture the bindings
                 Event::Keyboard(keyboard::Event::KeyReleased {
-                    key_code: KeyCode::Escape,
-                    ..
-                }) => Some(TopLevelMessage::MainScreen(Message::ChangeMode(
-                    Mode::Normal,
-                ))),
-                Event::Keyboard(keyboard::Event::KeyReleased {
-                    key_code: KeyCode::K,
-                    modifiers: keyboard::Modifiers { control: true, .. },
-                }) => Some(TopLevelMessage::MainScreen(Message::QuickSwitch)),
-                Event::Keyboard(keyboard::Event::KeyReleased {
-                    key_code: KeyCode::E,
-                    modifiers: keyboard::Modifiers { control: true, .. },
-                }) => Some(TopLevelMessage::MainScreen(Message::ChangeMode(
-                    Mode::EditMessage,
-                ))),
-                Event::Keyboard(keyboard::Event::KeyReleased {
-                    key_code: KeyCode::Up,
-                    ..
-                }) => Some(TopLevelMessage::MainScreen(Message::EditLastMessage)),
+                    key_code,
+                    modifiers,
+                }) => Some(TopLevelMessage::MainScreen(Message::KeyReleased {
+                    binding: KeyBinding::new(key_code, modifiers),
+                    captured: status == iced_native::event::Status::Captured,
+                })),
                 Event::Window(window::Event::CloseRequested) => Some(TopLevelMessage::Exit),
+                // Channels catch the release of the mouse button when something is dropped on them
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+                    if status == iced_native::event::Status::Ignored =>
+                {
+                    Some(TopLevelMessage::MainScreen(Message::ChannelListAction(
+                        ChannelListAction::CancelDrag,
+                    )))
+                }
+                // Any other input only counts as activity for idle detection
+                Event::Keyboard(keyboard::Event::KeyPressed { .. })
+                | Event::Mouse(mouse::Event::ButtonPressed(_))
+                | Event::Mouse(mouse::Event::WheelScrolled { .. }) => {
+                    Some(TopLevelMessage::MainScreen(Message::UserActivity))
+                }
                 _ => None,
             }
         }
 
-        iced_native::subscription::events_with(filter_events)
+        let events = iced_native::subscription::events_with(filter_events);
+        if self.idle_timeout.is_some() {
+            Subscription::batch(vec![
+                events,
+                iced::time::every(IDLE_CHECK_INTERVAL)
+                    .map(|_| TopLevelMessage::MainScreen(Message::CheckIdle)),
+            ])
+        } else {
+            events
+        }
     }
 
     pub fn on_error(&mut self, error: ClientError) -> Command<TopLevelMessage> {
         self.error_text = error.to_string();
-        self.logout_modal.show(false);
 
-        Command::batch(vec![
-            self.create_channel_modal.inner_mut().on_error(&error),
-            self.logout_modal.inner_mut().on_error(&error),
-        ])
+        Command::none()
+    }
+
+    /// Shows an error of a request a dialog made in that dialog, or in the banner if the dialog
+    /// was closed or can't show errors.
+    pub fn on_dialog_error(
+        &mut self,
+        dialog: Dialog,
+        error: ClientError,
+    ) -> Command<TopLevelMessage> {
+        let is_shown = self.shown_dialogs().contains(&dialog);
+        let cmd = match dialog {
+            Dialog::CreateChannel => self.create_channel_modal.inner_mut().on_error(&error),
+            Dialog::ChannelMenu => self.channel_menu_modal.inner_mut().on_error(&error),
+            Dialog::MemberCard => self.member_card_modal.inner_mut().on_error(&error),
+            Dialog::MessageMenu => self.message_menu_modal.inner_mut().on_error(&error),
+            Dialog::EmbedEditor => self.embed_editor_modal.inner_mut().on_error(&error),
+            Dialog::BulkDelete => self.bulk_delete_modal.inner_mut().on_error(&error),
+            Dialog::Logout => self.logout_modal.inner_mut().on_error(&error),
+            _ => Command::none(),
+        };
+        let shows_errors = matches!(
+            dialog,
+            Dialog::CreateChannel
+                | Dialog::ChannelMenu
+                | Dialog::MemberCard
+                | Dialog::MessageMenu
+                | Dialog::EmbedEditor
+        );
+
+        if is_shown && shows_errors {
+            cmd
+        } else {
+            Command::batch(vec![cmd, self.on_error(error)])
+        }
+    }
+}
+
+/// Marks the errors of a command a dialog made as coming from it, so they are shown there.
+fn dialog_cmd(dialog: Dialog, cmd: Command<TopLevelMessage>) -> Command<TopLevelMessage> {
+    cmd.map(move |msg| msg.from_dialog(dialog))
+}
+
+/// Loads the messages older than the loaded history of a channel, unless they are already being
+/// loaded or there are none.
+///
+/// Pages are loaded until a message is reached if `until` is `Some`, otherwise only one is.
+fn load_older_messages(
+    client: &mut Client,
+    guild_id: u64,
+    channel_id: u64,
+    until: Option<u64>,
+) -> Command<TopLevelMessage> {
+    let before = match client.get_channel(guild_id, channel_id) {
+        Some(channel) if !channel.reached_top && !channel.loading_messages_history => {
+            channel.loading_messages_history = true;
+            channel.messages.first().map(|m| m.id.id()).flatten()
+        }
+        _ => return Command::none(),
+    };
+    let requests = client.requests().clone();
+    let request = move || {
+        let requests = requests.clone();
+        async move {
+            match until {
+                Some(message_id) => {
+                    requests
+                        .channel_messages_until(guild_id, channel_id, before, message_id)
+                        .await
+                }
+                None => {
+                    requests
+                        .channel_messages(guild_id, channel_id, before)
+                        .await
+                }
+            }
+        }
+    };
+
+    Command::perform(
+        client.scheduler().run(
+            RequestKey::ChannelMessages {
+                guild_id,
+                channel_id,
+                before,
+            },
+            Priority::Visible,
+            request,
+        ),
+        // The flag set above has to be cleared however the request ends, or no more messages
+        // are loaded for the channel
+        move |result| match result {
+            Some(Ok((messages, reached_top))) => TopLevelMessage::GetEventsBackwardsResponse {
+                messages,
+                reached_top,
+                guild_id,
+                channel_id,
+            },
+            Some(Err(err)) => TopLevelMessage::HistoryLoadFailed {
+                guild_id,
+                channel_id,
+                err: Some(Box::new(err)),
+            },
+            None => TopLevelMessage::HistoryLoadFailed {
+                guild_id,
+                channel_id,
+                err: None,
+            },
+        },
+    )
+}
+
+/// Puts a message that was removed by a deletion back in its channel.
+fn restore_deleted(client: &mut Client, pending: PendingDelete) {
+    client.restore_message(pending.guild_id, pending.channel_id, pending.message);
+}
+
+/// Sets the status of the current user, updating it locally without waiting for the server.
+fn set_own_status(client: &mut Client, status: UserStatus) -> Command<TopLevelMessage> {
+    if let Some(member) = client.user_id.map(|id| client.get_member(id)).flatten() {
+        member.status = status;
     }
+    client.set_status_cmd(status)
 }
@@ -0,0 +1,410 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use ahash::AHashMap;
+use iced_native::keyboard::{KeyCode, Modifiers};
+
+/// A command the user can run in the main screen, with a key binding or from the command palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    CommandPalette,
+    SwitchChannel,
+    Search,
+    NextUnread,
+    PreviousUnread,
+    MarkRead,
+    EditLastMessage,
+    SelectMessageToEdit,
+    ReplyToLastMessage,
+    UploadFiles,
+    ToggleMembersList,
+    TogglePinnedMessages,
+    ScrollToBottom,
+    Help,
+    Back,
+    ImagePrevious,
+    ImageNext,
+    ImageRotate,
+    ImageZoomFit,
+    ImageZoomOriginal,
+    ImageZoomFill,
+}
+
+pub struct ActionInfo {
+    pub action: Action,
+    /// Name used to refer to this action in the config file.
+    pub id: &'static str,
+    pub description: &'static str,
+    pub default_binding: Option<&'static str>,
+}
+
+/// Every action, in the order they are shown in the help and command palette.
+pub const ACTIONS: &[ActionInfo] = &[
+    ActionInfo {
+        action: Action::CommandPalette,
+        id: "command_palette",
+        description: "Open the command palette",
+        default_binding: Some("Ctrl+Shift+P"),
+    },
+    ActionInfo {
+        action: Action::SwitchChannel,
+        id: "switch_channel",
+        description: "Switch to another channel",
+        default_binding: Some("Ctrl+K"),
+    },
+    ActionInfo {
+        action: Action::Search,
+        id: "search",
+        description: "Search loaded messages",
+        default_binding: Some("Ctrl+F"),
+    },
+    ActionInfo {
+        action: Action::NextUnread,
+        id: "next_unread",
+        description: "Go to the next channel with unread messages",
+        default_binding: Some("Alt+Down"),
+    },
+    ActionInfo {
+        action: Action::PreviousUnread,
+        id: "previous_unread",
+        description: "Go to the previous channel with unread messages",
+        default_binding: Some("Alt+Up"),
+    },
+    ActionInfo {
+        action: Action::MarkRead,
+        id: "mark_read",
+        description: "Mark the current channel as read",
+        default_binding: Some("Shift+Escape"),
+    },
+    ActionInfo {
+        action: Action::EditLastMessage,
+        id: "edit_last_message",
+        description: "Edit the last message you sent",
+        default_binding: Some("Up"),
+    },
+    ActionInfo {
+        action: Action::SelectMessageToEdit,
+        id: "select_message_to_edit",
+        description: "Select a message to edit by clicking on it",
+        default_binding: Some("Ctrl+E"),
+    },
+    ActionInfo {
+        action: Action::ReplyToLastMessage,
+        id: "reply_to_last_message",
+        description: "Reply to the last message in the channel",
+        default_binding: Some("Ctrl+R"),
+    },
+    ActionInfo {
+        action: Action::UploadFiles,
+        id: "upload_files",
+        description: "Upload files to the current channel",
+        default_binding: Some("Ctrl+U"),
+    },
+    ActionInfo {
+        action: Action::ToggleMembersList,
+        id: "toggle_members_list",
+        description: "Show or hide the members list",
+        default_binding: Some("Ctrl+M"),
+    },
+    ActionInfo {
+        action: Action::TogglePinnedMessages,
+        id: "toggle_pinned_messages",
+        description: "Show or hide the pinned messages of the channel",
+        default_binding: Some("Ctrl+P"),
+    },
+    ActionInfo {
+        action: Action::ScrollToBottom,
+        id: "scroll_to_bottom",
+        description: "Scroll to the newest message",
+        default_binding: Some("Ctrl+End"),
+    },
+    ActionInfo {
+        action: Action::Help,
+        id: "help",
+        description: "Show the help",
+        default_binding: Some("F1"),
+    },
+    ActionInfo {
+        action: Action::Back,
+        id: "back",
+        description: "Close dialogs, or return to normal mode",
+        default_binding: Some("Escape"),
+    },
+    ActionInfo {
+        action: Action::ImagePrevious,
+        id: "image_previous",
+        description: "Image viewer: show the previous image",
+        default_binding: Some("Left"),
+    },
+    ActionInfo {
+        action: Action::ImageNext,
+        id: "image_next",
+        description: "Image viewer: show the next image",
+        default_binding: Some("Right"),
+    },
+    ActionInfo {
+        action: Action::ImageRotate,
+        id: "image_rotate",
+        description: "Image viewer: rotate the image",
+        default_binding: Some("R"),
+    },
+    ActionInfo {
+        action: Action::ImageZoomFit,
+        id: "image_zoom_fit",
+        description: "Image viewer: fit the image to the window",
+        default_binding: Some("0"),
+    },
+    ActionInfo {
+        action: Action::ImageZoomOriginal,
+        id: "image_zoom_original",
+        description: "Image viewer: show the image at 100%",
+        default_binding: Some("1"),
+    },
+    ActionInfo {
+        action: Action::ImageZoomFill,
+        id: "image_zoom_fill",
+        description: "Image viewer: fill the window with the image",
+        default_binding: Some("F"),
+    },
+];
+
+impl Action {
+    pub fn info(self) -> &'static ActionInfo {
+        ACTIONS
+            .iter()
+            .find(|info| info.action == self)
+            .expect("every action is in the registry")
+    }
+
+    /// Whether this action only works in the image viewer.
+    ///
+    /// These are only run if no widget used the key, so they don't fire while typing.
+    pub fn is_image_viewer(self) -> bool {
+        matches!(
+            self,
+            Action::ImagePrevious
+                | Action::ImageNext
+                | Action::ImageRotate
+                | Action::ImageZoomFit
+                | Action::ImageZoomOriginal
+                | Action::ImageZoomFill
+        )
+    }
+}
+
+/// A key combination, like `Ctrl+Shift+P`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    pub key_code: KeyCode,
+    pub control: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+impl KeyBinding {
+    pub fn new(key_code: KeyCode, modifiers: Modifiers) -> Self {
+        Self {
+            key_code,
+            control: modifiers.control,
+            shift: modifiers.shift,
+            alt: modifiers.alt,
+            logo: modifiers.logo,
+        }
+    }
+}
+
+/// Names of the keys that can be bound, as they are written in the config file.
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("A", KeyCode::A),
+    ("B", KeyCode::B),
+    ("C", KeyCode::C),
+    ("D", KeyCode::D),
+    ("E", KeyCode::E),
+    ("F", KeyCode::F),
+    ("G", KeyCode::G),
+    ("H", KeyCode::H),
+    ("I", KeyCode::I),
+    ("J", KeyCode::J),
+    ("K", KeyCode::K),
+    ("L", KeyCode::L),
+    ("M", KeyCode::M),
+    ("N", KeyCode::N),
+    ("O", KeyCode::O),
+    ("P", KeyCode::P),
+    ("Q", KeyCode::Q),
+    ("R", KeyCode::R),
+    ("S", KeyCode::S),
+    ("T", KeyCode::T),
+    ("U", KeyCode::U),
+    ("V", KeyCode::V),
+    ("W", KeyCode::W),
+    ("X", KeyCode::X),
+    ("Y", KeyCode::Y),
+    ("Z", KeyCode::Z),
+    ("0", KeyCode::Key0),
+    ("1", KeyCode::Key1),
+    ("2", KeyCode::Key2),
+    ("3", KeyCode::Key3),
+    ("4", KeyCode::Key4),
+    ("5", KeyCode::Key5),
+    ("6", KeyCode::Key6),
+    ("7", KeyCode::Key7),
+    ("8", KeyCode::Key8),
+    ("9", KeyCode::Key9),
+    ("F1", KeyCode::F1),
+    ("F2", KeyCode::F2),
+    ("F3", KeyCode::F3),
+    ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6),
+    ("F7", KeyCode::F7),
+    ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9),
+    ("F10", KeyCode::F10),
+    ("F11", KeyCode::F11),
+    ("F12", KeyCode::F12),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Escape", KeyCode::Escape),
+    ("Enter", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("Space", KeyCode::Space),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+];
+
+impl FromStr for KeyBinding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut binding = KeyBinding {
+            key_code: KeyCode::Escape,
+            control: false,
+            shift: false,
+            alt: false,
+            logo: false,
+        };
+        let mut key_code = None;
+
+        for part in s.split('+').map(str::trim) {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => binding.control = true,
+                "shift" => binding.shift = true,
+                "alt" => binding.alt = true,
+                "super" | "logo" | "cmd" => binding.logo = true,
+                _ => {
+                    let code = KEY_NAMES
+                        .iter()
+                        .find(|(name, _)| name.eq_ignore_ascii_case(part))
+                        .map(|(_, code)| *code)
+                        .ok_or_else(|| format!("unknown key `{}` in `{}`", part, s))?;
+                    if key_code.replace(code).is_some() {
+                        return Err(format!("more than one key in `{}`", s));
+                    }
+                }
+            }
+        }
+
+        binding.key_code = key_code.ok_or_else(|| format!("no key in `{}`", s))?;
+        Ok(binding)
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.control {
+            f.write_str("Ctrl+")?;
+        }
+        if self.shift {
+            f.write_str("Shift+")?;
+        }
+        if self.alt {
+            f.write_str("Alt+")?;
+        }
+        if self.logo {
+            f.write_str("Super+")?;
+        }
+        let name = KEY_NAMES
+            .iter()
+            .find(|(_, code)| *code == self.key_code)
+            .map_or("?", |(name, _)| name);
+        f.write_str(name)
+    }
+}
+
+/// Maps key bindings to actions.
+#[derive(Debug, Default)]
+pub struct Keybindings {
+    actions: AHashMap<KeyBinding, Action>,
+    bindings: AHashMap<Action, KeyBinding>,
+}
+
+impl Keybindings {
+    /// Creates the bindings from the defaults, overridden by the bindings from the config.
+    ///
+    /// An empty binding in the config unbinds the action. Invalid bindings are logged and ignored.
+    pub fn new(overrides: &HashMap<String, String>) -> Self {
+        let mut keybindings = Self::default();
+
+        for info in ACTIONS {
+            let binding = match overrides.get(info.id) {
+                Some(binding) if binding.trim().is_empty() => continue,
+                Some(binding) => binding.as_str(),
+                None => match info.default_binding {
+                    Some(binding) => binding,
+                    None => continue,
+                },
+            };
+            match binding.parse::<KeyBinding>() {
+                Ok(binding) => {
+                    if let Some(other) = keybindings.actions.insert(binding, info.action) {
+                        tracing::warn!(
+                            "{} is bound to both {} and {}, using {}",
+                            binding,
+                            other.info().id,
+                            info.id,
+                            info.id
+                        );
+                        keybindings.bindings.remove(&other);
+                    }
+                    keybindings.bindings.insert(info.action, binding);
+                }
+                Err(err) => tracing::error!("invalid key binding for {}: {}", info.id, err),
+            }
+        }
+        for id in overrides.keys() {
+            if !ACTIONS.iter().any(|info| info.id == id) {
+                tracing::warn!("unknown action in key bindings: {}", id);
+            }
+        }
+
+        keybindings
+    }
+
+    pub fn action_for(&self, binding: &KeyBinding) -> Option<Action> {
+        self.actions.get(binding).copied()
+    }
+
+    pub fn binding_of(&self, action: Action) -> Option<KeyBinding> {
+        self.bindings.get(&action).copied()
+    }
+
+    /// Creates the help text for every action that has a key binding.
+    pub fn help_text(&self) -> String {
+        ACTIONS
+            .iter()
+            .flat_map(|info| {
+                let binding = self.binding_of(info.action)?;
+                Some(format!("{}: {}", binding, info.description))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
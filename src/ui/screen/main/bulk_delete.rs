@@ -0,0 +1,177 @@
+use super::super::Message as TopLevelMessage;
+
+use crate::{
+    client::{
+        error::{ClientError, ClientResult},
+        Client,
+    },
+    label, label_button, length, space,
+    ui::{
+        component::*,
+        style::{Theme, DEF_SIZE, ERROR_COLOR, PADDING},
+    },
+};
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    CountChanged(String),
+    /// `true` if the user confirmed the deletion.
+    Confirm(bool),
+}
+
+/// Asks for confirmation before deleting the last messages in a channel.
+#[derive(Debug)]
+pub struct BulkDeleteModal {
+    approve_but_state: button::State,
+    cancel_but_state: button::State,
+    count_edit_state: text_input::State,
+    count_field: String,
+    deleting: bool,
+}
+
+impl Default for BulkDeleteModal {
+    fn default() -> Self {
+        Self {
+            approve_but_state: Default::default(),
+            cancel_but_state: Default::default(),
+            count_edit_state: Default::default(),
+            count_field: "10".to_string(),
+            deleting: false,
+        }
+    }
+}
+
+impl BulkDeleteModal {
+    pub fn view(&mut self, theme: Theme) -> Element<Message> {
+        if self.deleting {
+            return fill_container(label!("Deleting messages...").size(30))
+                .style(theme)
+                .into();
+        }
+
+        let count = self.count();
+        let make_button = |state, confirm| {
+            let text = if confirm { "Yes" } else { "No" };
+
+            let mut but = label_button!(state, text).style(theme).width(length!(= 80));
+            if !confirm || count.is_some() {
+                but = but.on_press(Message::Confirm(confirm));
+            }
+            but
+        };
+
+        let confirm_panel = column(vec![
+            label!("How many of the latest messages do you want to delete?")
+                .size(DEF_SIZE + 2)
+                .into(),
+            TextInput::new(
+                &mut self.count_edit_state,
+                "Message count",
+                &self.count_field,
+                Message::CountChanged,
+            )
+            .padding(PADDING / 2)
+            .width(length!(= 200))
+            .style(theme)
+            .into(),
+            label!("This can't be undone.")
+                .color(ERROR_COLOR)
+                .size(DEF_SIZE + 2)
+                .into(),
+            row(vec![
+                make_button(&mut self.approve_but_state, true).into(),
+                space!(w = 200).into(),
+                make_button(&mut self.cancel_but_state, false).into(),
+            ])
+            .into(),
+        ])
+        .spacing(12);
+
+        Container::new(confirm_panel)
+            .style(theme.round())
+            .center_x()
+            .center_y()
+            .into()
+    }
+
+    /// Updates the modal, returning `true` if it should be closed.
+    pub fn update(
+        &mut self,
+        msg: Message,
+        guild_id: u64,
+        channel_id: u64,
+        client: &Client,
+    ) -> (Command<TopLevelMessage>, bool) {
+        match msg {
+            Message::CountChanged(count) => self.count_field = count,
+            Message::Confirm(false) => return (Command::none(), true),
+            Message::Confirm(true) => {
+                let count = match self.count() {
+                    Some(count) => count,
+                    None => return (Command::none(), false),
+                };
+                let message_ids = client
+                    .guilds
+                    .get(&guild_id)
+                    .map(|guild| guild.channels.get(&channel_id))
+                    .flatten()
+                    .map_or_else(Vec::new, |channel| {
+                        channel
+                            .messages
+                            .iter()
+                            .rev()
+                            .flat_map(|message| message.id.id())
+                            .take(count)
+                            .collect()
+                    });
+                let requests = client.requests().clone();
+                self.deleting = true;
+
+                return (
+                    Command::perform(
+                        async move {
+                            for message_id in message_ids {
+                                requests
+                                    .delete_message(guild_id, channel_id, message_id)
+                                    .await?;
+                            }
+                            Ok(())
+                        },
+                        |result: ClientResult<()>| {
+                            result.map_or_else(
+                                |err| TopLevelMessage::Error(Box::new(err)),
+                                |_| {
+                                    TopLevelMessage::MainScreen(super::Message::BulkDeleteMsg(
+                                        Message::Confirm(false),
+                                    ))
+                                },
+                            )
+                        },
+                    ),
+                    false,
+                );
+            }
+        }
+
+        (Command::none(), false)
+    }
+
+    pub fn on_error(&mut self, _error: &ClientError) -> Command<TopLevelMessage> {
+        self.deleting = false;
+
+        Command::none()
+    }
+
+    /// Sets the modal up to be shown again.
+    pub fn reset(&mut self) {
+        self.deleting = false;
+    }
+
+    fn count(&self) -> Option<usize> {
+        self.count_field
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|count| *count > 0)
+    }
+}
@@ -0,0 +1,238 @@
+use super::{super::Message as TopLevelMessage, Message as ParentMessage};
+use iced_aw::Card;
+
+use crate::{
+    client::{error::ClientError, Client},
+    label, label_button, length,
+    ui::{
+        client::ClientExt,
+        component::*,
+        style::{Theme, ERROR_COLOR, PADDING, SPACING, SUCCESS_COLOR},
+    },
+};
+
+#[derive(Clone, Debug)]
+pub enum Message {
+    NameChanged(String),
+    Rename,
+    Renamed,
+    Delete,
+    Move { up: bool },
+    GoBack,
+}
+
+/// Lets the user rename, delete or move a channel.
+#[derive(Default, Debug)]
+pub struct ChannelMenuModal {
+    pub channel_id: u64,
+    name_textedit_state: text_input::State,
+    rename_but_state: button::State,
+    delete_but_state: button::State,
+    move_up_but_state: button::State,
+    move_down_but_state: button::State,
+    name_field: String,
+    confirm_delete: bool,
+    status_text: String,
+    error_text: String,
+}
+
+impl ChannelMenuModal {
+    pub fn open(&mut self, channel_id: u64) {
+        *self = Self {
+            channel_id,
+            ..Default::default()
+        };
+    }
+
+    pub fn view<'a>(
+        &'a mut self,
+        theme: Theme,
+        client: &'a Client,
+        guild_id: u64,
+    ) -> Element<'a, Message> {
+        let guild = client.guilds.get(&guild_id);
+        let channel = guild
+            .map(|guild| guild.channels.get(&self.channel_id))
+            .flatten();
+        let (title, kind) = channel.map_or_else(
+            || ("Unknown channel".to_string(), "channel"),
+            |channel| {
+                if channel.is_category {
+                    (channel.name.clone(), "category")
+                } else {
+                    (format!("#{}", channel.name), "channel")
+                }
+            },
+        );
+        let position = guild
+            .map(|guild| guild.channels.get_index_of(&self.channel_id))
+            .flatten();
+        let channel_count = guild.map_or(0, |guild| guild.channels.len());
+
+        let mut name_edit = TextInput::new(
+            &mut self.name_textedit_state,
+            "Enter a new name...",
+            &self.name_field,
+            Message::NameChanged,
+        )
+        .padding(PADDING / 2)
+        .width(length!(= 300))
+        .style(theme);
+        let mut rename_but = label_button!(&mut self.rename_but_state, "Rename").style(theme);
+        if !self.name_field.trim().is_empty() {
+            name_edit = name_edit.on_submit(Message::Rename);
+            rename_but = rename_but.on_press(Message::Rename);
+        }
+
+        let mut move_up_but = label_button!(&mut self.move_up_but_state, "Move up").style(theme);
+        if position.map_or(false, |pos| pos > 0) {
+            move_up_but = move_up_but.on_press(Message::Move { up: true });
+        }
+        let mut move_down_but =
+            label_button!(&mut self.move_down_but_state, "Move down").style(theme);
+        if position.map_or(false, |pos| pos + 1 < channel_count) {
+            move_down_but = move_down_but.on_press(Message::Move { up: false });
+        }
+
+        let delete_text = if self.confirm_delete {
+            "Click again to delete".to_string()
+        } else {
+            format!("Delete {}", kind)
+        };
+        let delete_but = label_button!(&mut self.delete_but_state, delete_text)
+            .on_press(Message::Delete)
+            .style(theme);
+
+        let mut widgets = Vec::with_capacity(4);
+        if !self.error_text.is_empty() {
+            widgets.push(label!(&self.error_text).color(ERROR_COLOR).into());
+        } else if !self.status_text.is_empty() {
+            widgets.push(label!(&self.status_text).color(SUCCESS_COLOR).into());
+        }
+        widgets.push(
+            Row::with_children(vec![
+                name_edit.into(),
+                rename_but.width(length!(= 80)).into(),
+            ])
+            .align_items(align!(|))
+            .spacing(SPACING * 2)
+            .into(),
+        );
+        widgets.push(
+            Row::with_children(vec![
+                move_up_but.width(length!(= 110)).into(),
+                move_down_but.width(length!(= 110)).into(),
+                delete_but.width(length!(+)).into(),
+            ])
+            .align_items(align!(|))
+            .spacing(SPACING * 2)
+            .into(),
+        );
+
+        Container::new(
+            Card::new(
+                label!(title).width(length!(= 380 + PADDING + SPACING)),
+                column(widgets),
+            )
+            .style(theme.round())
+            .on_close(Message::GoBack),
+        )
+        .style(theme.round())
+        .center_x()
+        .center_y()
+        .into()
+    }
+
+    pub fn update(
+        &mut self,
+        msg: Message,
+        guild_id: u64,
+        client: &Client,
+    ) -> (Command<TopLevelMessage>, bool) {
+        let channel_id = self.channel_id;
+        let mut go_back = false;
+
+        match msg {
+            Message::NameChanged(name) => self.name_field = name,
+            Message::Rename => {
+                let name = self.name_field.trim().to_string();
+                let requests = client.requests().clone();
+                self.error_text.clear();
+                self.status_text.clear();
+
+                return (
+                    Command::perform(
+                        async move { requests.rename_channel(guild_id, channel_id, name).await },
+                        |result| {
+                            result.map_or_else(
+                                |err| TopLevelMessage::Error(Box::new(err)),
+                                |_| {
+                                    TopLevelMessage::MainScreen(ParentMessage::ChannelMenuMessage(
+                                        Message::Renamed,
+                                    ))
+                                },
+                            )
+                        },
+                    ),
+                    go_back,
+                );
+            }
+            Message::Renamed => {
+                self.name_field.clear();
+                self.status_text = "Renamed.".to_string();
+            }
+            Message::Delete => {
+                if !self.confirm_delete {
+                    self.confirm_delete = true;
+                } else {
+                    let requests = client.requests().clone();
+                    self.confirm_delete = false;
+
+                    return (
+                        Command::perform(
+                            async move { requests.delete_channel(guild_id, channel_id).await },
+                            |result| {
+                                result.map_or_else(
+                                    |err| TopLevelMessage::Error(Box::new(err)),
+                                    |_| TopLevelMessage::Nothing,
+                                )
+                            },
+                        ),
+                        true,
+                    );
+                }
+            }
+            Message::Move { up } => {
+                if let Some((previous_id, next_id)) = client
+                    .guilds
+                    .get(&guild_id)
+                    .map(|guild| {
+                        let position = guild.channels.get_index_of(&channel_id)?;
+                        let position = if up {
+                            position.checked_sub(1)?
+                        } else {
+                            position + 1
+                        };
+                        guild.channel_neighbours_at(channel_id, position)
+                    })
+                    .flatten()
+                {
+                    return (
+                        client.move_channel_cmd(guild_id, channel_id, previous_id, next_id),
+                        go_back,
+                    );
+                }
+            }
+            Message::GoBack => go_back = true,
+        }
+
+        (Command::none(), go_back)
+    }
+
+    pub fn on_error(&mut self, error: &ClientError) -> Command<TopLevelMessage> {
+        self.status_text.clear();
+        self.error_text = error.to_string();
+
+        Command::none()
+    }
+}
@@ -0,0 +1,116 @@
+use super::actions::{Action, Keybindings, ACTIONS};
+use crate::{
+    label, length, space,
+    ui::{
+        component::*,
+        style::{Theme, ALT_COLOR, DEF_SIZE, PADDING, SPACING},
+    },
+};
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    SearchChanged(String),
+    Run(Action),
+}
+
+#[derive(Debug, Default)]
+pub struct CommandPaletteModal {
+    search_state: text_input::State,
+    results_state: scrollable::State,
+    results_buts_state: Vec<button::State>,
+    search_value: String,
+    results: Vec<Action>,
+}
+
+impl CommandPaletteModal {
+    /// Clears the search, listing every action.
+    pub fn reset(&mut self) {
+        self.search(String::new());
+    }
+
+    /// Fuzzy searches the action descriptions, best matches first.
+    pub fn search(&mut self, term: String) {
+        let pattern = term.trim();
+        self.results = if pattern.is_empty() {
+            ACTIONS.iter().map(|info| info.action).collect()
+        } else {
+            let matcher = SkimMatcherV2::default();
+            let mut scored = ACTIONS
+                .iter()
+                .flat_map(|info| {
+                    let score = matcher
+                        .fuzzy_match(info.description, pattern)
+                        .max(matcher.fuzzy_match(info.id, pattern))?;
+                    Some((score, info.action))
+                })
+                .collect::<Vec<_>>();
+            // Stable sort keeps registry order for equal scores
+            scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+            scored.into_iter().map(|(_, action)| action).collect()
+        };
+        self.results_buts_state
+            .resize_with(self.results.len(), Default::default);
+        self.search_value = term;
+    }
+
+    pub fn view(&mut self, theme: Theme, keybindings: &Keybindings) -> Element<Message> {
+        self.search_state.focus();
+
+        let mut search_bar = TextInput::new(
+            &mut self.search_state,
+            "Search commands",
+            &self.search_value,
+            Message::SearchChanged,
+        )
+        .padding(PADDING / 2)
+        .size(DEF_SIZE + 4)
+        .style(theme);
+
+        if let Some(action) = self.results.first() {
+            search_bar = search_bar.on_submit(Message::Run(*action));
+        }
+
+        let mut result_list = Scrollable::new(&mut self.results_state)
+            .style(theme)
+            .width(length!(+))
+            .height(length!(+))
+            .spacing(SPACING);
+        for (action, but_stt) in self.results.iter().zip(self.results_buts_state.iter_mut()) {
+            let binding = keybindings
+                .binding_of(*action)
+                .map(|binding| binding.to_string())
+                .unwrap_or_default();
+            let content = row(vec![
+                label!(action.info().description).into(),
+                space!(w+).into(),
+                label!(binding).color(ALT_COLOR).into(),
+            ])
+            .padding(0);
+
+            result_list = result_list.push(
+                Button::new(but_stt, content)
+                    .width(length!(+))
+                    .style(theme)
+                    .on_press(Message::Run(*action)),
+            );
+        }
+
+        Row::with_children(vec![
+            space!(w % 2).into(),
+            Column::with_children(vec![
+                space!(h % 2).into(),
+                Container::new(column(vec![search_bar.into(), result_list.into()]))
+                    .style(theme.round())
+                    .height(length!(%6))
+                    .into(),
+                space!(h % 2).into(),
+            ])
+            .width(length!(%6))
+            .into(),
+            space!(w % 2).into(),
+        ])
+        .into()
+    }
+}
@@ -1,5 +1,5 @@
 use super::{super::Message as TopLevelMessage, Message as ParentMessage};
-use harmony_rust_sdk::client::api::chat::channel;
+use iced::Checkbox;
 use iced_aw::Card;
 
 use crate::{
@@ -33,6 +33,7 @@ impl Default for ChannelState {
 #[derive(Clone, Debug)]
 pub enum Message {
     ChannelNameChanged(String),
+    IsCategoryToggled(bool),
     CreateChannel,
     CreatedChannel { guild_id: u64, channel_id: u64 },
     GoBack,
@@ -45,14 +46,25 @@ pub struct ChannelCreationModal {
     channel_create_but_state: button::State,
     channel_creation_state: ChannelState,
     channel_name_field: String,
+    is_category: bool,
     error_text: String,
 }
 
 impl ChannelCreationModal {
     pub fn view(&mut self, theme: Theme) -> Element<Message> {
+        let kind = if self.is_category {
+            "category"
+        } else {
+            "channel"
+        };
+
         let mut create_text_edit = TextInput::new(
             &mut self.channel_name_textedit_state,
-            "Enter a channel name...",
+            if self.is_category {
+                "Enter a category name..."
+            } else {
+                "Enter a channel name..."
+            },
             &self.channel_name_field,
             Message::ChannelNameChanged,
         )
@@ -69,17 +81,17 @@ impl ChannelCreationModal {
             }
         }
 
-        let mut create_widgets = Vec::with_capacity(2);
+        let mut create_widgets = Vec::with_capacity(3);
         match &self.channel_creation_state {
             ChannelState::Created { name, .. } => {
                 create_widgets.push(
-                    label!("Successfully created channel {}", name)
+                    label!("Successfully created {} {}", kind, name)
                         .color(SUCCESS_COLOR)
                         .into(),
                 );
             }
             ChannelState::Creating { name } => {
-                create_widgets.push(label!("Creating channel {}", name).into())
+                create_widgets.push(label!("Creating {} {}", kind, name).into())
             }
             _ => {}
         }
@@ -96,10 +108,15 @@ impl ChannelCreationModal {
             .spacing(SPACING * 2)
             .into(),
         );
+        create_widgets.push(
+            Checkbox::new(self.is_category, "Category", Message::IsCategoryToggled)
+                .style(theme)
+                .into(),
+        );
 
         Container::new(
             Card::new(
-                label!("Create channel").width(length!(= 380 + PADDING + SPACING)),
+                label!("Create {}", kind).width(length!(= 380 + PADDING + SPACING)),
                 column(create_widgets),
             )
             .style(theme.round())
@@ -122,42 +139,41 @@ impl ChannelCreationModal {
             super::create_channel::Message::ChannelNameChanged(new_name) => {
                 self.channel_name_field = new_name;
             }
+            super::create_channel::Message::IsCategoryToggled(is_category) => {
+                self.is_category = is_category;
+            }
             super::create_channel::Message::CreateChannel => {
                 let channel_name = self.channel_name_field.clone();
+                let is_category = self.is_category;
 
                 self.error_text.clear();
                 self.channel_creation_state = ChannelState::Creating {
                     name: channel_name.clone(),
                 };
-                let inner = client.inner().clone();
+                let requests = client.requests().clone();
 
                 return (
                     Command::perform(
                         async move {
-                            let result = channel::create_channel(
-                                &inner,
-                                channel::CreateChannel::new(
-                                    guild_id,
-                                    channel_name,
-                                    harmony_rust_sdk::api::chat::Place::Top { before: 0 },
-                                ),
-                            )
-                            .await;
+                            requests
+                                .create_channel(guild_id, channel_name, is_category)
+                                .await
+                        },
+                        move |result| {
                             result.map_or_else(
-                                |e| TopLevelMessage::Error(Box::new(e.into())),
-                                |response| {
+                                |e| TopLevelMessage::Error(Box::new(e)),
+                                |channel_id| {
                                     TopLevelMessage::MainScreen(
                                         ParentMessage::ChannelCreationMessage(
                                             Message::CreatedChannel {
                                                 guild_id,
-                                                channel_id: response.channel_id,
+                                                channel_id,
                                             },
                                         ),
                                     )
                                 },
                             )
                         },
-                        |msg| msg,
                     ),
                     go_back,
                 );
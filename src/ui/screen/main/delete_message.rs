@@ -0,0 +1,60 @@
+use crate::{
+    label, label_button, length, space,
+    ui::{
+        component::*,
+        style::{Theme, ALT_COLOR, DEF_SIZE},
+    },
+};
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// `true` if the user confirmed the deletion.
+    Confirm(bool),
+}
+
+/// Asks for confirmation before deleting a single message.
+#[derive(Debug, Default)]
+pub struct DeleteMessageModal {
+    approve_but_state: button::State,
+    cancel_but_state: button::State,
+    pub message_id: u64,
+    /// A summary of the message, so the user knows what they are deleting.
+    summary: String,
+}
+
+impl DeleteMessageModal {
+    pub fn open(&mut self, message_id: u64, summary: String) {
+        self.message_id = message_id;
+        self.summary = summary;
+    }
+
+    pub fn view(&mut self, theme: Theme) -> Element<Message> {
+        let make_button = |state, confirm| {
+            let text = if confirm { "Delete" } else { "Cancel" };
+
+            label_button!(state, text)
+                .on_press(Message::Confirm(confirm))
+                .style(theme)
+                .width(length!(= 80))
+        };
+
+        let confirm_panel = column(vec![
+            label!("Delete this message?").size(DEF_SIZE + 2).into(),
+            label!(&self.summary).color(ALT_COLOR).into(),
+            label!("You can undo this for a few seconds.").into(),
+            row(vec![
+                make_button(&mut self.approve_but_state, true).into(),
+                space!(w = 200).into(),
+                make_button(&mut self.cancel_but_state, false).into(),
+            ])
+            .into(),
+        ])
+        .spacing(12);
+
+        Container::new(confirm_panel)
+            .style(theme.round())
+            .center_x()
+            .center_y()
+            .into()
+    }
+}
@@ -0,0 +1,390 @@
+use iced_aw::Card;
+
+use super::super::Message as TopLevelMessage;
+use crate::{
+    client::{
+        color::Color,
+        error::ClientError,
+        message::{Content as IcyContent, Embed, EmbedField, EmbedHeading},
+        Client,
+    },
+    label, label_button, length, space,
+    ui::{client::ClientExt, component::*, style::*},
+};
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    TitleChanged(String),
+    BodyChanged(String),
+    ColorChanged(String),
+    HeaderChanged(String),
+    FooterChanged(String),
+    FieldChanged(usize, FieldPart, String),
+    AddField,
+    RemoveField(usize),
+    Save,
+    Back,
+}
+
+/// A part of an embed field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldPart {
+    Title,
+    Subtitle,
+    Body,
+}
+
+/// An embed field being edited.
+#[derive(Debug, Default)]
+struct FieldEditor {
+    title_state: text_input::State,
+    subtitle_state: text_input::State,
+    body_state: text_input::State,
+    remove_but_state: button::State,
+    title: String,
+    subtitle: String,
+    body: String,
+}
+
+impl FieldEditor {
+    fn new(field: &EmbedField) -> Self {
+        Self {
+            title: field.title.clone(),
+            subtitle: field.subtitle.clone(),
+            body: field.body.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn to_field(&self) -> EmbedField {
+        EmbedField {
+            title: self.title.trim().to_string(),
+            subtitle: self.subtitle.trim().to_string(),
+            body: self.body.trim().to_string(),
+        }
+    }
+}
+
+/// Edits the content of an embed message the current user sent.
+#[derive(Debug, Default)]
+pub struct EmbedEditorModal {
+    title_state: text_input::State,
+    body_state: text_input::State,
+    color_state: text_input::State,
+    header_state: text_input::State,
+    footer_state: text_input::State,
+    fields_state: scrollable::State,
+    add_field_but_state: button::State,
+    save_but_state: button::State,
+    back_but_state: button::State,
+    message_id: u64,
+    /// The embed being edited, which keeps the parts that can't be edited here.
+    original: Option<Embed>,
+    title: String,
+    body: String,
+    color: String,
+    header: String,
+    footer: String,
+    fields: Vec<FieldEditor>,
+    /// Whether the edit was sent, and the modal waits for the server to apply it.
+    saving: bool,
+    error_text: String,
+}
+
+impl EmbedEditorModal {
+    pub fn open(&mut self, message_id: u64, embed: &Embed) {
+        let heading_text = |heading: &Option<EmbedHeading>| {
+            heading
+                .as_ref()
+                .map_or_else(String::new, |heading| heading.text.clone())
+        };
+
+        *self = Self {
+            message_id,
+            title: embed.title.clone(),
+            body: embed.body.clone(),
+            color: color_to_hex(embed.color),
+            header: heading_text(&embed.header),
+            footer: heading_text(&embed.footer),
+            fields: embed.fields.iter().map(FieldEditor::new).collect(),
+            original: Some(embed.clone()),
+            ..Default::default()
+        };
+    }
+
+    pub fn view(&mut self, theme: Theme) -> Element<Message> {
+        let color = hex_to_color(&self.color);
+
+        let make_field =
+            |state, placeholder: &str, value: &str, on_change: fn(String) -> Message| {
+                TextInput::new(state, placeholder, value, on_change)
+                    .padding(PADDING / 2)
+                    .width(length!(+))
+                    .style(theme)
+                    .into()
+            };
+
+        let mut widgets = Vec::with_capacity(10);
+        if !self.error_text.is_empty() {
+            widgets.push(label!(&self.error_text).color(ERROR_COLOR).into());
+        }
+        widgets.push(make_field(
+            &mut self.title_state,
+            "Title",
+            &self.title,
+            Message::TitleChanged,
+        ));
+        widgets.push(make_field(
+            &mut self.body_state,
+            "Body",
+            &self.body,
+            Message::BodyChanged,
+        ));
+        widgets.push(make_field(
+            &mut self.header_state,
+            "Header",
+            &self.header,
+            Message::HeaderChanged,
+        ));
+        widgets.push(make_field(
+            &mut self.footer_state,
+            "Footer",
+            &self.footer,
+            Message::FooterChanged,
+        ));
+        let mut fields_list = Scrollable::new(&mut self.fields_state)
+            .style(theme)
+            .width(length!(+))
+            .max_height(250)
+            .spacing(SPACING);
+        for (index, field) in self.fields.iter_mut().enumerate() {
+            let part = |part: FieldPart| move |value| Message::FieldChanged(index, part, value);
+            fields_list = fields_list.push(
+                Container::new(
+                    column(vec![
+                        row(vec![
+                            label!("Field {}", index + 1).width(length!(+)).into(),
+                            label_button!(&mut field.remove_but_state, "Remove")
+                                .on_press(Message::RemoveField(index))
+                                .style(theme)
+                                .into(),
+                        ])
+                        .padding(0)
+                        .into(),
+                        TextInput::new(
+                            &mut field.title_state,
+                            "Field title",
+                            &field.title,
+                            part(FieldPart::Title),
+                        )
+                        .padding(PADDING / 2)
+                        .style(theme)
+                        .into(),
+                        TextInput::new(
+                            &mut field.subtitle_state,
+                            "Field subtitle",
+                            &field.subtitle,
+                            part(FieldPart::Subtitle),
+                        )
+                        .padding(PADDING / 2)
+                        .style(theme)
+                        .into(),
+                        TextInput::new(
+                            &mut field.body_state,
+                            "Field body",
+                            &field.body,
+                            part(FieldPart::Body),
+                        )
+                        .padding(PADDING / 2)
+                        .style(theme)
+                        .into(),
+                    ])
+                    .padding(PADDING / 4),
+                )
+                .style(theme.round()),
+            );
+        }
+        widgets.push(fields_list.into());
+        widgets.push(
+            label_button!(&mut self.add_field_but_state, "Add field")
+                .on_press(Message::AddField)
+                .style(theme)
+                .into(),
+        );
+
+        let mut color_label = label!("Color");
+        if color.is_none() {
+            color_label = color_label.color(ERROR_COLOR);
+        }
+        widgets.push(
+            row(vec![
+                color_label.into(),
+                make_field(
+                    &mut self.color_state,
+                    "#RRGGBB",
+                    &self.color,
+                    Message::ColorChanged,
+                ),
+            ])
+            .padding(0)
+            .into(),
+        );
+
+        let mut save_but = if self.saving {
+            label_button!(&mut self.save_but_state, "Saving...")
+        } else {
+            label_button!(&mut self.save_but_state, "Save")
+        }
+        .width(length!(= 80))
+        .style(theme);
+        if color.is_some() && !self.saving {
+            save_but = save_but.on_press(Message::Save);
+        }
+        widgets.push(
+            row(vec![
+                save_but.into(),
+                space!(w+).into(),
+                label_button!(&mut self.back_but_state, "Cancel")
+                    .width(length!(= 80))
+                    .on_press(Message::Back)
+                    .style(theme)
+                    .into(),
+            ])
+            .padding(0)
+            .into(),
+        );
+
+        Container::new(
+            Card::new(
+                label!("Edit embed").width(length!(= 400 - PADDING - SPACING)),
+                column(widgets).width(length!(= 400)),
+            )
+            .style(theme.round())
+            .on_close(Message::Back),
+        )
+        .style(theme.round())
+        .center_x()
+        .center_y()
+        .into()
+    }
+
+    /// Updates the modal, returning `true` if it should be closed.
+    ///
+    /// Saving keeps the modal open until the edit is done, see [`Self::edit_finished`].
+    pub fn update(
+        &mut self,
+        msg: Message,
+        guild_id: u64,
+        channel_id: u64,
+        client: &Client,
+    ) -> (Command<TopLevelMessage>, bool) {
+        match msg {
+            Message::TitleChanged(title) => self.title = title,
+            Message::BodyChanged(body) => self.body = body,
+            Message::ColorChanged(color) => self.color = color,
+            Message::HeaderChanged(header) => self.header = header,
+            Message::FooterChanged(footer) => self.footer = footer,
+            Message::FieldChanged(index, part, value) => {
+                if let Some(field) = self.fields.get_mut(index) {
+                    match part {
+                        FieldPart::Title => field.title = value,
+                        FieldPart::Subtitle => field.subtitle = value,
+                        FieldPart::Body => field.body = value,
+                    }
+                }
+            }
+            Message::AddField => self.fields.push(FieldEditor::default()),
+            Message::RemoveField(index) => {
+                if index < self.fields.len() {
+                    self.fields.remove(index);
+                }
+            }
+            Message::Save => {
+                if let (Some(mut embed), Some(color), false) = (
+                    self.original.clone(),
+                    hex_to_color(&self.color),
+                    self.saving,
+                ) {
+                    embed.title = self.title.trim().to_string();
+                    embed.body = self.body.trim().to_string();
+                    embed.color = color;
+                    embed.header = edit_heading(embed.header, &self.header);
+                    embed.footer = edit_heading(embed.footer, &self.footer);
+                    embed.fields = self.fields.iter().map(FieldEditor::to_field).collect();
+
+                    self.saving = true;
+                    self.error_text.clear();
+                    return (
+                        client.edit_msg_cmd(
+                            guild_id,
+                            channel_id,
+                            self.message_id,
+                            IcyContent::Embeds(Box::new(embed)),
+                        ),
+                        false,
+                    );
+                }
+            }
+            Message::Back => return (Command::none(), true),
+        }
+
+        (Command::none(), false)
+    }
+
+    /// Handles the end of an edit of a message, returning whether it was the edit this modal saved.
+    ///
+    /// The modal should be closed if the edit was applied, otherwise the error is shown in it so
+    /// that saving can be tried again.
+    pub fn edit_finished(&mut self, message_id: u64, err: Option<&ClientError>) -> bool {
+        if !self.saving || self.message_id != message_id {
+            return false;
+        }
+        self.saving = false;
+        if let Some(err) = err {
+            self.error_text = err.to_string();
+        }
+
+        true
+    }
+
+    pub fn on_error(&mut self, error: &ClientError) -> Command<TopLevelMessage> {
+        self.saving = false;
+        self.error_text = error.to_string();
+
+        Command::none()
+    }
+}
+
+/// Changes the text of a heading, removing it if the text is cleared.
+fn edit_heading(heading: Option<EmbedHeading>, text: &str) -> Option<EmbedHeading> {
+    let text = text.trim();
+    match heading {
+        _ if text.is_empty() => None,
+        Some(heading) => Some(EmbedHeading {
+            text: text.to_string(),
+            ..heading
+        }),
+        None => Some(EmbedHeading {
+            text: text.to_string(),
+            subtext: String::new(),
+            url: None,
+            icon: None,
+        }),
+    }
+}
+
+fn color_to_hex(color: Color) -> String {
+    format!("#{:06X}", color.to_harmony())
+}
+
+/// Parses a `#RRGGBB` color, the `#` being optional.
+fn hex_to_color(hex: &str) -> Option<Color> {
+    let hex = hex.trim();
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16)
+        .ok()
+        .map(|rgb| Color::from_harmony(rgb.into()))
+}
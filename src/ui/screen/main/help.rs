@@ -1,5 +1,6 @@
 use iced_aw::Card;
 
+use super::actions::Keybindings;
 use crate::{
     length,
     ui::{component::*, style::*},
@@ -7,17 +8,20 @@ use crate::{
 
 pub type Message = bool;
 
-const HELP: &str = include_str!("help.txt");
+/// Tips that aren't about key bindings, which are listed from the action registry instead.
+const TIPS: &str = include_str!("help.txt");
 
 #[derive(Debug, Default)]
 pub struct HelpModal;
 
 impl HelpModal {
-    pub fn view(&mut self, theme: Theme) -> Element<Message> {
+    pub fn view(&mut self, theme: Theme, keybindings: &Keybindings) -> Element<Message> {
+        let help = format!("{}\n\n{}", keybindings.help_text(), TIPS.trim_end());
+
         Container::new(
             Card::new(
                 label!("Help").width(length!(=512 - PADDING - SPACING)),
-                label!(HELP).width(length!(=512)),
+                label!(help).width(length!(=512)),
             )
             .style(theme.round())
             .on_close(true),
@@ -3,78 +3,299 @@ use std::path::PathBuf;
 use super::super::Message as TopLevelMessage;
 
 use crate::{
-    client::content::ImageHandle,
-    label_button, length,
+    client::{error::ClientError, message::Attachment},
+    label, label_button, length, space,
     ui::{
         component::*,
-        style::{Theme, PADDING, SPACING},
+        style::{Theme, ALT_COLOR, DEF_SIZE, PADDING, SPACING},
+        thumbnail::{get_image_dimensions, ImageHandle},
     },
 };
 
+use chrono::NaiveDateTime;
 use iced::image::{viewer, Viewer};
 use iced_aw::Card;
 
+const VIEWER_WIDTH: u16 = 720;
+const VIEWER_HEIGHT: u16 = 480;
+
+/// Zoom presets for the image viewer. Mouse wheel zoom and drag panning work on top of all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zoom {
+    /// Scale the image down so that it fits the viewer.
+    Fit,
+    /// Show the image at its original size, as long as the window is big enough.
+    Original,
+    /// Use all the space the window can give.
+    Fill,
+}
+
+impl Default for Zoom {
+    fn default() -> Self {
+        Zoom::Fit
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Message {
     OpenExternal,
+    SaveAs,
+    SetZoom(Zoom),
+    Rotate,
+    Previous,
+    Next,
     Close,
 }
 
+/// An image opened in the viewer, along with where it came from.
+#[derive(Debug, Clone)]
+pub struct ViewedImage {
+    pub handle: ImageHandle,
+    pub path: PathBuf,
+    pub attachment: Attachment,
+    pub sender: Option<String>,
+    pub timestamp: Option<NaiveDateTime>,
+    /// Position of this image in the channel gallery and the total image count.
+    pub gallery_position: Option<(usize, usize)>,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct ImageViewerModal {
-    pub image_handle: Option<(ImageHandle, (PathBuf, String))>,
+    pub image: Option<ViewedImage>,
+    /// Dimensions of the unrotated image, if they could be read.
+    dimensions: Option<(u32, u32)>,
+    /// Size of the image file in bytes.
+    file_size: u64,
+    /// The image handle rotated by `rotation` quarter turns, `None` if not rotated.
+    rotated_handle: Option<ImageHandle>,
+    rotation: u8,
+    zoom: Zoom,
     viewer_state: viewer::State,
+    zoom_buts_state: [button::State; 3],
+    rotate_but_state: button::State,
+    prev_but_state: button::State,
+    next_but_state: button::State,
+    save_but_state: button::State,
     external_but_state: button::State,
-    close_but_state: button::State,
 }
 
 impl ImageViewerModal {
+    pub fn open(&mut self, image: ViewedImage) {
+        self.viewer_state = Default::default();
+        self.rotated_handle = None;
+        self.rotation = 0;
+        self.dimensions = get_image_dimensions(&image.handle, &image.path);
+        self.file_size = std::fs::metadata(&image.path)
+            .map(|meta| meta.len())
+            .unwrap_or(image.attachment.size as u64);
+        self.image = Some(image);
+    }
+
+    pub fn current_attachment(&self) -> Option<&Attachment> {
+        self.image.as_ref().map(|image| &image.attachment)
+    }
+
     pub fn view(&mut self, theme: Theme) -> Element<Message> {
-        if let Some((handle, name)) = self
-            .image_handle
-            .as_ref()
-            .map(|(handle, (_, name))| (handle.clone(), name))
+        let image = match self.image.as_ref() {
+            Some(image) => image,
+            None => unreachable!("image viewer is only shown when an image is opened"),
+        };
+        let handle = self
+            .rotated_handle
+            .clone()
+            .unwrap_or_else(|| image.handle.clone());
+        // Quarter turns swap the sides of the image
+        let dimensions = self.dimensions.map(|(w, h)| {
+            if self.rotation % 2 == 1 {
+                (h, w)
+            } else {
+                (w, h)
+            }
+        });
+
+        let (width, height) = match (self.zoom, dimensions) {
+            (Zoom::Original, Some((w, h))) => (
+                length!(= w.min(u16::MAX as u32) as u16),
+                length!(= h.min(u16::MAX as u32) as u16),
+            ),
+            (Zoom::Fill, _) => (length!(+), length!(+)),
+            _ => (length!(= VIEWER_WIDTH), length!(= VIEWER_HEIGHT)),
+        };
+
+        let mut info = Vec::with_capacity(4);
+        if let Some((w, h)) = dimensions {
+            info.push(format!("{}x{}", w, h));
+        }
+        info.push(human_readable_size(self.file_size));
+        if let Some(sender) = &image.sender {
+            info.push(format!("sent by {}", sender));
+        }
+        if let Some(timestamp) = image.timestamp {
+            info.push(timestamp.format("%d %B %Y %H:%M").to_string());
+        }
+
+        let title = match image.gallery_position {
+            Some((index, total)) => format!("{} ({}/{})", image.attachment.name, index + 1, total),
+            None => image.attachment.name.clone(),
+        };
+
+        let make_button = |state, text: &str, msg: Option<Message>| {
+            let mut but = label_button!(state, text).style(theme);
+            if let Some(msg) = msg {
+                but = but.on_press(msg);
+            }
+            but.into()
+        };
+
+        let zoom = self.zoom;
+        let mut zoom_buttons = Vec::with_capacity(3);
+        for ((preset, text), state) in [
+            (Zoom::Fit, "Fit"),
+            (Zoom::Original, "100%"),
+            (Zoom::Fill, "Fill"),
+        ]
+        .iter()
+        .zip(self.zoom_buts_state.iter_mut())
         {
+            zoom_buttons.push(make_button(
+                state,
+                text,
+                (*preset != zoom).then(|| Message::SetZoom(*preset)),
+            ));
+        }
+
+        let (has_prev, has_next) = image
+            .gallery_position
+            .map_or((false, false), |(i, n)| (i > 0, i + 1 < n));
+
+        let mut foot = zoom_buttons;
+        foot.push(make_button(
+            &mut self.rotate_but_state,
+            "Rotate",
+            Some(Message::Rotate),
+        ));
+        foot.push(space!(w+).into());
+        foot.push(make_button(
+            &mut self.prev_but_state,
+            "<",
+            has_prev.then(|| Message::Previous),
+        ));
+        foot.push(make_button(
+            &mut self.next_but_state,
+            ">",
+            has_next.then(|| Message::Next),
+        ));
+        foot.push(space!(w+).into());
+        foot.push(make_button(
+            &mut self.save_but_state,
+            "Save as...",
+            Some(Message::SaveAs),
+        ));
+        foot.push(make_button(
+            &mut self.external_but_state,
+            "Open externally",
+            Some(Message::OpenExternal),
+        ));
+
+        let body = Column::with_children(vec![
             Container::new(
-                Card::new(
-                    label!(name).width(length!(= 720 - PADDING - SPACING)),
-                    Container::new(
-                        Viewer::new(&mut self.viewer_state, handle).width(length!(= 720)),
-                    )
-                    .center_x()
-                    .center_y()
-                    .width(length!(= 720)),
-                )
-                .foot(
-                    label_button!(&mut self.external_but_state, "Open externally")
-                        .style(theme)
-                        .on_press(Message::OpenExternal),
-                )
-                .style(theme.round())
-                .on_close(Message::Close),
+                Viewer::new(&mut self.viewer_state, handle)
+                    .width(width)
+                    .height(height),
             )
-            .style(theme.round())
             .center_x()
             .center_y()
-            .into()
-        } else {
-            unreachable!()
-        }
+            .width(length!(+))
+            .into(),
+            label!(info.join(" · "))
+                .size(DEF_SIZE - 4)
+                .color(ALT_COLOR)
+                .into(),
+        ])
+        .align_items(align!(|))
+        .spacing(SPACING);
+
+        Container::new(
+            Card::new(
+                label!(title).width(length!(= VIEWER_WIDTH - PADDING - SPACING)),
+                body,
+            )
+            .foot(
+                Row::with_children(foot)
+                    .align_items(align!(|))
+                    .spacing(SPACING),
+            )
+            .style(theme.round())
+            .on_close(Message::Close),
+        )
+        .style(theme.round())
+        .center_x()
+        .center_y()
+        .into()
     }
 
     pub fn update(&mut self, msg: Message) -> (Command<TopLevelMessage>, bool) {
-        let can_go_back;
+        let mut can_go_back = false;
 
         match msg {
             Message::OpenExternal => {
-                if let Some((_, (path, _))) = self.image_handle.as_ref() {
-                    open::that_in_background(path);
+                if let Some(image) = self.image.as_ref() {
+                    open::that_in_background(&image.path);
+                }
+            }
+            Message::SaveAs => {
+                if let Some(image) = self.image.as_ref() {
+                    let path = image.path.clone();
+                    let name = image.attachment.name.clone();
+                    return (
+                        Command::perform(
+                            async move {
+                                if let Some(handle) = rfd::AsyncFileDialog::new()
+                                    .set_file_name(&name)
+                                    .save_file()
+                                    .await
+                                {
+                                    tokio::fs::copy(path, handle.path()).await?;
+                                }
+                                Ok::<_, ClientError>(())
+                            },
+                            |result| {
+                                result.map_or_else(
+                                    |err| TopLevelMessage::Error(Box::new(err)),
+                                    |_| TopLevelMessage::Nothing,
+                                )
+                            },
+                        ),
+                        false,
+                    );
+                }
+            }
+            Message::SetZoom(zoom) => {
+                self.zoom = zoom;
+                self.viewer_state = Default::default();
+            }
+            Message::Rotate => {
+                if let Some(image) = self.image.as_ref() {
+                    let rotation = (self.rotation + 1) % 4;
+                    if rotation == 0 {
+                        self.rotated_handle = None;
+                    } else if let Some(handle) = rotate_handle(&image.handle, rotation) {
+                        self.rotated_handle = Some(handle);
+                    } else {
+                        tracing::warn!("couldn't rotate image {}", image.attachment.name);
+                        return (Command::none(), false);
+                    }
+                    self.rotation = rotation;
+                    self.viewer_state = Default::default();
                 }
-                can_go_back = false;
             }
+            // Gallery navigation needs the channel messages, so it's handled by the main screen
+            Message::Previous | Message::Next => {}
             Message::Close => {
                 // clear viewer state
                 self.viewer_state = Default::default();
+                self.rotated_handle = None;
+                self.rotation = 0;
                 can_go_back = true;
             }
         }
@@ -82,3 +303,50 @@ impl ImageViewerModal {
         (Command::none(), can_go_back)
     }
 }
+
+/// Rotates an image handle clockwise by the given amount of quarter turns.
+///
+/// Only works on handles created from pixels, which is the case for all images we load.
+fn rotate_handle(handle: &ImageHandle, quarter_turns: u8) -> Option<ImageHandle> {
+    use iced_native::image::Data;
+    use image::{imageops, Bgra, ImageBuffer};
+
+    if let Data::Pixels {
+        width,
+        height,
+        pixels,
+    } = handle.data()
+    {
+        let buffer = ImageBuffer::<Bgra<u8>, _>::from_raw(*width, *height, pixels.clone())?;
+        let rotated = match quarter_turns % 4 {
+            1 => imageops::rotate90(&buffer),
+            2 => imageops::rotate180(&buffer),
+            3 => imageops::rotate270(&buffer),
+            _ => buffer,
+        };
+        Some(ImageHandle::from_pixels(
+            rotated.width(),
+            rotated.height(),
+            rotated.into_raw(),
+        ))
+    } else {
+        None
+    }
+}
+
+fn human_readable_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
@@ -60,11 +60,9 @@ impl LogoutModal {
     pub fn update(&mut self, msg: Message, client: &Client) -> Command<TopLevelMessage> {
         if msg {
             let content_store = client.content_store_arc();
-            let inner = client.inner().clone();
             Command::perform(
                 async move {
-                    let result =
-                        Client::logout(inner, content_store.session_file().to_path_buf()).await;
+                    let result = Client::logout(content_store.clone()).await;
 
                     result.map_or_else(
                         |err| TopLevelMessage::Error(Box::new(err)),
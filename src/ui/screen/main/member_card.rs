@@ -0,0 +1,370 @@
+use harmony_rust_sdk::api::harmonytypes::UserStatus;
+use iced::Checkbox;
+use iced_aw::Card;
+
+use super::{super::Message as TopLevelMessage, Message as ParentMessage};
+use crate::{
+    client::{
+        error::{ClientError, ClientResult},
+        permission::Node,
+        role::Role,
+        Client,
+    },
+    label, label_button, length,
+    ui::{client::ClientExt, component::*, style::*, thumbnail::ThumbnailCache},
+};
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Loaded {
+        user_id: u64,
+        roles: Vec<u64>,
+        guild_roles: Vec<(u64, Role)>,
+    },
+    Mention,
+    CopyId,
+    Kick,
+    Ban,
+    EditRoles,
+    ToggleRole {
+        role_id: u64,
+        give: bool,
+    },
+    Back,
+}
+
+/// A destructive action that needs to be confirmed by pressing its button again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Confirm {
+    Kick,
+    Ban,
+}
+
+#[derive(Debug, Default)]
+pub struct MemberCardModal {
+    pub user_id: u64,
+    editing_roles: bool,
+    confirming: Option<Confirm>,
+    mention_but_state: button::State,
+    copy_id_but_state: button::State,
+    kick_but_state: button::State,
+    ban_but_state: button::State,
+    edit_roles_but_state: button::State,
+    roles_list_state: scrollable::State,
+    error_text: String,
+}
+
+impl MemberCardModal {
+    pub fn open(&mut self, user_id: u64) {
+        *self = Self {
+            user_id,
+            ..Default::default()
+        };
+    }
+
+    /// Fetches the roles of the guild and the member.
+    pub fn load(&self, guild_id: u64, client: &Client) -> Command<TopLevelMessage> {
+        let user_id = self.user_id;
+        let requests = client.requests().clone();
+
+        Command::perform(
+            async move {
+                let roles = requests.member_roles(guild_id, user_id).await?;
+                let guild_roles = requests.guild_roles(guild_id).await?;
+                Ok(Message::Loaded {
+                    user_id,
+                    roles,
+                    guild_roles,
+                })
+            },
+            |result: ClientResult<_>| {
+                result.map_or_else(
+                    |err| TopLevelMessage::Error(Box::new(err)),
+                    |msg| TopLevelMessage::MainScreen(ParentMessage::MemberCardMsg(msg)),
+                )
+            },
+        )
+    }
+
+    pub fn view<'a>(
+        &'a mut self,
+        theme: Theme,
+        client: &'a Client,
+        guild_id: u64,
+        thumbnail_cache: &'a ThumbnailCache,
+    ) -> Element<'a, Message> {
+        let member = match client.members.get(&self.user_id) {
+            Some(member) => member,
+            None => {
+                return Container::new(
+                    Card::new(label!("Member"), label!("No profile loaded yet."))
+                        .style(theme.round())
+                        .on_close(Message::Back),
+                )
+                .center_x()
+                .center_y()
+                .into()
+            }
+        };
+        let guild = client.guilds.get(&guild_id);
+        let is_self = client.user_id == Some(self.user_id);
+
+        let avatar: Element<Message> = if let Some(handle) = member
+            .avatar_url
+            .as_ref()
+            .map(|id| thumbnail_cache.get_thumbnail(id))
+            .flatten()
+        {
+            Image::new(handle.clone())
+                .width(length!(= 96))
+                .height(length!(= 96))
+                .into()
+        } else {
+            let initial = member.username.chars().next().unwrap_or('U');
+            fill_container(label!(initial.to_ascii_uppercase()).size(DEF_SIZE + 30))
+                .width(length!(= 96))
+                .height(length!(= 96))
+                .style(theme.round())
+                .into()
+        };
+
+        let status = match member.status {
+            UserStatus::Offline => "Offline",
+            UserStatus::Idle => "Idle",
+            UserStatus::DoNotDisturb => "Do not disturb",
+            UserStatus::Streaming => "Streaming",
+            _ => "Online",
+        };
+        let mut name_widgets = vec![label!(&member.username).size(DEF_SIZE + 8).into()];
+        if member.is_bot {
+            name_widgets.push(
+                Container::new(label!("BOT").size(DEF_SIZE - 6))
+                    .padding(PADDING / 8)
+                    .style(theme.round().secondary())
+                    .into(),
+            );
+        }
+        let mut info_widgets = vec![
+            row(name_widgets).padding(0).into(),
+            label!(status).color(ALT_COLOR).into(),
+        ];
+        if let Some(last_seen) = client.ephemeral.last_seen(self.user_id) {
+            info_widgets.push(
+                label!(last_seen.format("Last seen %d %B %Y, %H:%M").to_string())
+                    .size(DEF_SIZE - 2)
+                    .color(ALT_COLOR)
+                    .into(),
+            );
+        }
+        let header = row(vec![
+            avatar,
+            column(info_widgets).align_items(align!(|<)).into(),
+        ]);
+
+        let shared_guilds = client
+            .guilds
+            .values()
+            .filter(|guild| guild.members.contains(&self.user_id))
+            .map(|guild| guild.name.as_str())
+            .collect::<Vec<_>>();
+        let membership = if shared_guilds.len() > 1 {
+            format!("Member of {}", shared_guilds.join(", "))
+        } else {
+            guild.map_or_else(String::new, |guild| format!("Member of {}", guild.name))
+        };
+
+        let mut roles_list = Scrollable::new(&mut self.roles_list_state)
+            .style(theme)
+            .align_items(align!(|<))
+            .max_height(200)
+            .spacing(SPACING);
+        if let Some(guild) = guild {
+            let member_roles = guild.user_roles.get(&self.user_id);
+            if self.editing_roles {
+                for (role_id, role) in &guild.roles {
+                    let role_id = *role_id;
+                    let has_role = member_roles.map_or(false, |roles| roles.contains(&role_id));
+                    roles_list = roles_list.push(
+                        Checkbox::new(has_role, &role.name, move |give| Message::ToggleRole {
+                            role_id,
+                            give,
+                        })
+                        .style(theme),
+                    );
+                }
+            } else {
+                match member_roles {
+                    Some(roles) if !roles.is_empty() => {
+                        for role in roles.iter().flat_map(|id| guild.roles.get(id)) {
+                            roles_list = roles_list.push(label!(&role.name).color(role.color));
+                        }
+                    }
+                    Some(_) => roles_list = roles_list.push(label!("No roles").color(ALT_COLOR)),
+                    None => {
+                        roles_list = roles_list.push(label!("Loading roles...").color(ALT_COLOR))
+                    }
+                }
+            }
+        }
+
+        let mut actions = vec![
+            label_button!(&mut self.mention_but_state, "Mention")
+                .on_press(Message::Mention)
+                .style(theme)
+                .into(),
+            label_button!(&mut self.copy_id_but_state, "Copy ID")
+                .on_press(Message::CopyId)
+                .style(theme)
+                .into(),
+        ];
+        let has = |node| client.permissions.has(guild_id, None, node);
+        if has(Node::ManageUserRoles) {
+            let text = if self.editing_roles {
+                "Done"
+            } else {
+                "Edit roles"
+            };
+            actions.push(
+                label_button!(&mut self.edit_roles_but_state, text)
+                    .on_press(Message::EditRoles)
+                    .style(theme)
+                    .into(),
+            );
+        }
+        if has(Node::Kick) && !is_self {
+            let text = if self.confirming == Some(Confirm::Kick) {
+                "Click to kick"
+            } else {
+                "Kick"
+            };
+            actions.push(
+                label_button!(&mut self.kick_but_state, text)
+                    .on_press(Message::Kick)
+                    .style(theme)
+                    .into(),
+            );
+        }
+        if has(Node::Ban) && !is_self {
+            let text = if self.confirming == Some(Confirm::Ban) {
+                "Click to ban"
+            } else {
+                "Ban"
+            };
+            actions.push(
+                label_button!(&mut self.ban_but_state, text)
+                    .on_press(Message::Ban)
+                    .style(theme)
+                    .into(),
+            );
+        }
+
+        let mut widgets = Vec::with_capacity(6);
+        if !self.error_text.is_empty() {
+            widgets.push(label!(&self.error_text).color(ERROR_COLOR).into());
+        }
+        widgets.push(header.into());
+        if !membership.is_empty() {
+            widgets.push(label!(membership).color(ALT_COLOR).into());
+        }
+        widgets.push(label!("Roles").size(DEF_SIZE + 2).into());
+        widgets.push(roles_list.into());
+        widgets.push(row(actions).into());
+
+        Container::new(
+            Card::new(
+                label!("Member").width(length!(= 380 + (PADDING * 2) - SPACING)),
+                column(widgets).align_items(align!(|<)),
+            )
+            .style(theme.round())
+            .on_close(Message::Back),
+        )
+        .style(theme.round())
+        .center_x()
+        .center_y()
+        .into()
+    }
+
+    pub fn update(
+        &mut self,
+        msg: Message,
+        guild_id: u64,
+        client: &mut Client,
+        clip: &mut dyn iced_native::Clipboard,
+    ) -> (Command<TopLevelMessage>, bool) {
+        let user_id = self.user_id;
+
+        match msg {
+            Message::Loaded {
+                user_id,
+                roles,
+                guild_roles,
+            } => {
+                if let Some(guild) = client.get_guild(guild_id) {
+                    guild.roles = guild_roles.into_iter().collect();
+                    guild.user_roles.insert(user_id, roles);
+                }
+            }
+            Message::CopyId => clip.write(user_id.to_string()),
+            Message::EditRoles => self.editing_roles = !self.editing_roles,
+            Message::ToggleRole { role_id, give } => {
+                if let Some(roles) = client
+                    .get_guild(guild_id)
+                    .map(|guild| guild.user_roles.entry(user_id).or_default())
+                {
+                    if give {
+                        roles.push(role_id);
+                    } else {
+                        roles.retain(|id| *id != role_id);
+                    }
+                }
+
+                let (give_role_ids, take_role_ids) = if give {
+                    (vec![role_id], Vec::new())
+                } else {
+                    (Vec::new(), vec![role_id])
+                };
+                let requests = client.requests().clone();
+                return (
+                    Command::perform(
+                        async move {
+                            requests
+                                .manage_user_roles(guild_id, user_id, give_role_ids, take_role_ids)
+                                .await
+                        },
+                        |result| {
+                            result.map_or_else(
+                                |err| TopLevelMessage::Error(Box::new(err)),
+                                |_| TopLevelMessage::Nothing,
+                            )
+                        },
+                    ),
+                    false,
+                );
+            }
+            Message::Kick => {
+                if self.confirming.take() == Some(Confirm::Kick) {
+                    return (client.kick_user_cmd(guild_id, user_id), true);
+                }
+                self.confirming = Some(Confirm::Kick);
+            }
+            Message::Ban => {
+                if self.confirming.take() == Some(Confirm::Ban) {
+                    return (client.ban_user_cmd(guild_id, user_id), true);
+                }
+                self.confirming = Some(Confirm::Ban);
+            }
+            // Mentioning is handled by the main screen, since it changes the composer
+            Message::Mention => return (Command::none(), true),
+            Message::Back => return (Command::none(), true),
+        }
+
+        (Command::none(), false)
+    }
+
+    pub fn on_error(&mut self, error: &ClientError) -> Command<TopLevelMessage> {
+        self.confirming = None;
+        self.error_text = error.to_string();
+
+        Command::none()
+    }
+}
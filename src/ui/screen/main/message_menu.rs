@@ -0,0 +1,292 @@
+use iced_aw::Card;
+
+use super::super::Message as TopLevelMessage;
+use crate::{
+    client::{
+        error::ClientError,
+        message::{Content as IcyContent, Message as IcyMessage},
+        permission::{Node, PermissionCache},
+        Client,
+    },
+    label, label_button, length,
+    ui::{client::ClientExt, component::*, style::*},
+};
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Reply,
+    Edit,
+    /// Edits the caption of the attachment at an index, for messages with more than one.
+    EditCaption(usize),
+    Delete,
+    CopyText,
+    CopyId,
+    CopyLink,
+    MarkUnread,
+    Pin,
+    ToggleHistory,
+    Back,
+}
+
+/// Actions for a single message, opened by clicking on it.
+#[derive(Debug, Default)]
+pub struct MessageMenuModal {
+    reply_but_state: button::State,
+    edit_but_state: button::State,
+    caption_but_states: Vec<button::State>,
+    delete_but_state: button::State,
+    copy_text_but_state: button::State,
+    copy_id_but_state: button::State,
+    copy_link_but_state: button::State,
+    mark_unread_but_state: button::State,
+    pin_but_state: button::State,
+    history_but_state: button::State,
+    history_list_state: scrollable::State,
+    guild_id: u64,
+    channel_id: u64,
+    pub message_id: u64,
+    /// The text of the message, if it's a text message.
+    text: Option<String>,
+    /// The names of the attachments of the message, if it has more than one.
+    attachments: Vec<String>,
+    /// Previous versions of the message, oldest first, as `(replaced at, content)`.
+    revisions: Vec<(String, String)>,
+    showing_history: bool,
+    is_own: bool,
+    is_pinned: bool,
+    error_text: String,
+}
+
+impl MessageMenuModal {
+    pub fn open(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        message: &IcyMessage,
+        current_user_id: u64,
+        is_pinned: bool,
+    ) {
+        *self = Self {
+            guild_id,
+            channel_id,
+            message_id: message.id.id().unwrap_or_default(),
+            text: match &message.content {
+                IcyContent::Text(text) => Some(text.clone()),
+                _ => None,
+            },
+            attachments: match &message.content {
+                IcyContent::Files(attachments) if attachments.len() > 1 => attachments
+                    .iter()
+                    .map(|attachment| attachment.name.clone())
+                    .collect(),
+                _ => Vec::new(),
+            },
+            revisions: message
+                .revisions
+                .iter()
+                .map(|revision| {
+                    let content = match &revision.content {
+                        IcyContent::Text(text) => text.clone(),
+                        IcyContent::Files(_) => "[file]".to_string(),
+                        IcyContent::Embeds(_) => "[embed]".to_string(),
+                    };
+                    (
+                        revision
+                            .replaced_at
+                            .format("Until %d %B %Y, %H:%M")
+                            .to_string(),
+                        content,
+                    )
+                })
+                .collect(),
+            is_own: message.sender == current_user_id,
+            is_pinned,
+            ..Default::default()
+        };
+    }
+
+    pub fn view(&mut self, theme: Theme, permissions: &PermissionCache) -> Element<Message> {
+        let has = |node| permissions.has(self.guild_id, Some(self.channel_id), node);
+        // Everyone can delete their own messages
+        let can_delete = self.is_own || has(Node::DeleteMessages);
+        let can_pin = has(if self.is_pinned {
+            Node::UnpinMessages
+        } else {
+            Node::PinMessages
+        });
+
+        let make_button = |state, text: &str, msg| {
+            label_button!(state, text)
+                .on_press(msg)
+                .width(length!(+))
+                .style(theme)
+                .into()
+        };
+
+        let mut widgets = Vec::with_capacity(11 + self.attachments.len());
+        if !self.error_text.is_empty() {
+            widgets.push(label!(&self.error_text).color(ERROR_COLOR).into());
+        }
+
+        if self.showing_history {
+            let mut history_list = Scrollable::new(&mut self.history_list_state)
+                .style(theme)
+                .align_items(align!(|<))
+                .max_height(300)
+                .spacing(SPACING);
+            for (replaced_at, content) in &self.revisions {
+                history_list = history_list
+                    .push(label!(replaced_at).size(DEF_SIZE - 4).color(ALT_COLOR))
+                    .push(label!(content).size(DEF_SIZE - 2));
+            }
+            widgets.push(history_list.into());
+            widgets.push(make_button(
+                &mut self.history_but_state,
+                "Back",
+                Message::ToggleHistory,
+            ));
+
+            return Container::new(
+                Card::new(
+                    label!("Edit history").width(length!(= 360 - PADDING - SPACING)),
+                    column(widgets)
+                        .align_items(align!(|<))
+                        .width(length!(= 360)),
+                )
+                .style(theme.round())
+                .on_close(Message::Back),
+            )
+            .style(theme.round())
+            .center_x()
+            .center_y()
+            .into();
+        }
+
+        widgets.push(make_button(
+            &mut self.reply_but_state,
+            "Reply",
+            Message::Reply,
+        ));
+        if self.is_own && self.attachments.is_empty() {
+            widgets.push(make_button(&mut self.edit_but_state, "Edit", Message::Edit));
+        } else if self.is_own {
+            self.caption_but_states
+                .resize_with(self.attachments.len(), Default::default);
+            for (index, (state, name)) in self
+                .caption_but_states
+                .iter_mut()
+                .zip(&self.attachments)
+                .enumerate()
+            {
+                let text = format!("Edit caption of {}", name);
+                widgets.push(make_button(state, &text, Message::EditCaption(index)));
+            }
+        }
+        if can_delete {
+            widgets.push(make_button(
+                &mut self.delete_but_state,
+                "Delete",
+                Message::Delete,
+            ));
+        }
+        if self.text.is_some() {
+            widgets.push(make_button(
+                &mut self.copy_text_but_state,
+                "Copy text",
+                Message::CopyText,
+            ));
+        }
+        widgets.push(make_button(
+            &mut self.copy_id_but_state,
+            "Copy message ID",
+            Message::CopyId,
+        ));
+        widgets.push(make_button(
+            &mut self.copy_link_but_state,
+            "Copy link",
+            Message::CopyLink,
+        ));
+        widgets.push(make_button(
+            &mut self.mark_unread_but_state,
+            "Mark unread from here",
+            Message::MarkUnread,
+        ));
+        if !self.revisions.is_empty() {
+            widgets.push(make_button(
+                &mut self.history_but_state,
+                "Edit history",
+                Message::ToggleHistory,
+            ));
+        }
+        if can_pin {
+            let text = if self.is_pinned { "Unpin" } else { "Pin" };
+            widgets.push(make_button(&mut self.pin_but_state, text, Message::Pin));
+        }
+
+        Container::new(
+            Card::new(
+                label!("Message").width(length!(= 240 - PADDING - SPACING)),
+                column(widgets).width(length!(= 240)),
+            )
+            .style(theme.round())
+            .on_close(Message::Back),
+        )
+        .style(theme.round())
+        .center_x()
+        .center_y()
+        .into()
+    }
+
+    /// Updates the modal, returning `true` if it should be closed.
+    pub fn update(
+        &mut self,
+        msg: Message,
+        guild_id: u64,
+        channel_id: u64,
+        client: &mut Client,
+        clip: &mut dyn iced_native::Clipboard,
+    ) -> (Command<TopLevelMessage>, bool) {
+        let message_id = self.message_id;
+
+        match msg {
+            Message::CopyText => {
+                if let Some(text) = &self.text {
+                    clip.write(text.clone());
+                }
+                return (Command::none(), true);
+            }
+            Message::CopyId => {
+                clip.write(message_id.to_string());
+                return (Command::none(), true);
+            }
+            Message::CopyLink => {
+                clip.write(client.message_link(guild_id, channel_id, message_id));
+                return (Command::none(), true);
+            }
+            Message::Pin => {
+                let cmd = if self.is_pinned {
+                    client.unpin_msg_cmd(guild_id, channel_id, message_id)
+                } else {
+                    client.pin_msg_cmd(guild_id, channel_id, message_id)
+                };
+                return (cmd, true);
+            }
+            // These are handled by the main screen, since they change its state
+            Message::Reply
+            | Message::Edit
+            | Message::EditCaption(_)
+            | Message::Delete
+            | Message::MarkUnread => return (Command::none(), true),
+            Message::ToggleHistory => self.showing_history = !self.showing_history,
+            Message::Back => return (Command::none(), true),
+        }
+
+        (Command::none(), false)
+    }
+
+    pub fn on_error(&mut self, error: &ClientError) -> Command<TopLevelMessage> {
+        self.error_text = error.to_string();
+
+        Command::none()
+    }
+}
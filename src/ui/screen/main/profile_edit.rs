@@ -1,10 +1,9 @@
-use harmony_rust_sdk::client::api::chat::profile::{profile_update, ProfileUpdate};
 use iced_aw::Card;
 
 use crate::{
-    client::{content::ThumbnailCache, Client},
+    client::Client,
     label_button, length,
-    ui::{component::*, style::*},
+    ui::{component::*, style::*, thumbnail::ThumbnailCache},
 };
 
 use super::super::{select_upload_files, Message as TopLevelMessage};
@@ -122,16 +121,10 @@ impl ProfileEditModal {
                     Command::none()
                 }
                 Message::ChangeName => {
-                    let inner = client.inner().clone();
+                    let requests = client.requests().clone();
                     let username = self.current_username.drain(..).collect::<String>();
                     Command::perform(
-                        async move {
-                            Ok(profile_update(
-                                &inner,
-                                ProfileUpdate::default().new_username(username),
-                            )
-                            .await?)
-                        },
+                        async move { requests.set_username(username).await },
                         |result| {
                             result.map_or_else(
                                 |err| TopLevelMessage::Error(Box::new(err)),
@@ -141,19 +134,11 @@ impl ProfileEditModal {
                     )
                 }
                 Message::UploadPfp => {
-                    let inner = client.inner().clone();
-                    let content_store = client.content_store_arc();
+                    let requests = client.requests().clone();
                     Command::perform(
                         async move {
-                            let id = select_upload_files(&inner, content_store)
-                                .await?
-                                .remove(0)
-                                .0;
-                            Ok(profile_update(
-                                &inner,
-                                ProfileUpdate::default().new_avatar(Some(id)),
-                            )
-                            .await?)
+                            let id = select_upload_files(&requests).await?.remove(0).id;
+                            requests.set_avatar(id).await
                         },
                         |result| {
                             result.map_or_else(
@@ -13,7 +13,7 @@ pub enum Message {
     SearchTermChanged(String),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SearchResult {
     Guild {
         id: u64,
@@ -0,0 +1,157 @@
+use crate::{
+    client::{message::Content, Client},
+    label, length, space,
+    ui::{
+        component::{event_history::message_summary, *},
+        style::{Theme, ALT_COLOR, DEF_SIZE, PADDING, SPACING},
+    },
+};
+
+/// How many matches are listed at most, newest first.
+const MAX_RESULTS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    SearchChanged(String),
+    /// Jumps to a message that matched.
+    Open(SearchResult),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub guild_id: u64,
+    pub channel_id: u64,
+    pub message_id: u64,
+    /// Who sent the message and how it starts.
+    pub summary: String,
+    pub channel_name: String,
+}
+
+/// Searches the text of messages.
+///
+/// Only messages that were loaded can be found, since the homeserver can't search messages.
+#[derive(Debug, Default)]
+pub struct SearchModal {
+    search_state: text_input::State,
+    results_state: scrollable::State,
+    results_buts_state: Vec<button::State>,
+    search_value: String,
+    pub results: Vec<SearchResult>,
+}
+
+impl SearchModal {
+    /// Clears the search.
+    pub fn reset(&mut self) {
+        self.search_value.clear();
+        self.results.clear();
+    }
+
+    /// Finds the loaded messages whose text contains a term, ignoring case.
+    pub fn search(&mut self, term: String, client: &Client) {
+        let pattern = term.trim().to_lowercase();
+        self.results.clear();
+        if !pattern.is_empty() {
+            let matches = |text: &str| text.to_lowercase().contains(&pattern);
+            let mut found = client
+                .guilds
+                .iter()
+                .flat_map(|(guild_id, guild)| {
+                    guild
+                        .channels
+                        .iter()
+                        .flat_map(move |(channel_id, channel)| {
+                            channel
+                                .messages
+                                .iter()
+                                .map(move |message| (*guild_id, *channel_id, channel, message))
+                        })
+                })
+                .filter(|(_, _, _, message)| match &message.content {
+                    Content::Text(text) => matches(text),
+                    Content::Files(attachments) => attachments
+                        .iter()
+                        .any(|attachment| matches(&attachment.caption)),
+                    Content::Embeds(embed) => matches(&embed.title) || matches(&embed.body),
+                })
+                .flat_map(|(guild_id, channel_id, channel, message)| {
+                    Some((
+                        message.timestamp,
+                        SearchResult {
+                            guild_id,
+                            channel_id,
+                            message_id: message.id.id()?,
+                            summary: message_summary(message, &client.members),
+                            channel_name: channel.name.clone(),
+                        },
+                    ))
+                })
+                .collect::<Vec<_>>();
+            found.sort_by(|(a, _), (b, _)| b.cmp(a));
+            self.results = found
+                .into_iter()
+                .take(MAX_RESULTS)
+                .map(|(_, result)| result)
+                .collect();
+        }
+        self.results_buts_state
+            .resize_with(self.results.len(), Default::default);
+        self.search_value = term;
+    }
+
+    pub fn view(&mut self, theme: Theme) -> Element<Message> {
+        self.search_state.focus();
+
+        let mut search_bar = TextInput::new(
+            &mut self.search_state,
+            "Search messages",
+            &self.search_value,
+            Message::SearchChanged,
+        )
+        .padding(PADDING / 2)
+        .size(DEF_SIZE + 4)
+        .style(theme);
+
+        if let Some(result) = self.results.first() {
+            search_bar = search_bar.on_submit(Message::Open(result.clone()));
+        }
+
+        let mut result_list = Scrollable::new(&mut self.results_state)
+            .style(theme)
+            .width(length!(+))
+            .height(length!(+))
+            .spacing(SPACING);
+        if self.results.is_empty() && !self.search_value.trim().is_empty() {
+            result_list = result_list.push(label!("No loaded message matches.").color(ALT_COLOR));
+        }
+        for (result, but_stt) in self.results.iter().zip(self.results_buts_state.iter_mut()) {
+            let content = row(vec![
+                label!(&result.summary).width(length!(+)).into(),
+                label!("#{}", result.channel_name).color(ALT_COLOR).into(),
+            ])
+            .padding(0);
+
+            result_list = result_list.push(
+                Button::new(but_stt, content)
+                    .width(length!(+))
+                    .style(theme)
+                    .on_press(Message::Open(result.clone())),
+            );
+        }
+
+        Row::with_children(vec![
+            space!(w % 2).into(),
+            Column::with_children(vec![
+                space!(h % 2).into(),
+                Container::new(column(vec![search_bar.into(), result_list.into()]))
+                    .style(theme.round())
+                    .height(length!(%6))
+                    .into(),
+                space!(h % 2).into(),
+            ])
+            .width(length!(%6))
+            .into(),
+            space!(w % 2).into(),
+        ])
+        .into()
+    }
+}
@@ -10,38 +10,30 @@ pub use main::MainScreen;
 
 use crate::{
     client::{
-        content::{ContentStore, ImageHandle, ThumbnailCache},
-        error::{ClientError, ClientResult},
-        message::{Attachment, Message as IcyMessage, MessageId},
-        Client, PostProcessEvent, Session,
+        change::StateChange,
+        content::ContentStore,
+        error::{ClientError, ClientResult, Recovery},
+        message::{Attachment, Content as IcyContent, Message as IcyMessage},
+        permission::Node,
+        requests::Requests,
+        scheduler::{Priority, RequestKey},
+        Client, PostProcessEvent,
+    },
+    ui::{
+        client::ClientExt,
+        config::Config,
+        style::Theme,
+        thumbnail::{ImageHandle, ThumbnailCache},
     },
-    ui::style::Theme,
 };
 
 use harmony_rust_sdk::{
-    api::{
-        chat::{
-            event::{Event, GuildAddedToList, GuildUpdated, ProfileUpdated},
-            GetGuildListRequest,
-        },
-        exports::hrpc::url::Url,
-        rest::FileId,
-    },
-    client::{
-        api::{
-            auth::AuthStepResponse,
-            chat::{
-                guild::{get_guild, get_guild_list},
-                profile::{get_user, get_user_bulk},
-                EventSource, GuildId, UserId,
-            },
-            harmonytypes::Message as HarmonyMessage,
-        },
-        Client as InnerClient, EventsSocket,
-    },
+    api::chat::event::Event,
+    client::{api::chat::EventSource, EventsSocket},
 };
 use iced::{executor, Application, Command, Element, Subscription};
-use std::{sync::Arc, time::Duration};
+use std::{sync::Arc, time::Instant};
+use tokio::sync::broadcast::{self, error::TryRecvError};
 
 #[derive(Debug)]
 pub enum Message {
@@ -55,6 +47,12 @@ pub enum Message {
     LoginComplete(Option<Client>),
     ClientCreated(Client),
     Nothing,
+    WindowResized {
+        width: u32,
+        height: u32,
+    },
+    /// Sent when some ephemeral state (like typing indicators) of the client expires.
+    EphemeralExpired,
     DownloadedThumbnail {
         data: Attachment,
         thumbnail: ImageHandle,
@@ -66,11 +64,18 @@ pub enum Message {
         event: Option<harmony_rust_sdk::client::error::ClientResult<Event>>,
     },
     GetEventsBackwardsResponse {
-        messages: Vec<HarmonyMessage>,
+        messages: Vec<IcyMessage>,
         reached_top: bool,
         guild_id: u64,
         channel_id: u64,
     },
+    /// Sent when loading messages of a channel failed or was cancelled, so that they can be
+    /// loaded again.
+    HistoryLoadFailed {
+        guild_id: u64,
+        channel_id: u64,
+        err: Option<Box<ClientError>>,
+    },
     MessageSent {
         message_id: u64,
         transaction_id: u64,
@@ -79,7 +84,6 @@ pub enum Message {
     },
     SendMessage {
         message: IcyMessage,
-        retry_after: Duration,
         guild_id: u64,
         channel_id: u64,
     },
@@ -87,14 +91,37 @@ pub enum Message {
         guild_id: u64,
         channel_id: u64,
         message_id: u64,
+        /// Content to apply to the message, for edits that the server doesn't send an event for.
+        new_content: Option<IcyContent>,
         err: Option<Box<ClientError>>,
     },
+    /// Sent when the permissions of the current user in a guild, or a channel of it, were fetched.
+    PermissionsLoaded {
+        guild_id: u64,
+        channel_id: Option<u64>,
+        permissions: Vec<(Node, bool)>,
+    },
     /// Sent whenever an error occurs.
     Error(Box<ClientError>),
+    /// Sent when a request made by a dialog of the main screen fails, so that it's shown there.
+    DialogError {
+        dialog: main::Dialog,
+        err: Box<ClientError>,
+    },
     Exit,
     ExitReady,
 }
 
+impl Message {
+    /// Marks this as coming from a dialog of the main screen, if it's an error.
+    pub fn from_dialog(self, dialog: main::Dialog) -> Self {
+        match self {
+            Message::Error(err) => Message::DialogError { dialog, err },
+            msg => msg,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Screen {
     Login(LoginScreen),
@@ -185,8 +212,12 @@ pub struct ScreenManager {
     theme: Theme,
     screens: ScreenStack,
     client: Option<Client>,
+    /// The changes made to the state of the client, which the screens are updated from.
+    changes: Option<broadcast::Receiver<StateChange>>,
     content_store: Arc<ContentStore>,
     thumbnail_cache: ThumbnailCache,
+    config: Config,
+    window_size: (u32, u32),
     cur_socket: Option<Box<EventsSocket>>,
     socket_reset: bool,
     should_exit: bool,
@@ -198,6 +229,9 @@ impl ScreenManager {
             theme: Theme::default(),
             screens: ScreenStack::new(Screen::Login(LoginScreen::new(content_store.clone()))),
             client: None,
+            changes: None,
+            config: Config::load(content_store.config_file()),
+            window_size: (1280, 720), // [ref:initial_window_size]
             content_store,
             thumbnail_cache: ThumbnailCache::default(),
             cur_socket: None,
@@ -206,10 +240,23 @@ impl ScreenManager {
         }
     }
 
+    pub fn screens(&self) -> &ScreenStack {
+        &self.screens
+    }
+
+    /// Returns the client, which is only `None` before logging in.
+    pub fn client(&self) -> Option<&Client> {
+        self.client.as_ref()
+    }
+
+    pub fn client_mut(&mut self) -> Option<&mut Client> {
+        self.client.as_mut()
+    }
+
     fn process_post_event(
         &mut self,
         post: PostProcessEvent,
-        clip: &mut iced::Clipboard,
+        clip: &mut dyn iced_native::Clipboard,
     ) -> Command<Message> {
         if let Some(client) = self.client.as_mut() {
             match post {
@@ -217,129 +264,130 @@ impl ScreenManager {
                     return make_thumbnail_command(client, id, &self.thumbnail_cache);
                 }
                 PostProcessEvent::FetchProfile(user_id) => {
-                    let inner = client.inner().clone();
+                    let requests = client.requests().clone();
+                    let request = move || {
+                        let requests = requests.clone();
+                        async move { Ok(vec![requests.profile(user_id).await?]) }
+                    };
                     return Command::perform(
-                        async move {
-                            let profile = get_user(&inner, UserId::new(user_id)).await?;
-                            let event = Event::ProfileUpdated(ProfileUpdated {
-                                user_id,
-                                new_avatar: profile.user_avatar,
-                                new_status: profile.user_status,
-                                new_username: profile.user_name,
-                                is_bot: profile.is_bot,
-                                update_is_bot: true,
-                                update_status: true,
-                                update_avatar: true,
-                                update_username: true,
-                            });
-                            Ok(vec![event])
-                        },
-                        |result| {
-                            result.map_or_else(
-                                |err| Message::Error(Box::new(err)),
-                                Message::EventsReceived,
-                            )
-                        },
+                        client.scheduler().run(
+                            RequestKey::Profile(user_id),
+                            Priority::Background,
+                            request,
+                        ),
+                        scheduled_events_received,
                     );
                 }
-                PostProcessEvent::GoToFirstMsgOnChannel(channel_id) => {
-                    if let Some(Screen::Main(screen)) = self
-                        .screens
-                        .stack
-                        .iter_mut()
-                        .find(|screen| matches!(screen, Screen::Main(_)))
-                    {
-                        return screen.update(
-                            main::Message::ScrollToBottom(channel_id),
-                            client,
-                            &self.thumbnail_cache,
-                            clip,
-                        );
+                PostProcessEvent::FetchPermissions(guild_id) => {
+                    let mut cmds = vec![client.fetch_permissions_cmd(guild_id, None)];
+                    if let Screen::Main(screen) = self.screens.current() {
+                        if let Some((current_guild_id, channel_id)) = screen.current_channel() {
+                            if current_guild_id == guild_id {
+                                cmds.push(client.fetch_permissions_cmd(guild_id, Some(channel_id)));
+                            }
+                        }
                     }
+                    return Command::batch(cmds);
                 }
                 PostProcessEvent::FetchGuildData(guild_id) => {
-                    let inner = client.inner().clone();
+                    let requests = client.requests().clone();
+                    let request = move || {
+                        let requests = requests.clone();
+                        async move { Ok(vec![requests.guild_data(guild_id).await?]) }
+                    };
                     return Command::perform(
-                        async move {
-                            let guild_data = get_guild(&inner, GuildId::new(guild_id)).await?;
-                            let event = Event::EditedGuild(GuildUpdated {
-                                guild_id,
-                                metadata: guild_data.metadata,
-                                name: guild_data.guild_name,
-                                picture: guild_data.guild_picture,
-                                update_name: true,
-                                update_picture: true,
-                                update_metadata: true,
-                            });
-                            Ok(vec![event])
-                        },
-                        |result| {
-                            result.map_or_else(
-                                |err| Message::Error(Box::new(err)),
-                                Message::EventsReceived,
-                            )
-                        },
+                        client.scheduler().run(
+                            RequestKey::GuildData(guild_id),
+                            Priority::Background,
+                            request,
+                        ),
+                        scheduled_events_received,
                     );
                 }
             }
         }
         Command::none()
     }
-}
-
-impl Application for ScreenManager {
-    type Executor = executor::Default;
-    type Message = Message;
-    type Flags = ContentStore;
 
-    fn new(content_store: Self::Flags) -> (Self, Command<Self::Message>) {
-        let content_store = Arc::new(content_store);
-        let mut manager = ScreenManager::new(content_store.clone());
-        let cmd = if content_store.session_file().exists() {
-            let session_file = content_store.session_file().to_path_buf();
-            if let Screen::Login(screen) = manager.screens.current_mut() {
-                screen.waiting = true;
-            }
-            Command::perform(
-                async move {
-                    let session_raw = tokio::fs::read(session_file).await?;
-                    let session: Session = toml::de::from_slice(&session_raw)
-                        .map_err(|_| ClientError::MissingLoginInfo)?;
-                    Client::new(
-                        session.homeserver.parse::<Url>().unwrap(),
-                        Some(session.into()),
-                        content_store.clone(),
-                    )
-                    .await
-                },
-                |result| {
-                    result.map_or_else(
-                        |err| Message::Error(err.into()),
-                        |client| Message::LoginComplete(Some(client)),
-                    )
-                },
-            )
-        } else {
-            Command::none()
-        };
-        (manager, cmd)
+    /// Handles a message for [`Application::update`], taking any clipboard so that it can also run
+    /// without a window.
+    pub fn handle_message(
+        &mut self,
+        msg: Message,
+        clip: &mut dyn iced_native::Clipboard,
+    ) -> Command<Message> {
+        let cmd = self.update_state(msg, clip);
+        Command::batch(vec![cmd, self.apply_state_changes(clip)])
     }
 
-    fn title(&self) -> String {
-        "Crust".into()
+    /// Replaces the client, listening to the changes of the new one.
+    fn set_client(&mut self, client: Option<Client>) {
+        self.changes = client.as_ref().map(Client::state_changes);
+        self.client = client;
     }
 
-    fn update(&mut self, msg: Self::Message, clip: &mut iced::Clipboard) -> Command<Self::Message> {
-        if let Some(client) = self.client.as_mut() {
-            for member in client.members.values_mut() {
-                member.typing_in_channel = member
-                    .typing_in_channel
-                    .filter(|(_, _, since)| since.elapsed().as_secs() < 5);
+    /// Updates the main screen from the changes the last message made to the state of the client.
+    fn apply_state_changes(&mut self, clip: &mut dyn iced_native::Clipboard) -> Command<Message> {
+        let (changes, client) = match (self.changes.as_mut(), self.client.as_mut()) {
+            (Some(changes), Some(client)) => (changes, client),
+            _ => return Command::none(),
+        };
+        let shown = matches!(self.screens.current(), Screen::Main(_));
+        let mut screen = self
+            .screens
+            .stack
+            .iter_mut()
+            .find_map(|screen| match screen {
+                Screen::Main(screen) => Some(screen),
+                _ => None,
+            });
+
+        let mut cmds = Vec::new();
+        loop {
+            match changes.try_recv() {
+                Ok(change) => {
+                    if let Some(screen) = screen.as_mut() {
+                        cmds.push(screen.on_state_change(
+                            change,
+                            shown,
+                            client,
+                            &self.thumbnail_cache,
+                            clip,
+                        ));
+                    }
+                }
+                Err(TryRecvError::Lagged(missed)) => {
+                    tracing::warn!("missed {} changes to the state of the client", missed);
+                    if let Some(screen) = screen.as_mut() {
+                        screen.on_changes_missed();
+                    }
+                }
+                Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => break,
             }
         }
+        Command::batch(cmds)
+    }
 
+    fn update_state(
+        &mut self,
+        msg: Message,
+        clip: &mut dyn iced_native::Clipboard,
+    ) -> Command<Message> {
         match msg {
             Message::Nothing => {}
+            Message::WindowResized { width, height } => {
+                self.window_size = (width, height);
+                for screen in &mut self.screens.stack {
+                    if let Screen::Main(screen) = screen {
+                        screen.window_resized(width, height);
+                    }
+                }
+            }
+            Message::EphemeralExpired => {
+                if let Some(client) = self.client.as_mut() {
+                    client.ephemeral.expire(Instant::now());
+                }
+            }
             Message::Exit => {
                 let sock = self.cur_socket.take();
                 return Command::perform(
@@ -373,26 +421,27 @@ impl Application for ScreenManager {
             }
             Message::GuildSettings(msg) => {
                 if let (Screen::GuildSettings(screen), Some(client)) =
-                    (self.screens.current_mut(), &self.client)
+                    (self.screens.current_mut(), &mut self.client)
                 {
-                    return screen.update(msg, client);
+                    return screen.update(msg, client, clip);
                 }
             }
             Message::ClientCreated(client) => {
-                self.client = Some(client);
-                let inner = self.client.as_ref().unwrap().inner().clone();
-                return Command::perform(
-                    async move {
-                        inner.begin_auth().await?;
-                        inner.next_auth_step(AuthStepResponse::Initial).await
-                    },
-                    |result| {
-                        result.map_or_else(
-                            |err| Message::Error(Box::new(err.into())),
-                            |step| Message::LoginScreen(login::Message::AuthStep(step)),
-                        )
-                    },
-                );
+                // A client made with the session pushed after a waiting step is already logged in
+                let logged_in = client.session().is_some();
+                self.set_client(Some(client));
+                if logged_in {
+                    return Command::perform(async {}, |()| {
+                        Message::LoginScreen(login::Message::AuthStep(None))
+                    });
+                }
+                let requests = self.client.as_ref().unwrap().requests().clone();
+                return Command::perform(async move { requests.begin_auth().await }, |result| {
+                    result.map_or_else(
+                        |err| Message::Error(Box::new(err)),
+                        |step| Message::LoginScreen(login::Message::AuthStep(step)),
+                    )
+                });
             }
             Message::SocketEvent { mut socket, event } => {
                 if self.client.is_some() {
@@ -401,8 +450,10 @@ impl Application for ScreenManager {
                     if let Some(ev) = event {
                         tracing::debug!("event received from socket: {:?}", ev);
                         let cmd = match ev {
-                            Ok(ev) => self.update(Message::EventsReceived(vec![ev]), clip),
-                            Err(err) => self.update(Message::Error(Box::new(err.into())), clip),
+                            Ok(ev) => self.handle_message(Message::EventsReceived(vec![ev]), clip),
+                            Err(err) => {
+                                self.handle_message(Message::Error(Box::new(err.into())), clip)
+                            }
                         };
                         cmds.push(cmd);
                     } else {
@@ -429,12 +480,12 @@ impl Application for ScreenManager {
                     } else {
                         let client = self.client.as_ref().unwrap();
                         let sources = client.subscribe_to();
-                        let inner = client.inner().clone();
+                        let requests = client.requests().clone();
                         cmds.push(Command::perform(
-                            async move { inner.subscribe_events(sources).await },
+                            async move { requests.subscribe(sources).await },
                             |result| {
                                 result.map_or_else(
-                                    |err| Message::Error(Box::new(err.into())),
+                                    |err| Message::Error(Box::new(err)),
                                     |socket| Message::SocketEvent {
                                         socket: socket.into(),
                                         event: None,
@@ -450,53 +501,34 @@ impl Application for ScreenManager {
             }
             Message::LoginComplete(maybe_client) => {
                 if let Some(client) = maybe_client {
-                    self.client = Some(client); // This is the only place we set a main screen [tag:client_set_before_main_view]
+                    self.set_client(Some(client)); // This is the only place we set a main screen [tag:client_set_before_main_view]
                 }
-                self.screens
-                    .push(Screen::Main(Box::new(MainScreen::default())));
+                self.screens.push(Screen::Main(Box::new(MainScreen::new(
+                    &self.config,
+                    self.window_size,
+                ))));
 
                 let client = self.client.as_mut().unwrap();
                 let sources = client.subscribe_to();
-                let inner = client.inner().clone();
-                let ws_cmd = Command::perform(
-                    async move { inner.subscribe_events(sources).await },
-                    |result| {
+                let requests = client.requests().clone();
+                let ws_cmd =
+                    Command::perform(async move { requests.subscribe(sources).await }, |result| {
                         result.map_or_else(
-                            |err| Message::Error(Box::new(err.into())),
+                            |err| Message::Error(Box::new(err)),
                             |socket| Message::SocketEvent {
                                 socket: socket.into(),
                                 event: None,
                             },
                         )
-                    },
-                );
-                let inner = client.inner().clone();
-                client.user_id = Some(inner.auth_status().session().unwrap().user_id);
+                    });
+                client.user_id = Some(client.auth_status().session().unwrap().user_id);
                 let self_id = client.user_id.unwrap();
+                let requests = client.requests().clone();
                 let init = Command::perform(
                     async move {
-                        let self_profile = get_user(&inner, UserId::new(self_id)).await?;
-                        let guilds = get_guild_list(&inner, GetGuildListRequest {}).await?.guilds;
-                        let mut events = guilds
-                            .into_iter()
-                            .map(|guild| {
-                                Event::GuildAddedToList(GuildAddedToList {
-                                    guild_id: guild.guild_id,
-                                    homeserver: guild.host,
-                                })
-                            })
-                            .collect::<Vec<_>>();
-                        events.push(Event::ProfileUpdated(ProfileUpdated {
-                            update_avatar: true,
-                            update_is_bot: true,
-                            update_status: true,
-                            update_username: true,
-                            is_bot: self_profile.is_bot,
-                            new_avatar: self_profile.user_avatar,
-                            new_status: self_profile.user_status,
-                            new_username: self_profile.user_name,
-                            user_id: self_id,
-                        }));
+                        let self_profile = requests.profile(self_id).await?;
+                        let mut events = requests.guild_list().await?;
+                        events.push(self_profile);
                         Ok(events)
                     },
                     |result| {
@@ -515,7 +547,7 @@ impl Application for ScreenManager {
                 self.screens.push(*screen);
             }
             Message::Logout(screen) => {
-                self.client = None;
+                self.set_client(None);
                 self.socket_reset = false;
                 self.screens.clear(*screen);
             }
@@ -525,56 +557,46 @@ impl Application for ScreenManager {
                 guild_id,
                 channel_id,
             } => {
-                if let Some(msg) = self
-                    .client
-                    .as_mut()
-                    .map(|client| client.get_channel(guild_id, channel_id))
-                    .flatten()
-                    .map(|channel| {
-                        channel
-                            .messages
-                            .iter_mut()
-                            .find(|msg| msg.id.transaction_id() == Some(transaction_id))
-                    })
-                    .flatten()
-                {
-                    msg.id = MessageId::Ack(message_id);
+                if let Some(client) = self.client.as_mut() {
+                    client.ack_message(guild_id, channel_id, transaction_id, message_id);
+                }
+            }
+            Message::PermissionsLoaded {
+                guild_id,
+                channel_id,
+                permissions,
+            } => {
+                if let Some(client) = self.client.as_mut() {
+                    client.insert_permissions(guild_id, channel_id, permissions);
                 }
             }
             Message::MessageEdited {
                 guild_id,
                 channel_id,
                 message_id,
+                new_content,
                 err,
             } => {
                 let client = self.client.as_mut().unwrap();
+                client.finish_edit(guild_id, channel_id, message_id, new_content);
 
-                if let Some(msg) = client
-                    .get_channel(guild_id, channel_id)
-                    .map(|c| {
-                        c.messages
-                            .iter_mut()
-                            .find(|m| m.id.id() == Some(message_id))
-                    })
-                    .flatten()
-                {
-                    msg.being_edited = None;
-                }
-
-                if let Some(err) = err {
-                    return self.update(Message::Error(err), clip);
+                let shown_in_editor = match self.screens.current_mut() {
+                    Screen::Main(screen) => screen.embed_edit_finished(message_id, err.as_deref()),
+                    _ => false,
+                };
+                if let (Some(err), false) = (err, shown_in_editor) {
+                    return self.handle_message(Message::Error(err), clip);
                 }
             }
             Message::SendMessage {
                 message,
-                retry_after,
                 guild_id,
                 channel_id,
             } => {
                 if let Some(cmd) = self
                     .client
                     .as_mut()
-                    .map(|c| c.send_msg_cmd(guild_id, channel_id, retry_after, message))
+                    .map(|c| c.send_msg_cmd(guild_id, channel_id, message))
                     .flatten()
                 {
                     return cmd;
@@ -587,12 +609,26 @@ impl Application for ScreenManager {
             } => {
                 let path = self.content_store.content_path(&data.id);
                 self.thumbnail_cache
-                    .put_thumbnail(data.id, thumbnail.clone());
+                    .put_thumbnail(data.id.clone(), thumbnail.clone());
+                for screen in self.screens.stack.iter_mut() {
+                    if let Screen::Main(screen) = screen {
+                        screen.on_thumbnail_loaded(data.id.clone());
+                    }
+                }
                 if open {
-                    if let Screen::Main(screen) = self.screens.current_mut() {
-                        screen.image_viewer_modal.inner_mut().image_handle =
-                            Some((thumbnail, (path, data.name)));
-                        screen.image_viewer_modal.show(true);
+                    if let (Screen::Main(screen), Some(client)) =
+                        (self.screens.current_mut(), self.client.as_mut())
+                    {
+                        return screen.update(
+                            main::Message::OpenImageView {
+                                handle: thumbnail,
+                                path,
+                                attachment: data,
+                            },
+                            client,
+                            &self.thumbnail_cache,
+                            clip,
+                        );
                     }
                 }
             }
@@ -644,29 +680,9 @@ impl Application for ScreenManager {
 
                     for chunk in fetch_users.chunks(64).map(|c| c.to_vec()) {
                         if !chunk.is_empty() {
-                            let inner = self.client.as_ref().unwrap().inner().clone();
+                            let requests = self.client.as_ref().unwrap().requests().clone();
                             let fetch_users_cmd = Command::perform(
-                                async move {
-                                    let profiles = get_user_bulk(&inner, chunk.clone()).await?;
-                                    Ok(profiles
-                                        .users
-                                        .into_iter()
-                                        .zip(chunk.into_iter())
-                                        .map(|(profile, user_id)| {
-                                            Event::ProfileUpdated(ProfileUpdated {
-                                                user_id,
-                                                new_avatar: profile.user_avatar,
-                                                new_status: profile.user_status,
-                                                new_username: profile.user_name,
-                                                is_bot: profile.is_bot,
-                                                update_is_bot: true,
-                                                update_status: true,
-                                                update_avatar: true,
-                                                update_username: true,
-                                            })
-                                        })
-                                        .collect::<Vec<_>>())
-                                },
+                                async move { requests.profiles(chunk).await },
                                 |result| {
                                     result.map_or_else(
                                         |err| Message::Error(Box::new(err)),
@@ -705,43 +721,118 @@ impl Application for ScreenManager {
 
                 let cmds = posts
                     .into_iter()
-                    .map(|post| self.process_post_event(post, clip));
+                    .map(|post| self.process_post_event(post, clip))
+                    .collect::<Vec<_>>();
 
                 return Command::batch(cmds);
             }
-            Message::Error(err) => {
-                let err_disp = err.to_string();
-                tracing::error!("{}\n{:?}", err_disp, err);
-
-                if matches!(
-                    &*err,
-                    ClientError::Internal(harmony_rust_sdk::client::error::ClientError::Internal(
-                        harmony_rust_sdk::api::exports::hrpc::client::ClientError::SocketError(_)
-                    ))
-                ) {
-                    self.socket_reset = true;
+            Message::HistoryLoadFailed {
+                guild_id,
+                channel_id,
+                err,
+            } => {
+                if let Some(channel) = self
+                    .client
+                    .as_mut()
+                    .and_then(|client| client.get_channel(guild_id, channel_id))
+                {
+                    channel.loading_messages_history = false;
                 }
-
-                if err_disp.contains("invalid-session") || err_disp.contains("connect error") {
-                    self.update(
-                        Message::Logout(
-                            Screen::Login(LoginScreen::new(self.content_store.clone())).into(),
-                        ),
-                        clip,
-                    );
+                if let Some(err) = err {
+                    return self.handle_error(err, None, clip);
                 }
-
-                return self.screens.current_mut().on_error(*err);
+            }
+            Message::Error(err) => return self.handle_error(err, None, clip),
+            Message::DialogError { dialog, err } => {
+                return self.handle_error(err, Some(dialog), clip)
             }
         }
         Command::none()
     }
 
+    /// Recovers from an error, and shows it in the dialog it came from or in the current screen.
+    fn handle_error(
+        &mut self,
+        err: Box<ClientError>,
+        dialog: Option<main::Dialog>,
+        clip: &mut dyn iced_native::Clipboard,
+    ) -> Command<Message> {
+        let kind = err.kind();
+        tracing::error!("{} ({:?})\n{:?}", err, kind, err);
+
+        match kind.recovery() {
+            Recovery::Relogin => {
+                self.handle_message(
+                    Message::Logout(
+                        Screen::Login(LoginScreen::new(self.content_store.clone())).into(),
+                    ),
+                    clip,
+                );
+            }
+            // Scheduled requests were already sent again before failing for good, so these
+            // are only shown
+            Recovery::Retry | Recovery::ShowInline => {}
+        }
+        if matches!(
+            &*err,
+            ClientError::Internal(harmony_rust_sdk::client::error::ClientError::Internal(
+                harmony_rust_sdk::api::exports::hrpc::client::ClientError::SocketError(_)
+            ))
+        ) {
+            self.socket_reset = true;
+        }
+
+        match (self.screens.current_mut(), dialog) {
+            (Screen::Main(screen), Some(dialog)) => screen.on_dialog_error(dialog, *err),
+            (screen, _) => screen.on_error(*err),
+        }
+    }
+}
+
+impl Application for ScreenManager {
+    type Executor = executor::Default;
+    type Message = Message;
+    type Flags = ContentStore;
+
+    fn new(content_store: Self::Flags) -> (Self, Command<Self::Message>) {
+        let content_store = Arc::new(content_store);
+        let mut manager = ScreenManager::new(content_store.clone());
+        let cmd = match manager.screens.current_mut() {
+            Screen::Login(screen) if content_store.session_file().exists() => {
+                screen.restore_session()
+            }
+            _ => Command::none(),
+        };
+        (manager, cmd)
+    }
+
+    fn title(&self) -> String {
+        "Crust".into()
+    }
+
+    fn update(&mut self, msg: Self::Message, clip: &mut iced::Clipboard) -> Command<Self::Message> {
+        self.handle_message(msg, clip)
+    }
+
     fn subscription(&self) -> Subscription<Self::Message> {
-        let time_sub = iced::time::every(Duration::from_secs(5)).map(|_| Message::Nothing);
         let main_sub = self.screens.current().subscription();
+        let expiry_sub = self
+            .client
+            .as_ref()
+            .map_or_else(Subscription::none, |client| {
+                client
+                    .expiry_subscription()
+                    .map(|_| Message::EphemeralExpired)
+            });
+
+        let resize_sub = iced_native::subscription::events_with(|event, _| match event {
+            iced_native::Event::Window(iced_native::window::Event::Resized { width, height }) => {
+                Some(Message::WindowResized { width, height })
+            }
+            _ => None,
+        });
 
-        Subscription::batch(vec![time_sub, main_sub])
+        Subscription::batch(vec![main_sub, expiry_sub, resize_sub])
     }
 
     fn view(&mut self) -> Element<Self::Message> {
@@ -758,7 +849,11 @@ impl Application for ScreenManager {
                 .view(self.theme, self.client.as_ref().unwrap()) // This will not panic cause [ref:client_set_before_main_view]
                 .map(Message::GuildDiscovery),
             Screen::GuildSettings(screen) => screen
-                .view(self.theme, self.client.as_ref().unwrap())
+                .view(
+                    self.theme,
+                    self.client.as_ref().unwrap(), // This will not panic cause [ref:client_set_before_main_view]
+                    &self.thumbnail_cache,
+                )
                 .map(Message::GuildSettings),
         }
     }
@@ -768,6 +863,16 @@ impl Application for ScreenManager {
     }
 }
 
+/// Maps the result of a scheduled request for events, which is `None` if it wasn't sent.
+fn scheduled_events_received(result: Option<ClientResult<Vec<Event>>>) -> Message {
+    match result {
+        Some(result) => {
+            result.map_or_else(|err| Message::Error(Box::new(err)), Message::EventsReceived)
+        }
+        None => Message::Nothing,
+    }
+}
+
 fn make_thumbnail_command(
     client: &Client,
     data: Attachment,
@@ -776,7 +881,7 @@ fn make_thumbnail_command(
     if !thumbnail_cache.has_thumbnail(&data.id) {
         let content_path = client.content_store().content_path(&data.id);
 
-        let inner = client.inner().clone();
+        let requests = client.requests().clone();
 
         Command::perform(
             async move {
@@ -799,13 +904,8 @@ fn make_thumbnail_command(
                             data.id,
                             err
                         );
-                        let file = harmony_rust_sdk::client::api::rest::download_extract_file(
-                            &inner,
-                            data.id.clone(),
-                        )
-                        .await?;
-                        tokio::fs::write(content_path, file.data()).await?;
-                        let bgra = image::load_from_memory(file.data()).unwrap().into_bgra8();
+                        let raw = requests.download_file(data.id.clone()).await?;
+                        let bgra = image::load_from_memory(&raw).unwrap().into_bgra8();
                         Ok(Message::DownloadedThumbnail {
                             data,
                             thumbnail: ImageHandle::from_pixels(
@@ -825,48 +925,20 @@ fn make_thumbnail_command(
     }
 }
 
-async fn select_upload_files(
-    inner: &InnerClient,
-    content_store: Arc<ContentStore>,
-) -> ClientResult<Vec<(FileId, String, String, usize)>> {
-    use crate::client::content;
-    use harmony_rust_sdk::client::api::rest::upload_extract_id;
-
+async fn select_upload_files(requests: &Requests) -> ClientResult<Vec<Attachment>> {
     let handles = rfd::AsyncFileDialog::new()
         .pick_files()
         .await
         .ok_or_else(|| ClientError::Custom("File selection error".to_string()))?;
-    let mut ids = Vec::with_capacity(handles.len());
+    let mut attachments = Vec::with_capacity(handles.len());
 
     for handle in handles {
-        match tokio::fs::read(handle.path()).await {
-            Ok(data) => {
-                let file_mimetype = content::infer_type_from_bytes(&data);
-                let filename = content::get_filename(handle.path()).to_string();
-                let filesize = data.len();
-
-                let send_result =
-                    upload_extract_id(inner, filename.clone(), file_mimetype.clone(), data).await;
-
-                match send_result.map(FileId::Id) {
-                    Ok(id) => {
-                        if let Err(err) =
-                            tokio::fs::hard_link(handle.path(), content_store.content_path(&id))
-                                .await
-                        {
-                            tracing::warn!("An IO error occured while hard linking a file you tried to upload (this may result in a duplication of the file): {}", err);
-                        }
-                        ids.push((id, file_mimetype, filename, filesize));
-                    }
-                    Err(err) => {
-                        tracing::error!("An error occured while trying to upload a file: {}", err);
-                    }
-                }
-            }
+        match requests.upload_file(handle.path()).await {
+            Ok(attachment) => attachments.push(attachment),
             Err(err) => {
-                tracing::error!("An IO error occured while trying to upload a file: {}", err);
+                tracing::error!("An error occured while trying to upload a file: {}", err);
             }
         }
     }
-    Ok(ids)
+    Ok(attachments)
 }
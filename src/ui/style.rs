@@ -1,4 +1,4 @@
-use crate::color;
+use crate::{client::color::Color as HarmonyColor, color};
 use iced::{
     button, checkbox, container, pick_list, progress_bar, radio, rule, scrollable, slider,
     text_input, Color,
@@ -19,6 +19,22 @@ pub const ALT_COLOR: Color = color!(. 0.5, 0.5, 0.5);
 
 pub const AVATAR_WIDTH: u16 = 32;
 
+impl From<HarmonyColor> for Color {
+    fn from(color: HarmonyColor) -> Self {
+        Color::from_rgb8(color.r, color.g, color.b)
+    }
+}
+
+impl From<Color> for HarmonyColor {
+    fn from(color: Color) -> Self {
+        HarmonyColor {
+            r: (color.r * 255.0) as u8,
+            g: (color.g * 255.0) as u8,
+            b: (color.b * 255.0) as u8,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Theme {
     dark: bool,
@@ -215,6 +231,16 @@ impl From<Theme> for Box<dyn iced_aw::modal::StyleSheet> {
     }
 }
 
+impl From<Theme> for Box<dyn iced_aw::tab_bar::StyleSheet> {
+    fn from(theme: Theme) -> Self {
+        if theme.dark {
+            dark::TabBar.into()
+        } else {
+            Default::default()
+        }
+    }
+}
+
 impl From<Theme> for Box<dyn iced_aw::card::StyleSheet> {
     fn from(theme: Theme) -> Self {
         if theme.dark {
@@ -258,7 +284,7 @@ mod dark {
         button, checkbox, container, pick_list, progress_bar, radio, rule, scrollable, slider,
         text_input, Color,
     };
-    use iced_aw::{card, modal};
+    use iced_aw::{card, modal, tab_bar};
 
     const DARK_BG: Color = color!(0x36, 0x39, 0x3F);
     const BRIGHT_BG: Color = color!(0x44, 0x48, 0x4F);
@@ -283,6 +309,30 @@ mod dark {
         }
     }
 
+    pub struct TabBar;
+
+    impl tab_bar::StyleSheet for TabBar {
+        fn active(&self, is_active: bool) -> tab_bar::Style {
+            tab_bar::Style {
+                background: None,
+                border_color: None,
+                border_width: 0.0,
+                tab_label_background: if is_active { BRIGHT_BG } else { DARK_BG }.into(),
+                tab_label_border_color: BRIGHT_BG,
+                tab_label_border_width: 1.0,
+                icon_color: Color::WHITE,
+                text_color: Color::WHITE,
+            }
+        }
+
+        fn hovered(&self, is_active: bool) -> tab_bar::Style {
+            tab_bar::Style {
+                tab_label_background: ACCENT.into(),
+                ..self.active(is_active)
+            }
+        }
+    }
+
     pub struct Modal;
 
     impl modal::StyleSheet for Modal {
@@ -0,0 +1,238 @@
+mod common;
+
+use common::{Fault, MockHomeserver, PASSWORD};
+use crust::client::{
+    auth::{self, FieldKind},
+    error::{ClientError, ErrorKind},
+    Client, Session,
+};
+use harmony_rust_sdk::{
+    api::auth::{auth_step::Step, next_step_request::form_fields::Field, AuthStep},
+    client::api::auth::AuthStepResponse,
+};
+
+async fn logged_out_client(homeserver: &MockHomeserver) -> Client {
+    Client::new(homeserver.url(), None, homeserver.content_store())
+        .await
+        .unwrap()
+}
+
+fn field_names(step: &AuthStep) -> Vec<&str> {
+    match &step.step {
+        Some(Step::Form(form)) => form
+            .fields
+            .iter()
+            .map(|field| field.name.as_str())
+            .collect(),
+        other => panic!("expected a form, got {:?}", other),
+    }
+}
+
+/// Starts logging in and chooses an option of the first step.
+async fn choose(client: &Client, choice: &str) -> AuthStep {
+    let inner = client.inner();
+    inner.begin_auth().await.unwrap();
+    let step = inner
+        .next_auth_step(AuthStepResponse::Initial)
+        .await
+        .unwrap()
+        .expect("there is no first step");
+    match &step.step {
+        Some(Step::Choice(first)) => assert_eq!(first.options, vec!["login", "register"]),
+        other => panic!("expected a choice, got {:?}", other),
+    }
+
+    inner
+        .next_auth_step(AuthStepResponse::Choice(choice.to_string()))
+        .await
+        .unwrap()
+        .expect("choosing ended the login")
+}
+
+#[tokio::test]
+async fn login_with_form() {
+    let homeserver = MockHomeserver::start().await;
+    let user_id = homeserver.state().add_user("alice");
+    let client = logged_out_client(&homeserver).await;
+
+    let form = choose(&client, "login").await;
+    assert_eq!(field_names(&form), ["email", "password"]);
+    let step = client
+        .inner()
+        .next_auth_step(AuthStepResponse::form(vec![
+            Field::String("alice@example.org".to_string()),
+            Field::Bytes(PASSWORD.as_bytes().to_vec()),
+        ]))
+        .await
+        .unwrap();
+    assert!(step.is_none(), "logging in didn't end with a session");
+
+    let session = client.session().expect("the client isn't logged in");
+    assert_eq!(session.user_id, user_id.to_string());
+    assert_eq!(session.homeserver, homeserver.url().to_string());
+
+    // A client restored from the session can make requests
+    let client = Client::from_session(session, homeserver.content_store())
+        .await
+        .unwrap();
+    assert_eq!(client.user_id, Some(user_id));
+    client.requests().guild_list().await.unwrap();
+}
+
+#[tokio::test]
+async fn wrong_password_can_be_retried() {
+    let homeserver = MockHomeserver::start().await;
+    homeserver.state().add_user("alice");
+    let client = logged_out_client(&homeserver).await;
+    choose(&client, "login").await;
+
+    let err = client
+        .inner()
+        .next_auth_step(AuthStepResponse::form(vec![
+            Field::String("alice@example.org".to_string()),
+            Field::Bytes(b"not the password".to_vec()),
+        ]))
+        .await
+        .unwrap_err();
+    assert_eq!(ClientError::from(err).kind(), ErrorKind::Validation);
+    assert!(client.session().is_none());
+
+    let step = client
+        .inner()
+        .next_auth_step(AuthStepResponse::form(vec![
+            Field::String("alice@example.org".to_string()),
+            Field::Bytes(PASSWORD.as_bytes().to_vec()),
+        ]))
+        .await
+        .unwrap();
+    assert!(step.is_none());
+    assert!(client.session().is_some());
+}
+
+#[tokio::test]
+async fn register_creates_user() {
+    let homeserver = MockHomeserver::start().await;
+    let client = logged_out_client(&homeserver).await;
+
+    let form = choose(&client, "register").await;
+    assert_eq!(field_names(&form), ["username", "email", "password"]);
+    client
+        .inner()
+        .next_auth_step(AuthStepResponse::form(vec![
+            Field::String("bob".to_string()),
+            Field::String("bob@example.org".to_string()),
+            Field::Bytes(b"hunter2".to_vec()),
+        ]))
+        .await
+        .unwrap();
+
+    let user_id: u64 = client.session().unwrap().user_id.parse().unwrap();
+    let state = homeserver.state();
+    let user = &state.users[&user_id];
+    assert_eq!(user.name, "bob");
+    assert_eq!(user.email, "bob@example.org");
+}
+
+#[tokio::test]
+async fn verified_email_pushes_session() {
+    let homeserver = MockHomeserver::start().await;
+    homeserver.state().verify_emails = true;
+    let client = logged_out_client(&homeserver).await;
+
+    choose(&client, "register").await;
+    let requests = client.requests();
+    let step = requests
+        .next_auth_step(AuthStepResponse::form(vec![
+            Field::String("bob".to_string()),
+            Field::String("bob@example.org".to_string()),
+            Field::Bytes(b"hunter2".to_vec()),
+        ]))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(step.step, Some(Step::Waiting(_))));
+    // Asking for the current step doesn't end the waiting
+    let step = requests
+        .next_auth_step(AuthStepResponse::Initial)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(step.step, Some(Step::Waiting(_))));
+
+    let pushed = tokio::spawn({
+        let requests = requests.clone();
+        async move { requests.wait_for_auth_step().await }
+    });
+    homeserver.state().verify_email("bob@example.org");
+    let session = match pushed.await.unwrap().unwrap().step {
+        Some(Step::Session(session)) => Session::new(&homeserver.url(), session),
+        other => panic!("expected a session, got {:?}", other),
+    };
+
+    let client = Client::from_session(session, homeserver.content_store())
+        .await
+        .unwrap();
+    let user_id = client.user_id.unwrap();
+    assert_eq!(homeserver.state().users[&user_id].name, "bob");
+}
+
+#[tokio::test]
+async fn step_back_returns_to_choice() {
+    let homeserver = MockHomeserver::start().await;
+    let client = logged_out_client(&homeserver).await;
+    choose(&client, "login").await;
+
+    let step = client.inner().prev_auth_step().await.unwrap();
+    assert!(matches!(step.step, Some(Step::Choice(_))));
+    let form = client
+        .inner()
+        .next_auth_step(AuthStepResponse::Choice("register".to_string()))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(field_names(&form), ["username", "email", "password"]);
+}
+
+#[tokio::test]
+async fn invalid_session_needs_relogin() {
+    let homeserver = MockHomeserver::start().await;
+    let user_id = homeserver.state().add_user("alice");
+
+    let session = Session {
+        session_token: "expired".to_string(),
+        user_id: user_id.to_string(),
+        homeserver: homeserver.url().to_string(),
+    };
+    let client = Client::from_session(session, homeserver.content_store())
+        .await
+        .unwrap();
+    let err = client.requests().guild_list().await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Auth);
+
+    // Sessions can also be revoked while the client runs
+    let client = homeserver.client_for(user_id).await;
+    homeserver.fail_next("GetGuildList", Fault::bad_session());
+    let err = client.requests().guild_list().await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Auth);
+}
+
+#[test]
+fn field_kinds_check_values() {
+    assert_eq!(FieldKind::from_type("new-password"), FieldKind::NewPassword);
+    assert_eq!(FieldKind::from_type("username"), FieldKind::Text);
+    assert_eq!(auth::label("new-password"), "New password");
+
+    assert!(FieldKind::Text.parse("Username", " ").is_err());
+    assert!(FieldKind::Email.parse("Email", "alice").is_err());
+    assert!(FieldKind::Email.parse("Email", "alice@example.org").is_ok());
+    assert!(matches!(
+        FieldKind::Number.parse("Age", "42"),
+        Ok(Field::Number(42))
+    ));
+    assert!(FieldKind::Number.parse("Age", "forty").is_err());
+    // Spaces are part of passwords
+    assert!(matches!(
+        FieldKind::Password.parse("Password", " secret "),
+        Ok(Field::Bytes(bytes)) if bytes == b" secret "
+    ));
+}
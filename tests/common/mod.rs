@@ -0,0 +1,1676 @@
+//! An in-process fake homeserver, so that client flows can be tested without a live one.
+//!
+//! It speaks the hRPC wire format (protobuf bodies over HTTP, and websockets for streams) for the
+//! endpoints the client uses. Tests set up its state directly, and can make requests fail or the
+//! event sockets drop to see how the client copes.
+
+// Every test binary includes this module, but none of them use all of it
+#![allow(dead_code)]
+
+pub mod ui;
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    convert::Infallible,
+    future::Future,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use async_tungstenite::{
+    tokio::TokioAdapter,
+    tungstenite::{handshake::derive_accept_key, protocol::Role, Message as WsMessage},
+    WebSocketStream,
+};
+use crust::client::{
+    content::ContentStore,
+    error::{ClientError, ClientResult},
+    secret::SecretStore,
+    Client, Session, Url,
+};
+use futures::{SinkExt, StreamExt};
+use harmony_rust_sdk::{
+    api::{
+        auth::{self, auth_step, next_step_request, AuthStep},
+        chat::{
+            self, event, get_guild_channels_response, get_guild_list_response,
+            stream_events_request,
+        },
+        harmonytypes::{self, Message as HarmonyMessage},
+    },
+    client::{error::ClientError as InnerClientError, EventsSocket},
+};
+use hyper::{
+    header::{self, HeaderMap},
+    service::{make_service_fn, service_fn},
+    upgrade::Upgraded,
+    Body, Request, Response, Server, StatusCode,
+};
+use prost::Message as _;
+use serde_json::json;
+use tempfile::TempDir;
+use tokio::sync::{mpsc, oneshot};
+
+/// How many messages are returned for each page of channel history.
+pub const PAGE_SIZE: usize = 10;
+/// The password of users created with [`State::add_user`].
+pub const PASSWORD: &str = "password";
+
+/// Something that goes wrong with the next call to an endpoint.
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// Answers with an hRPC error, as if the homeserver rejected the request.
+    Error {
+        status: StatusCode,
+        identifier: &'static str,
+    },
+    /// Waits before handling the request.
+    Delay(Duration),
+}
+
+impl Fault {
+    pub fn rate_limited() -> Self {
+        Fault::Error {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            identifier: "h.rate-limited",
+        }
+    }
+
+    pub fn permission_denied() -> Self {
+        Fault::Error {
+            status: StatusCode::FORBIDDEN,
+            identifier: "h.not-enough-permissions",
+        }
+    }
+
+    pub fn bad_session() -> Self {
+        Fault::Error {
+            status: StatusCode::UNAUTHORIZED,
+            identifier: "h.bad-session",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EndpointError {
+    status: StatusCode,
+    identifier: &'static str,
+}
+
+impl EndpointError {
+    fn new(status: StatusCode, identifier: &'static str) -> Self {
+        Self { status, identifier }
+    }
+
+    fn not_found(identifier: &'static str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, identifier)
+    }
+
+    fn forbidden() -> Self {
+        Self::new(StatusCode::FORBIDDEN, "h.not-enough-permissions")
+    }
+
+    fn bad_request(identifier: &'static str) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, identifier)
+    }
+
+    fn into_response(self) -> Response<Body> {
+        let body = json!({
+            "identifier": self.identifier,
+            "message": self.identifier,
+        });
+        Response::builder()
+            .status(self.status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.to_string().into())
+            .unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct User {
+    pub name: String,
+    pub email: String,
+    pub password: String,
+    pub avatar: String,
+    pub status: i32,
+    pub is_bot: bool,
+    /// Whether the user followed the link in the email sent when they registered.
+    pub email_verified: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Guild {
+    pub name: String,
+    pub owner: u64,
+    pub picture: String,
+    pub members: Vec<u64>,
+    /// Channels in the order they are listed.
+    pub channels: Vec<Channel>,
+    /// Permissions that were set, keyed by channel (`None` for the whole guild) and node.
+    ///
+    /// Nodes that weren't set are allowed.
+    pub permissions: HashMap<(Option<u64>, String), bool>,
+}
+
+#[derive(Debug, Default)]
+pub struct Channel {
+    pub id: u64,
+    pub name: String,
+    pub is_category: bool,
+    /// Messages oldest first.
+    pub messages: Vec<HarmonyMessage>,
+    pub pinned: Vec<u64>,
+}
+
+#[derive(Debug)]
+struct StoredFile {
+    name: String,
+    kind: String,
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AuthStage {
+    Initial,
+    Login,
+    Register,
+    /// Waiting until the user who registered verifies their email.
+    Verify(u64),
+}
+
+/// Everything the homeserver knows, which tests can change directly.
+#[derive(Debug, Default)]
+pub struct State {
+    pub users: HashMap<u64, User>,
+    pub guilds: BTreeMap<u64, Guild>,
+    /// Whether users who register have to verify their email before they are logged in.
+    pub verify_emails: bool,
+    sessions: HashMap<String, u64>,
+    auth_flows: HashMap<String, AuthStage>,
+    /// Sockets that get the steps pushed to auth flows, keyed by the flow.
+    auth_streams: HashMap<String, mpsc::UnboundedSender<AuthStep>>,
+    files: HashMap<String, StoredFile>,
+    last_id: u64,
+}
+
+impl State {
+    /// IDs are shared by everything and increase, so newer messages have bigger IDs.
+    fn next_id(&mut self) -> u64 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    /// Adds a user that can log in with `{name}@example.org` and [`PASSWORD`].
+    pub fn add_user(&mut self, name: &str) -> u64 {
+        let user_id = self.next_id();
+        self.users.insert(
+            user_id,
+            User {
+                name: name.to_string(),
+                email: format!("{}@example.org", name),
+                password: PASSWORD.to_string(),
+                ..Default::default()
+            },
+        );
+        user_id
+    }
+
+    /// Verifies the email of a user, which pushes a session to the login of the user if it waits
+    /// for that.
+    pub fn verify_email(&mut self, email: &str) {
+        for user in self.users.values_mut().filter(|user| user.email == email) {
+            user.email_verified = true;
+        }
+        self.push_verified_sessions();
+    }
+
+    /// Ends the auth flows that wait for an email which was verified, if their steps are streamed.
+    fn push_verified_sessions(&mut self) {
+        let verified = self
+            .auth_flows
+            .iter()
+            .filter_map(|(auth_id, stage)| match stage {
+                AuthStage::Verify(user_id)
+                    if self.users[user_id].email_verified
+                        && self.auth_streams.contains_key(auth_id) =>
+                {
+                    Some((auth_id.clone(), *user_id))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for (auth_id, user_id) in verified {
+            self.auth_flows.remove(&auth_id);
+            let step = session_step(self.new_session(user_id));
+            if let Some(stream) = self.auth_streams.remove(&auth_id) {
+                let _ = stream.send(step);
+            }
+        }
+    }
+
+    pub fn add_guild(&mut self, owner: u64, name: &str) -> u64 {
+        let guild_id = self.next_id();
+        self.guilds.insert(
+            guild_id,
+            Guild {
+                name: name.to_string(),
+                owner,
+                members: vec![owner],
+                ..Default::default()
+            },
+        );
+        guild_id
+    }
+
+    pub fn join_guild(&mut self, guild_id: u64, user_id: u64) {
+        let guild = self.guilds.get_mut(&guild_id).expect("no such guild");
+        if !guild.members.contains(&user_id) {
+            guild.members.push(user_id);
+        }
+    }
+
+    pub fn add_channel(&mut self, guild_id: u64, name: &str) -> u64 {
+        let channel_id = self.next_id();
+        let guild = self.guilds.get_mut(&guild_id).expect("no such guild");
+        guild.channels.push(Channel {
+            id: channel_id,
+            name: name.to_string(),
+            ..Default::default()
+        });
+        channel_id
+    }
+
+    /// Adds a text message to the end of a channel, returning its ID.
+    pub fn add_message(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        author_id: u64,
+        text: &str,
+    ) -> u64 {
+        self.insert_message(HarmonyMessage {
+            guild_id,
+            channel_id,
+            author_id,
+            content: Some(text_content(text)),
+            ..Default::default()
+        })
+        .expect("no such channel")
+        .message_id
+    }
+
+    /// Adds an embed message to the end of a channel, returning its ID.
+    pub fn add_embed_message(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        author_id: u64,
+        title: &str,
+    ) -> u64 {
+        let embed = harmonytypes::Embed {
+            title: title.to_string(),
+            ..Default::default()
+        };
+        self.insert_message(HarmonyMessage {
+            guild_id,
+            channel_id,
+            author_id,
+            content: Some(harmonytypes::Content {
+                content: Some(harmonytypes::content::Content::EmbedMessage(
+                    harmonytypes::ContentEmbed {
+                        embeds: Some(Box::new(embed)),
+                    },
+                )),
+            }),
+            ..Default::default()
+        })
+        .expect("no such channel")
+        .message_id
+    }
+
+    /// Adds a message with an attachment for each name to the end of a channel, returning its ID.
+    ///
+    /// The files aren't stored, so they can't be downloaded.
+    pub fn add_files_message(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        author_id: u64,
+        names: &[&str],
+    ) -> u64 {
+        let attachments = names
+            .iter()
+            .map(|name| harmonytypes::Attachment {
+                id: format!("file-{}", self.next_id()),
+                name: name.to_string(),
+                r#type: "text/plain".to_string(),
+                ..Default::default()
+            })
+            .collect();
+        self.insert_message(HarmonyMessage {
+            guild_id,
+            channel_id,
+            author_id,
+            content: Some(harmonytypes::Content {
+                content: Some(harmonytypes::content::Content::FilesMessage(
+                    harmonytypes::ContentFiles { attachments },
+                )),
+            }),
+            ..Default::default()
+        })
+        .expect("no such channel")
+        .message_id
+    }
+
+    /// Sets whether members can do something in a guild, or in a channel of it.
+    pub fn set_permission(&mut self, guild_id: u64, channel_id: Option<u64>, node: &str, ok: bool) {
+        let guild = self.guilds.get_mut(&guild_id).expect("no such guild");
+        guild.permissions.insert((channel_id, node.to_string()), ok);
+    }
+
+    pub fn channel(&self, guild_id: u64, channel_id: u64) -> Option<&Channel> {
+        self.guilds
+            .get(&guild_id)
+            .map(|guild| {
+                guild
+                    .channels
+                    .iter()
+                    .find(|channel| channel.id == channel_id)
+            })
+            .flatten()
+    }
+
+    fn channel_mut(&mut self, guild_id: u64, channel_id: u64) -> Option<&mut Channel> {
+        self.guilds
+            .get_mut(&guild_id)
+            .map(|guild| {
+                guild
+                    .channels
+                    .iter_mut()
+                    .find(|channel| channel.id == channel_id)
+            })
+            .flatten()
+    }
+
+    /// Gives a message an ID and the current time, and adds it to the end of its channel.
+    fn insert_message(
+        &mut self,
+        mut message: HarmonyMessage,
+    ) -> Result<HarmonyMessage, EndpointError> {
+        message.message_id = self.next_id();
+        message.created_at = Some(SystemTime::now().into());
+        let channel = self
+            .channel_mut(message.guild_id, message.channel_id)
+            .ok_or_else(|| EndpointError::not_found("h.no-such-channel"))?;
+        channel.messages.push(message.clone());
+        Ok(message)
+    }
+
+    /// Returns the guild if the user is a member of it.
+    fn guild_of(&self, guild_id: u64, user_id: u64) -> Result<&Guild, EndpointError> {
+        let guild = self
+            .guilds
+            .get(&guild_id)
+            .ok_or_else(|| EndpointError::not_found("h.no-such-guild"))?;
+        if guild.members.contains(&user_id) {
+            Ok(guild)
+        } else {
+            Err(EndpointError::forbidden())
+        }
+    }
+
+    fn channel_of(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        user_id: u64,
+    ) -> Result<&Channel, EndpointError> {
+        self.guild_of(guild_id, user_id)?
+            .channels
+            .iter()
+            .find(|channel| channel.id == channel_id)
+            .ok_or_else(|| EndpointError::not_found("h.no-such-channel"))
+    }
+
+    /// Owners can do anything, other members anything that wasn't denied to them.
+    fn has_permission(
+        &self,
+        guild_id: u64,
+        channel_id: Option<u64>,
+        user_id: u64,
+        node: &str,
+    ) -> bool {
+        let guild = match self.guild_of(guild_id, user_id) {
+            Ok(guild) => guild,
+            Err(_) => return false,
+        };
+        if guild.owner == user_id {
+            return true;
+        }
+        let get = |channel_id| {
+            guild
+                .permissions
+                .get(&(channel_id, node.to_string()))
+                .copied()
+        };
+        channel_id
+            .map(|channel_id| get(Some(channel_id)))
+            .flatten()
+            .or_else(|| get(None))
+            .unwrap_or(true)
+    }
+
+    fn check_permission(
+        &self,
+        guild_id: u64,
+        channel_id: Option<u64>,
+        user_id: u64,
+        node: &str,
+    ) -> Result<(), EndpointError> {
+        if self.has_permission(guild_id, channel_id, user_id, node) {
+            Ok(())
+        } else {
+            Err(EndpointError::forbidden())
+        }
+    }
+
+    fn new_session(&mut self, user_id: u64) -> auth::Session {
+        let session_token = format!("session-{}-{}", user_id, self.next_id());
+        self.sessions.insert(session_token.clone(), user_id);
+        auth::Session {
+            user_id,
+            session_token,
+        }
+    }
+
+    fn profile(&self, user_id: u64) -> Result<chat::GetUserResponse, EndpointError> {
+        let user = self
+            .users
+            .get(&user_id)
+            .ok_or_else(|| EndpointError::not_found("h.no-such-user"))?;
+        Ok(chat::GetUserResponse {
+            user_name: user.name.clone(),
+            user_avatar: user.avatar.clone(),
+            user_status: user.status,
+            is_bot: user.is_bot,
+            ..Default::default()
+        })
+    }
+}
+
+enum Outgoing {
+    Event(event::Event),
+    /// Closes the socket without a close frame, like a connection that was cut.
+    Drop,
+}
+
+struct Subscriber {
+    user_id: u64,
+    guilds: HashSet<u64>,
+    homeserver: bool,
+    sender: mpsc::UnboundedSender<Outgoing>,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    faults: Mutex<HashMap<String, VecDeque<Fault>>>,
+    calls: Mutex<HashMap<String, usize>>,
+    subscribers: Mutex<HashMap<u64, Subscriber>>,
+    next_subscriber: AtomicU64,
+}
+
+/// A secret store in memory, so that tests don't need a keyring or touch the one of the user.
+#[derive(Debug, Default)]
+pub struct MockSecretStore {
+    secrets: Mutex<HashMap<String, String>>,
+    unavailable: AtomicBool,
+}
+
+impl MockSecretStore {
+    /// Makes the store fail like the system keyring does when there is none.
+    pub fn set_unavailable(&self, unavailable: bool) {
+        self.unavailable.store(unavailable, Ordering::SeqCst);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.secrets.lock().unwrap().is_empty()
+    }
+
+    /// Returns every secret that was saved, to check that it isn't also written somewhere else.
+    pub fn values(&self) -> Vec<String> {
+        self.secrets.lock().unwrap().values().cloned().collect()
+    }
+
+    fn check(&self) -> ClientResult<()> {
+        if self.unavailable.load(Ordering::SeqCst) {
+            Err(ClientError::KeyringUnavailable(
+                "no secret service in tests".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl SecretStore for MockSecretStore {
+    fn get(&self, key: &str) -> ClientResult<Option<String>> {
+        self.check()?;
+        Ok(self.secrets.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, secret: &str) -> ClientResult<()> {
+        self.check()?;
+        self.secrets
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), secret.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> ClientResult<()> {
+        self.check()?;
+        self.secrets.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+/// A homeserver running on a local port, which stops when dropped.
+pub struct MockHomeserver {
+    url: Url,
+    shared: Arc<Shared>,
+    dir: TempDir,
+    /// Stands in for the system keyring of the content stores.
+    pub secrets: Arc<MockSecretStore>,
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl MockHomeserver {
+    pub async fn start() -> Self {
+        let shared = Arc::new(Shared::default());
+        let make_service = {
+            let shared = shared.clone();
+            make_service_fn(move |_| {
+                let shared = shared.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request| shared.clone().handle(request)))
+                }
+            })
+        };
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let url = format!("http://{}", server.local_addr()).parse().unwrap();
+        let (shutdown, on_shutdown) = oneshot::channel::<()>();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            on_shutdown.await.ok();
+        }));
+
+        Self {
+            url,
+            shared,
+            dir: TempDir::new().expect("couldn't create a directory for the content store"),
+            secrets: Arc::default(),
+            _shutdown: shutdown,
+        }
+    }
+
+    pub fn url(&self) -> Url {
+        self.url.clone()
+    }
+
+    /// A content store in a directory that is removed with the homeserver.
+    pub fn content_store(&self) -> Arc<ContentStore> {
+        let content_store =
+            ContentStore::in_dir(self.dir.path()).with_secret_store(self.secrets.clone());
+        content_store.create_req_dirs().unwrap();
+        Arc::new(content_store)
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, State> {
+        self.shared.state.lock().unwrap()
+    }
+
+    /// Creates a session for a user, as if they logged in.
+    pub fn session_for(&self, user_id: u64) -> Session {
+        let session = self.state().new_session(user_id);
+        Session {
+            session_token: session.session_token,
+            user_id: user_id.to_string(),
+            homeserver: self.url.to_string(),
+        }
+    }
+
+    /// Creates a client that is logged in as a user.
+    pub async fn client_for(&self, user_id: u64) -> Client {
+        Client::from_session(self.session_for(user_id), self.content_store())
+            .await
+            .expect("couldn't create a client")
+    }
+
+    /// Makes the next call to an endpoint fail, or wait. Faults for the same endpoint happen in order.
+    pub fn fail_next(&self, method: &str, fault: Fault) {
+        self.shared
+            .faults
+            .lock()
+            .unwrap()
+            .entry(method.to_string())
+            .or_default()
+            .push_back(fault);
+    }
+
+    /// How many times an endpoint was called, including calls that failed.
+    pub fn calls(&self, method: &str) -> usize {
+        self.shared
+            .calls
+            .lock()
+            .unwrap()
+            .get(method)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Cuts every open event socket.
+    pub fn drop_sockets(&self) {
+        for subscriber in self.shared.subscribers.lock().unwrap().values() {
+            let _ = subscriber.sender.send(Outgoing::Drop);
+        }
+    }
+
+    /// Waits until `count` sockets subscribed to something, since subscribing doesn't wait for the homeserver.
+    pub async fn wait_for_subscriptions(&self, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let subscribed = self
+                .shared
+                .subscribers
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|subscriber| subscriber.homeserver || !subscriber.guilds.is_empty())
+                .count();
+            if subscribed >= count {
+                return;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "only {} of {} sockets subscribed",
+                subscribed,
+                count
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    /// Posts a message as if a user sent it, sending it to the sockets subscribed to the guild.
+    pub fn post_message(&self, guild_id: u64, channel_id: u64, author_id: u64, text: &str) -> u64 {
+        let message = self
+            .state()
+            .insert_message(HarmonyMessage {
+                guild_id,
+                channel_id,
+                author_id,
+                content: Some(text_content(text)),
+                ..Default::default()
+            })
+            .expect("no such channel");
+        let message_id = message.message_id;
+        self.shared.broadcast(
+            guild_id,
+            event::Event::SentMessage(Box::new(event::MessageSent {
+                message: Some(message),
+                ..Default::default()
+            })),
+        );
+        message_id
+    }
+}
+
+impl Shared {
+    async fn handle(self: Arc<Self>, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+        let path = request.uri().path().to_string();
+        if let Some(id) = path.strip_prefix("/_harmony/media/download/") {
+            return Ok(self.download(id));
+        }
+        if path == "/_harmony/media/upload" {
+            return Ok(self
+                .upload(request)
+                .await
+                .unwrap_or_else(EndpointError::into_response));
+        }
+
+        let method = path.rsplit('/').next().unwrap_or_default().to_string();
+        *self
+            .calls
+            .lock()
+            .unwrap()
+            .entry(method.clone())
+            .or_default() += 1;
+        let fault = self
+            .faults
+            .lock()
+            .unwrap()
+            .get_mut(&method)
+            .map(VecDeque::pop_front)
+            .flatten();
+        match fault {
+            Some(Fault::Delay(delay)) => tokio::time::sleep(delay).await,
+            Some(Fault::Error { status, identifier }) => {
+                return Ok(EndpointError::new(status, identifier).into_response());
+            }
+            None => {}
+        }
+
+        if method == "StreamEvents" {
+            return Ok(match self.authenticate(request.headers()) {
+                Ok(user_id) => accept_socket(request, move |socket| async move {
+                    self.serve_socket(user_id, socket).await
+                }),
+                Err(err) => err.into_response(),
+            });
+        }
+        if method == "StreamSteps" {
+            return Ok(accept_socket(request, move |socket| async move {
+                self.serve_steps(socket).await
+            }));
+        }
+
+        let headers = request.headers().clone();
+        let body = hyper::body::to_bytes(request.into_body())
+            .await
+            .unwrap_or_default();
+        Ok(match self.unary(&method, &headers, &body) {
+            Ok(response) => Response::builder()
+                .header(header::CONTENT_TYPE, "application/hrpc")
+                .body(response.into())
+                .unwrap(),
+            Err(err) => err.into_response(),
+        })
+    }
+
+    fn authenticate(&self, headers: &HeaderMap) -> Result<u64, EndpointError> {
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .map(|token| token.to_str().ok())
+            .flatten()
+            .map(|token| token.trim_start_matches("Bearer "))
+            .ok_or_else(|| EndpointError::new(StatusCode::UNAUTHORIZED, "h.missing-session"))?;
+        self.state
+            .lock()
+            .unwrap()
+            .sessions
+            .get(token)
+            .copied()
+            .ok_or_else(|| EndpointError::new(StatusCode::UNAUTHORIZED, "h.bad-session"))
+    }
+
+    fn unary(
+        &self,
+        method: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Vec<u8>, EndpointError> {
+        match method {
+            "BeginAuth" => return respond(Ok(self.begin_auth())),
+            "NextStep" => return respond(self.next_step(decode(body)?)),
+            "StepBack" => return respond(self.step_back(decode(body)?)),
+            _ => {}
+        }
+
+        let user_id = self.authenticate(headers)?;
+        match method {
+            // Only checks the session, and answers with an empty message
+            "CheckLoggedIn" | "Typing" => Ok(Vec::new()),
+            "GetGuildList" => respond(Ok(self.guild_list(user_id))),
+            "GetGuild" => respond(self.guild(user_id, decode(body)?)),
+            "GetGuildChannels" => respond(self.guild_channels(user_id, decode(body)?)),
+            "GetGuildMembers" => respond(self.guild_members(user_id, decode(body)?)),
+            "GetChannelMessages" => respond(self.channel_messages(user_id, decode(body)?)),
+            "SendMessage" => respond(self.send_message(user_id, decode(body)?)),
+            "UpdateMessageText" => respond(self.update_message_text(user_id, decode(body)?)),
+            "UpdateMessageContent" => respond(self.update_message_content(user_id, decode(body)?)),
+            "DeleteMessage" => respond(self.delete_message(user_id, decode(body)?)),
+            "GetPinnedMessages" => respond(self.pinned_messages(user_id, decode(body)?)),
+            "PinMessage" => respond(self.pin_message(user_id, decode(body)?, true)),
+            "UnpinMessage" => {
+                let request: chat::UnpinMessageRequest = decode(body)?;
+                let request = chat::PinMessageRequest {
+                    guild_id: request.guild_id,
+                    channel_id: request.channel_id,
+                    message_id: request.message_id,
+                };
+                respond(self.pin_message(user_id, request, false))
+            }
+            "GetUser" => respond(self.user(decode(body)?)),
+            "GetUserBulk" => respond(self.user_bulk(decode(body)?)),
+            "ProfileUpdate" => respond(self.profile_update(user_id, decode(body)?)),
+            "QueryHasPermission" => respond(self.query_permission(user_id, decode(body)?)),
+            _ => Err(EndpointError::not_found("h.unknown-endpoint")),
+        }
+    }
+
+    fn begin_auth(&self) -> auth::BeginAuthResponse {
+        let mut state = self.state.lock().unwrap();
+        let auth_id = format!("auth-{}", state.next_id());
+        state.auth_flows.insert(auth_id.clone(), AuthStage::Initial);
+        auth::BeginAuthResponse { auth_id }
+    }
+
+    /// Logging in asks to choose between logging in and registering, then for a form.
+    fn next_step(&self, request: auth::NextStepRequest) -> Result<AuthStep, EndpointError> {
+        use next_step_request::{form_fields::Field, Step};
+
+        let mut state = self.state.lock().unwrap();
+        let stage = *state
+            .auth_flows
+            .get(&request.auth_id)
+            .ok_or_else(|| EndpointError::bad_request("h.bad-auth-id"))?;
+
+        let step = match (request.step, stage) {
+            // Asking for the current step, like when the flow starts
+            (None, _) => return Ok(auth_step_for(stage)),
+            (Some(step), _) => step,
+        };
+        let fields = match (stage, step) {
+            (AuthStage::Initial, Step::Choice(choice)) => {
+                let next = match choice.choice.as_str() {
+                    "login" => AuthStage::Login,
+                    "register" => AuthStage::Register,
+                    _ => return Err(EndpointError::bad_request("h.bad-choice")),
+                };
+                state.auth_flows.insert(request.auth_id, next);
+                return Ok(auth_step_for(next));
+            }
+            (AuthStage::Login, Step::Form(form)) | (AuthStage::Register, Step::Form(form)) => form
+                .fields
+                .into_iter()
+                .map(|field| match field.field {
+                    Some(Field::String(value)) => Ok(value),
+                    Some(Field::Bytes(value)) => String::from_utf8(value)
+                        .map_err(|_| EndpointError::bad_request("h.bad-field")),
+                    _ => Err(EndpointError::bad_request("h.bad-field")),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err(EndpointError::bad_request("h.bad-step")),
+        };
+
+        let user_id = match (stage, fields.as_slice()) {
+            (AuthStage::Login, [email, password]) => state
+                .users
+                .iter()
+                .find(|(_, user)| &user.email == email && &user.password == password)
+                .map(|(user_id, _)| *user_id)
+                .ok_or_else(|| EndpointError::bad_request("h.bad-password"))?,
+            (AuthStage::Register, [username, email, password]) => {
+                if state.users.values().any(|user| &user.email == email) {
+                    return Err(EndpointError::bad_request("h.bad-email"));
+                }
+                let user_id = state.next_id();
+                state.users.insert(
+                    user_id,
+                    User {
+                        name: username.clone(),
+                        email: email.clone(),
+                        password: password.clone(),
+                        ..Default::default()
+                    },
+                );
+                if state.verify_emails {
+                    let next = AuthStage::Verify(user_id);
+                    state.auth_flows.insert(request.auth_id, next);
+                    return Ok(auth_step_for(next));
+                }
+                user_id
+            }
+            _ => return Err(EndpointError::bad_request("h.bad-form")),
+        };
+
+        state.auth_flows.remove(&request.auth_id);
+        Ok(session_step(state.new_session(user_id)))
+    }
+
+    fn step_back(&self, request: auth::StepBackRequest) -> Result<AuthStep, EndpointError> {
+        let mut state = self.state.lock().unwrap();
+        let stage = state
+            .auth_flows
+            .get_mut(&request.auth_id)
+            .ok_or_else(|| EndpointError::bad_request("h.bad-auth-id"))?;
+        *stage = AuthStage::Initial;
+        Ok(auth_step_for(AuthStage::Initial))
+    }
+
+    fn guild_list(&self, user_id: u64) -> chat::GetGuildListResponse {
+        let state = self.state.lock().unwrap();
+        let guilds = state
+            .guilds
+            .iter()
+            .filter(|(_, guild)| guild.members.contains(&user_id))
+            .map(|(guild_id, _)| get_guild_list_response::GuildListEntry {
+                guild_id: *guild_id,
+                ..Default::default()
+            })
+            .collect();
+        chat::GetGuildListResponse { guilds }
+    }
+
+    fn guild(
+        &self,
+        user_id: u64,
+        request: chat::GetGuildRequest,
+    ) -> Result<chat::GetGuildResponse, EndpointError> {
+        let state = self.state.lock().unwrap();
+        let guild = state.guild_of(request.guild_id, user_id)?;
+        Ok(chat::GetGuildResponse {
+            guild_name: guild.name.clone(),
+            guild_owner: guild.owner,
+            guild_picture: guild.picture.clone(),
+            ..Default::default()
+        })
+    }
+
+    fn guild_channels(
+        &self,
+        user_id: u64,
+        request: chat::GetGuildChannelsRequest,
+    ) -> Result<chat::GetGuildChannelsResponse, EndpointError> {
+        let state = self.state.lock().unwrap();
+        let channels = state
+            .guild_of(request.guild_id, user_id)?
+            .channels
+            .iter()
+            .map(|channel| get_guild_channels_response::Channel {
+                channel_id: channel.id,
+                channel_name: channel.name.clone(),
+                is_category: channel.is_category,
+                ..Default::default()
+            })
+            .collect();
+        Ok(chat::GetGuildChannelsResponse { channels })
+    }
+
+    fn guild_members(
+        &self,
+        user_id: u64,
+        request: chat::GetGuildMembersRequest,
+    ) -> Result<chat::GetGuildMembersResponse, EndpointError> {
+        let state = self.state.lock().unwrap();
+        let members = state.guild_of(request.guild_id, user_id)?.members.clone();
+        Ok(chat::GetGuildMembersResponse { members })
+    }
+
+    /// Returns a page of messages newest first, like the real homeserver.
+    fn channel_messages(
+        &self,
+        user_id: u64,
+        request: chat::GetChannelMessagesRequest,
+    ) -> Result<chat::GetChannelMessagesResponse, EndpointError> {
+        let state = self.state.lock().unwrap();
+        let messages = &state
+            .channel_of(request.guild_id, request.channel_id, user_id)?
+            .messages;
+        let end = if request.before_message == 0 {
+            messages.len()
+        } else {
+            messages
+                .iter()
+                .position(|message| message.message_id == request.before_message)
+                .ok_or_else(|| EndpointError::not_found("h.no-such-message"))?
+        };
+        let start = end.saturating_sub(PAGE_SIZE);
+
+        Ok(chat::GetChannelMessagesResponse {
+            reached_top: start == 0,
+            messages: messages[start..end].iter().rev().cloned().collect(),
+        })
+    }
+
+    fn send_message(
+        &self,
+        user_id: u64,
+        request: chat::SendMessageRequest,
+    ) -> Result<chat::SendMessageResponse, EndpointError> {
+        let message = {
+            let mut state = self.state.lock().unwrap();
+            state.channel_of(request.guild_id, request.channel_id, user_id)?;
+            state.check_permission(
+                request.guild_id,
+                Some(request.channel_id),
+                user_id,
+                "messages.send",
+            )?;
+            state.insert_message(HarmonyMessage {
+                guild_id: request.guild_id,
+                channel_id: request.channel_id,
+                author_id: user_id,
+                content: request.content,
+                in_reply_to: request.in_reply_to,
+                overrides: request.overrides,
+                ..Default::default()
+            })?
+        };
+
+        let message_id = message.message_id;
+        self.broadcast(
+            request.guild_id,
+            event::Event::SentMessage(Box::new(event::MessageSent {
+                echo_id: request.echo_id,
+                message: Some(message),
+            })),
+        );
+        Ok(chat::SendMessageResponse { message_id })
+    }
+
+    /// Replaces the content of a message the user sent, returning it.
+    fn edit_message(
+        &self,
+        user_id: u64,
+        guild_id: u64,
+        channel_id: u64,
+        message_id: u64,
+        content: harmonytypes::Content,
+    ) -> Result<HarmonyMessage, EndpointError> {
+        let mut state = self.state.lock().unwrap();
+        state.channel_of(guild_id, channel_id, user_id)?;
+        let message = state
+            .channel_mut(guild_id, channel_id)
+            .map(|channel| {
+                channel
+                    .messages
+                    .iter_mut()
+                    .find(|message| message.message_id == message_id)
+            })
+            .flatten()
+            .ok_or_else(|| EndpointError::not_found("h.no-such-message"))?;
+        if message.author_id != user_id {
+            return Err(EndpointError::forbidden());
+        }
+        message.content = Some(content);
+        message.edited_at = Some(SystemTime::now().into());
+        Ok(message.clone())
+    }
+
+    fn update_message_text(
+        &self,
+        user_id: u64,
+        request: chat::UpdateMessageTextRequest,
+    ) -> Result<(), EndpointError> {
+        let message = self.edit_message(
+            user_id,
+            request.guild_id,
+            request.channel_id,
+            request.message_id,
+            text_content(&request.new_content),
+        )?;
+        self.broadcast(
+            request.guild_id,
+            event::Event::EditedMessage(event::MessageUpdated {
+                guild_id: request.guild_id,
+                channel_id: request.channel_id,
+                message_id: request.message_id,
+                edited_at: message.edited_at,
+                content: request.new_content,
+                ..Default::default()
+            }),
+        );
+        Ok(())
+    }
+
+    /// The event only has text, so edits of anything other than text are sent without any.
+    fn update_message_content(
+        &self,
+        user_id: u64,
+        request: chat::UpdateMessageContentRequest,
+    ) -> Result<(), EndpointError> {
+        let content = request
+            .new_content
+            .ok_or_else(|| EndpointError::bad_request("h.empty-content"))?;
+        let message = self.edit_message(
+            user_id,
+            request.guild_id,
+            request.channel_id,
+            request.message_id,
+            content,
+        )?;
+        self.broadcast(
+            request.guild_id,
+            event::Event::EditedMessage(event::MessageUpdated {
+                guild_id: request.guild_id,
+                channel_id: request.channel_id,
+                message_id: request.message_id,
+                edited_at: message.edited_at,
+                ..Default::default()
+            }),
+        );
+        Ok(())
+    }
+
+    /// Authors can delete their messages, and members with the permission any message.
+    fn delete_message(
+        &self,
+        user_id: u64,
+        request: chat::DeleteMessageRequest,
+    ) -> Result<(), EndpointError> {
+        {
+            let mut state = self.state.lock().unwrap();
+            let channel = state.channel_of(request.guild_id, request.channel_id, user_id)?;
+            let position = channel
+                .messages
+                .iter()
+                .position(|message| message.message_id == request.message_id)
+                .ok_or_else(|| EndpointError::not_found("h.no-such-message"))?;
+            if channel.messages[position].author_id != user_id {
+                state.check_permission(
+                    request.guild_id,
+                    Some(request.channel_id),
+                    user_id,
+                    "messages.manage.delete",
+                )?;
+            }
+            let channel = state
+                .channel_mut(request.guild_id, request.channel_id)
+                .unwrap();
+            channel.messages.remove(position);
+            channel.pinned.retain(|id| *id != request.message_id);
+        }
+
+        self.broadcast(
+            request.guild_id,
+            event::Event::DeletedMessage(event::MessageDeleted {
+                guild_id: request.guild_id,
+                channel_id: request.channel_id,
+                message_id: request.message_id,
+            }),
+        );
+        Ok(())
+    }
+
+    fn pinned_messages(
+        &self,
+        user_id: u64,
+        request: chat::GetPinnedMessagesRequest,
+    ) -> Result<chat::GetPinnedMessagesResponse, EndpointError> {
+        let state = self.state.lock().unwrap();
+        let channel = state.channel_of(request.guild_id, request.channel_id, user_id)?;
+        Ok(chat::GetPinnedMessagesResponse {
+            pinned_message_ids: channel.pinned.clone(),
+        })
+    }
+
+    fn pin_message(
+        &self,
+        user_id: u64,
+        request: chat::PinMessageRequest,
+        pin: bool,
+    ) -> Result<(), EndpointError> {
+        let chat::PinMessageRequest {
+            guild_id,
+            channel_id,
+            message_id,
+        } = request;
+        {
+            let mut state = self.state.lock().unwrap();
+            let channel = state.channel_of(guild_id, channel_id, user_id)?;
+            if !channel
+                .messages
+                .iter()
+                .any(|message| message.message_id == message_id)
+            {
+                return Err(EndpointError::not_found("h.no-such-message"));
+            }
+            let node = if pin {
+                "messages.pins.add"
+            } else {
+                "messages.pins.remove"
+            };
+            state.check_permission(guild_id, Some(channel_id), user_id, node)?;
+
+            let pinned = &mut state.channel_mut(guild_id, channel_id).unwrap().pinned;
+            pinned.retain(|id| *id != message_id);
+            if pin {
+                pinned.push(message_id);
+            }
+        }
+
+        let event = if pin {
+            event::Event::PinnedMessage(event::MessagePinned {
+                guild_id,
+                channel_id,
+                message_id,
+            })
+        } else {
+            event::Event::UnpinnedMessage(event::MessageUnpinned {
+                guild_id,
+                channel_id,
+                message_id,
+            })
+        };
+        self.broadcast(guild_id, event);
+        Ok(())
+    }
+
+    fn user(&self, request: chat::GetUserRequest) -> Result<chat::GetUserResponse, EndpointError> {
+        self.state.lock().unwrap().profile(request.user_id)
+    }
+
+    fn user_bulk(
+        &self,
+        request: chat::GetUserBulkRequest,
+    ) -> Result<chat::GetUserBulkResponse, EndpointError> {
+        let state = self.state.lock().unwrap();
+        let users = request
+            .user_id
+            .into_iter()
+            .map(|user_id| state.profile(user_id))
+            .collect::<Result<_, _>>()?;
+        Ok(chat::GetUserBulkResponse { users })
+    }
+
+    /// Updates the profile of the user, telling everyone who shares a guild with them.
+    fn profile_update(
+        &self,
+        user_id: u64,
+        request: chat::ProfileUpdateRequest,
+    ) -> Result<(), EndpointError> {
+        let guild_ids = {
+            let mut state = self.state.lock().unwrap();
+            let user = state
+                .users
+                .get_mut(&user_id)
+                .ok_or_else(|| EndpointError::not_found("h.no-such-user"))?;
+            if request.update_username {
+                user.name = request.new_username.clone();
+            }
+            if request.update_avatar {
+                user.avatar = request.new_avatar.clone();
+            }
+            if request.update_status {
+                user.status = request.new_status;
+            }
+            if request.update_is_bot {
+                user.is_bot = request.is_bot;
+            }
+            state
+                .guilds
+                .iter()
+                .filter(|(_, guild)| guild.members.contains(&user_id))
+                .map(|(guild_id, _)| *guild_id)
+                .collect::<Vec<_>>()
+        };
+
+        let event = event::Event::ProfileUpdated(event::ProfileUpdated {
+            user_id,
+            new_username: request.new_username,
+            update_username: request.update_username,
+            new_avatar: request.new_avatar,
+            update_avatar: request.update_avatar,
+            new_status: request.new_status,
+            update_status: request.update_status,
+            is_bot: request.is_bot,
+            update_is_bot: request.update_is_bot,
+        });
+        self.send_to(&event, |subscriber| {
+            guild_ids
+                .iter()
+                .any(|guild_id| subscriber.guilds.contains(guild_id))
+        });
+        Ok(())
+    }
+
+    fn query_permission(
+        &self,
+        user_id: u64,
+        request: chat::QueryPermissionsRequest,
+    ) -> Result<chat::QueryPermissionsResponse, EndpointError> {
+        let state = self.state.lock().unwrap();
+        state.guild_of(request.guild_id, user_id)?;
+        let channel_id = (request.channel_id != 0).then(|| request.channel_id);
+        Ok(chat::QueryPermissionsResponse {
+            ok: state.has_permission(request.guild_id, channel_id, user_id, &request.check_for),
+        })
+    }
+
+    fn download(&self, id: &str) -> Response<Body> {
+        let state = self.state.lock().unwrap();
+        match state.files.get(id) {
+            Some(file) => Response::builder()
+                .header(header::CONTENT_TYPE, file.kind.as_str())
+                .header(
+                    header::CONTENT_DISPOSITION,
+                    format!("inline; filename=\"{}\"", file.name),
+                )
+                .body(file.data.clone().into())
+                .unwrap(),
+            None => EndpointError::not_found("h.no-such-file").into_response(),
+        }
+    }
+
+    /// Stores the first file of a multipart form, answering with its ID.
+    async fn upload(&self, request: Request<Body>) -> Result<Response<Body>, EndpointError> {
+        self.authenticate(request.headers())?;
+        let query = request.uri().query().unwrap_or_default().to_string();
+        let param = |name: &str| {
+            query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == name)
+                .map_or_else(String::new, |(_, value)| value.to_string())
+        };
+        let boundary = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|kind| kind.to_str().ok())
+            .flatten()
+            .map(|kind| kind.split("boundary=").nth(1))
+            .flatten()
+            .map(|boundary| boundary.trim_matches('"').to_string())
+            .ok_or_else(|| EndpointError::bad_request("h.bad-upload"))?;
+        let body = hyper::body::to_bytes(request.into_body())
+            .await
+            .map_err(|_| EndpointError::bad_request("h.bad-upload"))?;
+        let data = multipart_file(&body, &boundary)
+            .ok_or_else(|| EndpointError::bad_request("h.bad-upload"))?;
+
+        let mut state = self.state.lock().unwrap();
+        let id = format!("file-{}", state.next_id());
+        state.files.insert(
+            id.clone(),
+            StoredFile {
+                name: param("filename"),
+                kind: param("contentType"),
+                data,
+            },
+        );
+        Ok(Response::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(json!({ "id": id }).to_string().into())
+            .unwrap())
+    }
+
+    /// Subscribes the socket to what the client asks for, and sends it the events of its subscriptions.
+    async fn serve_socket(&self, user_id: u64, socket: WebSocketStream<TokioAdapter<Upgraded>>) {
+        let (sender, mut outgoing) = mpsc::unbounded_channel();
+        let subscriber_id = self.next_subscriber.fetch_add(1, Ordering::Relaxed);
+        self.subscribers.lock().unwrap().insert(
+            subscriber_id,
+            Subscriber {
+                user_id,
+                guilds: HashSet::new(),
+                homeserver: false,
+                sender,
+            },
+        );
+
+        let (mut sink, mut incoming) = socket.split();
+        loop {
+            tokio::select! {
+                message = incoming.next() => match message {
+                    Some(Ok(WsMessage::Binary(data))) => match decode(&data) {
+                        Ok(request) => self.subscribe(subscriber_id, request),
+                        Err(_) => break,
+                    },
+                    Some(Ok(_)) => {}
+                    _ => break,
+                },
+                outgoing = outgoing.recv() => match outgoing {
+                    Some(Outgoing::Event(event)) => {
+                        let event = chat::Event { event: Some(event) };
+                        if sink.send(WsMessage::Binary(encode(&event))).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Outgoing::Drop) | None => break,
+                },
+            }
+        }
+
+        self.subscribers.lock().unwrap().remove(&subscriber_id);
+    }
+
+    /// Sends the steps that are pushed to the auth flow the socket asks for.
+    async fn serve_steps(&self, socket: WebSocketStream<TokioAdapter<Upgraded>>) {
+        let (mut sink, mut incoming) = socket.split();
+        let auth_id = match incoming.next().await {
+            Some(Ok(WsMessage::Binary(data))) => match decode::<auth::StreamStepsRequest>(&data) {
+                Ok(request) => request.auth_id,
+                Err(_) => return,
+            },
+            _ => return,
+        };
+
+        let (sender, mut outgoing) = mpsc::unbounded_channel();
+        {
+            let mut state = self.state.lock().unwrap();
+            if !state.auth_flows.contains_key(&auth_id) {
+                return;
+            }
+            state.auth_streams.insert(auth_id.clone(), sender);
+            // The email could have been verified before the socket was opened
+            state.push_verified_sessions();
+        }
+
+        loop {
+            tokio::select! {
+                message = incoming.next() => match message {
+                    Some(Ok(_)) => {}
+                    _ => break,
+                },
+                step = outgoing.recv() => match step {
+                    Some(step) => {
+                        if sink.send(WsMessage::Binary(encode(&step))).await.is_err() {
+                            break;
+                        }
+                    }
+                    None => break,
+                },
+            }
+        }
+
+        self.state.lock().unwrap().auth_streams.remove(&auth_id);
+    }
+
+    fn subscribe(&self, subscriber_id: u64, request: chat::StreamEventsRequest) {
+        use stream_events_request::Request as Subscription;
+
+        let state = self.state.lock().unwrap();
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let subscriber = match subscribers.get_mut(&subscriber_id) {
+            Some(subscriber) => subscriber,
+            None => return,
+        };
+        match request.request {
+            Some(Subscription::SubscribeToGuild(guild)) => {
+                if state.guild_of(guild.guild_id, subscriber.user_id).is_ok() {
+                    subscriber.guilds.insert(guild.guild_id);
+                }
+            }
+            Some(Subscription::SubscribeToHomeserverEvents(_)) => subscriber.homeserver = true,
+            _ => {}
+        }
+    }
+
+    /// Sends an event to the sockets subscribed to a guild.
+    fn broadcast(&self, guild_id: u64, event: event::Event) {
+        self.send_to(&event, |subscriber| subscriber.guilds.contains(&guild_id));
+    }
+
+    fn send_to(&self, event: &event::Event, filter: impl Fn(&Subscriber) -> bool) {
+        for subscriber in self.subscribers.lock().unwrap().values() {
+            if filter(subscriber) {
+                let _ = subscriber.sender.send(Outgoing::Event(event.clone()));
+            }
+        }
+    }
+}
+
+/// Answers a websocket handshake, and serves the socket with `serve` once it's upgraded.
+fn accept_socket<F, Fut>(request: Request<Body>, serve: F) -> Response<Body>
+where
+    F: FnOnce(WebSocketStream<TokioAdapter<Upgraded>>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let accept_key = match request.headers().get(header::SEC_WEBSOCKET_KEY) {
+        Some(key) => derive_accept_key(key.as_bytes()),
+        None => return EndpointError::bad_request("h.not-a-socket").into_response(),
+    };
+
+    tokio::spawn(async move {
+        if let Ok(upgraded) = hyper::upgrade::on(request).await {
+            let socket =
+                WebSocketStream::from_raw_socket(TokioAdapter::new(upgraded), Role::Server, None)
+                    .await;
+            serve(socket).await;
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::CONNECTION, "upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(header::SEC_WEBSOCKET_ACCEPT, accept_key)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn auth_step_for(stage: AuthStage) -> AuthStep {
+    let form = |title: &str, fields: &[(&str, &str)]| AuthStep {
+        can_go_back: true,
+        step: Some(auth_step::Step::Form(auth_step::Form {
+            title: title.to_string(),
+            fields: fields
+                .iter()
+                .map(|(name, kind)| auth_step::form::FormField {
+                    name: name.to_string(),
+                    r#type: kind.to_string(),
+                })
+                .collect(),
+        })),
+        ..Default::default()
+    };
+
+    match stage {
+        AuthStage::Initial => AuthStep {
+            can_go_back: false,
+            step: Some(auth_step::Step::Choice(auth_step::Choice {
+                title: "initial".to_string(),
+                options: vec!["login".to_string(), "register".to_string()],
+            })),
+            ..Default::default()
+        },
+        AuthStage::Login => form("login", &[("email", "email"), ("password", "password")]),
+        AuthStage::Register => form(
+            "register",
+            &[
+                ("username", "username"),
+                ("email", "email"),
+                ("password", "new-password"),
+            ],
+        ),
+        AuthStage::Verify(_) => AuthStep {
+            can_go_back: false,
+            step: Some(auth_step::Step::Waiting(auth_step::Waiting {
+                title: "verify-email".to_string(),
+                description: "Follow the link in the email we sent you.".to_string(),
+            })),
+            ..Default::default()
+        },
+    }
+}
+
+fn session_step(session: auth::Session) -> AuthStep {
+    AuthStep {
+        step: Some(auth_step::Step::Session(session)),
+        ..Default::default()
+    }
+}
+
+pub fn text_content(text: &str) -> harmonytypes::Content {
+    harmonytypes::Content {
+        content: Some(harmonytypes::content::Content::TextMessage(
+            harmonytypes::ContentText {
+                content: text.to_string(),
+            },
+        )),
+    }
+}
+
+/// Finds the contents of the first part of a multipart body.
+fn multipart_file(body: &[u8], boundary: &str) -> Option<Vec<u8>> {
+    let find = |needle: &[u8], from: usize| {
+        body.get(from..)?
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .map(|position| from + position)
+    };
+
+    let part = find(format!("--{}", boundary).as_bytes(), 0)?;
+    let start = find(b"\r\n\r\n", part)? + 4;
+    let end = find(format!("\r\n--{}", boundary).as_bytes(), start)?;
+    Some(body[start..end].to_vec())
+}
+
+fn decode<M: prost::Message + Default>(body: &[u8]) -> Result<M, EndpointError> {
+    M::decode(body).map_err(|_| EndpointError::bad_request("h.bad-request"))
+}
+
+fn encode<M: prost::Message>(message: &M) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(message.encoded_len());
+    message
+        .encode(&mut buf)
+        .expect("a vec grows to fit the message");
+    buf
+}
+
+fn respond<M: prost::Message>(result: Result<M, EndpointError>) -> Result<Vec<u8>, EndpointError> {
+    result.map(|response| encode(&response))
+}
+
+/// Puts the guilds of the user, with their channels and members, in the state of the client.
+pub async fn sync_guilds(client: &mut Client) {
+    let requests = client.requests().clone();
+    for event in requests
+        .guild_list()
+        .await
+        .expect("couldn't fetch the guilds")
+    {
+        client.process_event(event);
+    }
+    let guild_ids = client.guilds.keys().copied().collect::<Vec<_>>();
+    for guild_id in guild_ids {
+        client.process_event(requests.guild_data(guild_id).await.unwrap());
+        for event in requests.guild_contents(guild_id).await.unwrap() {
+            client.process_event(event);
+        }
+    }
+}
+
+/// Waits for the next event of a socket, failing the test if it takes too long or the socket fails.
+pub async fn next_event(socket: &mut EventsSocket) -> event::Event {
+    next_result(socket)
+        .await
+        .expect("the socket failed instead of receiving an event")
+}
+
+/// Waits for a socket to fail, failing the test if it receives an event instead.
+pub async fn next_error(socket: &mut EventsSocket) -> InnerClientError {
+    match next_result(socket).await {
+        Ok(event) => panic!("expected the socket to fail, got {:?}", event),
+        Err(err) => err,
+    }
+}
+
+async fn next_result(socket: &mut EventsSocket) -> Result<event::Event, InnerClientError> {
+    let receive = async {
+        loop {
+            if let Some(result) = socket.get_event().await {
+                return result;
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), receive)
+        .await
+        .expect("nothing arrived on the socket in time")
+}
@@ -0,0 +1,284 @@
+//! Drives the UI without a window or a GPU.
+//!
+//! Messages go to a [`ScreenManager`] whose client talks to a [`MockHomeserver`], and the commands
+//! they produce are run to get the messages that follow, like the iced runtime would.
+
+use std::{collections::VecDeque, path::PathBuf, time::Duration};
+
+use super::MockHomeserver;
+use crust::{
+    client::Client,
+    ui::{
+        component::event_history::message_summary,
+        screen::{
+            main::{self, quick_switcher::SearchResult, Dialog, MainScreen, Mode},
+            LoginScreen, Message, Screen, ScreenManager,
+        },
+    },
+};
+use iced::{Application, Command};
+
+/// A clipboard that keeps what was written to it.
+#[derive(Debug, Default)]
+pub struct TestClipboard {
+    pub contents: Option<String>,
+}
+
+impl iced_native::Clipboard for TestClipboard {
+    fn read(&self) -> Option<String> {
+        self.contents.clone()
+    }
+
+    fn write(&mut self, contents: String) {
+        self.contents = Some(contents);
+    }
+}
+
+pub struct Harness {
+    pub homeserver: MockHomeserver,
+    pub manager: ScreenManager,
+    pub clipboard: TestClipboard,
+}
+
+impl Harness {
+    /// Starts on the login screen, like the app does without a saved session.
+    pub fn new(homeserver: MockHomeserver) -> Self {
+        Self {
+            manager: ScreenManager::new(homeserver.content_store()),
+            homeserver,
+            clipboard: TestClipboard::default(),
+        }
+    }
+
+    /// Logs in as a user, and waits until their guilds are loaded.
+    pub async fn login(&mut self, user_id: u64) {
+        let client = self.homeserver.client_for(user_id).await;
+        // Logging in saves the session, which logging out removes
+        let content_store = self.homeserver.content_store();
+        client
+            .session()
+            .unwrap()
+            .save(&content_store, None)
+            .await
+            .unwrap();
+        self.settle(Message::LoginComplete(Some(client))).await;
+    }
+
+    /// Updates the state with a message, returning the command it produced.
+    pub fn update(&mut self, msg: Message) -> Command<Message> {
+        self.manager.handle_message(msg, &mut self.clipboard)
+    }
+
+    /// Updates the state with a message and runs the command it produced, returning the messages
+    /// the command produced without handling them.
+    pub async fn send(&mut self, msg: Message) -> Vec<Message> {
+        let cmd = self.update(msg);
+        run(cmd).await
+    }
+
+    /// Updates the state with a message, and then with every message that follows from it.
+    pub async fn settle(&mut self, msg: Message) {
+        let mut queue = VecDeque::from(vec![msg]);
+        let mut handled = 0;
+        while let Some(msg) = queue.pop_front() {
+            // The event socket is never read, tests send events with `Message::EventsReceived`
+            if let Message::SocketEvent { .. } = msg {
+                continue;
+            }
+            handled += 1;
+            assert!(
+                handled < 1000,
+                "messages kept coming, last one was {:?}",
+                msg
+            );
+
+            let cmd = self.update(msg);
+            queue.extend(run(cmd).await);
+        }
+    }
+
+    /// Sends a message to the main screen, settling what follows from it.
+    pub async fn settle_main(&mut self, msg: main::Message) {
+        self.settle(Message::MainScreen(msg)).await;
+    }
+
+    pub fn screen(&self) -> &Screen {
+        self.manager.screens().current()
+    }
+
+    pub fn main(&self) -> &MainScreen {
+        match self.screen() {
+            Screen::Main(screen) => screen,
+            other => panic!("expected the main screen, got {:?}", other),
+        }
+    }
+
+    pub fn login_screen(&self) -> &LoginScreen {
+        match self.screen() {
+            Screen::Login(screen) => screen,
+            other => panic!("expected the login screen, got {:?}", other),
+        }
+    }
+
+    pub fn client(&self) -> &Client {
+        self.manager.client().expect("not logged in")
+    }
+
+    pub fn client_mut(&mut self) -> &mut Client {
+        self.manager.client_mut().expect("not logged in")
+    }
+
+    /// Describes what the current screen shows, one thing per line.
+    ///
+    /// Laying out the widget tree needs a renderer with fonts, so this describes the state the view
+    /// is built from instead. The view is still built, which catches views that panic.
+    pub fn describe(&mut self) -> String {
+        let _ = self.manager.view();
+
+        let mut lines = Vec::new();
+        match self.screen() {
+            Screen::Login(screen) => {
+                lines.push("login".to_string());
+                if !screen.title().is_empty() {
+                    lines.push(format!("title {:?}", screen.title()));
+                }
+                for (name, error) in screen.field_errors() {
+                    lines.push(match error {
+                        Some(error) => format!("field {} ({})", name, error),
+                        None => format!("field {}", name),
+                    });
+                }
+                if screen.waiting {
+                    lines.push("waiting".to_string());
+                }
+                if let Some(error) = screen.error_text() {
+                    lines.push(format!("error {}", error));
+                }
+            }
+            Screen::Main(screen) => {
+                lines.push("main".to_string());
+                describe_main(screen, self.client(), &mut lines);
+            }
+            Screen::GuildDiscovery(_) => lines.push("guild discovery".to_string()),
+            Screen::GuildSettings(_) => lines.push("guild settings".to_string()),
+        }
+
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    /// Checks that the current screen shows the same as in `tests/snapshots/{name}.snap`.
+    ///
+    /// `UPDATE_SNAPSHOTS=1` saves the snapshots again, after a view was changed on purpose or to
+    /// add new ones.
+    pub fn assert_snapshot(&mut self, name: &str) {
+        let description = self.describe();
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("snapshots")
+            .join(format!("{}.snap", name));
+
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, description).unwrap();
+            eprintln!("saved snapshot {}", path.display());
+            return;
+        }
+        match std::fs::read_to_string(&path) {
+            Ok(saved) => assert_eq!(
+                saved, description,
+                "the view doesn't match snapshot {}, run with UPDATE_SNAPSHOTS=1 if this is expected",
+                name
+            ),
+            Err(err) => panic!(
+                "couldn't read snapshot {} ({}), run with UPDATE_SNAPSHOTS=1 to save it",
+                path.display(),
+                err
+            ),
+        }
+    }
+}
+
+/// Describes the guilds, the current channel and the dialogs of the main screen.
+fn describe_main(screen: &MainScreen, client: &Client, lines: &mut Vec<String>) {
+    let current = screen.current_channel();
+    let mut guilds = client.guilds.iter().collect::<Vec<_>>();
+    guilds.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+    for (guild_id, guild) in guilds {
+        if current.map(|(id, _)| id) != Some(*guild_id) {
+            lines.push(format!("guild {:?}", guild.name));
+            continue;
+        }
+
+        lines.push(format!("guild {:?} (current)", guild.name));
+        for (channel_id, channel) in &guild.channels {
+            let mut line = if channel.is_category {
+                format!("  category {}", channel.name)
+            } else {
+                format!("  channel #{}", channel.name)
+            };
+            if current == Some((*guild_id, *channel_id)) {
+                line.push_str(" (current)");
+            }
+            if channel.unread > 0 {
+                line.push_str(&format!(" ({} unread)", channel.unread));
+            }
+            lines.push(line);
+        }
+        let mut members = guild
+            .members
+            .iter()
+            .map(|id| {
+                client
+                    .members
+                    .get(id)
+                    .map_or_else(|| id.to_string(), |member| member.username.clone())
+            })
+            .collect::<Vec<_>>();
+        members.sort();
+        lines.extend(members.into_iter().map(|name| format!("member {}", name)));
+    }
+
+    if let Some(channel) = current.and_then(|(guild_id, channel_id)| {
+        client
+            .guilds
+            .get(&guild_id)
+            .and_then(|guild| guild.channels.get(&channel_id))
+    }) {
+        for message in &channel.messages {
+            lines.push(format!(
+                "message {}",
+                message_summary(message, &client.members)
+            ));
+        }
+    }
+
+    if screen.mode() != Mode::Normal {
+        lines.push(format!("mode {:?}", screen.mode()));
+    }
+    if !screen.composer_text().is_empty() {
+        lines.push(format!("composer {:?}", screen.composer_text()));
+    }
+    for dialog in screen.shown_dialogs() {
+        lines.push(format!("dialog {:?}", dialog));
+        if dialog == Dialog::QuickSwitcher {
+            for result in screen.quick_switcher_results() {
+                lines.push(match result {
+                    SearchResult::Guild { name, .. } => format!("  result *{}", name),
+                    SearchResult::Channel { name, .. } => format!("  result #{}", name),
+                });
+            }
+        }
+    }
+    if let Some(error) = screen.error_text() {
+        lines.push(format!("error {}", error));
+    }
+}
+
+/// Runs every future of a command, returning the messages they produced.
+pub async fn run(cmd: Command<Message>) -> Vec<Message> {
+    let futures = cmd.futures();
+    tokio::time::timeout(Duration::from_secs(5), futures::future::join_all(futures))
+        .await
+        .expect("a command didn't finish")
+}
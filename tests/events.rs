@@ -0,0 +1,202 @@
+mod common;
+
+use common::{next_error, next_event, sync_guilds, MockHomeserver};
+use crust::client::{
+    change::StateChange,
+    error::{ClientError, ErrorKind, Recovery},
+    message::MessageId,
+    permission::Node,
+    Client, PostProcessEvent,
+};
+use harmony_rust_sdk::api::{
+    chat::event::{Event, PermissionUpdated},
+    harmonytypes::UserStatus,
+};
+
+/// A guild with a channel, owned by alice, that bob is a member of. Returns a client logged in as alice.
+async fn setup(homeserver: &MockHomeserver) -> (Client, u64, u64, u64) {
+    let (alice, bob, guild_id, channel_id) = {
+        let mut state = homeserver.state();
+        let alice = state.add_user("alice");
+        let bob = state.add_user("bob");
+        let guild_id = state.add_guild(alice, "guild");
+        state.join_guild(guild_id, bob);
+        let channel_id = state.add_channel(guild_id, "general");
+        (alice, bob, guild_id, channel_id)
+    };
+    let mut client = homeserver.client_for(alice).await;
+    sync_guilds(&mut client).await;
+    (client, bob, guild_id, channel_id)
+}
+
+#[tokio::test]
+async fn messages_of_others_arrive() {
+    let homeserver = MockHomeserver::start().await;
+    let (mut client, bob, guild_id, channel_id) = setup(&homeserver).await;
+    let mut socket = client
+        .requests()
+        .subscribe(client.subscribe_to())
+        .await
+        .unwrap();
+    homeserver.wait_for_subscriptions(1).await;
+    let mut changes = client.state_changes();
+
+    let message_id = homeserver.post_message(guild_id, channel_id, bob, "hi alice");
+    let event = next_event(&mut socket).await;
+    client.process_event(event);
+
+    let channel = client.get_channel(guild_id, channel_id).unwrap();
+    assert_eq!(channel.messages.len(), 1);
+    assert_eq!(channel.messages[0].sender, bob);
+    assert_eq!(channel.unread, 1);
+    assert_eq!(
+        changes.try_recv().unwrap(),
+        StateChange::MessageInserted {
+            guild_id,
+            channel_id,
+            message_id: MessageId::Ack(message_id),
+        }
+    );
+}
+
+#[tokio::test]
+async fn resubscribe_after_socket_drop() {
+    let homeserver = MockHomeserver::start().await;
+    let (mut client, bob, guild_id, channel_id) = setup(&homeserver).await;
+    let requests = client.requests().clone();
+    let mut socket = requests.subscribe(client.subscribe_to()).await.unwrap();
+    homeserver.wait_for_subscriptions(1).await;
+
+    homeserver.drop_sockets();
+    let err = ClientError::from(next_error(&mut socket).await);
+    assert_eq!(err.kind(), ErrorKind::Network);
+    assert_eq!(err.kind().recovery(), Recovery::Retry);
+
+    // Messages sent while the socket was down are only in the history
+    let missed_id = homeserver.post_message(guild_id, channel_id, bob, "while you were away");
+    let mut socket = requests.subscribe(client.subscribe_to()).await.unwrap();
+    homeserver.wait_for_subscriptions(1).await;
+    let (messages, reached_top) = requests
+        .channel_messages(guild_id, channel_id, None)
+        .await
+        .unwrap();
+    client.process_get_message_history_response(guild_id, channel_id, messages, reached_top);
+
+    let message_id = homeserver.post_message(guild_id, channel_id, bob, "welcome back");
+    let event = next_event(&mut socket).await;
+    client.process_event(event);
+
+    let ids = client
+        .get_channel(guild_id, channel_id)
+        .unwrap()
+        .messages
+        .iter()
+        .map(|message| message.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [MessageId::Ack(missed_id), MessageId::Ack(message_id)]);
+}
+
+#[tokio::test]
+async fn pins_follow_events() {
+    let homeserver = MockHomeserver::start().await;
+    let (mut client, bob, guild_id, channel_id) = setup(&homeserver).await;
+    let message_id = homeserver
+        .state()
+        .add_message(guild_id, channel_id, bob, "pin me");
+    let requests = client.requests().clone();
+    let pinned = requests
+        .pinned_messages(guild_id, channel_id)
+        .await
+        .unwrap();
+    client.set_pins(guild_id, channel_id, pinned);
+    let mut socket = requests.subscribe(client.subscribe_to()).await.unwrap();
+    homeserver.wait_for_subscriptions(1).await;
+
+    requests
+        .pin_message(guild_id, channel_id, message_id)
+        .await
+        .unwrap();
+    let event = next_event(&mut socket).await;
+    client.process_event(event);
+    assert_eq!(
+        client.get_channel(guild_id, channel_id).unwrap().pinned,
+        Some(vec![message_id])
+    );
+
+    requests
+        .unpin_message(guild_id, channel_id, message_id)
+        .await
+        .unwrap();
+    let event = next_event(&mut socket).await;
+    client.process_event(event);
+    assert_eq!(
+        client.get_channel(guild_id, channel_id).unwrap().pinned,
+        Some(Vec::new())
+    );
+}
+
+#[tokio::test]
+async fn profile_updates_reach_guild_members() {
+    let homeserver = MockHomeserver::start().await;
+    let (mut client, bob, _, _) = setup(&homeserver).await;
+    let mut socket = client
+        .requests()
+        .subscribe(client.subscribe_to())
+        .await
+        .unwrap();
+    homeserver.wait_for_subscriptions(1).await;
+
+    let bob_client = homeserver.client_for(bob).await;
+    bob_client
+        .requests()
+        .set_status(UserStatus::DoNotDisturb)
+        .await
+        .unwrap();
+    let event = next_event(&mut socket).await;
+    client.process_event(event);
+
+    assert_eq!(client.members[&bob].status, UserStatus::DoNotDisturb);
+}
+
+#[tokio::test]
+async fn guild_wide_updates_of_channel_permissions_refetch_channels() {
+    let homeserver = MockHomeserver::start().await;
+    let (mut client, _, guild_id, channel_id) = setup(&homeserver).await;
+    client.insert_permissions(guild_id, Some(channel_id), vec![(Node::SendMessages, true)]);
+
+    // A channel can override the guild, so what it allows isn't known anymore
+    let post = client.process_event(Event::PermissionUpdated(PermissionUpdated {
+        guild_id,
+        channel_id: 0,
+        query: Node::SendMessages.as_str().to_string(),
+        ok: false,
+    }));
+    assert_eq!(
+        client
+            .permissions
+            .get(guild_id, Some(channel_id), Node::SendMessages),
+        None
+    );
+    assert_eq!(
+        client.permissions.get(guild_id, None, Node::SendMessages),
+        Some(false)
+    );
+    assert!(post
+        .iter()
+        .any(|post| matches!(post, PostProcessEvent::FetchPermissions(id) if *id == guild_id)));
+
+    // Updates of a channel only change that channel
+    let post = client.process_event(Event::PermissionUpdated(PermissionUpdated {
+        guild_id,
+        channel_id,
+        query: Node::SendMessages.as_str().to_string(),
+        ok: true,
+    }));
+    assert_eq!(
+        client
+            .permissions
+            .get(guild_id, Some(channel_id), Node::SendMessages),
+        Some(true)
+    );
+    assert!(post.is_empty());
+}
@@ -0,0 +1,492 @@
+mod common;
+
+use common::{next_event, sync_guilds, Fault, MockHomeserver, PAGE_SIZE};
+use crust::client::{
+    change::StateChange,
+    error::{ErrorKind, Recovery},
+    message::{Content, Message, MessageId},
+    scheduler::{Priority, RequestKey},
+    Client,
+};
+use harmony_rust_sdk::client::api::rest::{download_extract_file, FileId};
+use hyper::StatusCode;
+
+struct Fixture {
+    homeserver: MockHomeserver,
+    client: Client,
+    user_id: u64,
+    guild_id: u64,
+    channel_id: u64,
+}
+
+/// A homeserver with one user, who owns a guild with one channel, and a client logged in as them.
+async fn fixture() -> Fixture {
+    let homeserver = MockHomeserver::start().await;
+    let (user_id, guild_id, channel_id) = {
+        let mut state = homeserver.state();
+        let user_id = state.add_user("alice");
+        let guild_id = state.add_guild(user_id, "guild");
+        let channel_id = state.add_channel(guild_id, "general");
+        (user_id, guild_id, channel_id)
+    };
+    let mut client = homeserver.client_for(user_id).await;
+    sync_guilds(&mut client).await;
+
+    Fixture {
+        homeserver,
+        client,
+        user_id,
+        guild_id,
+        channel_id,
+    }
+}
+
+impl Fixture {
+    fn messages(&mut self) -> &[Message] {
+        &self
+            .client
+            .get_channel(self.guild_id, self.channel_id)
+            .expect("the client doesn't know the channel")
+            .messages
+    }
+
+    /// Adds a member to the guild, returning a client logged in as them.
+    async fn member(&self, name: &str) -> (u64, Client) {
+        let user_id = {
+            let mut state = self.homeserver.state();
+            let user_id = state.add_user(name);
+            state.join_guild(self.guild_id, user_id);
+            user_id
+        };
+        (user_id, self.homeserver.client_for(user_id).await)
+    }
+}
+
+fn text(message: &Message) -> &str {
+    match &message.content {
+        Content::Text(text) => text,
+        other => panic!("expected a text message, got {:?}", other),
+    }
+}
+
+fn text_message(text: &str) -> Message {
+    Message {
+        content: Content::Text(text.to_string()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn history_pages_until_top() {
+    let mut fixture = fixture().await;
+    let count = PAGE_SIZE * 2 + PAGE_SIZE / 2;
+    for i in 0..count {
+        fixture.homeserver.state().add_message(
+            fixture.guild_id,
+            fixture.channel_id,
+            fixture.user_id,
+            &format!("message {}", i),
+        );
+    }
+
+    let requests = fixture.client.requests().clone();
+    let mut pages = Vec::new();
+    loop {
+        let before = fixture
+            .messages()
+            .first()
+            .map(|message| message.id.id())
+            .flatten();
+        let (page, reached_top) = requests
+            .channel_messages(fixture.guild_id, fixture.channel_id, before)
+            .await
+            .unwrap();
+        pages.push(page.len());
+        fixture.client.process_get_message_history_response(
+            fixture.guild_id,
+            fixture.channel_id,
+            page,
+            reached_top,
+        );
+        if reached_top {
+            break;
+        }
+    }
+
+    assert_eq!(pages, [PAGE_SIZE, PAGE_SIZE, PAGE_SIZE / 2]);
+    let texts = fixture
+        .messages()
+        .iter()
+        .map(|message| text(message).to_string())
+        .collect::<Vec<_>>();
+    let expected = (0..count)
+        .map(|i| format!("message {}", i))
+        .collect::<Vec<_>>();
+    assert_eq!(texts, expected);
+    assert!(
+        fixture
+            .client
+            .get_channel(fixture.guild_id, fixture.channel_id)
+            .unwrap()
+            .reached_top
+    );
+}
+
+#[tokio::test]
+async fn sent_message_is_acked_by_echo_id() {
+    let mut fixture = fixture().await;
+    let requests = fixture.client.requests().clone();
+    let mut socket = requests
+        .subscribe(fixture.client.subscribe_to())
+        .await
+        .unwrap();
+    fixture.homeserver.wait_for_subscriptions(1).await;
+    let mut changes = fixture.client.state_changes();
+
+    let message = text_message("hello");
+    let transaction_id = message.id.transaction_id().unwrap();
+    assert!(fixture
+        .client
+        .push_message(fixture.guild_id, fixture.channel_id, message.clone()));
+    let message_id = requests
+        .send_message(fixture.guild_id, fixture.channel_id, &message)
+        .await
+        .unwrap();
+    let event = next_event(&mut socket).await;
+    fixture.client.process_event(event);
+
+    // The echo replaces the message that was shown while sending, instead of adding another one
+    let user_id = fixture.user_id;
+    let messages = fixture.messages();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].id, MessageId::Ack(message_id));
+    assert_eq!(messages[0].sender, user_id);
+    assert_eq!(text(&messages[0]), "hello");
+
+    assert_eq!(
+        changes.try_recv().unwrap(),
+        StateChange::MessageInserted {
+            guild_id: fixture.guild_id,
+            channel_id: fixture.channel_id,
+            message_id: MessageId::Unack(transaction_id),
+        }
+    );
+    assert_eq!(
+        changes.try_recv().unwrap(),
+        StateChange::MessageUpdated {
+            guild_id: fixture.guild_id,
+            channel_id: fixture.channel_id,
+            message_id: MessageId::Ack(message_id),
+        }
+    );
+}
+
+#[tokio::test]
+async fn ack_before_echo() {
+    let mut fixture = fixture().await;
+    let requests = fixture.client.requests().clone();
+
+    let message = text_message("hello");
+    let transaction_id = message.id.transaction_id().unwrap();
+    fixture
+        .client
+        .push_message(fixture.guild_id, fixture.channel_id, message.clone());
+    let message_id = requests
+        .send_message(fixture.guild_id, fixture.channel_id, &message)
+        .await
+        .unwrap();
+    fixture.client.ack_message(
+        fixture.guild_id,
+        fixture.channel_id,
+        transaction_id,
+        message_id,
+    );
+
+    assert_eq!(fixture.messages()[0].id, MessageId::Ack(message_id));
+}
+
+#[tokio::test]
+async fn edits_keep_revisions() {
+    let mut fixture = fixture().await;
+    let message_id = fixture.homeserver.state().add_message(
+        fixture.guild_id,
+        fixture.channel_id,
+        fixture.user_id,
+        "first",
+    );
+    let requests = fixture.client.requests().clone();
+    let (page, reached_top) = requests
+        .channel_messages(fixture.guild_id, fixture.channel_id, None)
+        .await
+        .unwrap();
+    fixture.client.process_get_message_history_response(
+        fixture.guild_id,
+        fixture.channel_id,
+        page,
+        reached_top,
+    );
+    let mut socket = requests
+        .subscribe(fixture.client.subscribe_to())
+        .await
+        .unwrap();
+    fixture.homeserver.wait_for_subscriptions(1).await;
+
+    requests
+        .edit_message(
+            fixture.guild_id,
+            fixture.channel_id,
+            message_id,
+            Content::Text("second".to_string()),
+        )
+        .await
+        .unwrap();
+    let event = next_event(&mut socket).await;
+    fixture.client.process_event(event);
+
+    let message = &fixture.messages()[0];
+    assert_eq!(text(message), "second");
+    assert!(message.edited_at.is_some());
+    assert_eq!(message.revisions.len(), 1);
+    assert!(matches!(&message.revisions[0].content, Content::Text(text) if text == "first"));
+}
+
+#[tokio::test]
+async fn caption_edit_events_keep_attachments() {
+    let mut fixture = fixture().await;
+    let path = fixture
+        .homeserver
+        .content_store()
+        .content_dir()
+        .join("cat.txt");
+    tokio::fs::write(&path, b"meow").await.unwrap();
+    let requests = fixture.client.requests().clone();
+    let attachment = requests.upload_file(&path).await.unwrap();
+    let mut socket = requests
+        .subscribe(fixture.client.subscribe_to())
+        .await
+        .unwrap();
+    fixture.homeserver.wait_for_subscriptions(1).await;
+
+    let message = Message {
+        content: Content::Files(vec![attachment.clone()]),
+        ..Default::default()
+    };
+    let message_id = requests
+        .send_message(fixture.guild_id, fixture.channel_id, &message)
+        .await
+        .unwrap();
+    let event = next_event(&mut socket).await;
+    fixture.client.process_event(event);
+
+    let mut captioned = attachment.clone();
+    captioned.caption = "a cat".to_string();
+    requests
+        .edit_message(
+            fixture.guild_id,
+            fixture.channel_id,
+            message_id,
+            Content::Files(vec![captioned]),
+        )
+        .await
+        .unwrap();
+    // The event of the edit only has text, which is empty here
+    let event = next_event(&mut socket).await;
+    fixture.client.process_event(event);
+
+    match &fixture.messages()[0].content {
+        Content::Files(attachments) => {
+            assert_eq!(attachments.len(), 1);
+            assert_eq!(attachments[0].id, attachment.id);
+        }
+        other => panic!("expected attachments, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn only_authors_edit() {
+    let fixture = fixture().await;
+    let message_id = fixture.homeserver.state().add_message(
+        fixture.guild_id,
+        fixture.channel_id,
+        fixture.user_id,
+        "mine",
+    );
+    let (_, bob) = fixture.member("bob").await;
+
+    let err = bob
+        .requests()
+        .edit_message(
+            fixture.guild_id,
+            fixture.channel_id,
+            message_id,
+            Content::Text("yours".to_string()),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+}
+
+#[tokio::test]
+async fn deleted_message_is_removed() {
+    let mut fixture = fixture().await;
+    let message_id = fixture.homeserver.state().add_message(
+        fixture.guild_id,
+        fixture.channel_id,
+        fixture.user_id,
+        "oops",
+    );
+    let requests = fixture.client.requests().clone();
+    let (page, reached_top) = requests
+        .channel_messages(fixture.guild_id, fixture.channel_id, None)
+        .await
+        .unwrap();
+    fixture.client.process_get_message_history_response(
+        fixture.guild_id,
+        fixture.channel_id,
+        page,
+        reached_top,
+    );
+    let mut socket = requests
+        .subscribe(fixture.client.subscribe_to())
+        .await
+        .unwrap();
+    fixture.homeserver.wait_for_subscriptions(1).await;
+    let mut changes = fixture.client.state_changes();
+
+    requests
+        .delete_message(fixture.guild_id, fixture.channel_id, message_id)
+        .await
+        .unwrap();
+    let event = next_event(&mut socket).await;
+    fixture.client.process_event(event);
+
+    assert!(fixture.messages().is_empty());
+    assert_eq!(
+        changes.try_recv().unwrap(),
+        StateChange::MessageRemoved {
+            guild_id: fixture.guild_id,
+            channel_id: fixture.channel_id,
+            message_id,
+        }
+    );
+}
+
+#[tokio::test]
+async fn deleting_others_messages_needs_permission() {
+    let fixture = fixture().await;
+    let message_id = fixture.homeserver.state().add_message(
+        fixture.guild_id,
+        fixture.channel_id,
+        fixture.user_id,
+        "keep this",
+    );
+    fixture.homeserver.state().set_permission(
+        fixture.guild_id,
+        None,
+        "messages.manage.delete",
+        false,
+    );
+    let (_, bob) = fixture.member("bob").await;
+
+    let err = bob
+        .requests()
+        .delete_message(fixture.guild_id, fixture.channel_id, message_id)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    assert_eq!(err.kind().recovery(), Recovery::ShowInline);
+    let state = fixture.homeserver.state();
+    let channel = state.channel(fixture.guild_id, fixture.channel_id).unwrap();
+    assert_eq!(channel.messages.len(), 1);
+}
+
+#[tokio::test]
+async fn rate_limited_requests_are_retried() {
+    let fixture = fixture().await;
+    fixture
+        .homeserver
+        .fail_next("GetPinnedMessages", Fault::rate_limited());
+
+    let requests = fixture.client.requests().clone();
+    let (guild_id, channel_id) = (fixture.guild_id, fixture.channel_id);
+    let result = fixture
+        .client
+        .scheduler()
+        .run(
+            RequestKey::PinnedMessages {
+                guild_id,
+                channel_id,
+            },
+            Priority::Visible,
+            move || {
+                let requests = requests.clone();
+                async move { requests.pinned_messages(guild_id, channel_id).await }
+            },
+        )
+        .await;
+
+    assert!(matches!(result, Some(Ok(pinned)) if pinned.is_empty()));
+    assert_eq!(fixture.homeserver.calls("GetPinnedMessages"), 2);
+}
+
+#[tokio::test]
+async fn unknown_errors_are_classified_by_status() {
+    let fixture = fixture().await;
+    let requests = fixture.client.requests().clone();
+    let (guild_id, channel_id) = (fixture.guild_id, fixture.channel_id);
+
+    fixture.homeserver.fail_next(
+        "GetPinnedMessages",
+        Fault::Error {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            identifier: "h.slow-down",
+        },
+    );
+    let err = requests
+        .pinned_messages(guild_id, channel_id)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::RateLimit);
+    assert_eq!(err.kind().recovery(), Recovery::Retry);
+
+    // Known identifiers win over the status
+    fixture.homeserver.fail_next(
+        "GetPinnedMessages",
+        Fault::Error {
+            status: StatusCode::BAD_REQUEST,
+            identifier: "h.not-enough-permissions",
+        },
+    );
+    let err = requests
+        .pinned_messages(guild_id, channel_id)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+}
+
+#[tokio::test]
+async fn uploaded_file_can_be_downloaded() {
+    let fixture = fixture().await;
+    let data = b"not really a picture".to_vec();
+    let path = fixture
+        .homeserver
+        .content_store()
+        .content_dir()
+        .join("upload.txt");
+    tokio::fs::write(&path, &data).await.unwrap();
+
+    let attachment = fixture.client.requests().upload_file(&path).await.unwrap();
+    assert!(matches!(attachment.id, FileId::Id(_)));
+    assert_eq!(attachment.name, "upload.txt");
+    assert_eq!(attachment.size as usize, data.len());
+    // Uploaded files don't have to be downloaded again
+    assert!(fixture
+        .client
+        .content_store()
+        .content_exists(&attachment.id));
+
+    let file = download_extract_file(fixture.client.inner(), attachment.id)
+        .await
+        .unwrap();
+    assert_eq!(file.data(), data.as_slice());
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use common::MockHomeserver;
+use crust::client::{error::ClientError, Session};
+
+fn session(homeserver: &MockHomeserver) -> Session {
+    Session {
+        session_token: "secret-token".to_string(),
+        user_id: "42".to_string(),
+        homeserver: homeserver.url().to_string(),
+    }
+}
+
+fn session_file_has_token(homeserver: &MockHomeserver) -> bool {
+    let data = std::fs::read(homeserver.content_store().session_file()).unwrap();
+    String::from_utf8_lossy(&data).contains("secret-token")
+}
+
+#[tokio::test]
+async fn token_is_saved_in_keyring() {
+    let homeserver = MockHomeserver::start().await;
+    let content_store = homeserver.content_store();
+
+    session(&homeserver)
+        .save(&content_store, None)
+        .await
+        .unwrap();
+    assert!(!session_file_has_token(&homeserver));
+    assert_eq!(homeserver.secrets.values(), ["secret-token"]);
+
+    let loaded = Session::load(&content_store, None).await.unwrap();
+    assert_eq!(loaded.session_token, "secret-token");
+    assert_eq!(loaded.user_id, "42");
+
+    Session::remove(&content_store).await.unwrap();
+    assert!(!content_store.session_file().exists());
+    assert!(homeserver.secrets.is_empty());
+}
+
+#[tokio::test]
+async fn encrypted_without_keyring() {
+    let homeserver = MockHomeserver::start().await;
+    homeserver.secrets.set_unavailable(true);
+    let content_store = homeserver.content_store();
+    let session = session(&homeserver);
+
+    let err = session.save(&content_store, None).await.unwrap_err();
+    assert!(matches!(err, ClientError::KeyringUnavailable(_)));
+    assert!(!content_store.session_file().exists());
+
+    session
+        .save(&content_store, Some("passphrase".to_string()))
+        .await
+        .unwrap();
+    assert!(!session_file_has_token(&homeserver));
+
+    let err = Session::load(&content_store, None).await.unwrap_err();
+    assert!(matches!(err, ClientError::PassphraseRequired));
+    let err = Session::load(&content_store, Some("wrong".to_string()))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ClientError::WrongPassphrase));
+    let loaded = Session::load(&content_store, Some("passphrase".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(loaded.session_token, "secret-token");
+
+    // Encrypted sessions stay encrypted when the keyring comes back
+    homeserver.secrets.set_unavailable(false);
+    Session::load(&content_store, Some("passphrase".to_string()))
+        .await
+        .unwrap();
+    assert!(homeserver.secrets.is_empty());
+}
+
+#[tokio::test]
+async fn plain_session_is_migrated() {
+    let homeserver = MockHomeserver::start().await;
+    let content_store = homeserver.content_store();
+    // What older versions saved
+    let plain = toml::ser::to_vec(&session(&homeserver)).unwrap();
+    std::fs::write(content_store.session_file(), plain).unwrap();
+
+    let loaded = Session::load(&content_store, None).await.unwrap();
+    assert_eq!(loaded.session_token, "secret-token");
+    assert!(!session_file_has_token(&homeserver));
+    assert_eq!(homeserver.secrets.values(), ["secret-token"]);
+
+    // Without a keyring, the plain session stays until there is a passphrase to encrypt it with
+    std::fs::write(
+        content_store.session_file(),
+        toml::ser::to_vec(&loaded).unwrap(),
+    )
+    .unwrap();
+    homeserver.secrets.set_unavailable(true);
+    let err = Session::load(&content_store, None).await.unwrap_err();
+    assert!(matches!(err, ClientError::KeyringUnavailable(_)));
+    assert!(session_file_has_token(&homeserver));
+
+    Session::load(&content_store, Some("passphrase".to_string()))
+        .await
+        .unwrap();
+    assert!(!session_file_has_token(&homeserver));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn session_file_is_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let homeserver = MockHomeserver::start().await;
+    let content_store = homeserver.content_store();
+    let session_file = content_store.session_file();
+    // A file saved by an older version could be readable by anyone
+    std::fs::write(session_file, "").unwrap();
+    std::fs::set_permissions(session_file, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+    session(&homeserver)
+        .save(&content_store, None)
+        .await
+        .unwrap();
+    let mode = std::fs::metadata(session_file)
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert!(!session_file.with_extension("tmp").exists());
+}
@@ -0,0 +1,1108 @@
+mod common;
+
+use common::{
+    text_content,
+    ui::{run, Harness},
+    Fault, MockHomeserver, PAGE_SIZE, PASSWORD,
+};
+use crust::{
+    client::{error::ClientError, message::Content, Session},
+    ui::{
+        component::ChannelListAction,
+        screen::{
+            login::Message as LoginMessage,
+            main::{
+                actions::Action,
+                channel_menu::Message as ChannelMenuMessage,
+                delete_message::Message as DeleteMessageMessage,
+                embed_editor::{FieldPart, Message as EmbedEditorMessage},
+                message_menu::Message as MessageMenuMessage,
+                quick_switcher::{Message as QuickSwitcherMessage, SearchResult},
+                search::Message as SearchMessage,
+                Dialog, Message as MainMessage, Mode,
+            },
+            Message, Screen,
+        },
+    },
+};
+use harmony_rust_sdk::api::{
+    chat::event::{Event, MessageDeleted, MessageSent, MessageUpdated},
+    harmonytypes,
+};
+use std::time::Duration;
+
+struct Ids {
+    user_id: u64,
+    guild_id: u64,
+    channel_id: u64,
+}
+
+/// A user who owns a guild with one channel, like in the message tests.
+fn setup(homeserver: &MockHomeserver) -> Ids {
+    let mut state = homeserver.state();
+    let user_id = state.add_user("alice");
+    let guild_id = state.add_guild(user_id, "guild");
+    let channel_id = state.add_channel(guild_id, "general");
+    Ids {
+        user_id,
+        guild_id,
+        channel_id,
+    }
+}
+
+/// Logs in and opens a channel, loading its newest messages.
+async fn open_channel(ids: &Ids, homeserver: MockHomeserver) -> Harness {
+    let mut harness = Harness::new(homeserver);
+    harness.login(ids.user_id).await;
+    harness
+        .settle_main(MainMessage::GuildChanged(ids.guild_id))
+        .await;
+    harness
+        .settle_main(MainMessage::ChannelChanged(ids.channel_id))
+        .await;
+    harness
+}
+
+fn texts(messages: &[crust::client::message::Message]) -> Vec<String> {
+    let mut texts = messages
+        .iter()
+        .map(|message| match &message.content {
+            Content::Text(text) => text.clone(),
+            other => panic!("expected a text message, got {:?}", other),
+        })
+        .collect::<Vec<_>>();
+    texts.sort();
+    texts
+}
+
+#[tokio::test]
+async fn login_opens_main_screen() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let mut harness = Harness::new(homeserver);
+    assert!(matches!(harness.screen(), Screen::Login(_)));
+
+    harness.login(ids.user_id).await;
+
+    let main = harness.main();
+    assert_eq!(main.mode(), Mode::Normal);
+    assert_eq!(main.current_channel(), None);
+    assert!(main.shown_dialogs().is_empty());
+    let client = harness.client();
+    assert_eq!(client.user_id, Some(ids.user_id));
+    assert_eq!(client.guilds[&ids.guild_id].name, "guild");
+    assert_eq!(client.members[&ids.user_id].username, "alice");
+}
+
+#[tokio::test]
+async fn expired_session_returns_to_login() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    homeserver.fail_next("GetGuildList", Fault::bad_session());
+    let mut harness = Harness::new(homeserver);
+
+    harness.login(ids.user_id).await;
+
+    assert!(matches!(harness.screen(), Screen::Login(_)));
+    assert!(harness.manager.client().is_none());
+}
+
+/// Enters the address of the mock homeserver, and fills the form the choice leads to.
+async fn fill_auth_form(harness: &mut Harness, choice: LoginMessage, values: &[&str]) {
+    let url = harness.homeserver.url().to_string();
+    harness
+        .settle(Message::LoginScreen(LoginMessage::FieldChanged(0, url)))
+        .await;
+    harness.settle(Message::LoginScreen(choice)).await;
+    for (index, value) in values.iter().enumerate() {
+        harness
+            .settle(Message::LoginScreen(LoginMessage::FieldChanged(
+                index,
+                value.to_string(),
+            )))
+            .await;
+    }
+}
+
+#[tokio::test]
+async fn login_form_logs_in() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let session_file = homeserver.content_store().session_file().to_path_buf();
+    let mut harness = Harness::new(homeserver);
+
+    fill_auth_form(
+        &mut harness,
+        LoginMessage::Proceed,
+        &["alice@example.org", PASSWORD],
+    )
+    .await;
+    assert_eq!(harness.login_screen().title(), "login");
+    harness
+        .settle(Message::LoginScreen(LoginMessage::Proceed))
+        .await;
+
+    assert_eq!(harness.client().user_id, Some(ids.user_id));
+    assert!(session_file.exists());
+}
+
+#[tokio::test]
+async fn invalid_fields_stay_on_form() {
+    let homeserver = MockHomeserver::start().await;
+    let mut harness = Harness::new(homeserver);
+
+    fill_auth_form(
+        &mut harness,
+        LoginMessage::Register,
+        &["bob", "bob", "hunter2"],
+    )
+    .await;
+    let calls = harness.homeserver.calls("NextStep");
+    harness
+        .settle(Message::LoginScreen(LoginMessage::Proceed))
+        .await;
+
+    // Nothing is sent until every field is valid
+    assert_eq!(harness.homeserver.calls("NextStep"), calls);
+    let errors = harness.login_screen().field_errors();
+    assert_eq!(errors[0], ("username", None));
+    assert!(errors[1].1.unwrap().contains("email address"));
+    assert!(errors[2].1.unwrap().contains("doesn't match"));
+
+    // The homeserver rejecting a value keeps the form, so that it can be fixed
+    harness.homeserver.state().add_user("alice");
+    for (index, value) in ["bob", "alice@example.org"].iter().enumerate() {
+        harness
+            .settle(Message::LoginScreen(LoginMessage::FieldChanged(
+                index,
+                value.to_string(),
+            )))
+            .await;
+    }
+    harness
+        .settle(Message::LoginScreen(LoginMessage::ConfirmationChanged(
+            2,
+            "hunter2".to_string(),
+        )))
+        .await;
+    harness
+        .settle(Message::LoginScreen(LoginMessage::Proceed))
+        .await;
+    assert!(harness.login_screen().error_text().is_some());
+    assert_eq!(harness.login_screen().title(), "register");
+}
+
+#[tokio::test]
+async fn register_waits_for_email() {
+    let homeserver = MockHomeserver::start().await;
+    homeserver.state().verify_emails = true;
+    let mut harness = Harness::new(homeserver);
+
+    fill_auth_form(
+        &mut harness,
+        LoginMessage::Register,
+        &["bob", "bob@example.org", "hunter2"],
+    )
+    .await;
+    harness
+        .settle(Message::LoginScreen(LoginMessage::ConfirmationChanged(
+            2,
+            "hunter2".to_string(),
+        )))
+        .await;
+    let waiting = harness
+        .send(Message::LoginScreen(LoginMessage::Proceed))
+        .await;
+    let wait = harness.update(waiting.into_iter().next().unwrap());
+    assert_eq!(harness.login_screen().title(), "verify-email");
+
+    // The step isn't asked for again, the homeserver pushes the next one once the email is verified
+    let steps_asked = harness.homeserver.calls("NextStep");
+    let mut pushed = tokio::spawn(run(wait));
+    assert!(
+        tokio::time::timeout(Duration::from_millis(200), &mut pushed)
+            .await
+            .is_err(),
+        "the waiting step ended before the email was verified"
+    );
+
+    harness.homeserver.state().verify_email("bob@example.org");
+    for msg in pushed.await.unwrap() {
+        harness.settle(msg).await;
+    }
+    assert_eq!(harness.homeserver.calls("NextStep"), steps_asked);
+    let user_id = harness.client().user_id.unwrap();
+    assert_eq!(harness.homeserver.state().users[&user_id].name, "bob");
+}
+
+#[tokio::test]
+async fn restored_session_is_checked() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let session_file = homeserver.content_store().session_file().to_path_buf();
+    let mut harness = Harness::new(homeserver);
+    let restore = || Message::LoginScreen(LoginMessage::RestoreSession);
+
+    // Errors that aren't about the session keep it, to try again later
+    let content_store = harness.homeserver.content_store();
+    harness
+        .homeserver
+        .session_for(ids.user_id)
+        .save(&content_store, None)
+        .await
+        .unwrap();
+    harness
+        .homeserver
+        .fail_next("GetUser", Fault::rate_limited());
+    harness.settle(restore()).await;
+    assert!(harness.login_screen().error_text().is_some());
+    assert!(session_file.exists());
+
+    harness.settle(restore()).await;
+    assert_eq!(harness.client().user_id, Some(ids.user_id));
+
+    // A session the homeserver doesn't know anymore is removed
+    let mut harness = Harness::new(harness.homeserver);
+    let session = Session {
+        session_token: "expired".to_string(),
+        user_id: ids.user_id.to_string(),
+        homeserver: harness.homeserver.url().to_string(),
+    };
+    session.save(&content_store, None).await.unwrap();
+    harness.settle(restore()).await;
+    assert!(harness
+        .login_screen()
+        .error_text()
+        .unwrap()
+        .contains("no longer valid"));
+    assert!(!session_file.exists());
+    assert!(harness.homeserver.secrets.is_empty());
+}
+
+#[tokio::test]
+async fn session_is_encrypted_without_keyring() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    homeserver.secrets.set_unavailable(true);
+    let mut harness = Harness::new(homeserver);
+    let login = Message::LoginScreen;
+
+    fill_auth_form(
+        &mut harness,
+        LoginMessage::Proceed,
+        &["alice@example.org", PASSWORD],
+    )
+    .await;
+    harness.settle(login(LoginMessage::Proceed)).await;
+    assert_eq!(harness.login_screen().title(), "protect-session");
+
+    harness
+        .settle(login(LoginMessage::FieldChanged(
+            0,
+            "passphrase".to_string(),
+        )))
+        .await;
+    harness
+        .settle(login(LoginMessage::ConfirmationChanged(
+            0,
+            "passphrase".to_string(),
+        )))
+        .await;
+    harness.settle(login(LoginMessage::Proceed)).await;
+    assert_eq!(harness.client().user_id, Some(ids.user_id));
+
+    // Starting again asks for the passphrase
+    let mut harness = Harness::new(harness.homeserver);
+    harness.settle(login(LoginMessage::RestoreSession)).await;
+    assert_eq!(harness.login_screen().title(), "unlock-session");
+    harness
+        .settle(login(LoginMessage::FieldChanged(0, "wrong".to_string())))
+        .await;
+    harness.settle(login(LoginMessage::Proceed)).await;
+    assert_eq!(
+        harness.login_screen().error_text(),
+        Some("Wrong passphrase.")
+    );
+
+    harness
+        .settle(login(LoginMessage::FieldChanged(
+            0,
+            "passphrase".to_string(),
+        )))
+        .await;
+    harness.settle(login(LoginMessage::Proceed)).await;
+    assert_eq!(harness.client().user_id, Some(ids.user_id));
+}
+
+#[tokio::test]
+async fn editing_modes() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let message_id =
+        homeserver
+            .state()
+            .add_message(ids.guild_id, ids.channel_id, ids.user_id, "first");
+    let mut harness = open_channel(&ids, homeserver).await;
+    assert_eq!(
+        harness.main().current_channel(),
+        Some((ids.guild_id, ids.channel_id))
+    );
+
+    // Editing the last message puts its text in the composer, going back clears it
+    harness
+        .settle_main(MainMessage::RunAction(Action::EditLastMessage))
+        .await;
+    assert_eq!(harness.main().mode(), Mode::EditingMessage(message_id));
+    assert_eq!(harness.main().composer_text(), "first");
+    harness
+        .settle_main(MainMessage::RunAction(Action::Back))
+        .await;
+    assert_eq!(harness.main().mode(), Mode::Normal);
+    assert_eq!(harness.main().composer_text(), "");
+
+    // Picking the message to edit goes through a mode where nothing can be typed
+    harness
+        .settle_main(MainMessage::RunAction(Action::SelectMessageToEdit))
+        .await;
+    assert_eq!(harness.main().mode(), Mode::EditMessage);
+    harness
+        .settle_main(MainMessage::ChangeMode(Mode::EditingMessage(message_id)))
+        .await;
+    assert_eq!(harness.main().composer_text(), "first");
+
+    harness
+        .settle_main(MainMessage::ComposerMessageChanged("second".to_string()))
+        .await;
+    harness
+        .settle_main(MainMessage::SendMessageComposer {
+            guild_id: ids.guild_id,
+            channel_id: ids.channel_id,
+        })
+        .await;
+    assert_eq!(harness.main().mode(), Mode::Normal);
+    assert_eq!(harness.main().composer_text(), "");
+    let state = harness.homeserver.state();
+    let channel = state.channel(ids.guild_id, ids.channel_id).unwrap();
+    assert_eq!(channel.messages[0].content, Some(text_content("second")));
+}
+
+#[tokio::test]
+async fn captions_of_any_attachment_can_be_edited() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let message_id = homeserver.state().add_files_message(
+        ids.guild_id,
+        ids.channel_id,
+        ids.user_id,
+        &["first.txt", "second.txt"],
+    );
+    let mut harness = open_channel(&ids, homeserver).await;
+
+    harness
+        .settle_main(MainMessage::OpenMessageMenu(message_id))
+        .await;
+    harness
+        .settle_main(MainMessage::MessageMenuMsg(
+            MessageMenuMessage::EditCaption(1),
+        ))
+        .await;
+    assert_eq!(harness.main().mode(), Mode::EditingMessage(message_id));
+    harness
+        .settle_main(MainMessage::ComposerMessageChanged(
+            "the second one".to_string(),
+        ))
+        .await;
+    harness
+        .settle_main(MainMessage::SendMessageComposer {
+            guild_id: ids.guild_id,
+            channel_id: ids.channel_id,
+        })
+        .await;
+
+    let state = harness.homeserver.state();
+    let channel = state.channel(ids.guild_id, ids.channel_id).unwrap();
+    let captions = match &channel.messages[0].content {
+        Some(harmonytypes::Content {
+            content: Some(harmonytypes::content::Content::FilesMessage(files)),
+        }) => files
+            .attachments
+            .iter()
+            .map(|attachment| attachment.caption.as_str())
+            .collect::<Vec<_>>(),
+        other => panic!("expected files, got {:?}", other),
+    };
+    assert_eq!(captions, ["", "the second one"]);
+}
+
+#[tokio::test]
+async fn deleted_messages_stop_being_edited() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let message_id =
+        homeserver
+            .state()
+            .add_message(ids.guild_id, ids.channel_id, ids.user_id, "first");
+    let mut harness = open_channel(&ids, homeserver).await;
+    harness
+        .settle_main(MainMessage::RunAction(Action::EditLastMessage))
+        .await;
+    assert_eq!(harness.main().mode(), Mode::EditingMessage(message_id));
+
+    // Deleted from somewhere else, like another client
+    harness
+        .settle(Message::EventsReceived(vec![Event::DeletedMessage(
+            MessageDeleted {
+                guild_id: ids.guild_id,
+                channel_id: ids.channel_id,
+                message_id,
+            },
+        )]))
+        .await;
+    assert_eq!(harness.main().mode(), Mode::Normal);
+    assert_eq!(harness.main().composer_text(), "");
+}
+
+#[tokio::test]
+async fn deleting_a_message_stops_editing_it_until_undone() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let message_id =
+        homeserver
+            .state()
+            .add_message(ids.guild_id, ids.channel_id, ids.user_id, "first");
+    let mut harness = open_channel(&ids, homeserver).await;
+    harness
+        .settle_main(MainMessage::OpenMessageMenu(message_id))
+        .await;
+    harness
+        .settle_main(MainMessage::MessageMenuMsg(MessageMenuMessage::Reply))
+        .await;
+    harness
+        .settle_main(MainMessage::RunAction(Action::EditLastMessage))
+        .await;
+    assert_eq!(harness.main().replying_to(), Some(message_id));
+    assert_eq!(harness.main().mode(), Mode::EditingMessage(message_id));
+
+    harness
+        .settle_main(MainMessage::OpenMessageMenu(message_id))
+        .await;
+    harness
+        .settle_main(MainMessage::MessageMenuMsg(MessageMenuMessage::Delete))
+        .await;
+    // The deletion is only sent after the undo window, which isn't waited for here
+    let _ = harness.update(Message::MainScreen(MainMessage::DeleteMessageMsg(
+        DeleteMessageMessage::Confirm(true),
+    )));
+    assert_eq!(harness.main().mode(), Mode::Normal);
+    assert_eq!(harness.main().composer_text(), "");
+    assert_eq!(harness.main().replying_to(), None);
+    let messages = |harness: &mut Harness| {
+        harness
+            .client_mut()
+            .get_channel(ids.guild_id, ids.channel_id)
+            .unwrap()
+            .messages
+            .len()
+    };
+    assert_eq!(messages(&mut harness), 0);
+
+    harness.settle_main(MainMessage::UndoDelete).await;
+    assert_eq!(messages(&mut harness), 1);
+    assert_eq!(harness.homeserver.calls("DeleteMessage"), 0);
+}
+
+#[tokio::test]
+async fn embed_editor_waits_for_the_edit() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let message_id =
+        homeserver
+            .state()
+            .add_embed_message(ids.guild_id, ids.channel_id, ids.user_id, "news");
+    let mut harness = open_channel(&ids, homeserver).await;
+
+    harness
+        .settle_main(MainMessage::RunAction(Action::SelectMessageToEdit))
+        .await;
+    harness
+        .settle_main(MainMessage::ChangeMode(Mode::EditingMessage(message_id)))
+        .await;
+    assert_eq!(harness.main().shown_dialogs(), [Dialog::EmbedEditor]);
+
+    for msg in vec![
+        EmbedEditorMessage::AddField,
+        EmbedEditorMessage::FieldChanged(0, FieldPart::Title, "when".to_string()),
+        EmbedEditorMessage::FieldChanged(0, FieldPart::Body, "today".to_string()),
+    ] {
+        harness.settle_main(MainMessage::EmbedEditorMsg(msg)).await;
+    }
+
+    // A failed edit is shown in the editor, which stays open to try again
+    harness
+        .homeserver
+        .fail_next("UpdateMessageContent", Fault::permission_denied());
+    harness
+        .settle_main(MainMessage::EmbedEditorMsg(EmbedEditorMessage::Save))
+        .await;
+    assert_eq!(harness.main().shown_dialogs(), [Dialog::EmbedEditor]);
+    assert_eq!(harness.main().error_text(), None);
+
+    harness
+        .settle_main(MainMessage::EmbedEditorMsg(EmbedEditorMessage::Save))
+        .await;
+    assert!(harness.main().shown_dialogs().is_empty());
+    let state = harness.homeserver.state();
+    let channel = state.channel(ids.guild_id, ids.channel_id).unwrap();
+    let embed = match channel.messages[0].content.clone().and_then(|c| c.content) {
+        Some(harmonytypes::content::Content::EmbedMessage(embed)) => embed.embeds.unwrap(),
+        other => panic!("expected an embed, got {:?}", other),
+    };
+    assert_eq!(embed.title, "news");
+    assert_eq!(embed.fields.len(), 1);
+    assert_eq!(embed.fields[0].title, "when");
+    assert_eq!(embed.fields[0].body, "today");
+}
+
+#[tokio::test]
+async fn sending_retries_temporary_errors() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let mut harness = open_channel(&ids, homeserver).await;
+
+    harness
+        .homeserver
+        .fail_next("SendMessage", Fault::rate_limited());
+    harness
+        .settle_main(MainMessage::ComposerMessageChanged("hello".to_string()))
+        .await;
+    harness
+        .settle_main(MainMessage::SendMessageComposer {
+            guild_id: ids.guild_id,
+            channel_id: ids.channel_id,
+        })
+        .await;
+
+    assert_eq!(harness.homeserver.calls("SendMessage"), 2);
+    assert_eq!(harness.main().error_text(), None);
+    let state = harness.homeserver.state();
+    let channel = state.channel(ids.guild_id, ids.channel_id).unwrap();
+    assert_eq!(channel.messages.len(), 1);
+    assert_eq!(channel.messages[0].content, Some(text_content("hello")));
+}
+
+#[tokio::test]
+async fn scrolling_up_pages_history() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let count = PAGE_SIZE * 2 + PAGE_SIZE / 2;
+    for i in 0..count {
+        homeserver.state().add_message(
+            ids.guild_id,
+            ids.channel_id,
+            ids.user_id,
+            &format!("message {:02}", i),
+        );
+    }
+    let mut harness = open_channel(&ids, homeserver).await;
+    let loaded = |harness: &Harness| {
+        harness.client().guilds[&ids.guild_id].channels[&ids.channel_id]
+            .messages
+            .len()
+    };
+    assert_eq!(loaded(&harness), PAGE_SIZE);
+    assert!(
+        harness
+            .main()
+            .history_view(ids.guild_id, ids.channel_id)
+            .at_bottom
+    );
+
+    let scroll_to_top = || {
+        Message::MainScreen(MainMessage::MessageHistoryScrolled {
+            prev_scroll_perc: 0.5,
+            scroll_perc: 0.0,
+        })
+    };
+    let mut pages = Vec::new();
+    loop {
+        let mut responses = harness.send(scroll_to_top()).await;
+        // Scrolling more while the page is loading doesn't ask for it again
+        assert!(harness.send(scroll_to_top()).await.is_empty());
+        assert_eq!(responses.len(), 1);
+        let response = responses.pop().unwrap();
+        let reached_top = match &response {
+            Message::GetEventsBackwardsResponse {
+                messages,
+                reached_top,
+                ..
+            } => {
+                pages.push(texts(messages));
+                *reached_top
+            }
+            other => panic!("expected older messages, got {:?}", other),
+        };
+
+        // The messages that were in view stay there, with the older ones put above them
+        let view = harness.main().history_view(ids.guild_id, ids.channel_id);
+        assert!(!view.at_bottom);
+        let old_height = harness.main().history_layout().total_height();
+        harness.settle(response).await;
+        let added_height = harness.main().history_layout().total_height() - old_height;
+        assert!(added_height > 0.0);
+        let moved = harness.main().history_view(ids.guild_id, ids.channel_id);
+        assert_eq!(moved.offset, view.offset + added_height);
+
+        if reached_top {
+            break;
+        }
+    }
+
+    let expected = |range: std::ops::Range<usize>| {
+        range
+            .map(|i| format!("message {:02}", i))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        pages,
+        [
+            expected(PAGE_SIZE / 2..PAGE_SIZE / 2 + PAGE_SIZE),
+            expected(0..PAGE_SIZE / 2)
+        ]
+    );
+    assert_eq!(loaded(&harness), count);
+    // Nothing is left to load
+    assert!(harness.send(scroll_to_top()).await.is_empty());
+}
+
+#[tokio::test]
+async fn failed_history_pages_can_be_loaded_again() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    for i in 0..PAGE_SIZE * 2 {
+        homeserver.state().add_message(
+            ids.guild_id,
+            ids.channel_id,
+            ids.user_id,
+            &format!("message {:02}", i),
+        );
+    }
+    let mut harness = open_channel(&ids, homeserver).await;
+    let scroll_to_top = MainMessage::MessageHistoryScrolled {
+        prev_scroll_perc: 0.5,
+        scroll_perc: 0.0,
+    };
+
+    harness
+        .homeserver
+        .fail_next("GetChannelMessages", Fault::permission_denied());
+    harness.settle_main(scroll_to_top.clone()).await;
+    assert!(harness.main().error_text().is_some());
+    let channel = &harness.client().guilds[&ids.guild_id].channels[&ids.channel_id];
+    assert!(!channel.loading_messages_history);
+    assert_eq!(channel.messages.len(), PAGE_SIZE);
+
+    harness.settle_main(scroll_to_top).await;
+    let channel = &harness.client().guilds[&ids.guild_id].channels[&ids.channel_id];
+    assert_eq!(channel.messages.len(), PAGE_SIZE * 2);
+}
+
+#[tokio::test]
+async fn jumping_to_older_message_loads_history() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let count = PAGE_SIZE * 3;
+    let message_ids = (0..count)
+        .map(|i| {
+            homeserver.state().add_message(
+                ids.guild_id,
+                ids.channel_id,
+                ids.user_id,
+                &format!("message {:02}", i),
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut harness = open_channel(&ids, homeserver).await;
+    let calls = harness.homeserver.calls("GetChannelMessages");
+
+    // Like a pin of the first message, which is two pages older than what is loaded
+    harness
+        .settle_main(MainMessage::JumpToMessage(message_ids[0]))
+        .await;
+    let channel = &harness.client().guilds[&ids.guild_id].channels[&ids.channel_id];
+    assert_eq!(channel.messages.len(), count);
+    assert_eq!(harness.homeserver.calls("GetChannelMessages"), calls + 2);
+    let view = harness.main().history_view(ids.guild_id, ids.channel_id);
+    assert!(!view.at_bottom);
+    assert_eq!(view.offset, 0.0);
+}
+
+#[tokio::test]
+async fn history_layout_follows_changes() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let message_id =
+        homeserver
+            .state()
+            .add_message(ids.guild_id, ids.channel_id, ids.user_id, "short");
+    let mut harness = open_channel(&ids, homeserver).await;
+    let height = |harness: &mut Harness| {
+        harness.describe();
+        harness.main().history_layout().total_height()
+    };
+    let short = height(&mut harness);
+    assert!(short > 0.0);
+
+    // Building the view again without changes keeps the measurements
+    assert_eq!(height(&mut harness), short);
+
+    harness
+        .settle(Message::EventsReceived(vec![Event::EditedMessage(
+            MessageUpdated {
+                guild_id: ids.guild_id,
+                channel_id: ids.channel_id,
+                message_id,
+                content: "long\n".repeat(20),
+                ..Default::default()
+            },
+        )]))
+        .await;
+    let edited = height(&mut harness);
+    assert!(edited > short);
+
+    harness
+        .settle(sent_message(
+            ids.guild_id,
+            ids.channel_id,
+            ids.user_id,
+            1000,
+        ))
+        .await;
+    let inserted = height(&mut harness);
+    assert!(inserted > edited);
+
+    harness
+        .settle(Message::EventsReceived(vec![Event::DeletedMessage(
+            MessageDeleted {
+                guild_id: ids.guild_id,
+                channel_id: ids.channel_id,
+                message_id: 1000,
+            },
+        )]))
+        .await;
+    assert_eq!(height(&mut harness), edited);
+}
+
+#[tokio::test]
+async fn quick_switcher_matches() {
+    let homeserver = MockHomeserver::start().await;
+    let (user_id, rust_id, help_id, garden_id) = {
+        let mut state = homeserver.state();
+        let user_id = state.add_user("alice");
+        let rust_id = state.add_guild(user_id, "Rust Lovers");
+        state.add_channel(rust_id, "general");
+        let help_id = state.add_channel(rust_id, "rust-help");
+        let garden_id = state.add_guild(user_id, "Gardening");
+        state.add_channel(garden_id, "garden-pics");
+        (user_id, rust_id, help_id, garden_id)
+    };
+    let mut harness = Harness::new(homeserver);
+    harness.login(user_id).await;
+    // Channels are only known once their guild was opened
+    for guild_id in &[rust_id, garden_id] {
+        harness
+            .settle_main(MainMessage::GuildChanged(*guild_id))
+            .await;
+    }
+
+    let search = |term: &str| {
+        MainMessage::QuickSwitchMsg(QuickSwitcherMessage::SearchTermChanged(term.to_string()))
+    };
+    harness
+        .settle_main(MainMessage::RunAction(Action::SwitchChannel))
+        .await;
+    assert_eq!(harness.main().shown_dialogs(), [Dialog::QuickSwitcher]);
+    // Without a search term, recently visited channels are shown, but none were visited yet
+    harness.settle_main(search("")).await;
+    assert!(harness.main().quick_switcher_results().is_empty());
+
+    harness.settle_main(search("*gard")).await;
+    assert_eq!(
+        harness.main().quick_switcher_results(),
+        [SearchResult::Guild {
+            id: garden_id,
+            name: "Gardening".to_string(),
+        }]
+    );
+    harness.settle_main(search("#rust")).await;
+    let rust_help = SearchResult::Channel {
+        guild_id: rust_id,
+        id: help_id,
+        name: "rust-help".to_string(),
+    };
+    assert_eq!(harness.main().quick_switcher_results(), [rust_help.clone()]);
+    // Guilds come before channels when searching for both
+    harness.settle_main(search("rust")).await;
+    assert_eq!(
+        harness.main().quick_switcher_results(),
+        [
+            SearchResult::Guild {
+                id: rust_id,
+                name: "Rust Lovers".to_string(),
+            },
+            rust_help.clone(),
+        ]
+    );
+
+    harness
+        .settle_main(MainMessage::QuickSwitchMsg(
+            QuickSwitcherMessage::SwitchToChannel {
+                guild_id: rust_id,
+                channel_id: help_id,
+            },
+        ))
+        .await;
+    assert_eq!(harness.main().current_channel(), Some((rust_id, help_id)));
+    assert!(harness.main().shown_dialogs().is_empty());
+
+    harness
+        .settle_main(MainMessage::RunAction(Action::SwitchChannel))
+        .await;
+    harness.settle_main(search("")).await;
+    assert_eq!(harness.main().quick_switcher_results(), [rust_help]);
+}
+
+#[tokio::test]
+async fn search_jumps_to_messages() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let (other_id, pancakes_id) = {
+        let mut state = homeserver.state();
+        let pancakes_id = state.add_message(
+            ids.guild_id,
+            ids.channel_id,
+            ids.user_id,
+            "Who wants Pancakes?",
+        );
+        state.add_message(ids.guild_id, ids.channel_id, ids.user_id, "me");
+        let other_id = state.add_channel(ids.guild_id, "random");
+        state.add_message(ids.guild_id, other_id, ids.user_id, "waffles are better");
+        (other_id, pancakes_id)
+    };
+    let mut harness = open_channel(&ids, homeserver).await;
+    harness
+        .settle_main(MainMessage::ChannelChanged(other_id))
+        .await;
+
+    harness
+        .settle_main(MainMessage::RunAction(Action::Search))
+        .await;
+    assert_eq!(harness.main().shown_dialogs(), [Dialog::Search]);
+    let search =
+        |term: &str| MainMessage::SearchMsg(SearchMessage::SearchChanged(term.to_string()));
+    harness.settle_main(search("sushi")).await;
+    assert!(harness.main().search_results().is_empty());
+    // Case doesn't matter, and messages of every loaded channel are searched
+    harness.settle_main(search("pancake")).await;
+    let results = harness.main().search_results().to_vec();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].message_id, pancakes_id);
+    assert_eq!(results[0].channel_id, ids.channel_id);
+
+    harness
+        .settle_main(MainMessage::SearchMsg(SearchMessage::Open(
+            results[0].clone(),
+        )))
+        .await;
+    assert!(harness.main().shown_dialogs().is_empty());
+    assert_eq!(
+        harness.main().current_channel(),
+        Some((ids.guild_id, ids.channel_id))
+    );
+    assert!(
+        !harness
+            .main()
+            .history_view(ids.guild_id, ids.channel_id)
+            .at_bottom
+    );
+}
+
+/// An event for a message someone sent, which only the client learns about.
+fn sent_message(guild_id: u64, channel_id: u64, author_id: u64, message_id: u64) -> Message {
+    Message::EventsReceived(vec![Event::SentMessage(Box::new(MessageSent {
+        message: Some(harmonytypes::Message {
+            guild_id,
+            channel_id,
+            author_id,
+            message_id,
+            content: Some(text_content("hello")),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))])
+}
+
+#[tokio::test]
+async fn new_messages_mark_only_the_current_channel_read() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let (bob, other_id, first_id) = {
+        let mut state = homeserver.state();
+        let bob = state.add_user("bob");
+        state.join_guild(ids.guild_id, bob);
+        let first_id = state.add_message(ids.guild_id, ids.channel_id, bob, "first");
+        let other_id = state.add_channel(ids.guild_id, "random");
+        (bob, other_id, first_id)
+    };
+    let mut harness = open_channel(&ids, homeserver).await;
+    let unread = |harness: &Harness, channel_id| {
+        harness.client().guilds[&ids.guild_id].channels[&channel_id].unread
+    };
+
+    harness
+        .settle(sent_message(ids.guild_id, other_id, bob, 1000))
+        .await;
+    assert_eq!(unread(&harness, other_id), 1);
+    harness
+        .settle(sent_message(ids.guild_id, ids.channel_id, bob, 1001))
+        .await;
+    assert_eq!(unread(&harness, ids.channel_id), 0);
+    assert_eq!(unread(&harness, other_id), 1);
+
+    // A channel marked as unread stays so, even as new messages arrive in it
+    harness
+        .settle_main(MainMessage::OpenMessageMenu(first_id))
+        .await;
+    harness
+        .settle_main(MainMessage::MessageMenuMsg(MessageMenuMessage::MarkUnread))
+        .await;
+    assert_eq!(unread(&harness, ids.channel_id), 2);
+    harness
+        .settle(sent_message(ids.guild_id, ids.channel_id, bob, 1002))
+        .await;
+    assert_eq!(unread(&harness, ids.channel_id), 3);
+
+    harness
+        .settle_main(MainMessage::RunAction(Action::MarkRead))
+        .await;
+    assert_eq!(unread(&harness, ids.channel_id), 0);
+    harness
+        .settle(sent_message(ids.guild_id, ids.channel_id, bob, 1003))
+        .await;
+    assert_eq!(unread(&harness, ids.channel_id), 0);
+}
+
+#[tokio::test]
+async fn copy_ids_from_channel_menu() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let mut harness = open_channel(&ids, homeserver).await;
+
+    harness
+        .settle_main(MainMessage::SelectedChannelMenuOption(
+            "Copy Guild ID".to_string(),
+        ))
+        .await;
+    assert_eq!(harness.clipboard.contents, Some(ids.guild_id.to_string()));
+    harness
+        .settle_main(MainMessage::SelectedChannelMenuOption(
+            "Copy Channel ID".to_string(),
+        ))
+        .await;
+    assert_eq!(harness.clipboard.contents, Some(ids.channel_id.to_string()));
+}
+
+#[tokio::test]
+async fn errors_go_to_the_dialog_that_made_the_request() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let mut harness = open_channel(&ids, homeserver).await;
+
+    harness
+        .settle_main(MainMessage::ChannelListAction(ChannelListAction::OpenMenu(
+            ids.channel_id,
+        )))
+        .await;
+    assert_eq!(harness.main().shown_dialogs(), [Dialog::ChannelMenu]);
+
+    // The menu shows why renaming failed
+    harness
+        .homeserver
+        .fail_next("UpdateChannelInformation", Fault::permission_denied());
+    for msg in vec![
+        ChannelMenuMessage::NameChanged("random".to_string()),
+        ChannelMenuMessage::Rename,
+    ] {
+        harness
+            .settle_main(MainMessage::ChannelMenuMessage(msg))
+            .await;
+    }
+    assert_eq!(harness.homeserver.calls("UpdateChannelInformation"), 1);
+    assert_eq!(harness.main().shown_dialogs(), [Dialog::ChannelMenu]);
+    assert_eq!(harness.main().error_text(), None);
+
+    // Errors of requests the menu didn't make aren't shown in it just because it's open
+    harness
+        .settle(Message::Error(Box::new(ClientError::Custom(
+            "something else".to_string(),
+        ))))
+        .await;
+    assert_eq!(harness.main().error_text(), Some("something else"));
+}
+
+#[tokio::test]
+async fn logout_asks_first() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let mut harness = Harness::new(homeserver);
+    harness.login(ids.user_id).await;
+    let session_file = harness
+        .homeserver
+        .content_store()
+        .session_file()
+        .to_path_buf();
+
+    let logout = || MainMessage::SelectedMenuOption("Logout".to_string());
+    harness.settle_main(logout()).await;
+    assert_eq!(harness.main().shown_dialogs(), [Dialog::Logout]);
+    harness.settle_main(MainMessage::LogoutChoice(false)).await;
+    assert!(harness.main().shown_dialogs().is_empty());
+    assert!(session_file.exists());
+
+    harness.settle_main(logout()).await;
+    harness.settle_main(MainMessage::LogoutChoice(true)).await;
+    assert!(matches!(harness.screen(), Screen::Login(_)));
+    assert!(harness.manager.client().is_none());
+    assert!(!session_file.exists());
+}
+
+#[tokio::test]
+async fn views_match_snapshots() {
+    let homeserver = MockHomeserver::start().await;
+    let ids = setup(&homeserver);
+    let mut harness = Harness::new(homeserver);
+    harness.assert_snapshot("login");
+
+    harness.login(ids.user_id).await;
+    harness.assert_snapshot("main_no_guild");
+
+    harness
+        .settle_main(MainMessage::GuildChanged(ids.guild_id))
+        .await;
+    harness
+        .settle_main(MainMessage::ChannelChanged(ids.channel_id))
+        .await;
+    harness.assert_snapshot("main_empty_channel");
+
+    harness
+        .settle_main(MainMessage::RunAction(Action::SwitchChannel))
+        .await;
+    harness.assert_snapshot("quick_switcher");
+    harness
+        .settle_main(MainMessage::RunAction(Action::SwitchChannel))
+        .await;
+
+    harness
+        .settle_main(MainMessage::SelectedMenuOption("Logout".to_string()))
+        .await;
+    harness.assert_snapshot("logout_dialog");
+}